@@ -10,6 +10,11 @@ use macros::*;
 /// Declares an async task that can be run by `embassy-executor`. The optional `pool_size` parameter can be used to specify how
 /// many concurrent tasks can be spawned (default is 1) for the function.
 ///
+/// The optional `name` parameter sets the name reported for the task by tracing and debugging tools (default is
+/// the name of the function). Task names are only stored if the `task-registry` feature of `embassy-executor` is enabled.
+///
+/// The optional `priority` parameter sets the run queue priority level of the task, see `SpawnToken::with_priority`.
+///
 ///
 /// The following restrictions apply:
 ///
@@ -37,6 +42,24 @@ use macros::*;
 ///     // Function body
 /// }
 /// ```
+///
+/// Declaring a task with a higher priority (requires the `task-priority` feature of `embassy-executor`):
+///
+/// ``` rust
+/// #[embassy_executor::task(priority = 2)]
+/// async fn mytask() {
+///     // Function body
+/// }
+/// ```
+///
+/// Declaring a task with a custom name:
+///
+/// ``` rust
+/// #[embassy_executor::task(name = "blinky")]
+/// async fn mytask() {
+///     // Function body
+/// }
+/// ```
 #[proc_macro_attribute]
 pub fn task(args: TokenStream, item: TokenStream) -> TokenStream {
     task::run(args.into(), item.into()).into()
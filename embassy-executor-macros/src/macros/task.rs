@@ -13,6 +13,12 @@ use crate::util::*;
 struct Args {
     #[darling(default)]
     pool_size: Option<syn::Expr>,
+    /// Name of the task, used for tracing and debugging. Defaults to the name of the function.
+    #[darling(default)]
+    name: Option<syn::LitStr>,
+    /// Run queue priority level of the task. Requires the `task-priority` feature of `embassy-executor`.
+    #[darling(default)]
+    priority: Option<syn::Expr>,
     /// Use this to override the `embassy_executor` crate path. Defaults to `::embassy_executor`.
     #[darling(default)]
     embassy_executor: Option<syn::Expr>,
@@ -47,6 +53,9 @@ pub fn run(args: TokenStream, item: TokenStream) -> TokenStream {
         lit: Lit::Int(LitInt::new("1", Span::call_site())),
     }));
 
+    let task_name = args.name;
+    let task_priority = args.priority.map(|priority| quote!(.with_priority(#priority)));
+
     let embassy_executor = args
         .embassy_executor
         .unwrap_or(Expr::Verbatim(TokenStream::from_str("::embassy_executor").unwrap()));
@@ -107,6 +116,7 @@ pub fn run(args: TokenStream, item: TokenStream) -> TokenStream {
     }
 
     let task_ident = f.sig.ident.clone();
+    let task_name = task_name.unwrap_or_else(|| syn::LitStr::new(&task_ident.to_string(), task_ident.span()));
     let task_inner_ident = format_ident!("__{}_task", task_ident);
 
     let mut task_inner = f.clone();
@@ -141,13 +151,13 @@ pub fn run(args: TokenStream, item: TokenStream) -> TokenStream {
 
         const POOL_SIZE: usize = #pool_size;
         static POOL: #embassy_executor::raw::TaskPool<<() as _EmbassyInternalTaskTrait>::Fut, POOL_SIZE> = #embassy_executor::raw::TaskPool::new();
-        unsafe { POOL._spawn_async_fn(move || <() as _EmbassyInternalTaskTrait>::construct(#(#full_args,)*)) }
+        unsafe { POOL._spawn_async_fn(move || <() as _EmbassyInternalTaskTrait>::construct(#(#full_args,)*)) }._with_name(#task_name)#task_priority
     };
     #[cfg(not(feature = "nightly"))]
     let mut task_outer_body = quote! {
         const POOL_SIZE: usize = #pool_size;
         static POOL: #embassy_executor::_export::TaskPoolRef = #embassy_executor::_export::TaskPoolRef::new();
-        unsafe { POOL.get::<_, POOL_SIZE>()._spawn_async_fn(move || #task_inner_ident(#(#full_args,)*)) }
+        unsafe { POOL.get::<_, POOL_SIZE>()._spawn_async_fn(move || #task_inner_ident(#(#full_args,)*)) }._with_name(#task_name)#task_priority
     };
 
     let task_outer_attrs = task_inner.attrs.clone();
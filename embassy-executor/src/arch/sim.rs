@@ -0,0 +1,258 @@
+#[cfg(feature = "executor-interrupt")]
+compile_error!("`executor-interrupt` is not supported with `arch-sim`.");
+
+#[cfg(feature = "executor-thread")]
+pub use thread::*;
+#[cfg(feature = "executor-thread")]
+mod thread {
+    use std::cell::Cell;
+    use std::fmt;
+    use std::marker::PhantomData;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::vec::Vec;
+
+    use crate::raw::TaskRef;
+    use crate::{raw, Spawner};
+
+    #[export_name = "__pender"]
+    fn __pender(context: *mut ()) {
+        let pending: &'static AtomicBool = unsafe { &*(context as *const AtomicBool) };
+        pending.store(true, Ordering::Release);
+    }
+
+    /// Deterministic simulation executor, for testing on the host.
+    ///
+    /// Unlike the `arch-std` executor, this executor never blocks. It is driven explicitly
+    /// with [`run_until_stalled`](Executor::run_until_stalled) or [`run`](Executor::run),
+    /// and time only moves forward when the tasks have nothing left to do.
+    ///
+    /// Each batch of ready tasks is polled in a random order, picked by a pseudo-random
+    /// generator initialized with the seed passed to [`Executor::new`]. Running the same
+    /// tasks with the same seed always polls them in the same order, so a failure caused by an
+    /// unlucky interleaving can be reproduced by rerunning with the seed that triggered it.
+    ///
+    /// To make timers fire, pass a function to [`run`](Executor::run) that advances the time
+    /// driver to its next expiration, such as `MockDriver::advance_to_next_expiration` from
+    /// `embassy-time`:
+    ///
+    /// ```rust,ignore
+    /// let executor: &'static Executor = Box::leak(Box::new(Executor::new(seed)));
+    /// executor.spawner().spawn(my_task()).unwrap();
+    /// executor.run(|| MockDriver::get().advance_to_next_expiration()).unwrap();
+    /// ```
+    pub struct Executor {
+        inner: raw::Executor,
+        not_send: PhantomData<*mut ()>,
+        pending: &'static AtomicBool,
+        rng: Cell<u64>,
+    }
+
+    impl Executor {
+        /// Create a new Executor, shuffling ready tasks with the given seed.
+        pub fn new(seed: u64) -> Self {
+            let pending = Box::leak(Box::new(AtomicBool::new(false)));
+            Self {
+                inner: raw::Executor::new(pending as *mut AtomicBool as *mut ()),
+                not_send: PhantomData,
+                pending,
+                rng: Cell::new(seed),
+            }
+        }
+
+        /// Get a spawner that spawns tasks in this executor.
+        pub fn spawner(&'static self) -> Spawner {
+            self.inner.spawner()
+        }
+
+        /// Poll tasks until none of them is ready to run.
+        ///
+        /// Returns the number of batches of tasks that were polled.
+        pub fn run_until_stalled(&'static self) -> usize {
+            let mut batches = 0;
+            while self.pending.swap(false, Ordering::Acquire) {
+                unsafe { self.inner.inner.poll_ordered(|batch| self.shuffle(batch)) };
+                batches += 1;
+            }
+            batches
+        }
+
+        /// Run tasks until all of them have exited.
+        ///
+        /// Whenever no task is ready to run, `advance_time` is called. It must advance the time
+        /// driver to the next timer expiration, waking the tasks waiting for it, and return
+        /// `false` if there are no timers pending.
+        ///
+        /// Returns [`Deadlock`] if tasks are still spawned, but none of them is ready to run and
+        /// `advance_time` has no timers left to fire.
+        pub fn run(&'static self, mut advance_time: impl FnMut() -> bool) -> Result<(), Deadlock> {
+            loop {
+                self.run_until_stalled();
+
+                if self.inner.tasks().next().is_none() {
+                    return Ok(());
+                }
+
+                if !self.pending.load(Ordering::Acquire) && !advance_time() {
+                    return Err(Deadlock {
+                        tasks: self.inner.tasks().collect(),
+                    });
+                }
+            }
+        }
+
+        fn shuffle(&self, batch: &mut [TaskRef]) {
+            // Fisher-Yates shuffle.
+            for i in (1..batch.len()).rev() {
+                let j = (self.next_random() % (i as u64 + 1)) as usize;
+                batch.swap(i, j);
+            }
+        }
+
+        /// splitmix64
+        fn next_random(&self) -> u64 {
+            let state = self.rng.get().wrapping_add(0x9e37_79b9_7f4a_7c15);
+            self.rng.set(state);
+            let mut z = state;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            z ^ (z >> 31)
+        }
+    }
+
+    /// Error returned by [`Executor::run`] when tasks are blocked forever.
+    pub struct Deadlock {
+        tasks: Vec<TaskRef>,
+    }
+
+    impl Deadlock {
+        /// The tasks that were still spawned when the deadlock was detected.
+        pub fn tasks(&self) -> &[TaskRef] {
+            &self.tasks
+        }
+    }
+
+    impl fmt::Debug for Deadlock {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            fmt::Display::fmt(self, f)
+        }
+    }
+
+    impl fmt::Display for Deadlock {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(
+                f,
+                "Deadlock - no task is ready to run and no timer is pending. Blocked tasks:"
+            )?;
+            for task in &self.tasks {
+                write!(f, " {}", task.name().unwrap_or("<unnamed>"))?;
+            }
+            Ok(())
+        }
+    }
+
+    impl std::error::Error for Deadlock {}
+
+    #[cfg(test)]
+    mod tests {
+        use std::sync::Mutex;
+
+        use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+        use embassy_sync::channel::Channel;
+        use embassy_sync::signal::Signal;
+
+        use super::*;
+
+        fn executor(seed: u64) -> &'static Executor {
+            Box::leak(Box::new(Executor::new(seed)))
+        }
+
+        #[test]
+        fn runs_to_completion() {
+            static CHANNEL: Channel<CriticalSectionRawMutex, u32, 1> = Channel::new();
+
+            #[crate::task(embassy_executor = crate)]
+            async fn producer() {
+                for i in 0..10 {
+                    CHANNEL.send(i).await;
+                }
+            }
+
+            #[crate::task(embassy_executor = crate)]
+            async fn consumer() {
+                for i in 0..10 {
+                    assert_eq!(CHANNEL.receive().await, i);
+                }
+            }
+
+            let executor = executor(0);
+            executor.spawner().spawn(producer()).unwrap();
+            executor.spawner().spawn(consumer()).unwrap();
+            executor.run(|| false).unwrap();
+        }
+
+        #[test]
+        fn reports_deadlock() {
+            static SIGNAL: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+            #[crate::task(embassy_executor = crate)]
+            async fn waiter() {
+                SIGNAL.wait().await
+            }
+
+            let executor = executor(0);
+            executor.spawner().spawn(waiter()).unwrap();
+            let deadlock = executor.run(|| false).unwrap_err();
+            assert_eq!(deadlock.tasks().len(), 1);
+            assert_eq!(deadlock.tasks()[0].name(), Some("waiter"));
+        }
+
+        #[test]
+        fn advances_time_when_stalled() {
+            static TIMER: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+            #[crate::task(embassy_executor = crate)]
+            async fn sleeper() {
+                TIMER.wait().await
+            }
+
+            let executor = executor(0);
+            executor.spawner().spawn(sleeper()).unwrap();
+
+            let mut fired = false;
+            executor
+                .run(|| {
+                    // Stand-in for a time driver with one pending timer.
+                    let pending = !fired;
+                    if pending {
+                        TIMER.signal(());
+                        fired = true;
+                    }
+                    pending
+                })
+                .unwrap();
+            assert!(fired);
+        }
+
+        #[test]
+        fn order_depends_on_seed() {
+            static ORDER: Mutex<Vec<u32>> = Mutex::new(Vec::new());
+
+            #[crate::task(embassy_executor = crate, pool_size = 8)]
+            async fn push(id: u32) {
+                ORDER.lock().unwrap().push(id);
+            }
+
+            let run = |seed| {
+                let executor = executor(seed);
+                for id in 0..8 {
+                    executor.spawner().spawn(push(id)).unwrap();
+                }
+                executor.run(|| false).unwrap();
+                core::mem::take(&mut *ORDER.lock().unwrap())
+            };
+
+            assert_eq!(run(1), run(1));
+            assert!((2..10).any(|seed| run(seed) != run(1)));
+        }
+    }
+}
@@ -0,0 +1,155 @@
+use core::cell::RefCell;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+
+use critical_section::{CriticalSection, Mutex};
+
+use crate::raw::{self, TaskRef};
+
+/// How a task exited.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ExitStatus {
+    /// The task's future ran to completion.
+    Completed,
+    /// The task was cancelled with [`JoinHandle::cancel()`] before it completed.
+    Cancelled,
+    /// The task exited, but its [`TaskStorage`](crate::raw::TaskStorage) has been spawned again more
+    /// than once since then, so how it exited is no longer known.
+    Unknown,
+}
+
+struct JoinInner {
+    /// Incremented every time the task storage is spawned.
+    generation: u32,
+    /// Exit status of the current generation, if it has exited.
+    status: Option<ExitStatus>,
+    /// Exit status of the previous generation.
+    prev_status: Option<ExitStatus>,
+    /// Waker of a task awaiting the exit of the current generation.
+    waker: Option<Waker>,
+}
+
+/// Join state stored in each task header.
+pub(crate) struct JoinState {
+    inner: Mutex<RefCell<JoinInner>>,
+}
+
+impl JoinState {
+    pub(crate) const fn new() -> Self {
+        Self {
+            inner: Mutex::new(RefCell::new(JoinInner {
+                generation: 0,
+                status: None,
+                prev_status: None,
+                waker: None,
+            })),
+        }
+    }
+
+    /// Start a new generation. Called when the task storage has been claimed for spawning.
+    pub(crate) fn spawn(&self) {
+        critical_section::with(|cs| {
+            let mut inner = self.inner.borrow_ref_mut(cs);
+            inner.generation = inner.generation.wrapping_add(1);
+            inner.prev_status = inner.status.take();
+            inner.waker = None;
+        })
+    }
+
+    /// Record the exit of the current generation and wake the joining task, if any.
+    ///
+    /// Must be called in the same critical section that despawns the task, so that
+    /// [`JoinHandle::cancel()`] can't observe a half-exited task.
+    pub(crate) fn exit(&self, cs: CriticalSection<'_>, status: ExitStatus) {
+        let mut inner = self.inner.borrow_ref_mut(cs);
+        inner.status = Some(status);
+        if let Some(waker) = inner.waker.take() {
+            waker.wake();
+        }
+    }
+
+    fn generation(&self) -> u32 {
+        critical_section::with(|cs| self.inner.borrow_ref(cs).generation)
+    }
+}
+
+/// Handle to a spawned task.
+///
+/// A `JoinHandle` is obtained with [`Spawner::spawn_with_handle()`](crate::Spawner::spawn_with_handle) or
+/// [`SendSpawner::spawn_with_handle()`](crate::SendSpawner::spawn_with_handle). It can be used to
+/// [cancel](JoinHandle::cancel) the task, and it can be `.await`ed to wait for the task to exit.
+///
+/// Dropping a `JoinHandle` detaches it from the task, the task keeps running.
+///
+/// Task functions declared with `#[embassy_executor::task]` can only return `()` or `!`, so the only
+/// information returned on exit is the [`ExitStatus`].
+pub struct JoinHandle {
+    task: TaskRef,
+    generation: u32,
+}
+
+impl JoinHandle {
+    pub(crate) fn new(task: TaskRef) -> Self {
+        Self {
+            task,
+            generation: task.header().join.generation(),
+        }
+    }
+
+    /// Request the task to be cancelled.
+    ///
+    /// The task's future is dropped the next time the executor would poll it, and its
+    /// [`TaskStorage`](crate::raw::TaskStorage) is released so it can be spawned again. The task
+    /// is woken so this happens promptly even if it is currently waiting for something.
+    ///
+    /// Does nothing if the task has already exited.
+    pub fn cancel(&self) {
+        let header = self.task.header();
+        let marked = critical_section::with(|cs| {
+            let inner = header.join.inner.borrow_ref(cs);
+            inner.generation == self.generation && inner.status.is_none() && header.state.cancel()
+        });
+
+        if marked {
+            raw::wake_task(self.task);
+        }
+    }
+
+    /// Return whether the task has exited.
+    pub fn is_finished(&self) -> bool {
+        critical_section::with(|cs| {
+            let inner = self.task.header().join.inner.borrow_ref(cs);
+            inner.generation != self.generation || inner.status.is_some()
+        })
+    }
+
+    fn poll_exit(&self, waker: &Waker) -> Poll<ExitStatus> {
+        critical_section::with(|cs| {
+            let mut inner = self.task.header().join.inner.borrow_ref_mut(cs);
+            if inner.generation == self.generation.wrapping_add(1) {
+                // The task storage has been spawned again since the task exited.
+                Poll::Ready(inner.prev_status.unwrap_or(ExitStatus::Unknown))
+            } else if inner.generation != self.generation {
+                Poll::Ready(ExitStatus::Unknown)
+            } else if let Some(status) = inner.status {
+                Poll::Ready(status)
+            } else {
+                match &inner.waker {
+                    Some(w) if w.will_wake(waker) => {}
+                    _ => inner.waker = Some(waker.clone()),
+                }
+                Poll::Pending
+            }
+        })
+    }
+}
+
+impl Future for JoinHandle {
+    type Output = ExitStatus;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.poll_exit(cx.waker())
+    }
+}
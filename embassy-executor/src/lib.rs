@@ -1,4 +1,4 @@
-#![cfg_attr(not(any(feature = "arch-std", feature = "arch-wasm")), no_std)]
+#![cfg_attr(not(any(feature = "arch-std", feature = "arch-wasm", feature = "arch-sim")), no_std)]
 #![allow(clippy::new_without_default)]
 #![doc = include_str!("../README.md")]
 #![warn(missing_docs)]
@@ -30,6 +30,7 @@ check_at_most_one!(
     "arch-std",
     "arch-wasm",
     "arch-spin",
+    "arch-sim",
 );
 
 #[cfg(feature = "_arch")]
@@ -39,6 +40,7 @@ check_at_most_one!(
 #[cfg_attr(feature = "arch-std", path = "arch/std.rs")]
 #[cfg_attr(feature = "arch-wasm", path = "arch/wasm.rs")]
 #[cfg_attr(feature = "arch-spin", path = "arch/spin.rs")]
+#[cfg_attr(feature = "arch-sim", path = "arch/sim.rs")]
 mod arch;
 
 #[cfg(feature = "_arch")]
@@ -50,6 +52,16 @@ pub mod raw;
 mod spawner;
 pub use spawner::*;
 
+#[cfg(feature = "join-handle")]
+mod join;
+#[cfg(feature = "join-handle")]
+pub use join::*;
+
+#[cfg(feature = "metrics")]
+mod metrics;
+#[cfg(feature = "metrics")]
+pub use metrics::*;
+
 mod config {
     #![allow(unused)]
     include!(concat!(env!("OUT_DIR"), "/config.rs"));
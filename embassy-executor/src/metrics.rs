@@ -0,0 +1,71 @@
+use core::cell::Cell;
+
+use critical_section::Mutex;
+
+/// Runtime statistics of a task.
+///
+/// Obtained with [`TaskRef::metrics()`](crate::raw::TaskRef::metrics). The statistics are reset
+/// every time the task is spawned.
+///
+/// All times are in ticks of the time driver, see [`embassy_time_driver::TICK_HZ`].
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct TaskMetrics {
+    /// Number of times the task's future has been polled.
+    pub poll_count: u64,
+    /// Total time spent polling the task's future.
+    pub total_poll_time: u64,
+    /// Longest time spent in a single poll of the task's future.
+    pub max_poll_time: u64,
+    /// Timestamp of the last time the task was enqueued to run, either by being woken or by being spawned.
+    pub last_woken: u64,
+}
+
+/// Metrics state stored in each task header.
+pub(crate) struct MetricsState {
+    metrics: Mutex<Cell<TaskMetrics>>,
+}
+
+impl MetricsState {
+    pub(crate) const fn new() -> Self {
+        Self {
+            metrics: Mutex::new(Cell::new(TaskMetrics {
+                poll_count: 0,
+                total_poll_time: 0,
+                max_poll_time: 0,
+                last_woken: 0,
+            })),
+        }
+    }
+
+    /// Clear the statistics. Called when the task storage has been claimed for spawning.
+    pub(crate) fn spawn(&self) {
+        critical_section::with(|cs| self.metrics.borrow(cs).set(TaskMetrics::default()))
+    }
+
+    pub(crate) fn get(&self) -> TaskMetrics {
+        critical_section::with(|cs| self.metrics.borrow(cs).get())
+    }
+
+    /// Record a poll of the task's future that took `elapsed` ticks.
+    pub(crate) fn record_poll(&self, elapsed: u64) {
+        critical_section::with(|cs| {
+            let cell = self.metrics.borrow(cs);
+            let mut m = cell.get();
+            m.poll_count = m.poll_count.wrapping_add(1);
+            m.total_poll_time = m.total_poll_time.wrapping_add(elapsed);
+            m.max_poll_time = m.max_poll_time.max(elapsed);
+            cell.set(m);
+        })
+    }
+
+    /// Record that the task has been enqueued to run at time `now`.
+    pub(crate) fn record_wake(&self, now: u64) {
+        critical_section::with(|cs| {
+            let cell = self.metrics.borrow(cs);
+            let mut m = cell.get();
+            m.last_woken = now;
+            cell.set(m);
+        })
+    }
+}
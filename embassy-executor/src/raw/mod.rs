@@ -16,6 +16,8 @@ mod run_queue;
 #[cfg_attr(not(target_has_atomic = "8"), path = "state_critical_section.rs")]
 mod state;
 
+#[cfg(feature = "task-registry")]
+mod registry;
 pub mod timer_queue;
 #[cfg(feature = "trace")]
 mod trace;
@@ -31,12 +33,22 @@ use core::ptr::NonNull;
 use core::sync::atomic::{AtomicPtr, Ordering};
 use core::task::{Context, Poll};
 
+#[cfg(feature = "task-registry")]
+pub use self::registry::TaskIter;
+#[cfg(feature = "task-registry")]
+use self::registry::{RegistryItem, TaskRegistry};
 use self::run_queue::{RunQueue, RunQueueItem};
 use self::state::State;
 use self::util::{SyncUnsafeCell, UninitCell};
 pub use self::waker::task_from_waker;
 use super::SpawnToken;
 
+/// Number of priority levels of the run queue.
+///
+/// Task priorities range from `0` (the default, lowest) to `PRIORITY_LEVELS - 1` (highest).
+#[cfg(feature = "task-priority")]
+pub const PRIORITY_LEVELS: usize = 4;
+
 /// Raw task header for use in task pointers.
 ///
 /// A task can be in one of the following states:
@@ -46,6 +58,8 @@ use super::SpawnToken;
 /// - `RUN_ENQUEUED`: the task is enqueued to be polled. Note that the task may be `!SPAWNED`.
 ///    In this case, the `RUN_ENQUEUED` state will be cleared when the task is next polled, without
 ///    polling the task's future.
+/// - `CANCELLED` (only with the `join-handle` feature): the task has been asked to exit. Its future is
+///    dropped instead of polled the next time the task is polled. Cleared when the task is despawned.
 ///
 /// A task's complete life cycle is as follows:
 ///
@@ -84,6 +98,19 @@ pub(crate) struct TaskHeader {
 
     /// Integrated timer queue storage. This field should not be accessed outside of the timer queue.
     pub(crate) timer_queue_item: timer_queue::TimerQueueItem,
+
+    #[cfg(feature = "join-handle")]
+    pub(crate) join: crate::join::JoinState,
+
+    /// Run queue priority level. Only written between claiming and spawning the task,
+    /// when it can't be enqueued by anyone else.
+    #[cfg(feature = "task-priority")]
+    pub(crate) priority: SyncUnsafeCell<u8>,
+
+    #[cfg(feature = "task-registry")]
+    pub(crate) registry_item: RegistryItem,
+    #[cfg(feature = "metrics")]
+    pub(crate) metrics: crate::metrics::MetricsState,
 }
 
 /// This is essentially a `&'static TaskStorage<F>` where the type of the future has been erased.
@@ -134,6 +161,21 @@ impl TaskRef {
         &self.header().timer_queue_item
     }
 
+    /// Returns the name of the task, if it has one.
+    ///
+    /// Tasks spawned with the [`embassy_executor::task`](embassy_executor_macros::task) macro are
+    /// named after their task function, unless a name is set with `#[task(name = "...")]`.
+    #[cfg(feature = "task-registry")]
+    pub fn name(&self) -> Option<&'static str> {
+        self.header().registry_item.name()
+    }
+
+    /// Returns the runtime statistics of the task.
+    #[cfg(feature = "metrics")]
+    pub fn metrics(&self) -> crate::TaskMetrics {
+        self.header().metrics.get()
+    }
+
     /// The returned pointer is valid for the entire TaskStorage.
     pub(crate) fn as_ptr(self) -> *const TaskHeader {
         self.ptr.as_ptr()
@@ -179,6 +221,17 @@ impl<F: Future + 'static> TaskStorage<F> {
                 poll_fn: SyncUnsafeCell::new(None),
 
                 timer_queue_item: timer_queue::TimerQueueItem::new(),
+
+                #[cfg(feature = "join-handle")]
+                join: crate::join::JoinState::new(),
+
+                #[cfg(feature = "task-priority")]
+                priority: SyncUnsafeCell::new(0),
+
+                #[cfg(feature = "task-registry")]
+                registry_item: RegistryItem::new(),
+                #[cfg(feature = "metrics")]
+                metrics: crate::metrics::MetricsState::new(),
             },
             future: UninitCell::uninit(),
         }
@@ -208,22 +261,36 @@ impl<F: Future + 'static> TaskStorage<F> {
     unsafe fn poll(p: TaskRef) {
         let this = &*p.as_ptr().cast::<TaskStorage<F>>();
 
+        #[cfg(feature = "join-handle")]
+        if this.raw.state.is_cancelled() {
+            // The task was cancelled while it was waiting. Drop the future without polling it.
+            this.future.drop_in_place();
+            this.exit(crate::ExitStatus::Cancelled);
+            return;
+        }
+
         let future = Pin::new_unchecked(this.future.as_mut());
         let waker = waker::from_task(p);
         let mut cx = Context::from_waker(&waker);
-        match future.poll(&mut cx) {
+
+        #[cfg(feature = "metrics")]
+        let start = embassy_time_driver::now();
+        let poll = future.poll(&mut cx);
+        #[cfg(feature = "metrics")]
+        this.raw
+            .metrics
+            .record_poll(embassy_time_driver::now().saturating_sub(start));
+
+        match poll {
             Poll::Ready(_) => {
                 // As the future has finished and this function will not be called
                 // again, we can safely drop the future here.
                 this.future.drop_in_place();
 
-                // We replace the poll_fn with a despawn function, so that the task is cleaned up
-                // when the executor polls it next.
-                this.raw.poll_fn.set(Some(poll_exited));
-
-                // Make sure we despawn last, so that other threads can only spawn the task
-                // after we're done with it.
-                this.raw.state.despawn();
+                #[cfg(feature = "join-handle")]
+                this.exit(crate::ExitStatus::Completed);
+                #[cfg(not(feature = "join-handle"))]
+                this.exit();
             }
             Poll::Pending => {}
         }
@@ -233,6 +300,29 @@ impl<F: Future + 'static> TaskStorage<F> {
         mem::forget(waker);
     }
 
+    /// Clean up after the task's future has been dropped.
+    unsafe fn exit(&self, #[cfg(feature = "join-handle")] status: crate::ExitStatus) {
+        // We replace the poll_fn with a despawn function, so that the task is cleaned up
+        // when the executor polls it next.
+        self.raw.poll_fn.set(Some(poll_exited));
+
+        #[cfg(feature = "task-registry")]
+        {
+            let executor = self.raw.executor.load(Ordering::Relaxed).as_ref().unwrap_unchecked();
+            executor.registry.remove(TaskRef::from_ptr(&self.raw));
+        }
+
+        // Make sure we despawn last, so that other threads can only spawn the task
+        // after we're done with it.
+        #[cfg(feature = "join-handle")]
+        critical_section::with(|cs| {
+            self.raw.join.exit(cs, status);
+            self.raw.state.despawn();
+        });
+        #[cfg(not(feature = "join-handle"))]
+        self.raw.state.despawn();
+    }
+
     #[doc(hidden)]
     #[allow(dead_code)]
     fn _assert_sync(self) {
@@ -260,6 +350,15 @@ impl<F: Future + 'static> AvailableTask<F> {
             self.task.raw.poll_fn.set(Some(TaskStorage::<F>::poll));
             self.task.future.write_in_place(future);
 
+            #[cfg(feature = "join-handle")]
+            self.task.raw.join.spawn();
+            #[cfg(feature = "task-priority")]
+            self.task.raw.priority.set(0);
+            #[cfg(feature = "task-registry")]
+            self.task.raw.registry_item.spawn();
+            #[cfg(feature = "metrics")]
+            self.task.raw.metrics.spawn();
+
             let task = TaskRef::new(self.task);
 
             SpawnToken::new(task)
@@ -375,15 +474,26 @@ impl Pender {
 }
 
 pub(crate) struct SyncExecutor {
+    #[cfg(not(feature = "task-priority"))]
     run_queue: RunQueue,
+    /// One run queue per priority level, lowest first.
+    #[cfg(feature = "task-priority")]
+    run_queue: [RunQueue; PRIORITY_LEVELS],
     pender: Pender,
+    #[cfg(feature = "task-registry")]
+    registry: TaskRegistry,
 }
 
 impl SyncExecutor {
     pub(crate) fn new(pender: Pender) -> Self {
         Self {
+            #[cfg(not(feature = "task-priority"))]
             run_queue: RunQueue::new(),
+            #[cfg(feature = "task-priority")]
+            run_queue: core::array::from_fn(|_| RunQueue::new()),
             pender,
+            #[cfg(feature = "task-registry")]
+            registry: TaskRegistry::new(),
         }
     }
 
@@ -397,12 +507,26 @@ impl SyncExecutor {
     unsafe fn enqueue(&self, task: TaskRef, l: state::Token) {
         #[cfg(feature = "trace")]
         trace::task_ready_begin(self, &task);
+        #[cfg(feature = "metrics")]
+        task.header().metrics.record_wake(embassy_time_driver::now());
 
-        if self.run_queue.enqueue(task, l) {
+        if self.run_queue_for(task).enqueue(task, l) {
             self.pender.pend();
         }
     }
 
+    /// The run queue `task` must be enqueued in.
+    #[inline(always)]
+    fn run_queue_for(&self, task: TaskRef) -> &RunQueue {
+        #[cfg(feature = "task-priority")]
+        return unsafe { &self.run_queue[task.header().priority.get() as usize] };
+        #[cfg(not(feature = "task-priority"))]
+        {
+            let _ = task;
+            &self.run_queue
+        }
+    }
+
     pub(super) unsafe fn spawn(&'static self, task: TaskRef) {
         task.header()
             .executor
@@ -410,6 +534,8 @@ impl SyncExecutor {
 
         #[cfg(feature = "trace")]
         trace::task_new(self, &task);
+        #[cfg(feature = "task-registry")]
+        TaskRegistry::insert(self, task);
 
         state::locked(|l| {
             self.enqueue(task, l);
@@ -420,22 +546,78 @@ impl SyncExecutor {
     ///
     /// Same as [`Executor::poll`], plus you must only call this on the thread this executor was created.
     pub(crate) unsafe fn poll(&'static self) {
-        self.run_queue.dequeue_all(|p| {
-            let task = p.header();
+        #[cfg(not(feature = "task-priority"))]
+        self.run_queue.dequeue_all(|p| self.poll_task(p));
+        #[cfg(feature = "task-priority")]
+        self.poll_levels(0);
 
-            #[cfg(feature = "trace")]
-            trace::task_exec_begin(self, &p);
+        #[cfg(feature = "trace")]
+        trace::executor_idle(self)
+    }
 
-            // Run the task
-            task.poll_fn.get().unwrap_unchecked()(p);
+    /// Poll one batch of each priority level from `min` upwards, highest level first.
+    ///
+    /// Before each task is polled, higher-priority tasks that became ready in the meantime
+    /// are polled first.
+    #[cfg(feature = "task-priority")]
+    unsafe fn poll_levels(&'static self, min: usize) {
+        for level in (min..PRIORITY_LEVELS).rev() {
+            self.run_queue[level].dequeue_all(|p| {
+                if level + 1 < PRIORITY_LEVELS {
+                    self.poll_levels(level + 1);
+                }
+                self.poll_task(p);
+            });
+        }
+    }
 
-            #[cfg(feature = "trace")]
-            trace::task_exec_end(self, &p);
-        });
+    /// Like [`poll`](Self::poll), but the batch of queued tasks is collected first, and `order`
+    /// can reorder it before the tasks are polled.
+    ///
+    /// A task woken while the batch is being polled is enqueued again, even if it's still in the
+    /// batch. It is then polled a second time in the next batch.
+    ///
+    /// With the `task-priority` feature, each priority level is collected and reordered
+    /// separately, highest level first.
+    ///
+    /// # Safety
+    ///
+    /// Same as [`poll`](Self::poll).
+    #[cfg(feature = "arch-sim")]
+    pub(crate) unsafe fn poll_ordered(&'static self, mut order: impl FnMut(&mut [TaskRef])) {
+        #[cfg(not(feature = "task-priority"))]
+        let queues = core::slice::from_ref(&self.run_queue);
+        #[cfg(feature = "task-priority")]
+        let queues = &self.run_queue;
+
+        for queue in queues.iter().rev() {
+            let batch = core::cell::RefCell::new(std::vec::Vec::new());
+            queue.dequeue_all(|p| batch.borrow_mut().push(p));
+
+            let mut batch = batch.into_inner();
+            order(&mut batch);
+            for p in batch {
+                self.poll_task(p);
+            }
+        }
 
         #[cfg(feature = "trace")]
         trace::executor_idle(self)
     }
+
+    #[inline(always)]
+    unsafe fn poll_task(&'static self, p: TaskRef) {
+        let task = p.header();
+
+        #[cfg(feature = "trace")]
+        trace::task_exec_begin(self, &p);
+
+        // Run the task
+        task.poll_fn.get().unwrap_unchecked()(p);
+
+        #[cfg(feature = "trace")]
+        trace::task_exec_end(self, &p);
+    }
 }
 
 /// Raw executor.
@@ -540,6 +722,12 @@ impl Executor {
     pub fn spawner(&'static self) -> super::Spawner {
         super::Spawner::new(self)
     }
+
+    /// Iterate over the tasks currently spawned in this executor.
+    #[cfg(feature = "task-registry")]
+    pub fn tasks(&'static self) -> TaskIter {
+        self.inner.registry.iter(&self.inner)
+    }
 }
 
 /// Wake a task by `TaskRef`.
@@ -565,7 +753,11 @@ pub fn wake_task_no_pend(task: TaskRef) {
         // We have just marked the task as scheduled, so enqueue it.
         unsafe {
             let executor = header.executor.load(Ordering::Relaxed).as_ref().unwrap_unchecked();
-            executor.run_queue.enqueue(task, l);
+
+            #[cfg(feature = "metrics")]
+            header.metrics.record_wake(embassy_time_driver::now());
+
+            executor.run_queue_for(task).enqueue(task, l);
         }
     });
 }
@@ -0,0 +1,149 @@
+use core::cell::Cell;
+
+use critical_section::Mutex;
+
+use super::{SyncExecutor, TaskRef};
+
+pub(crate) struct RegistryItem {
+    next: Mutex<Cell<Option<TaskRef>>>,
+    name: Mutex<Cell<Option<&'static str>>>,
+}
+
+impl RegistryItem {
+    pub const fn new() -> Self {
+        Self {
+            next: Mutex::new(Cell::new(None)),
+            name: Mutex::new(Cell::new(None)),
+        }
+    }
+
+    /// Clear the name. Called when the task storage has been claimed for spawning.
+    pub(crate) fn spawn(&self) {
+        critical_section::with(|cs| self.name.borrow(cs).set(None))
+    }
+
+    pub(crate) fn set_name(&self, name: &'static str) {
+        critical_section::with(|cs| self.name.borrow(cs).set(Some(name)))
+    }
+
+    pub(crate) fn name(&self) -> Option<&'static str> {
+        critical_section::with(|cs| self.name.borrow(cs).get())
+    }
+}
+
+/// Head of the list of all executors that have spawned at least one task.
+static EXECUTORS: Mutex<Cell<Option<&'static SyncExecutor>>> = Mutex::new(Cell::new(None));
+
+/// Intrusive linked list of all tasks currently spawned in an executor.
+///
+/// Tasks are pushed to the front when spawned, and unlinked when they exit.
+///
+/// Registries are themselves linked in a global list the first time a task is
+/// inserted, so that tasks can be listed without a reference to their executor.
+pub(crate) struct TaskRegistry {
+    head: Mutex<Cell<Option<TaskRef>>>,
+    next_executor: Mutex<Cell<Option<&'static SyncExecutor>>>,
+    listed: Mutex<Cell<bool>>,
+}
+
+impl TaskRegistry {
+    pub const fn new() -> Self {
+        Self {
+            head: Mutex::new(Cell::new(None)),
+            next_executor: Mutex::new(Cell::new(None)),
+            listed: Mutex::new(Cell::new(false)),
+        }
+    }
+
+    /// Add a task to the registry of `executor`.
+    ///
+    /// # Safety
+    ///
+    /// `task` must NOT be already in any registry.
+    pub(crate) unsafe fn insert(executor: &'static SyncExecutor, task: TaskRef) {
+        let this = &executor.registry;
+        critical_section::with(|cs| {
+            if !this.listed.borrow(cs).replace(true) {
+                let prev = EXECUTORS.borrow(cs).replace(Some(executor));
+                this.next_executor.borrow(cs).set(prev);
+            }
+
+            let prev = this.head.borrow(cs).replace(Some(task));
+            task.header().registry_item.next.borrow(cs).set(prev);
+        })
+    }
+
+    /// Remove a task from the registry. Does nothing if the task is not in it.
+    pub(crate) fn remove(&self, task: TaskRef) {
+        critical_section::with(|cs| {
+            let mut link = &self.head;
+            while let Some(t) = link.borrow(cs).get() {
+                let next = &t.header().registry_item.next;
+                if t == task {
+                    link.borrow(cs).set(next.borrow(cs).get());
+                    next.borrow(cs).set(None);
+                    return;
+                }
+                link = next;
+            }
+        })
+    }
+
+    pub(crate) fn iter(&'static self, executor: &'static SyncExecutor) -> TaskIter {
+        TaskIter {
+            next: critical_section::with(|cs| self.head.borrow(cs).get()),
+            executor,
+        }
+    }
+}
+
+/// Call `f` for every task spawned in any executor.
+///
+/// This holds a critical section for the whole iteration, so `f` should be quick.
+#[cfg(feature = "rtos-trace")]
+pub(crate) fn for_each_task(mut f: impl FnMut(TaskRef)) {
+    critical_section::with(|cs| {
+        let mut executor = EXECUTORS.borrow(cs).get();
+        while let Some(e) = executor {
+            let mut task = e.registry.head.borrow(cs).get();
+            while let Some(t) = task {
+                f(t);
+                task = t.header().registry_item.next.borrow(cs).get();
+            }
+            executor = e.registry.next_executor.borrow(cs).get();
+        }
+    })
+}
+
+/// Iterator over the tasks spawned in an executor.
+///
+/// Returned by [`Executor::tasks()`](super::Executor::tasks) and [`Spawner::tasks()`](crate::Spawner::tasks).
+///
+/// Tasks spawned while iterating may or may not be returned. If a task exits while iterating,
+/// the iteration may end early.
+pub struct TaskIter {
+    next: Option<TaskRef>,
+    executor: &'static SyncExecutor,
+}
+
+impl Iterator for TaskIter {
+    type Item = TaskRef;
+
+    fn next(&mut self) -> Option<TaskRef> {
+        let task = self.next?;
+        let header = task.header();
+
+        // If the task has exited and has been respawned in another executor since we got the
+        // pointer to it, its `next` pointer now belongs to another list. Stop here.
+        if !core::ptr::eq(
+            header.executor.load(core::sync::atomic::Ordering::Relaxed),
+            self.executor,
+        ) {
+            self.next = None;
+            return None;
+        }
+
+        self.next = critical_section::with(|cs| header.registry_item.next.borrow(cs).get());
+        Some(task)
+    }
+}
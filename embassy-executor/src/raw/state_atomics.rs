@@ -14,6 +14,9 @@ pub(crate) fn locked<R>(f: impl FnOnce(Token) -> R) -> R {
 pub(crate) const STATE_SPAWNED: u32 = 1 << 0;
 /// Task is in the executor run queue
 pub(crate) const STATE_RUN_QUEUED: u32 = 1 << 1;
+/// Task has been asked to exit at its next poll
+#[cfg(feature = "join-handle")]
+pub(crate) const STATE_CANCELLED: u32 = 1 << 2;
 
 pub(crate) struct State {
     state: AtomicU32,
@@ -37,9 +40,30 @@ impl State {
     /// Unmark the task as spawned.
     #[inline(always)]
     pub fn despawn(&self) {
+        #[cfg(feature = "join-handle")]
+        self.state
+            .fetch_and(!(STATE_SPAWNED | STATE_CANCELLED), Ordering::AcqRel);
+        #[cfg(not(feature = "join-handle"))]
         self.state.fetch_and(!STATE_SPAWNED, Ordering::AcqRel);
     }
 
+    /// Mark the task as cancelled if it's spawned. Return whether the task was marked.
+    #[cfg(feature = "join-handle")]
+    #[inline(always)]
+    pub fn cancel(&self) -> bool {
+        let prev = self.state.fetch_update(Ordering::AcqRel, Ordering::Acquire, |s| {
+            (s & STATE_SPAWNED != 0).then_some(s | STATE_CANCELLED)
+        });
+        prev.is_ok()
+    }
+
+    /// Return whether the task has been marked as cancelled.
+    #[cfg(feature = "join-handle")]
+    #[inline(always)]
+    pub fn is_cancelled(&self) -> bool {
+        self.state.load(Ordering::Acquire) & STATE_CANCELLED != 0
+    }
+
     /// Mark the task as run-queued if it's spawned and isn't already run-queued. Run the given
     /// function if the task was successfully marked.
     #[inline(always)]
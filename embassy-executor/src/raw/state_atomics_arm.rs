@@ -13,6 +13,8 @@ pub(crate) fn locked<R>(f: impl FnOnce(Token) -> R) -> R {
 // Must be kept in sync with the layout of `State`!
 pub(crate) const STATE_SPAWNED: u32 = 1 << 0;
 pub(crate) const STATE_RUN_QUEUED: u32 = 1 << 8;
+#[cfg(feature = "join-handle")]
+pub(crate) const STATE_CANCELLED: u32 = 1 << 16;
 
 #[repr(C, align(4))]
 pub(crate) struct State {
@@ -20,8 +22,9 @@ pub(crate) struct State {
     spawned: AtomicBool,
     /// Task is in the executor run queue
     run_queued: AtomicBool,
+    /// Task has been asked to exit at its next poll
+    cancelled: AtomicBool,
     pad: AtomicBool,
-    pad2: AtomicBool,
 }
 
 impl State {
@@ -29,8 +32,8 @@ impl State {
         Self {
             spawned: AtomicBool::new(false),
             run_queued: AtomicBool::new(false),
+            cancelled: AtomicBool::new(false),
             pad: AtomicBool::new(false),
-            pad2: AtomicBool::new(false),
         }
     }
 
@@ -59,9 +62,28 @@ impl State {
     #[inline(always)]
     pub fn despawn(&self) {
         compiler_fence(Ordering::Release);
+        #[cfg(feature = "join-handle")]
+        self.cancelled.store(false, Ordering::Relaxed);
         self.spawned.store(false, Ordering::Relaxed);
     }
 
+    /// Mark the task as cancelled if it's spawned. Return whether the task was marked.
+    #[cfg(feature = "join-handle")]
+    #[inline(always)]
+    pub fn cancel(&self) -> bool {
+        let prev = self.as_u32().fetch_update(Ordering::AcqRel, Ordering::Acquire, |s| {
+            (s & STATE_SPAWNED != 0).then_some(s | STATE_CANCELLED)
+        });
+        prev.is_ok()
+    }
+
+    /// Return whether the task has been marked as cancelled.
+    #[cfg(feature = "join-handle")]
+    #[inline(always)]
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Acquire)
+    }
+
     /// Mark the task as run-queued if it's spawned and isn't already run-queued. Run the given
     /// function if the task was successfully marked.
     #[inline(always)]
@@ -7,6 +7,9 @@ use critical_section::{CriticalSection, Mutex};
 pub(crate) const STATE_SPAWNED: u32 = 1 << 0;
 /// Task is in the executor run queue
 pub(crate) const STATE_RUN_QUEUED: u32 = 1 << 1;
+/// Task has been asked to exit at its next poll
+#[cfg(feature = "join-handle")]
+pub(crate) const STATE_CANCELLED: u32 = 1 << 2;
 
 pub(crate) struct State {
     state: Mutex<Cell<u32>>,
@@ -47,9 +50,33 @@ impl State {
     /// Unmark the task as spawned.
     #[inline(always)]
     pub fn despawn(&self) {
+        #[cfg(feature = "join-handle")]
+        self.update(|s| *s &= !(STATE_SPAWNED | STATE_CANCELLED));
+        #[cfg(not(feature = "join-handle"))]
         self.update(|s| *s &= !STATE_SPAWNED);
     }
 
+    /// Mark the task as cancelled if it's spawned. Return whether the task was marked.
+    #[cfg(feature = "join-handle")]
+    #[inline(always)]
+    pub fn cancel(&self) -> bool {
+        self.update(|s| {
+            if *s & STATE_SPAWNED != 0 {
+                *s |= STATE_CANCELLED;
+                true
+            } else {
+                false
+            }
+        })
+    }
+
+    /// Return whether the task has been marked as cancelled.
+    #[cfg(feature = "join-handle")]
+    #[inline(always)]
+    pub fn is_cancelled(&self) -> bool {
+        self.update(|s| *s & STATE_CANCELLED != 0)
+    }
+
     /// Mark the task as run-queued if it's spawned and isn't already run-queued. Run the given
     /// function if the task was successfully marked.
     #[inline(always)]
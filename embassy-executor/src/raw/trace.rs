@@ -18,7 +18,25 @@ pub(crate) fn task_new(executor: &SyncExecutor, task: &TaskRef) {
     }
 
     #[cfg(feature = "rtos-trace")]
-    rtos_trace::trace::task_new(task.as_ptr() as u32);
+    {
+        rtos_trace::trace::task_new(task.as_ptr() as u32);
+        send_task_info(task);
+    }
+}
+
+#[cfg(feature = "rtos-trace")]
+fn send_task_info(task: &TaskRef) {
+    if let Some(name) = task.name() {
+        rtos_trace::trace::task_send_info(
+            task.as_ptr() as u32,
+            rtos_trace::TaskInfo {
+                name,
+                priority: 0,
+                stack_base: 0,
+                stack_size: 0,
+            },
+        );
+    }
 }
 
 #[inline]
@@ -64,7 +82,7 @@ pub(crate) fn executor_idle(executor: &SyncExecutor) {
 #[cfg(feature = "rtos-trace")]
 impl rtos_trace::RtosTraceOSCallbacks for crate::raw::SyncExecutor {
     fn task_list() {
-        // We don't know what tasks exist, so we can't send them.
+        super::registry::for_each_task(|task| send_task_info(&task));
     }
     fn time() -> u64 {
         const fn gcd(a: u64, b: u64) -> u64 {
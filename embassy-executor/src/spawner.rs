@@ -41,6 +41,41 @@ impl<S> SpawnToken<S> {
             phantom: PhantomData,
         }
     }
+
+    /// Set the priority level of the task.
+    ///
+    /// Ready tasks with a higher priority are always polled before ready tasks with a lower
+    /// priority in the same executor. The default priority is `0`, the lowest.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `priority` is not less than [`PRIORITY_LEVELS`](raw::PRIORITY_LEVELS).
+    #[cfg(feature = "task-priority")]
+    pub fn with_priority(self, priority: u8) -> Self {
+        assert!(
+            (priority as usize) < raw::PRIORITY_LEVELS,
+            "task priority must be less than PRIORITY_LEVELS"
+        );
+        if let Some(task) = self.raw_task {
+            // safety: the task has been claimed but not spawned yet, so it can't be enqueued concurrently.
+            unsafe { task.header().priority.set(priority) };
+        }
+        self
+    }
+
+    /// Set the name of the task. Does nothing if the `task-registry` feature is disabled.
+    ///
+    /// Not covered by semver guarantees. DO NOT call this directly. Intended to be used
+    /// by the Embassy macros ONLY.
+    #[doc(hidden)]
+    #[allow(unused_variables)]
+    pub fn _with_name(self, name: &'static str) -> Self {
+        #[cfg(feature = "task-registry")]
+        if let Some(task) = self.raw_task {
+            task.header().registry_item.set_name(name);
+        }
+        self
+    }
 }
 
 impl<S> Drop for SpawnToken<S> {
@@ -145,6 +180,26 @@ impl Spawner {
         }
     }
 
+    /// Spawn a task into an executor, returning a [`JoinHandle`](crate::JoinHandle) to it.
+    ///
+    /// The handle can be used to cancel the task, or to wait for it to exit.
+    ///
+    /// You obtain the `token` by calling a task function (i.e. one marked with `#[embassy_executor::task]`).
+    #[cfg(feature = "join-handle")]
+    pub fn spawn_with_handle<S>(&self, token: SpawnToken<S>) -> Result<crate::JoinHandle, SpawnError> {
+        let task = token.raw_task;
+        mem::forget(token);
+
+        match task {
+            Some(task) => {
+                let handle = crate::JoinHandle::new(task);
+                unsafe { self.executor.spawn(task) };
+                Ok(handle)
+            }
+            None => Err(SpawnError::Busy),
+        }
+    }
+
     // Used by the `embassy_executor_macros::main!` macro to throw an error when spawn
     // fails. This is here to allow conditional use of `defmt::unwrap!`
     // without introducing a `defmt` feature in the `embassy_executor_macros` package,
@@ -158,6 +213,15 @@ impl Spawner {
         unwrap!(self.spawn(token));
     }
 
+    /// Iterate over the tasks currently spawned in this spawner's executor.
+    ///
+    /// Together with [`TaskRef::name()`](raw::TaskRef::name), this can be used to list tasks by name,
+    /// for example to report their runtime statistics with the `metrics` feature.
+    #[cfg(feature = "task-registry")]
+    pub fn tasks(&self) -> raw::TaskIter {
+        self.executor.tasks()
+    }
+
     /// Convert this Spawner to a SendSpawner. This allows you to send the
     /// spawner to other threads, but the spawner loses the ability to spawn
     /// non-Send tasks.
@@ -221,6 +285,26 @@ impl SendSpawner {
         }
     }
 
+    /// Spawn a task into an executor, returning a [`JoinHandle`](crate::JoinHandle) to it.
+    ///
+    /// The handle can be used to cancel the task, or to wait for it to exit.
+    ///
+    /// You obtain the `token` by calling a task function (i.e. one marked with `#[embassy_executor::task]`).
+    #[cfg(feature = "join-handle")]
+    pub fn spawn_with_handle<S: Send>(&self, token: SpawnToken<S>) -> Result<crate::JoinHandle, SpawnError> {
+        let header = token.raw_task;
+        mem::forget(token);
+
+        match header {
+            Some(header) => {
+                let handle = crate::JoinHandle::new(header);
+                unsafe { self.executor.spawn(header) };
+                Ok(handle)
+            }
+            None => Err(SpawnError::Busy),
+        }
+    }
+
     /// Spawn a task into an executor, panicking on failure.
     ///
     /// # Panics
@@ -8,6 +8,7 @@ use std::task::Poll;
 use embassy_executor::raw::Executor;
 use embassy_executor::task;
 
+#[cfg(not(feature = "_arch"))]
 #[export_name = "__pender"]
 fn __pender(context: *mut ()) {
     unsafe {
@@ -16,11 +17,13 @@ fn __pender(context: *mut ()) {
     }
 }
 
+#[cfg(not(feature = "_arch"))]
 #[derive(Clone)]
 struct Trace {
     trace: Arc<Mutex<Vec<&'static str>>>,
 }
 
+#[cfg(not(feature = "_arch"))]
 impl Trace {
     fn new() -> Self {
         Self {
@@ -36,6 +39,7 @@ impl Trace {
     }
 }
 
+#[cfg(not(feature = "_arch"))]
 fn setup() -> (&'static Executor, Trace) {
     let trace = Trace::new();
     let context = Box::leak(Box::new(trace.clone())) as *mut _ as *mut ();
@@ -44,6 +48,7 @@ fn setup() -> (&'static Executor, Trace) {
     (executor, trace)
 }
 
+#[cfg(not(feature = "_arch"))]
 #[test]
 fn executor_noop() {
     let (executor, trace) = setup();
@@ -51,6 +56,7 @@ fn executor_noop() {
     assert!(trace.get().is_empty())
 }
 
+#[cfg(not(feature = "_arch"))]
 #[test]
 fn executor_task() {
     #[task]
@@ -73,6 +79,7 @@ fn executor_task() {
     )
 }
 
+#[cfg(not(feature = "_arch"))]
 #[test]
 fn executor_task_self_wake() {
     #[task]
@@ -103,6 +110,7 @@ fn executor_task_self_wake() {
     )
 }
 
+#[cfg(not(feature = "_arch"))]
 #[test]
 fn executor_task_self_wake_twice() {
     #[task]
@@ -137,6 +145,7 @@ fn executor_task_self_wake_twice() {
     )
 }
 
+#[cfg(not(feature = "_arch"))]
 #[test]
 fn waking_after_completion_does_not_poll() {
     use embassy_sync::waitqueue::AtomicWaker;
@@ -183,6 +192,7 @@ fn waking_after_completion_does_not_poll() {
     )
 }
 
+#[cfg(not(feature = "_arch"))]
 #[test]
 fn waking_with_old_waker_after_respawn() {
     use embassy_sync::waitqueue::AtomicWaker;
@@ -283,3 +293,324 @@ fn executor_task_cfg_args() {
         let (_, _, _) = (a, b, c);
     }
 }
+
+#[cfg(all(feature = "join-handle", not(feature = "_arch")))]
+#[test]
+fn join_handle_completed() {
+    use embassy_executor::{ExitStatus, JoinHandle};
+
+    #[task]
+    async fn task1(trace: Trace) {
+        trace.push("poll task1")
+    }
+
+    #[task]
+    async fn joiner(trace: Trace, handle: JoinHandle) {
+        assert_eq!(handle.await, ExitStatus::Completed);
+        trace.push("joined task1")
+    }
+
+    let (executor, trace) = setup();
+    let handle = executor.spawner().spawn_with_handle(task1(trace.clone())).unwrap();
+    assert!(!handle.is_finished());
+    executor.spawner().spawn(joiner(trace.clone(), handle)).unwrap();
+
+    unsafe { executor.poll() };
+    unsafe { executor.poll() };
+
+    assert_eq!(
+        trace.get(),
+        &[
+            "pend",         // spawning a task pends the executor
+            "poll task1",   //
+            "pend",         // task1 exiting wakes the joiner
+            "joined task1", //
+        ]
+    )
+}
+
+#[cfg(all(feature = "join-handle", not(feature = "_arch")))]
+#[test]
+fn join_handle_cancel() {
+    use embassy_executor::{ExitStatus, JoinHandle};
+
+    struct DropTrace(Trace);
+
+    impl Drop for DropTrace {
+        fn drop(&mut self) {
+            self.0.push("drop task1")
+        }
+    }
+
+    #[task]
+    async fn task1(trace: Trace) {
+        let _guard = DropTrace(trace.clone());
+        poll_fn(|_| {
+            trace.push("poll task1");
+            Poll::<()>::Pending
+        })
+        .await
+    }
+
+    #[task]
+    async fn joiner(trace: Trace, handle: JoinHandle) {
+        assert_eq!(handle.await, ExitStatus::Cancelled);
+        trace.push("joined task1")
+    }
+
+    let (executor, trace) = setup();
+    let handle = executor.spawner().spawn_with_handle(task1(trace.clone())).unwrap();
+    unsafe { executor.poll() };
+
+    // The pool is exhausted while task1 runs.
+    assert!(executor.spawner().spawn(task1(trace.clone())).is_err());
+
+    handle.cancel();
+    executor.spawner().spawn(joiner(trace.clone(), handle)).unwrap();
+    unsafe { executor.poll() };
+    unsafe { executor.poll() };
+
+    // The storage was released, so the task can be spawned again.
+    let handle = executor.spawner().spawn_with_handle(task1(trace.clone())).unwrap();
+    unsafe { executor.poll() };
+    assert!(!handle.is_finished());
+
+    assert_eq!(
+        trace.get(),
+        &[
+            "pend",         // spawning a task pends the executor
+            "poll task1",   //
+            "pend",         // cancelling wakes task1
+            "drop task1",   // task1 is dropped instead of polled
+            "pend",         // task1 exiting wakes the joiner
+            "joined task1", //
+            "pend",         // respawning task1 pends the executor
+            "poll task1",   //
+        ]
+    )
+}
+
+#[cfg(all(feature = "join-handle", not(feature = "_arch")))]
+#[test]
+fn join_handle_cancel_after_completion() {
+    use core::cell::RefCell;
+
+    use embassy_executor::{ExitStatus, JoinHandle};
+
+    // Cancels itself, but completes before it's polled again.
+    #[task]
+    async fn task1(trace: Trace, handle: &'static RefCell<Option<JoinHandle>>) {
+        if let Some(handle) = &*handle.borrow() {
+            handle.cancel();
+        }
+        trace.push("poll task1")
+    }
+
+    #[task]
+    async fn joiner(trace: Trace, handle: JoinHandle) {
+        assert_eq!(handle.await, ExitStatus::Completed);
+        trace.push("joined task1")
+    }
+
+    let handle = Box::leak(Box::new(RefCell::new(None)));
+
+    let (executor, trace) = setup();
+    *handle.borrow_mut() = Some(
+        executor
+            .spawner()
+            .spawn_with_handle(task1(trace.clone(), handle))
+            .unwrap(),
+    );
+    unsafe { executor.poll() };
+    unsafe { executor.poll() };
+    let first = handle.borrow_mut().take().unwrap();
+    assert!(first.is_finished());
+
+    // The storage is spawned again before the first handle is awaited.
+    executor.spawner().spawn(task1(trace.clone(), handle)).unwrap();
+    executor.spawner().spawn(joiner(trace.clone(), first)).unwrap();
+    unsafe { executor.poll() };
+
+    assert_eq!(
+        trace.get(),
+        &[
+            "pend",         // spawning a task pends the executor
+            "pend",         // cancelling wakes task1
+            "poll task1",   //
+            "pend",         // respawning task1 pends the executor
+            "joined task1", // the joiner was queued last, so it runs first
+            "poll task1",   //
+        ]
+    )
+}
+
+#[cfg(all(feature = "metrics", not(feature = "_arch")))]
+#[test]
+fn metrics() {
+    use embassy_time::{Duration, MockDriver};
+
+    #[task]
+    async fn busy_task() {
+        let mut polls = 0;
+        poll_fn(|cx| {
+            // Pretend each poll takes 10 ticks, then 20 ticks.
+            polls += 1;
+            MockDriver::get().advance(Duration::from_ticks(polls * 10));
+            if polls == 2 {
+                Poll::Ready(())
+            } else {
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+        })
+        .await;
+        poll_fn(|_| Poll::<()>::Pending).await
+    }
+
+    #[task]
+    async fn idle_task() {
+        poll_fn(|_| Poll::<()>::Pending).await
+    }
+
+    let (executor, _) = setup();
+    executor.spawner().spawn(busy_task()).unwrap();
+    executor.spawner().spawn(idle_task()).unwrap();
+
+    let mut names: Vec<_> = executor.tasks().map(|t| t.name().unwrap()).collect();
+    names.sort();
+    assert_eq!(names, ["busy_task", "idle_task"]);
+
+    unsafe { executor.poll() };
+    unsafe { executor.poll() };
+    unsafe { executor.poll() };
+
+    let busy = executor.tasks().find(|t| t.name() == Some("busy_task")).unwrap();
+    let m = busy.metrics();
+    assert_eq!(m.poll_count, 2);
+    assert_eq!(m.total_poll_time, 30);
+    assert_eq!(m.max_poll_time, 20);
+
+    let idle = executor.tasks().find(|t| t.name() == Some("idle_task")).unwrap();
+    assert_eq!(idle.metrics().poll_count, 1);
+}
+
+#[cfg(all(feature = "task-registry", not(feature = "_arch")))]
+#[test]
+fn task_registry() {
+    #[task]
+    async fn short_task() {}
+
+    #[task(name = "renamed")]
+    async fn long_task() {
+        poll_fn(|_| Poll::<()>::Pending).await
+    }
+
+    let (executor, _) = setup();
+    executor.spawner().spawn(long_task()).unwrap();
+    executor.spawner().spawn(short_task()).unwrap();
+
+    let names: Vec<_> = executor.tasks().map(|t| t.name()).collect();
+    assert_eq!(names, [Some("short_task"), Some("renamed")]);
+
+    // Exited tasks are removed from the list.
+    unsafe { executor.poll() };
+
+    let names: Vec<_> = executor.tasks().map(|t| t.name()).collect();
+    assert_eq!(names, [Some("renamed")]);
+}
+
+#[cfg(all(feature = "task-priority", not(feature = "_arch")))]
+#[test]
+fn task_priority() {
+    #[task]
+    async fn low(trace: Trace) {
+        trace.push("poll low")
+    }
+
+    #[task(priority = 2)]
+    async fn high(trace: Trace) {
+        trace.push("poll high")
+    }
+
+    let (executor, trace) = setup();
+    executor.spawner().spawn(high(trace.clone())).unwrap();
+    executor.spawner().spawn(low(trace.clone())).unwrap();
+    unsafe { executor.poll() };
+
+    assert_eq!(
+        trace.get(),
+        &[
+            "pend",      // spawning a task pends the executor
+            "pend",      // each priority level has its own queue
+            "poll high", // higher priority is polled first
+            "poll low",  //
+        ]
+    )
+}
+
+#[cfg(all(feature = "task-priority", not(feature = "_arch")))]
+#[test]
+fn task_priority_preempts_batch() {
+    use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+    use embassy_sync::signal::Signal;
+
+    static SIGNAL: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+    #[task(pool_size = 2)]
+    async fn low(trace: Trace, name: &'static str) {
+        trace.push(name);
+        SIGNAL.signal(());
+    }
+
+    #[task]
+    async fn high(trace: Trace) {
+        trace.push("high waiting");
+        SIGNAL.wait().await;
+        trace.push("high woken");
+    }
+
+    let (executor, trace) = setup();
+    executor.spawner().spawn(high(trace.clone()).with_priority(3)).unwrap();
+    executor.spawner().spawn(low(trace.clone(), "poll low_b")).unwrap();
+    executor.spawner().spawn(low(trace.clone(), "poll low_a")).unwrap();
+    unsafe { executor.poll() };
+
+    assert_eq!(
+        trace.get(),
+        &[
+            "pend",         // spawning a task pends the executor
+            "pend",         // each priority level has its own queue
+            "high waiting", // higher priority is polled first
+            "poll low_a",   // wakes high
+            "pend",         //
+            "high woken",   // polled before the rest of the low priority batch
+            "poll low_b",   //
+        ]
+    )
+}
+
+#[cfg(all(feature = "arch-sim", feature = "executor-thread"))]
+#[test]
+fn sim_timers() {
+    use embassy_executor::Executor;
+    use embassy_time::{Duration, Instant, MockDriver, Timer};
+
+    static WOKEN: Mutex<Vec<(&'static str, u64)>> = Mutex::new(Vec::new());
+
+    #[task(pool_size = 2)]
+    async fn sleeper(name: &'static str, secs: u64) {
+        Timer::after_secs(secs).await;
+        WOKEN.lock().unwrap().push((name, Instant::now().as_secs()));
+    }
+
+    let executor: &'static Executor = Box::leak(Box::new(Executor::new(0)));
+    executor.spawner().spawn(sleeper("late", 10)).unwrap();
+    executor.spawner().spawn(sleeper("early", 3)).unwrap();
+
+    let start = Instant::now();
+    executor.run(|| MockDriver::get().advance_to_next_expiration()).unwrap();
+
+    // Time jumps straight to each expiration, so the tasks wake exactly on time.
+    assert_eq!(*WOKEN.lock().unwrap(), [("early", 3), ("late", 10)]);
+    assert_eq!(Instant::now() - start, Duration::from_secs(10));
+}
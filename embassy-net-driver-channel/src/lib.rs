@@ -46,6 +46,7 @@ struct Shared {
     link_state: LinkState,
     waker: WakerRegistration,
     hardware_address: driver::HardwareAddress,
+    rx_dropped: u32,
 }
 
 /// Channel runner.
@@ -70,6 +71,7 @@ pub struct StateRunner<'d> {
 /// Holds the lower end of the channel for passing inbound packets up the stack.
 pub struct RxRunner<'d, const MTU: usize> {
     rx_chan: zerocopy_channel::Sender<'d, NoopRawMutex, PacketBuf<MTU>>,
+    shared: &'d Mutex<NoopRawMutex, RefCell<Shared>>,
 }
 
 /// TX runner.
@@ -84,7 +86,10 @@ impl<'d, const MTU: usize> Runner<'d, MTU> {
     pub fn split(self) -> (StateRunner<'d>, RxRunner<'d, MTU>, TxRunner<'d, MTU>) {
         (
             StateRunner { shared: self.shared },
-            RxRunner { rx_chan: self.rx_chan },
+            RxRunner {
+                rx_chan: self.rx_chan,
+                shared: self.shared,
+            },
             TxRunner { tx_chan: self.tx_chan },
         )
     }
@@ -95,6 +100,7 @@ impl<'d, const MTU: usize> Runner<'d, MTU> {
             StateRunner { shared: self.shared },
             RxRunner {
                 rx_chan: self.rx_chan.borrow(),
+                shared: self.shared,
             },
             TxRunner {
                 tx_chan: self.tx_chan.borrow(),
@@ -152,6 +158,17 @@ impl<'d, const MTU: usize> Runner<'d, MTU> {
         self.rx_chan.send_done();
     }
 
+    /// Count an inbound packet dropped because there was no space for it, such as when
+    /// [`try_rx_buf`](Self::try_rx_buf) returned `None`.
+    ///
+    /// The stack reads the count with [`Driver::rx_dropped`](driver::Driver::rx_dropped).
+    pub fn rx_dropped(&mut self) {
+        self.shared.lock(|s| {
+            let s = &mut *s.borrow_mut();
+            s.rx_dropped = s.rx_dropped.wrapping_add(1);
+        });
+    }
+
     /// Wait until there is space for more outbound packets and return a slice they can be copied into.
     pub async fn tx_buf(&mut self) -> &mut [u8] {
         let p = self.tx_chan.receive().await;
@@ -225,6 +242,17 @@ impl<'d, const MTU: usize> RxRunner<'d, MTU> {
         p.len = len;
         self.rx_chan.send_done();
     }
+
+    /// Count an inbound packet dropped because there was no space for it, such as when
+    /// [`try_rx_buf`](Self::try_rx_buf) returned `None`.
+    ///
+    /// The stack reads the count with [`Driver::rx_dropped`](driver::Driver::rx_dropped).
+    pub fn rx_dropped(&mut self) {
+        self.shared.lock(|s| {
+            let s = &mut *s.borrow_mut();
+            s.rx_dropped = s.rx_dropped.wrapping_add(1);
+        });
+    }
 }
 
 impl<'d, const MTU: usize> TxRunner<'d, MTU> {
@@ -279,6 +307,7 @@ pub fn new<'d, const MTU: usize, const N_RX: usize, const N_TX: usize>(
             link_state: LinkState::Down,
             hardware_address,
             waker: WakerRegistration::new(),
+            rx_dropped: 0,
         })),
     });
 
@@ -366,6 +395,10 @@ impl<'d, const MTU: usize> embassy_net_driver::Driver for Device<'d, MTU> {
             s.link_state
         })
     }
+
+    fn rx_dropped(&mut self) -> u32 {
+        self.shared.lock(|s| s.borrow().rx_dropped)
+    }
 }
 
 /// A rx token.
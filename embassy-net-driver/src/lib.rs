@@ -80,6 +80,15 @@ pub trait Driver {
     /// what kind of packet the sent/received bytes are, and determines some behaviors of
     /// the interface. For example, ARP/NDISC address resolution is only done for Ethernet mediums.
     fn hardware_address(&self) -> HardwareAddress;
+
+    /// Get the number of received packets dropped because the driver had no receive buffer
+    /// for them, since it was created. It wraps around on overflow.
+    ///
+    /// This happens when packets arrive faster than the stack consumes them. Drivers that
+    /// don't count these drops return 0, which is the default.
+    fn rx_dropped(&mut self) -> u32 {
+        0
+    }
 }
 
 impl<T: ?Sized + Driver> Driver for &mut T {
@@ -107,6 +116,9 @@ impl<T: ?Sized + Driver> Driver for &mut T {
     fn hardware_address(&self) -> HardwareAddress {
         T::hardware_address(self)
     }
+    fn rx_dropped(&mut self) -> u32 {
+        T::rx_dropped(self)
+    }
 }
 
 /// A token to receive a single network packet.
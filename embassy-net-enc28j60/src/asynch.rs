@@ -0,0 +1,524 @@
+//! Interrupt-driven async driver.
+//!
+//! Unlike [`Enc28j60`](crate::Enc28j60), which polls the chip from the `embassy-net` driver, this
+//! driver talks to the chip over an async [`SpiDevice`] from a background [`Runner`], and sleeps
+//! until the chip asserts its INT pin.
+
+use embassy_futures::select::{select3, Either3};
+use embassy_net_driver_channel as ch;
+use embassy_net_driver_channel::driver::LinkState;
+use embassy_time::Timer;
+use embedded_hal::digital::OutputPin;
+use embedded_hal_async::digital::Wait;
+use embedded_hal_async::spi::{Operation, SpiDevice};
+
+use crate::traits::U16Ext;
+use crate::{
+    bank0, bank1, bank2, bank3, common, header, phy, Bank, Instruction, Register, CRC_SZ, MAX_FRAME_LENGTH, MTU, RXND,
+    RXST, TXST,
+};
+
+/// Type alias for the embassy-net driver.
+pub type Device<'d> = embassy_net_driver_channel::Device<'d, MTU>;
+
+/// Internal state for the embassy-net integration.
+///
+/// The two generic arguments `N_RX` and `N_TX` set the size of the receive and send packet
+/// queues, each packet taking up 1514 bytes.
+pub struct State<const N_RX: usize, const N_TX: usize> {
+    ch_state: ch::State<MTU, N_RX, N_TX>,
+}
+
+impl<const N_RX: usize, const N_TX: usize> State<N_RX, N_TX> {
+    /// Create a new `State`.
+    pub const fn new() -> Self {
+        Self {
+            ch_state: ch::State::new(),
+        }
+    }
+}
+
+/// Background runner for the driver.
+///
+/// You must call `.run()` in a background task for the driver to operate.
+pub struct Runner<'d, SPI: SpiDevice, INT: Wait, RST: OutputPin> {
+    mac: Mac<SPI>,
+    ch: ch::Runner<'d, MTU>,
+    int: INT,
+    _rst: Option<RST>,
+}
+
+impl<SPI: SpiDevice, INT: Wait, RST: OutputPin> Runner<'_, SPI, INT, RST> {
+    /// Run the driver.
+    pub async fn run(mut self) -> ! {
+        let (state_chan, mut rx_chan, mut tx_chan) = self.ch.split();
+
+        state_chan.set_link_state(match self.mac.is_link_up().await {
+            true => LinkState::Up,
+            false => LinkState::Down,
+        });
+
+        // Set when packets are waiting in the chip for a free RX buffer. The packet interrupt is
+        // disabled meanwhile, so INT only reports the other events.
+        let mut rx_pending = false;
+
+        loop {
+            let wait_rx = rx_pending;
+            let rx_fut = async {
+                match wait_rx {
+                    true => rx_chan.rx_buf().await,
+                    false => core::future::pending().await,
+                }
+            };
+            match select3(self.int.wait_for_low(), tx_chan.tx_buf(), rx_fut).await {
+                Either3::First(_) => {
+                    let eir = common::EIR(self.mac.read_control_register(common::Register::EIR).await);
+
+                    if eir.linkif() == 1 {
+                        // Reading PHIR clears the interrupt.
+                        self.mac.read_phy_register(phy::Register::PHIR).await;
+                        let up = self.mac.is_link_up().await;
+                        debug!("enc28j60: link up: {}", up);
+                        state_chan.set_link_state(match up {
+                            true => LinkState::Up,
+                            false => LinkState::Down,
+                        });
+                    }
+
+                    if eir.rxerif() == 1 {
+                        // Frames were dropped because the RX buffer was full. Reception resumes
+                        // as soon as the pending packets are read out.
+                        warn!("enc28j60: RX buffer overflow");
+                        self.mac
+                            .bit_field_clear(common::Register::EIR, common::EIR::mask().rxerif())
+                            .await;
+                    }
+
+                    // Errata #6: we can't rely on PKTIF so we check PKTCNT
+                    if !rx_pending && self.mac.pending_packets().await != 0 {
+                        rx_pending = true;
+                        self.mac
+                            .bit_field_clear(common::Register::EIE, common::EIE::mask().pktie())
+                            .await;
+                    }
+                }
+                Either3::Second(p) => {
+                    self.mac.transmit(p).await;
+                    tx_chan.tx_done();
+                }
+                Either3::Third(p) => {
+                    if let Some(n) = self.mac.receive(p).await {
+                        rx_chan.rx_done(n);
+                    }
+                    if self.mac.pending_packets().await == 0 {
+                        rx_pending = false;
+                        self.mac
+                            .bit_field_set(common::Register::EIE, common::EIE::mask().pktie())
+                            .await;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Create an interrupt-driven ENC28J60 driver for [`embassy-net`](https://crates.io/crates/embassy-net).
+///
+/// The INT pin of the chip must be connected to `int`. The RST pin is optional. If None, reset
+/// will be done with a SPI soft reset command, instead of via the RST pin.
+///
+/// This returns two structs:
+/// - a `Device` that you must pass to the `embassy-net` stack.
+/// - a `Runner`. You must call `.run()` on it in a background task.
+pub async fn new<const N_RX: usize, const N_TX: usize, SPI: SpiDevice, INT: Wait, RST: OutputPin>(
+    mac_addr: [u8; 6],
+    state: &mut State<N_RX, N_TX>,
+    spi: SPI,
+    int: INT,
+    mut rst: Option<RST>,
+) -> (Device<'_>, Runner<'_, SPI, INT, RST>) {
+    let mut mac = Mac {
+        spi,
+        bank: Bank::Bank0,
+        next_packet: RXST,
+    };
+
+    if let Some(rst) = &mut rst {
+        rst.set_low().unwrap();
+        Timer::after_millis(5).await;
+        rst.set_high().unwrap();
+        Timer::after_millis(5).await;
+    } else {
+        Timer::after_millis(5).await;
+        mac.soft_reset().await;
+        Timer::after_millis(5).await;
+    }
+    // The bank select bits are cleared by the reset.
+    mac.bank = Bank::Bank0;
+
+    mac.init(mac_addr).await;
+
+    let (runner, device) = ch::new(&mut state.ch_state, ch::driver::HardwareAddress::Ethernet(mac_addr));
+
+    (
+        device,
+        Runner {
+            mac,
+            ch: runner,
+            int,
+            _rst: rst,
+        },
+    )
+}
+
+/// ENC28J60 register and buffer access, over an async SPI device.
+struct Mac<SPI> {
+    spi: SPI,
+
+    bank: Bank,
+
+    // address of the next packet in buffer memory
+    next_packet: u16,
+}
+
+impl<SPI: SpiDevice> Mac<SPI> {
+    async fn init(&mut self, mac_addr: [u8; 6]) {
+        debug!(
+            "enc28j60: erevid {:02x}",
+            self.read_control_register(bank3::Register::EREVID).await
+        );
+        debug!("enc28j60: waiting for clk");
+        while common::ESTAT(self.read_control_register(common::Register::ESTAT).await).clkrdy() == 0 {}
+        debug!("enc28j60: clk ok");
+
+        if self.read_control_register(bank3::Register::EREVID).await == 0 {
+            panic!("ErevidIsZero");
+        }
+
+        // disable CLKOUT output
+        self.write_control_register(bank3::Register::ECOCON, 0).await;
+
+        self.init_rx().await;
+
+        // TX start
+        // "It is recommended that an even address be used for ETXST"
+        debug_assert_eq!(TXST % 2, 0);
+        self.write_control_register(bank0::Register::ETXSTL, TXST.low()).await;
+        self.write_control_register(bank0::Register::ETXSTH, TXST.high()).await;
+
+        // MAC initialization (see section 6.5), as done by the blocking driver.
+        self.write_control_register(
+            bank2::Register::MACON1,
+            bank2::MACON1::default().marxen(1).passall(0).rxpaus(1).txpaus(1).bits(),
+        )
+        .await;
+        self.write_control_register(
+            bank2::Register::MACON3,
+            bank2::MACON3::default().frmlnen(1).txcrcen(1).padcfg(0b001).bits(),
+        )
+        .await;
+        self.write_control_register(bank2::Register::MAMXFLL, MAX_FRAME_LENGTH.low())
+            .await;
+        self.write_control_register(bank2::Register::MAMXFLH, MAX_FRAME_LENGTH.high())
+            .await;
+        self.write_control_register(bank2::Register::MABBIPG, 0x12).await;
+        self.write_control_register(bank2::Register::MAIPGL, 0x12).await;
+        self.write_control_register(bank2::Register::MAIPGH, 0x0c).await;
+
+        self.write_control_register(bank3::Register::MAADR1, mac_addr[0]).await;
+        self.write_control_register(bank3::Register::MAADR2, mac_addr[1]).await;
+        self.write_control_register(bank3::Register::MAADR3, mac_addr[2]).await;
+        self.write_control_register(bank3::Register::MAADR4, mac_addr[3]).await;
+        self.write_control_register(bank3::Register::MAADR5, mac_addr[4]).await;
+        self.write_control_register(bank3::Register::MAADR6, mac_addr[5]).await;
+
+        // Set the PHCON2.HDLDIS bit to prevent automatic loopback of the data which is transmitted
+        self.write_phy_register(phy::Register::PHCON2, phy::PHCON2::default().hdldis(1).bits())
+            .await;
+
+        // Enable the PHY link change interrupt, and clear it in case it's already set.
+        self.write_phy_register(phy::Register::PHIE, phy::PHIE::default().pgeie(1).plnkie(1).bits())
+            .await;
+        self.read_phy_register(phy::Register::PHIR).await;
+
+        // Report received packets, link changes and RX buffer overflows on INT.
+        let mask = common::EIE::mask();
+        self.bit_field_set(
+            common::Register::EIE,
+            mask.intie() | mask.pktie() | mask.linkie() | mask.rxerie(),
+        )
+        .await;
+
+        // Set the per packet control byte; we'll always use the value 0
+        self.write_buffer_memory(Some(TXST), &[0]).await;
+
+        // Enable reception
+        self.bit_field_set(common::Register::ECON1, common::ECON1::mask().rxen())
+            .await;
+    }
+
+    async fn init_rx(&mut self) {
+        // RX start
+        // "It is recommended that the ERXST Pointer be programmed with an even address"
+        self.write_control_register(bank0::Register::ERXSTL, RXST.low()).await;
+        self.write_control_register(bank0::Register::ERXSTH, RXST.high()).await;
+
+        // RX read pointer
+        // NOTE Errata #14 so we are using an *odd* address here instead of ERXST
+        self.write_control_register(bank0::Register::ERXRDPTL, RXND.low()).await;
+        self.write_control_register(bank0::Register::ERXRDPTH, RXND.high())
+            .await;
+
+        // RX end
+        self.write_control_register(bank0::Register::ERXNDL, RXND.low()).await;
+        self.write_control_register(bank0::Register::ERXNDH, RXND.high()).await;
+
+        // decrease the packet count to 0
+        while self.read_control_register(bank1::Register::EPKTCNT).await != 0 {
+            self.bit_field_set(common::Register::ECON2, common::ECON2::mask().pktdec())
+                .await;
+        }
+
+        self.next_packet = RXST;
+    }
+
+    async fn reset_rx(&mut self) {
+        self.bit_field_set(common::Register::ECON1, common::ECON1::mask().rxrst())
+            .await;
+        self.bit_field_clear(common::Register::ECON1, common::ECON1::mask().rxrst())
+            .await;
+        self.init_rx().await;
+        self.bit_field_set(common::Register::ECON1, common::ECON1::mask().rxen())
+            .await;
+    }
+
+    async fn receive(&mut self, buf: &mut [u8]) -> Option<usize> {
+        if self.pending_packets().await == 0 {
+            return None;
+        }
+
+        let curr_packet = self.next_packet;
+
+        // read out the first 6 bytes
+        let mut temp_buf = [0; 6];
+        self.read_buffer_memory(Some(curr_packet), &mut temp_buf).await;
+
+        // next packet pointer
+        let next_packet = u16::from_parts(temp_buf[0], temp_buf[1]);
+        // status vector
+        let status = header::RxStatus(u32::from_le_bytes(temp_buf[2..].try_into().unwrap()));
+        let len_with_crc = status.byte_count() as u16;
+
+        if !(CRC_SZ..=MAX_FRAME_LENGTH).contains(&len_with_crc) || next_packet > RXND {
+            warn!("RX buffer corrupted, resetting RX logic to recover...");
+            self.reset_rx().await;
+            return None;
+        }
+
+        let len = len_with_crc - CRC_SZ;
+        self.read_buffer_memory(None, &mut buf[..len as usize]).await;
+
+        // update ERXRDPT
+        // due to Errata #14 we must write an odd address to ERXRDPT
+        // we know that ERXST = 0, that ERXND is odd and that next_packet is even
+        let rxrdpt = if (1..=RXND + 1).contains(&next_packet) {
+            next_packet - 1
+        } else {
+            RXND
+        };
+        // "To move ERXRDPT, the host controller must write to ERXRDPTL first."
+        self.write_control_register(bank0::Register::ERXRDPTL, rxrdpt.low())
+            .await;
+        self.write_control_register(bank0::Register::ERXRDPTH, rxrdpt.high())
+            .await;
+
+        // decrease the packet count
+        self.bit_field_set(common::Register::ECON2, common::ECON2::mask().pktdec())
+            .await;
+
+        self.next_packet = next_packet;
+
+        Some(len as usize)
+    }
+
+    async fn transmit(&mut self, bytes: &[u8]) {
+        // Wait for the previous transmission, which is short compared to the time it took to
+        // write the frame.
+        let mut ready = false;
+        for _ in 0u32..10000 {
+            if common::ECON1(self.read_control_register(common::Register::ECON1).await).txrts() == 0 {
+                ready = true;
+                break;
+            }
+        }
+        if !ready {
+            // work around errata #12 by resetting the transmit logic
+            self.bit_field_set(common::Register::ECON1, common::ECON1::mask().txrst())
+                .await;
+            self.bit_field_clear(common::Register::ECON1, common::ECON1::mask().txrst())
+                .await;
+        }
+
+        // NOTE the plus one is to not overwrite the per packet control byte
+        let wrpt = TXST + 1;
+
+        self.write_buffer_memory(Some(wrpt), bytes).await;
+
+        let txnd = wrpt + bytes.len() as u16 - 1;
+        self.write_control_register(bank0::Register::ETXNDL, txnd.low()).await;
+        self.write_control_register(bank0::Register::ETXNDH, txnd.high()).await;
+
+        // start transmission
+        self.bit_field_set(common::Register::ECON1, common::ECON1::mask().txrts())
+            .await;
+    }
+
+    async fn is_link_up(&mut self) -> bool {
+        let bits = self.read_phy_register(phy::Register::PHSTAT2).await;
+        phy::PHSTAT2(bits).lstat() == 1
+    }
+
+    async fn pending_packets(&mut self) -> u8 {
+        self.read_control_register(bank1::Register::EPKTCNT).await
+    }
+
+    async fn read_control_register<R: Into<Register>>(&mut self, register: R) -> u8 {
+        let register = register.into();
+        self.change_bank(register).await;
+
+        if register.is_eth_register() {
+            let mut buffer = [Instruction::RCR.opcode() | register.addr(), 0];
+            self.spi.transfer_in_place(&mut buffer).await.unwrap();
+            buffer[1]
+        } else {
+            // MAC, MII regs need a dummy byte.
+            let mut buffer = [Instruction::RCR.opcode() | register.addr(), 0, 0];
+            self.spi.transfer_in_place(&mut buffer).await.unwrap();
+            buffer[2]
+        }
+    }
+
+    async fn read_phy_register(&mut self, register: phy::Register) -> u16 {
+        // set PHY register address
+        self.write_control_register(bank2::Register::MIREGADR, register.addr())
+            .await;
+
+        // start read operation
+        self.write_control_register(bank2::Register::MICMD, bank2::MICMD::default().miird(1).bits())
+            .await;
+
+        // wait until the read operation finishes
+        while self.read_control_register(bank3::Register::MISTAT).await & 0b1 != 0 {}
+
+        self.write_control_register(bank2::Register::MICMD, bank2::MICMD::default().miird(0).bits())
+            .await;
+
+        let l = self.read_control_register(bank2::Register::MIRDL).await;
+        let h = self.read_control_register(bank2::Register::MIRDH).await;
+        (l as u16) | (h as u16) << 8
+    }
+
+    async fn write_control_register<R: Into<Register>>(&mut self, register: R, value: u8) {
+        let register = register.into();
+        self.change_bank(register).await;
+
+        let buffer = [Instruction::WCR.opcode() | register.addr(), value];
+        self.spi.write(&buffer).await.unwrap();
+    }
+
+    async fn write_phy_register(&mut self, register: phy::Register, value: u16) {
+        // set PHY register address
+        self.write_control_register(bank2::Register::MIREGADR, register.addr())
+            .await;
+
+        self.write_control_register(bank2::Register::MIWRL, (value & 0xff) as u8)
+            .await;
+        // this starts the write operation
+        self.write_control_register(bank2::Register::MIWRH, (value >> 8) as u8)
+            .await;
+
+        // wait until the write operation finishes
+        while self.read_control_register(bank3::Register::MISTAT).await & 0b1 != 0 {}
+    }
+
+    async fn change_bank(&mut self, register: Register) {
+        let Some(bank) = register.bank() else {
+            // common register
+            return;
+        };
+        if self.bank == bank {
+            // already on the register bank
+            return;
+        }
+
+        // ECON1 is a common register, so the bank select bits are written directly.
+        self.bank = bank;
+        let econ1 = common::Register::ECON1.addr();
+        self.spi
+            .write(&[Instruction::BFC.opcode() | econ1, 0b11])
+            .await
+            .unwrap();
+        let bsel = match bank {
+            Bank::Bank0 => return,
+            Bank::Bank1 => 0b01,
+            Bank::Bank2 => 0b10,
+            Bank::Bank3 => 0b11,
+        };
+        self.spi
+            .write(&[Instruction::BFS.opcode() | econ1, bsel])
+            .await
+            .unwrap();
+    }
+
+    async fn bit_field_clear<R: Into<Register>>(&mut self, register: R, mask: u8) {
+        let register = register.into();
+        debug_assert!(register.is_eth_register());
+
+        self.change_bank(register).await;
+
+        self.spi
+            .write(&[Instruction::BFC.opcode() | register.addr(), mask])
+            .await
+            .unwrap();
+    }
+
+    async fn bit_field_set<R: Into<Register>>(&mut self, register: R, mask: u8) {
+        let register = register.into();
+        debug_assert!(register.is_eth_register());
+
+        self.change_bank(register).await;
+
+        self.spi
+            .write(&[Instruction::BFS.opcode() | register.addr(), mask])
+            .await
+            .unwrap();
+    }
+
+    async fn read_buffer_memory(&mut self, addr: Option<u16>, buf: &mut [u8]) {
+        if let Some(addr) = addr {
+            self.write_control_register(bank0::Register::ERDPTL, addr.low()).await;
+            self.write_control_register(bank0::Register::ERDPTH, addr.high()).await;
+        }
+
+        self.spi
+            .transaction(&mut [Operation::Write(&[Instruction::RBM.opcode()]), Operation::Read(buf)])
+            .await
+            .unwrap();
+    }
+
+    async fn soft_reset(&mut self) {
+        self.spi.write(&[Instruction::SRC.opcode()]).await.unwrap();
+    }
+
+    async fn write_buffer_memory(&mut self, addr: Option<u16>, buffer: &[u8]) {
+        if let Some(addr) = addr {
+            self.write_control_register(bank0::Register::EWRPTL, addr.low()).await;
+            self.write_control_register(bank0::Register::EWRPTH, addr.high()).await;
+        }
+
+        self.spi
+            .transaction(&mut [Operation::Write(&[Instruction::WBM.opcode()]), Operation::Write(buffer)])
+            .await
+            .unwrap();
+    }
+}
@@ -16,6 +16,8 @@ mod header;
 mod phy;
 mod traits;
 
+pub mod asynch;
+
 use core::cmp;
 
 use embassy_net_driver::{Capabilities, HardwareAddress, LinkState};
@@ -33,3 +33,17 @@ register!(PHSTAT2, 0, u16, {
     #[doc = "Link Status bit"]
     lstat @ 10,
 });
+
+register!(PHIE, 0, u16, {
+    #[doc = "PHY Global Interrupt Enable bit"]
+    pgeie @ 1,
+    #[doc = "PHY Link Change Interrupt Enable bit"]
+    plnkie @ 4,
+});
+
+register!(PHIR, 0, u16, {
+    #[doc = "PHY Global Interrupt Flag bit"]
+    pgif @ 2,
+    #[doc = "PHY Link Change Interrupt Flag bit"]
+    plnkif @ 4,
+});
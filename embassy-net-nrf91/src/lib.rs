@@ -635,6 +635,8 @@ impl StateInner {
                                 unsafe { ptr::copy_nonoverlapping(msg.data, buf.as_mut_ptr(), len) }
                                 fence(Ordering::SeqCst); // synchronize volatile accesses with the nonvolatile copy_nonoverlapping.
                                 ch.rx_done(len);
+                            } else {
+                                ch.rx_dropped();
                             }
                             false
                         }
@@ -0,0 +1,257 @@
+//! AT-command dialer, to bring a modem into data mode before running PPP.
+
+use embassy_time::{with_timeout, Duration, Timer};
+use embedded_io_async::{BufRead, Write};
+
+/// Responses that make a chat step fail, by default.
+pub const DEFAULT_ABORT_RESPONSES: &[&str] = &["ERROR", "NO CARRIER", "BUSY", "NO DIALTONE", "NO ANSWER"];
+
+/// Longer response lines are truncated before matching.
+const MAX_LINE_LEN: usize = 128;
+
+/// A step of a chat script: a command sent to the modem, and the response expected to it.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ChatStep<'a> {
+    /// Command sent to the modem, without the trailing carriage return. Nothing is sent if empty.
+    pub command: &'a str,
+    /// Expected response. The step succeeds when a line received from the modem contains it, and
+    /// doesn't wait for a response if empty.
+    pub expect: &'a str,
+    /// How long to wait for the expected response.
+    pub timeout: Duration,
+}
+
+impl<'a> ChatStep<'a> {
+    /// Create a new `ChatStep`, waiting up to 5 seconds for the response.
+    pub const fn new(command: &'a str, expect: &'a str) -> Self {
+        Self {
+            command,
+            expect,
+            timeout: Duration::from_secs(5),
+        }
+    }
+
+    /// Set how long to wait for the response.
+    pub const fn with_timeout(self, timeout: Duration) -> Self {
+        Self { timeout, ..self }
+    }
+}
+
+/// Error returned by [`Dialer::dial`] and [`Dialer::hang_up`].
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ChatError<E> {
+    /// Reading from the serial port failed.
+    Read(E),
+    /// Writing to the serial port failed.
+    Write(E),
+    /// Reading from the serial port got EOF.
+    Eof,
+    /// The expected response wasn't received in time.
+    Timeout,
+    /// The modem answered with one of the abort responses.
+    Aborted,
+}
+
+#[derive(Clone, Copy)]
+enum Script<'a> {
+    Custom(&'a [ChatStep<'a>]),
+    Cellular { apn: &'a str },
+}
+
+/// AT-command dialer.
+///
+/// It runs a chat script over the serial port to bring the modem into data mode, before PPP runs
+/// over the same port, and brings the modem back to command mode to hang up. See
+/// [`Runner::run_with_dialer`](crate::Runner::run_with_dialer), which also dials again when the
+/// PPP session ends.
+///
+/// Every line received from the modem while waiting for a response is matched against the
+/// expected response and the abort responses, so modem echo and unsolicited result codes are
+/// skipped.
+#[derive(Clone, Copy)]
+pub struct Dialer<'a> {
+    script: Script<'a>,
+    abort_responses: &'a [&'a str],
+    redial_delay: Duration,
+    guard_time: Duration,
+}
+
+impl<'a> Dialer<'a> {
+    /// Create a dialer running a custom chat script.
+    ///
+    /// The last step should bring the modem into data mode, usually by dialing and expecting
+    /// `CONNECT`.
+    pub const fn new(script: &'a [ChatStep<'a>]) -> Self {
+        Self {
+            script: Script::Custom(script),
+            abort_responses: DEFAULT_ABORT_RESPONSES,
+            redial_delay: Duration::from_secs(5),
+            guard_time: Duration::from_secs(1),
+        }
+    }
+
+    /// Create a dialer for cellular modems.
+    ///
+    /// It defines PDP context 1 with the access point name `apn` (`AT+CGDCONT`), and dials
+    /// `*99#` to start the data session. A modem still in data mode from an earlier session is
+    /// brought back to command mode first.
+    pub const fn cellular(apn: &'a str) -> Self {
+        Self {
+            script: Script::Cellular { apn },
+            ..Self::new(&[])
+        }
+    }
+
+    /// Set the responses that make a chat step fail. Defaults to [`DEFAULT_ABORT_RESPONSES`].
+    pub fn set_abort_responses(&mut self, responses: &'a [&'a str]) {
+        self.abort_responses = responses;
+    }
+
+    /// Set the delay before dialing again, after dialing failed or the PPP session ended.
+    /// Defaults to 5 seconds.
+    pub fn set_redial_delay(&mut self, delay: Duration) {
+        self.redial_delay = delay;
+    }
+
+    /// Set the silence kept around the `+++` escape sequence. Defaults to 1 second, the usual
+    /// `S12` register setting of modems.
+    pub fn set_guard_time(&mut self, guard_time: Duration) {
+        self.guard_time = guard_time;
+    }
+
+    pub(crate) fn redial_delay(&self) -> Duration {
+        self.redial_delay
+    }
+
+    /// Run the chat script, to bring the modem into data mode.
+    pub async fn dial<RW: BufRead + Write>(&self, rw: &mut RW) -> Result<(), ChatError<RW::Error>> {
+        match self.script {
+            Script::Custom(script) => {
+                for step in script {
+                    self.step(rw, &[step.command], step.expect, step.timeout).await?;
+                }
+            }
+            Script::Cellular { apn } => {
+                if self.attention(rw).await.is_err() {
+                    // Maybe still in data mode.
+                    let _ = self.hang_up(rw).await;
+                    self.attention(rw).await?;
+                }
+                let timeout = Duration::from_secs(5);
+                self.step(rw, &["ATE0"], "OK", timeout).await?;
+                self.step(rw, &["AT+CGDCONT=1,\"IP\",\"", apn, "\""], "OK", timeout)
+                    .await?;
+                self.step(rw, &["ATD*99#"], "CONNECT", Duration::from_secs(30)).await?;
+            }
+        }
+        info!("modem in data mode");
+        Ok(())
+    }
+
+    /// Bring the modem back to command mode with the `+++` escape sequence, and hang up.
+    ///
+    /// The modem may already be in command mode, if it dropped the connection.
+    pub async fn hang_up<RW: BufRead + Write>(&self, rw: &mut RW) -> Result<(), ChatError<RW::Error>> {
+        Timer::after(self.guard_time).await;
+        rw.write_all(b"+++").await.map_err(ChatError::Write)?;
+        rw.flush().await.map_err(ChatError::Write)?;
+        // The modem answers after the guard time, if it was in data mode.
+        match self.step(rw, &[], "OK", self.guard_time * 2).await {
+            Ok(()) | Err(ChatError::Timeout) | Err(ChatError::Aborted) => {}
+            Err(e) => return Err(e),
+        }
+        self.step(rw, &["ATH"], "OK", Duration::from_secs(5)).await
+    }
+
+    /// Check the modem answers, retrying a few times for it to wake up or detect the baud rate.
+    async fn attention<RW: BufRead + Write>(&self, rw: &mut RW) -> Result<(), ChatError<RW::Error>> {
+        let mut res = Ok(());
+        for _ in 0..3 {
+            res = self.step(rw, &["AT"], "OK", Duration::from_secs(1)).await;
+            if !matches!(res, Err(ChatError::Timeout)) {
+                break;
+            }
+        }
+        res
+    }
+
+    /// Send a command made of `parts`, and wait for the `expect` response.
+    async fn step<RW: BufRead + Write>(
+        &self,
+        rw: &mut RW,
+        parts: &[&str],
+        expect: &str,
+        timeout: Duration,
+    ) -> Result<(), ChatError<RW::Error>> {
+        if parts.iter().any(|p| !p.is_empty()) {
+            for part in parts {
+                rw.write_all(part.as_bytes()).await.map_err(ChatError::Write)?;
+            }
+            rw.write_all(b"\r").await.map_err(ChatError::Write)?;
+            rw.flush().await.map_err(ChatError::Write)?;
+        }
+
+        if expect.is_empty() {
+            return Ok(());
+        }
+        match with_timeout(timeout, self.expect(rw, expect.as_bytes())).await {
+            Ok(res) => res,
+            Err(_) => {
+                warn!("modem didn't answer {:?} in time", expect);
+                Err(ChatError::Timeout)
+            }
+        }
+    }
+
+    /// Read lines from the modem, until one contains `expect` or an abort response.
+    ///
+    /// This consumes nothing past the matching line, so data that follows it is left for PPP.
+    async fn expect<RW: BufRead + Write>(&self, rw: &mut RW, expect: &[u8]) -> Result<(), ChatError<RW::Error>> {
+        let mut line = [0; MAX_LINE_LEN];
+        let mut len = 0;
+        loop {
+            let buf = rw.fill_buf().await.map_err(ChatError::Read)?;
+            if buf.is_empty() {
+                return Err(ChatError::Eof);
+            }
+
+            let mut res = None;
+            let mut n = 0;
+            for &b in buf {
+                n += 1;
+                if b != b'\r' && b != b'\n' {
+                    if len < line.len() {
+                        line[len] = b;
+                        len += 1;
+                    }
+                    continue;
+                }
+                if len == 0 {
+                    continue;
+                }
+
+                let l = &line[..len];
+                debug!("modem: {:?}", core::str::from_utf8(l).unwrap_or("<invalid>"));
+                if contains(l, expect) {
+                    res = Some(Ok(()));
+                } else if self.abort_responses.iter().any(|a| contains(l, a.as_bytes())) {
+                    res = Some(Err(ChatError::Aborted));
+                }
+                len = 0;
+                if res.is_some() {
+                    break;
+                }
+            }
+            rw.consume(n);
+            if let Some(res) = res {
+                return res;
+            }
+        }
+    }
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack.windows(needle.len()).any(|w| w == needle)
+}
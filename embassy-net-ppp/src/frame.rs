@@ -0,0 +1,165 @@
+//! HDLC-like framing of PPP frames (RFC 1662), for the protocols handled outside `ppproto`.
+
+use embedded_io_async::Write;
+
+const FLAG: u8 = 0x7e;
+const ESCAPE: u8 = 0x7d;
+
+/// Good FCS of a frame, computed including its own FCS.
+const GOOD_FCS: u16 = 0xf0b8;
+
+/// Result code sent by modems when they drop the connection and return to command mode.
+const NO_CARRIER: &[u8] = b"NO CARRIER";
+
+pub(crate) fn fcs16(mut fcs: u16, data: &[u8]) -> u16 {
+    for &b in data {
+        let e = fcs as u8 ^ b;
+        let f = (e ^ (e << 4)) as u16;
+        fcs = (fcs >> 8) ^ (f << 8) ^ (f << 3) ^ (f >> 4);
+    }
+    fcs
+}
+
+/// Decodes frames received from the serial port.
+pub(crate) struct FrameReader {
+    in_frame: bool,
+    escape: bool,
+    len: usize,
+    /// Bytes of [`NO_CARRIER`] received since the last flag, after line breaks. `None` if
+    /// something else was received.
+    no_carrier: Option<usize>,
+}
+
+impl FrameReader {
+    pub fn new() -> Self {
+        Self {
+            in_frame: false,
+            escape: false,
+            len: 0,
+            no_carrier: Some(0),
+        }
+    }
+
+    /// The modem reported `NO CARRIER` between frames.
+    pub fn no_carrier(&self) -> bool {
+        self.no_carrier == Some(NO_CARRIER.len())
+    }
+
+    /// Decode `data` into `buf`.
+    ///
+    /// Returns how many bytes of `data` were consumed, and the length of the frame in `buf` if one
+    /// was completed. Frames start with the address and control fields and end with the FCS. They
+    /// stay in `buf` until the next call.
+    ///
+    /// Decoding stops after a `NO CARRIER` line, see [`no_carrier`](Self::no_carrier).
+    pub fn consume(&mut self, buf: &mut [u8], data: &[u8]) -> (usize, Option<usize>) {
+        if !self.in_frame {
+            self.len = 0;
+        }
+        for (i, &b) in data.iter().enumerate() {
+            // Frames start with the address field, so the modem's result codes can't be mistaken
+            // for one.
+            self.no_carrier = match (b, self.no_carrier) {
+                (FLAG, _) => Some(0),
+                (b'\r' | b'\n', Some(0)) => Some(0),
+                (b, Some(n)) if NO_CARRIER.get(n) == Some(&b) => Some(n + 1),
+                _ => None,
+            };
+            if self.no_carrier() {
+                return (i + 1, None);
+            }
+
+            match b {
+                FLAG => {
+                    let len = self.len;
+                    let complete = self.in_frame && !self.escape && len >= 6 && fcs16(0xffff, &buf[..len]) == GOOD_FCS;
+                    self.in_frame = true;
+                    self.escape = false;
+                    self.len = 0;
+                    if complete {
+                        self.in_frame = false;
+                        return (i, Some(len));
+                    }
+                    if len > 0 {
+                        debug!("dropping invalid frame");
+                    }
+                }
+                _ if !self.in_frame => {}
+                ESCAPE => self.escape = true,
+                mut b => {
+                    if self.escape {
+                        self.escape = false;
+                        b ^= 0x20;
+                    }
+                    if self.len < buf.len() {
+                        buf[self.len] = b;
+                        self.len += 1;
+                    } else {
+                        warn!("frame too long, dropping");
+                        self.in_frame = false;
+                    }
+                }
+            }
+        }
+        (data.len(), None)
+    }
+}
+
+/// Write a frame carrying `data` with protocol `proto`, using `buf` as scratch space.
+///
+/// All control characters are escaped, since the async control character map negotiated by
+/// `ppproto` isn't known here.
+pub(crate) async fn write_frame<W: Write>(w: &mut W, buf: &mut [u8], proto: u16, data: &[u8]) -> Result<(), W::Error> {
+    let header = [0xff, 0x03, (proto >> 8) as u8, proto as u8];
+    let fcs = !fcs16(fcs16(0xffff, &header), data);
+
+    buf[0] = FLAG;
+    let mut n = 1;
+    for &b in header.iter().chain(data).chain(&fcs.to_le_bytes()) {
+        if n + 2 > buf.len() {
+            w.write_all(&buf[..n]).await?;
+            n = 0;
+        }
+        if b < 0x20 || b == FLAG || b == ESCAPE {
+            buf[n] = ESCAPE;
+            buf[n + 1] = b ^ 0x20;
+            n += 2;
+        } else {
+            buf[n] = b;
+            n += 1;
+        }
+    }
+    if n == buf.len() {
+        w.write_all(&buf[..n]).await?;
+        n = 0;
+    }
+    buf[n] = FLAG;
+    w.write_all(&buf[..n + 1]).await
+}
+
+/// Re-encode a frame decoded by [`FrameReader`], to hand it to `ppproto`.
+pub(crate) fn encode(frame: &[u8], mut f: impl FnMut(&[u8])) {
+    let mut buf = [0; 64];
+    buf[0] = FLAG;
+    let mut n = 1;
+    for &b in frame {
+        if n + 2 > buf.len() {
+            f(&buf[..n]);
+            n = 0;
+        }
+        if b == FLAG || b == ESCAPE {
+            buf[n] = ESCAPE;
+            buf[n + 1] = b ^ 0x20;
+            n += 2;
+        } else {
+            buf[n] = b;
+            n += 1;
+        }
+    }
+    if n == buf.len() {
+        f(&buf[..n]);
+        n = 0;
+    }
+    buf[n] = FLAG;
+    f(&buf[..n + 1]);
+}
@@ -0,0 +1,371 @@
+//! IPv6 Control Protocol (RFC 5072).
+//!
+//! `ppproto` only supports IPv4, so IPV6CP runs next to it, with the same option negotiation
+//! state machine as `ppproto`'s control protocols.
+
+use core::net::Ipv6Addr;
+
+use embassy_time::{Duration, Instant};
+
+pub(crate) const PROTOCOL: u16 = 0x8057;
+
+const CONFIGURE_REQ: u8 = 1;
+const CONFIGURE_ACK: u8 = 2;
+const CONFIGURE_NAK: u8 = 3;
+const CONFIGURE_REJ: u8 = 4;
+const TERMINATE_REQ: u8 = 5;
+const TERMINATE_ACK: u8 = 6;
+
+const OPTION_INTERFACE_IDENTIFIER: u8 = 1;
+
+/// Time to wait for an answer to a Configure-Request before sending it again, see RFC 1661
+/// section 4.6.
+const RESTART_INTERVAL: Duration = Duration::from_secs(3);
+/// Configure-Requests sent without an answer before giving up.
+const MAX_CONFIGURE: u8 = 10;
+
+/// Longer option lists in a Configure-Request are ignored, so replies always fit in [`Output`].
+const MAX_OPTIONS_LEN: usize = 64;
+
+/// Status of the IPv6 connection.
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Ipv6Status {
+    /// Our interface identifier, negotiated with IPV6CP.
+    ///
+    /// Other addresses are formed with it, e.g. by SLAAC from the prefixes advertised by the peer.
+    pub interface_identifier: Option<[u8; 8]>,
+    /// The peer's interface identifier.
+    pub peer_interface_identifier: Option<[u8; 8]>,
+    /// Our link-local address, made from our interface identifier.
+    pub address: Option<Ipv6Addr>,
+    /// The peer's link-local address.
+    pub peer_address: Option<Ipv6Addr>,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+enum State {
+    Closed,
+    ReqSent,
+    AckReceived,
+    AckSent,
+    Opened,
+}
+
+/// Control packets to transmit, each starting with its code, identifier and length.
+pub(crate) struct Output {
+    buf: [u8; 128],
+    len: usize,
+}
+
+impl Output {
+    pub fn new() -> Self {
+        Self { buf: [0; 128], len: 0 }
+    }
+
+    /// Iterate over the packets.
+    pub fn packets(&self) -> impl Iterator<Item = &[u8]> {
+        let mut rest = &self.buf[..self.len];
+        core::iter::from_fn(move || {
+            if rest.is_empty() {
+                return None;
+            }
+            let len = u16::from_be_bytes([rest[2], rest[3]]) as usize;
+            let (pkt, r) = rest.split_at(len);
+            rest = r;
+            Some(pkt)
+        })
+    }
+
+    fn push(&mut self, code: u8, id: u8, data: impl FnOnce(&mut [u8]) -> usize) {
+        let buf = &mut self.buf[self.len..];
+        if buf.len() < 4 {
+            warn!("IPV6CP: no room for reply");
+            return;
+        }
+        let len = 4 + data(&mut buf[4..]);
+        buf[0] = code;
+        buf[1] = id;
+        buf[2..4].copy_from_slice(&(len as u16).to_be_bytes());
+        self.len += len;
+    }
+}
+
+pub(crate) struct Ipv6cp {
+    state: State,
+    id: u8,
+    /// Negotiation was started in this session. It isn't restarted after IPV6CP is terminated or
+    /// rejected by the peer.
+    started: bool,
+    /// Our interface identifier, all zeros until the peer suggests one.
+    interface_identifier: [u8; 8],
+    interface_identifier_rejected: bool,
+    peer_interface_identifier: [u8; 8],
+    /// When to send our Configure-Request again, until negotiation completes.
+    restart_at: Option<Instant>,
+    /// Configure-Requests sent since the peer last answered one.
+    configure_count: u8,
+    /// Identifier of the last Configure-Request of the peer we acked, to recognize
+    /// retransmissions.
+    acked_id: Option<u8>,
+}
+
+impl Ipv6cp {
+    pub fn new() -> Self {
+        Self {
+            state: State::Closed,
+            id: 1,
+            started: false,
+            interface_identifier: [0; 8],
+            interface_identifier_rejected: false,
+            peer_interface_identifier: [0; 8],
+            restart_at: None,
+            configure_count: 0,
+            acked_id: None,
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.state == State::Opened
+    }
+
+    pub fn status(&self) -> Ipv6Status {
+        let iid = |iid: [u8; 8]| (iid != [0; 8]).then_some(iid);
+        Ipv6Status {
+            interface_identifier: iid(self.interface_identifier),
+            peer_interface_identifier: iid(self.peer_interface_identifier),
+            address: link_local(self.interface_identifier),
+            peer_address: link_local(self.peer_interface_identifier),
+        }
+    }
+
+    /// Start negotiation, if not done yet.
+    pub fn open(&mut self, out: &mut Output) {
+        if !self.started {
+            self.started = true;
+            self.state = State::ReqSent;
+            self.send_configure_request(out);
+        }
+    }
+
+    /// When [`poll`](Self::poll) must be called next.
+    pub fn poll_at(&self) -> Option<Instant> {
+        self.restart_at
+    }
+
+    /// Send our Configure-Request again if it got no answer in time, or give up after
+    /// [`MAX_CONFIGURE`] of them.
+    pub fn poll(&mut self, out: &mut Output) {
+        match self.restart_at {
+            Some(at) if at <= Instant::now() => {}
+            _ => return,
+        }
+        if self.configure_count >= MAX_CONFIGURE {
+            warn!("IPV6CP: no answer to Configure-Requests, giving up");
+            self.state = State::Closed;
+            self.restart_at = None;
+            return;
+        }
+        debug!("IPV6CP: Configure-Request timed out, sending again");
+        if self.state == State::AckReceived {
+            self.state = State::ReqSent;
+        }
+        self.send_configure_request(out);
+    }
+
+    /// Bring IPV6CP down, when the link is no longer in the network phase.
+    pub fn close(&mut self) {
+        *self = Self::new();
+    }
+
+    /// The peer doesn't support IPV6CP.
+    pub fn protocol_rejected(&mut self) {
+        info!("IPV6CP rejected by peer");
+        self.state = State::Closed;
+        self.restart_at = None;
+    }
+
+    /// Handle a received IPV6CP packet, starting with its code.
+    pub fn handle(&mut self, pkt: &[u8], out: &mut Output) {
+        if pkt.len() < 4 {
+            warn!("IPV6CP packet too short");
+            return;
+        }
+        let code = pkt[0];
+        let id = pkt[1];
+        let len = u16::from_be_bytes([pkt[2], pkt[3]]) as usize;
+        if len < 4 || len > pkt.len() {
+            warn!("IPV6CP packet len invalid");
+            return;
+        }
+        let options = &pkt[4..len];
+
+        debug!("IPV6CP: rx code {}", code);
+        if matches!(code, CONFIGURE_ACK | CONFIGURE_NAK | CONFIGURE_REJ) {
+            self.configure_count = 0;
+        }
+        let old_state = self.state;
+        match (code, self.state) {
+            (TERMINATE_ACK, State::Closed) => {}
+            (_, State::Closed) => out.push(TERMINATE_ACK, id, |_| 0),
+
+            (CONFIGURE_REQ, _) => {
+                // The peer sends its request again when it didn't get our ack, so it may have
+                // missed our request too.
+                let retransmitted = self.state == State::AckSent && self.acked_id == Some(id);
+                let acked = self.received_configure_req(id, options, out);
+                if acked {
+                    self.acked_id = Some(id);
+                }
+                match (acked, self.state) {
+                    (true, State::ReqSent) => self.state = State::AckSent,
+                    (true, State::AckSent) if retransmitted => self.send_configure_request(out),
+                    (true, State::AckReceived) => self.state = State::Opened,
+                    (true, State::Opened) => {
+                        self.send_configure_request(out);
+                        self.state = State::AckSent;
+                    }
+                    (false, State::AckSent) => self.state = State::ReqSent,
+                    (false, State::Opened) => {
+                        self.send_configure_request(out);
+                        self.state = State::ReqSent;
+                    }
+                    _ => {}
+                }
+            }
+
+            (CONFIGURE_ACK, State::ReqSent) => self.state = State::AckReceived,
+            (CONFIGURE_ACK, State::AckSent) => self.state = State::Opened,
+            (CONFIGURE_ACK, _) => {
+                self.state = State::ReqSent;
+                self.send_configure_request(out);
+            }
+
+            (CONFIGURE_NAK, _) | (CONFIGURE_REJ, _) => {
+                for (ocode, odata) in options_iter(options) {
+                    if ocode != OPTION_INTERFACE_IDENTIFIER {
+                        continue;
+                    }
+                    match <[u8; 8]>::try_from(odata) {
+                        Ok(iid) if code == CONFIGURE_NAK && iid != [0; 8] => self.interface_identifier = iid,
+                        _ => self.interface_identifier_rejected = true,
+                    }
+                }
+                if self.state != State::AckSent {
+                    self.state = State::ReqSent;
+                }
+                self.send_configure_request(out);
+            }
+
+            (TERMINATE_REQ, State::Opened) => {
+                self.state = State::Closed;
+                out.push(TERMINATE_ACK, id, |_| 0);
+            }
+            (TERMINATE_REQ, _) => {
+                self.state = State::ReqSent;
+                out.push(TERMINATE_ACK, id, |_| 0);
+            }
+
+            _ => debug!("IPV6CP: ignoring unexpected code {} in state {:?}", code, self.state),
+        }
+
+        if matches!(self.state, State::Closed | State::Opened) {
+            self.restart_at = None;
+        }
+        if old_state != self.state {
+            debug!("IPV6CP: state {:?} -> {:?}", old_state, self.state);
+        }
+    }
+
+    fn send_configure_request(&mut self, out: &mut Output) {
+        self.id = self.id.wrapping_add(1);
+        self.configure_count = self.configure_count.saturating_add(1);
+        self.restart_at = Some(Instant::now() + RESTART_INTERVAL);
+        let iid = (!self.interface_identifier_rejected).then_some(self.interface_identifier);
+        out.push(CONFIGURE_REQ, self.id, |buf| match iid {
+            Some(iid) => write_interface_identifier(buf, iid),
+            None => 0,
+        });
+    }
+
+    /// Reply to a Configure-Request, returning whether it was acked.
+    fn received_configure_req(&mut self, id: u8, options: &[u8], out: &mut Output) -> bool {
+        let valid_len = options_iter(options).map(|(_, data)| data.len() + 2).sum::<usize>();
+        if valid_len != options.len() || options.len() > MAX_OPTIONS_LEN {
+            warn!("IPV6CP: malformed options");
+            return false;
+        }
+
+        // Reject unknown options, then nak an unusable interface identifier.
+        let rejected = options_iter(options).any(|(code, data)| code != OPTION_INTERFACE_IDENTIFIER || data.len() != 8);
+        if rejected {
+            out.push(CONFIGURE_REJ, id, |buf| {
+                let mut n = 0;
+                for (code, data) in options_iter(options) {
+                    if code != OPTION_INTERFACE_IDENTIFIER || data.len() != 8 {
+                        if n + data.len() + 2 > buf.len() {
+                            break;
+                        }
+                        buf[n] = code;
+                        buf[n + 1] = data.len() as u8 + 2;
+                        buf[n + 2..][..data.len()].copy_from_slice(data);
+                        n += data.len() + 2;
+                    }
+                }
+                n
+            });
+            return false;
+        }
+
+        for (_, data) in options_iter(options) {
+            let iid: [u8; 8] = data.try_into().unwrap();
+            if iid == [0; 8] || iid == self.interface_identifier {
+                // Suggest an identifier different from ours.
+                let mut suggested = self.interface_identifier;
+                suggested[7] = suggested[7].wrapping_add(1);
+                if suggested == [0; 8] {
+                    suggested[7] = 1;
+                }
+                out.push(CONFIGURE_NAK, id, |buf| write_interface_identifier(buf, suggested));
+                return false;
+            }
+            self.peer_interface_identifier = iid;
+        }
+
+        out.push(CONFIGURE_ACK, id, |buf| {
+            buf[..options.len()].copy_from_slice(options);
+            options.len()
+        });
+        true
+    }
+}
+
+fn write_interface_identifier(buf: &mut [u8], iid: [u8; 8]) -> usize {
+    buf[0] = OPTION_INTERFACE_IDENTIFIER;
+    buf[1] = 10;
+    buf[2..10].copy_from_slice(&iid);
+    10
+}
+
+fn options_iter(mut options: &[u8]) -> impl Iterator<Item = (u8, &[u8])> {
+    core::iter::from_fn(move || {
+        if options.len() < 2 || (options[1] as usize) < 2 || options.len() < options[1] as usize {
+            return None;
+        }
+        let (opt, rest) = options.split_at(options[1] as usize);
+        options = rest;
+        Some((opt[0], &opt[2..]))
+    })
+}
+
+fn link_local(iid: [u8; 8]) -> Option<Ipv6Addr> {
+    if iid == [0; 8] {
+        return None;
+    }
+    let mut octets = [0; 16];
+    octets[0] = 0xfe;
+    octets[1] = 0x80;
+    octets[8..].copy_from_slice(&iid);
+    Some(Ipv6Addr::from(octets))
+}
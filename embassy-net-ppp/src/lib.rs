@@ -5,18 +5,39 @@
 // must be first
 mod fmt;
 
+mod dialer;
+mod frame;
+mod ipv6cp;
+
 use core::convert::Infallible;
 use core::mem::MaybeUninit;
 
-use embassy_futures::select::{select, Either};
+use embassy_futures::select::{select3, Either3};
 use embassy_net_driver_channel as ch;
 use embassy_net_driver_channel::driver::LinkState;
+use embassy_time::{Duration, Instant, Timer};
 use embedded_io_async::{BufRead, Write};
 use ppproto::pppos::{BufferFullError, PPPoS, PPPoSAction};
 pub use ppproto::{Config, Ipv4Status};
 
+pub use crate::dialer::{ChatError, ChatStep, Dialer, DEFAULT_ABORT_RESPONSES};
+use crate::frame::FrameReader;
+pub use crate::ipv6cp::Ipv6Status;
+use crate::ipv6cp::Ipv6cp;
+
 const MTU: usize = 1500;
 
+const PROTOCOL_IPV4: u16 = 0x0021;
+const PROTOCOL_IPV6: u16 = 0x0057;
+const PROTOCOL_LCP: u16 = 0xc021;
+const LCP_PROTOCOL_REJECT: u8 = 8;
+const LCP_ECHO_REQUEST: u8 = 9;
+
+/// An LCP Echo-Request is sent when nothing was received for this long.
+const LCP_ECHO_INTERVAL: Duration = Duration::from_secs(10);
+/// The link is considered lost when this many Echo-Requests in a row get no answer.
+const LCP_ECHO_FAILURES: u8 = 3;
+
 /// Type alias for the embassy-net driver.
 pub type Device<'d> = embassy_net_driver_channel::Device<'d, MTU>;
 
@@ -53,6 +74,10 @@ pub enum RunError<E> {
     Eof,
     /// PPP protocol was terminated by the peer
     Terminated,
+    /// The modem reported `NO CARRIER`, it dropped the connection.
+    NoCarrier,
+    /// The peer stopped answering LCP Echo-Requests.
+    EchoTimeout,
 }
 
 impl<'d> Runner<'d> {
@@ -61,16 +86,37 @@ impl<'d> Runner<'d> {
     /// If reading/writing to the underlying serial port fails, the link state
     /// is set to Down and the error is returned.
     ///
+    /// Once LCP is open, an LCP Echo-Request is sent whenever nothing was received for 10
+    /// seconds. If 3 of them in a row get no answer, or the modem reports `NO CARRIER`, the
+    /// connection is considered lost and an error is returned.
+    ///
     /// It is allowed to cancel this function's future (i.e. drop it). This will terminate
     /// the PPP connection and set the link state to Down.
     ///
     /// After this function returns or is canceled, you can call it again to establish
     /// a new PPP connection.
+    ///
+    /// IPv6 is still negotiated, use [`run_with_ipv6`](Self::run_with_ipv6) to be told
+    /// about it.
     pub async fn run<RW: BufRead + Write>(
+        &mut self,
+        rw: RW,
+        config: ppproto::Config<'_>,
+        on_ipv4_up: impl FnMut(Ipv4Status),
+    ) -> Result<Infallible, RunError<RW::Error>> {
+        self.run_with_ipv6(rw, config, on_ipv4_up, |_| {}).await
+    }
+
+    /// Like [`run`](Self::run), also calling `on_ipv6_up` once IPV6CP is open.
+    ///
+    /// `on_ipv6_up` gets the negotiated interface identifiers, and the link-local addresses
+    /// made from them.
+    pub async fn run_with_ipv6<RW: BufRead + Write>(
         &mut self,
         mut rw: RW,
         config: ppproto::Config<'_>,
         mut on_ipv4_up: impl FnMut(Ipv4Status),
+        mut on_ipv6_up: impl FnMut(Ipv6Status),
     ) -> Result<Infallible, RunError<RW::Error>> {
         let mut ppp = PPPoS::new(config);
         ppp.open().unwrap();
@@ -79,11 +125,20 @@ impl<'d> Runner<'d> {
         state_chan.set_link_state(LinkState::Down);
         let _ondrop = OnDrop::new(|| state_chan.set_link_state(LinkState::Down));
 
+        let mut reader = FrameReader::new();
+        let mut ipv6cp = Ipv6cp::new();
+
         let mut rx_buf = [0; 2048];
+        let mut ppp_rx_buf = [0; 512];
         let mut tx_buf = [0; 2048];
 
         let mut needs_poll = true;
-        let mut was_up = false;
+        let mut ipv4_up = false;
+        let mut ipv6_up = false;
+
+        let mut echo_at = Instant::now() + LCP_ECHO_INTERVAL;
+        let mut echo_id: u8 = 0;
+        let mut echo_unanswered = 0;
 
         loop {
             let rx_fut = async {
@@ -99,50 +154,218 @@ impl<'d> Runner<'d> {
                 Ok((buf, rx_data))
             };
             let tx_fut = tx_chan.tx_buf();
-            match select(rx_fut, tx_fut).await {
-                Either::First(r) => {
+            let timer_at = match ipv6cp.poll_at() {
+                Some(at) => at.min(echo_at),
+                None => echo_at,
+            };
+            match select3(rx_fut, tx_fut, Timer::at(timer_at)).await {
+                Either3::First(r) => {
                     needs_poll = false;
 
                     let (buf, rx_data) = r?;
-                    let n = ppp.consume(rx_data, &mut rx_buf);
+                    let (n, frame_len) = reader.consume(&mut rx_buf, rx_data);
                     rw.consume(n);
+                    if reader.no_carrier() {
+                        info!("modem reported NO CARRIER");
+                        return Err(RunError::NoCarrier);
+                    }
 
-                    match ppp.poll(&mut tx_buf, &mut rx_buf) {
-                        PPPoSAction::None => {}
-                        PPPoSAction::Received(rg) => {
-                            let pkt = &rx_buf[rg];
-                            buf[..pkt.len()].copy_from_slice(pkt);
-                            rx_chan.rx_done(pkt.len());
+                    // IP packets and IPV6CP are handled here, the other protocols by `ppproto`.
+                    let mut ipv6cp_pkt = None;
+                    if let Some(len) = frame_len {
+                        echo_at = Instant::now() + LCP_ECHO_INTERVAL;
+                        echo_unanswered = 0;
+
+                        let frame = &rx_buf[..len];
+                        let proto = u16::from_be_bytes([frame[2], frame[3]]);
+                        let data = &frame[4..len - 2];
+                        match proto {
+                            _ if frame[..2] != [0xff, 0x03] => warn!("unsupported address and control fields"),
+                            PROTOCOL_IPV4 | PROTOCOL_IPV6 => {
+                                if proto == PROTOCOL_IPV6 && !ipv6cp.is_open() {
+                                    debug!("IPV6CP not open, dropping IPv6 packet");
+                                } else if data.len() > buf.len() {
+                                    warn!("packet too long, dropping");
+                                } else {
+                                    buf[..data.len()].copy_from_slice(data);
+                                    rx_chan.rx_done(data.len());
+                                }
+                            }
+                            ipv6cp::PROTOCOL => ipv6cp_pkt = Some(4..len - 2),
+                            _ => {
+                                if proto == PROTOCOL_LCP
+                                    && data.len() >= 6
+                                    && data[0] == LCP_PROTOCOL_REJECT
+                                    && data[4..6] == ipv6cp::PROTOCOL.to_be_bytes()
+                                {
+                                    ipv6cp.protocol_rejected();
+                                }
+                                // `ppproto` stops consuming after a complete frame until it's polled,
+                                // which is done after each frame, so it should take the whole frame.
+                                let mut consumed = true;
+                                frame::encode(frame, |chunk| {
+                                    consumed &= ppp.consume(chunk, &mut ppp_rx_buf) == chunk.len();
+                                });
+                                if !consumed {
+                                    warn!("PPP frame not fully consumed, dropping");
+                                }
+                            }
                         }
+                    }
+
+                    match ppp.poll(&mut tx_buf, &mut ppp_rx_buf) {
+                        PPPoSAction::None => {}
+                        // IPv4 packets are never handed to `ppproto`.
+                        PPPoSAction::Received(_) => unreachable!(),
                         PPPoSAction::Transmit(n) => rw.write_all(&tx_buf[..n]).await.map_err(RunError::Write)?,
                     }
 
                     let status = ppp.status();
+                    let mut out = ipv6cp::Output::new();
                     match status.phase {
                         ppproto::Phase::Dead => {
                             return Err(RunError::Terminated);
                         }
                         ppproto::Phase::Open => {
-                            if !was_up {
+                            if !ipv4_up {
                                 on_ipv4_up(status.ipv4.unwrap());
                             }
-                            was_up = true;
-                            state_chan.set_link_state(LinkState::Up);
+                            ipv4_up = true;
+                            ipv6cp.open(&mut out);
+                        }
+                        ppproto::Phase::Network => {
+                            ipv4_up = false;
+                            ipv6cp.open(&mut out);
                         }
                         _ => {
-                            was_up = false;
-                            state_chan.set_link_state(LinkState::Down);
+                            ipv4_up = false;
+                            ipv6cp.close();
+                        }
+                    }
+
+                    if let Some(range) = ipv6cp_pkt {
+                        ipv6cp.handle(&rx_buf[range], &mut out);
+                    }
+                    for pkt in out.packets() {
+                        frame::write_frame(&mut rw, &mut tx_buf, ipv6cp::PROTOCOL, pkt)
+                            .await
+                            .map_err(RunError::Write)?;
+                    }
+                    if ipv6cp.is_open() {
+                        if !ipv6_up {
+                            on_ipv6_up(ipv6cp.status());
                         }
+                        ipv6_up = true;
+                    } else {
+                        ipv6_up = false;
+                    }
+
+                    match ipv4_up || ipv6_up {
+                        true => state_chan.set_link_state(LinkState::Up),
+                        false => state_chan.set_link_state(LinkState::Down),
                     }
                 }
-                Either::Second(pkt) => {
-                    match ppp.send(pkt, &mut tx_buf) {
-                        Ok(n) => rw.write_all(&tx_buf[..n]).await.map_err(RunError::Write)?,
-                        Err(BufferFullError) => unreachable!(),
+                Either3::Second(pkt) => {
+                    if pkt.first().map(|b| b >> 4) == Some(6) {
+                        // Packets are dropped until IPV6CP is open.
+                        if ipv6cp.is_open() {
+                            frame::write_frame(&mut rw, &mut tx_buf, PROTOCOL_IPV6, pkt)
+                                .await
+                                .map_err(RunError::Write)?;
+                        }
+                    } else {
+                        match ppp.send(pkt, &mut tx_buf) {
+                            Ok(n) => rw.write_all(&tx_buf[..n]).await.map_err(RunError::Write)?,
+                            Err(BufferFullError) => unreachable!(),
+                        }
                     }
                     tx_chan.tx_done();
                 }
+                Either3::Third(()) => {
+                    let mut out = ipv6cp::Output::new();
+                    ipv6cp.poll(&mut out);
+                    for pkt in out.packets() {
+                        frame::write_frame(&mut rw, &mut tx_buf, ipv6cp::PROTOCOL, pkt)
+                            .await
+                            .map_err(RunError::Write)?;
+                    }
+
+                    if Instant::now() < echo_at {
+                        continue;
+                    }
+                    echo_at = Instant::now() + LCP_ECHO_INTERVAL;
+                    // Echo-Requests are only allowed once LCP is open.
+                    if matches!(ppp.status().phase, ppproto::Phase::Network | ppproto::Phase::Open) {
+                        if echo_unanswered == LCP_ECHO_FAILURES {
+                            warn!("no answer to LCP Echo-Requests");
+                            return Err(RunError::EchoTimeout);
+                        }
+                        echo_unanswered += 1;
+                        echo_id = echo_id.wrapping_add(1);
+                        // No magic number is negotiated, so it's zero.
+                        let pkt = [LCP_ECHO_REQUEST, echo_id, 0, 8, 0, 0, 0, 0];
+                        frame::write_frame(&mut rw, &mut tx_buf, PROTOCOL_LCP, &pkt)
+                            .await
+                            .map_err(RunError::Write)?;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Dial with the modem, and run PPP over the connection, dialing again when it ends.
+    ///
+    /// `dialer` brings the modem into data mode before PPP runs with [`run`](Self::run).
+    /// When the PPP connection is terminated or lost, or the serial port gets EOF, the modem is
+    /// hung up and dialed again after the dialer's redial delay. Dialing is also retried when the modem
+    /// doesn't answer as expected.
+    ///
+    /// Errors reading or writing the serial port are returned.
+    pub async fn run_with_dialer<RW: BufRead + Write>(
+        &mut self,
+        rw: RW,
+        dialer: &Dialer<'_>,
+        config: ppproto::Config<'_>,
+        on_ipv4_up: impl FnMut(Ipv4Status),
+    ) -> Result<Infallible, RunError<RW::Error>> {
+        self.run_with_dialer_ipv6(rw, dialer, config, on_ipv4_up, |_| {}).await
+    }
+
+    /// Like [`run_with_dialer`](Self::run_with_dialer), also calling `on_ipv6_up` once IPV6CP
+    /// is open, as [`run_with_ipv6`](Self::run_with_ipv6) does.
+    pub async fn run_with_dialer_ipv6<RW: BufRead + Write>(
+        &mut self,
+        mut rw: RW,
+        dialer: &Dialer<'_>,
+        config: ppproto::Config<'_>,
+        mut on_ipv4_up: impl FnMut(Ipv4Status),
+        mut on_ipv6_up: impl FnMut(Ipv6Status),
+    ) -> Result<Infallible, RunError<RW::Error>> {
+        loop {
+            match dialer.dial(&mut rw).await {
+                Ok(()) => match self
+                    .run_with_ipv6(&mut rw, config.clone(), &mut on_ipv4_up, &mut on_ipv6_up)
+                    .await
+                {
+                    Err(RunError::Terminated)
+                    | Err(RunError::NoCarrier)
+                    | Err(RunError::EchoTimeout)
+                    | Err(RunError::Eof) => {
+                        info!("PPP connection ended, hanging up");
+                        match dialer.hang_up(&mut rw).await {
+                            Err(ChatError::Read(e)) => return Err(RunError::Read(e)),
+                            Err(ChatError::Write(e)) => return Err(RunError::Write(e)),
+                            Err(_) => warn!("hanging up failed"),
+                            Ok(()) => {}
+                        }
+                    }
+                    Err(e) => return Err(e),
+                },
+                Err(ChatError::Read(e)) => return Err(RunError::Read(e)),
+                Err(ChatError::Write(e)) => return Err(RunError::Write(e)),
+                Err(_) => warn!("dialing failed"),
             }
+            Timer::after(dialer.redial_delay()).await;
         }
     }
 }
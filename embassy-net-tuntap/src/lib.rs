@@ -11,6 +11,8 @@ use log::*;
 
 /// Get the MTU of the given interface.
 pub const SIOCGIFMTU: libc::c_ulong = 0x8921;
+/// Set the MTU of the given interface.
+pub const SIOCSIFMTU: libc::c_ulong = 0x8922;
 /// Get the index of the given interface.
 pub const _SIOCGIFINDEX: libc::c_ulong = 0x8933;
 /// Capture all packages.
@@ -18,14 +20,66 @@ pub const _ETH_P_ALL: libc::c_short = 0x0003;
 /// Set the interface flags.
 pub const TUNSETIFF: libc::c_ulong = 0x400454CA;
 /// TUN device.
-pub const _IFF_TUN: libc::c_int = 0x0001;
+pub const IFF_TUN: libc::c_int = 0x0001;
 /// TAP device.
 pub const IFF_TAP: libc::c_int = 0x0002;
 /// No packet information.
 pub const IFF_NO_PI: libc::c_int = 0x1000;
+/// Multi-queue device.
+pub const IFF_MULTI_QUEUE: libc::c_int = 0x0100;
 
 const ETHERNET_HEADER_LEN: usize = 14;
 
+/// MAC address used in TAP mode by default.
+pub const DEFAULT_MAC_ADDRESS: [u8; 6] = [0x02, 0x03, 0x04, 0x05, 0x06, 0x07];
+
+/// Kind of TUN/TAP device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// TUN device, carrying IP packets. Use it with the `medium-ip` feature of `embassy-net`.
+    Tun,
+    /// TAP device, carrying Ethernet frames. Use it with the `medium-ethernet` feature of `embassy-net`.
+    Tap {
+        /// MAC address of the `embassy-net` side of the interface.
+        mac_address: [u8; 6],
+    },
+}
+
+/// TUN/TAP device configuration.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct Config {
+    /// Kind of device. Defaults to TAP, with [`DEFAULT_MAC_ADDRESS`].
+    pub mode: Mode,
+    /// IP MTU to set on the interface. If `None` (the default), the interface's MTU is kept.
+    pub mtu: Option<usize>,
+    /// Open the interface with `IFF_MULTI_QUEUE`. Each device created with the same name then
+    /// attaches another queue to the interface, and the kernel spreads traffic across them.
+    pub multi_queue: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            mode: Mode::Tap {
+                mac_address: DEFAULT_MAC_ADDRESS,
+            },
+            mtu: None,
+            multi_queue: false,
+        }
+    }
+}
+
+impl Config {
+    /// Default configuration of a TUN device.
+    pub fn tun() -> Self {
+        Self {
+            mode: Mode::Tun,
+            ..Default::default()
+        }
+    }
+}
+
 #[repr(C)]
 #[derive(Debug)]
 #[allow(non_camel_case_types)]
@@ -60,6 +114,7 @@ fn ifreq_ioctl(lower: libc::c_int, ifreq: &mut ifreq, cmd: libc::c_ulong) -> io:
 #[derive(Debug)]
 pub struct TunTap {
     fd: libc::c_int,
+    mode: Mode,
     mtu: usize,
 }
 
@@ -70,8 +125,13 @@ impl AsRawFd for TunTap {
 }
 
 impl TunTap {
-    /// Create a new TUN/TAP device.
+    /// Create a new TAP device.
     pub fn new(name: &str) -> io::Result<TunTap> {
+        Self::with_config(name, &Config::default())
+    }
+
+    /// Create a new TUN/TAP device with the given configuration.
+    pub fn with_config(name: &str, config: &Config) -> io::Result<TunTap> {
         unsafe {
             let fd = libc::open(
                 "/dev/net/tun\0".as_ptr() as *const libc::c_char,
@@ -81,8 +141,21 @@ impl TunTap {
                 return Err(io::Error::last_os_error());
             }
 
+            // Close the fd if anything below fails.
+            let mut tuntap = TunTap {
+                fd,
+                mode: config.mode,
+                mtu: 0,
+            };
+
             let mut ifreq = ifreq_for(name);
-            ifreq.ifr_data = IFF_TAP | IFF_NO_PI;
+            ifreq.ifr_data = match config.mode {
+                Mode::Tun => IFF_TUN,
+                Mode::Tap { .. } => IFF_TAP,
+            } | IFF_NO_PI;
+            if config.multi_queue {
+                ifreq.ifr_data |= IFF_MULTI_QUEUE;
+            }
             ifreq_ioctl(fd, &mut ifreq, TUNSETIFF)?;
 
             let socket = libc::socket(libc::AF_INET, libc::SOCK_DGRAM, libc::IPPROTO_IP);
@@ -90,15 +163,24 @@ impl TunTap {
                 return Err(io::Error::last_os_error());
             }
 
-            let ip_mtu = ifreq_ioctl(socket, &mut ifreq, SIOCGIFMTU);
+            let ip_mtu = match config.mtu {
+                Some(mtu) => {
+                    ifreq.ifr_data = mtu as libc::c_int;
+                    ifreq_ioctl(socket, &mut ifreq, SIOCSIFMTU)
+                }
+                None => ifreq_ioctl(socket, &mut ifreq, SIOCGIFMTU),
+            };
             libc::close(socket);
             let ip_mtu = ip_mtu? as usize;
 
             // SIOCGIFMTU returns the IP MTU (typically 1500 bytes.)
             // smoltcp counts the entire Ethernet packet in the MTU, so add the Ethernet header size to it.
-            let mtu = ip_mtu + ETHERNET_HEADER_LEN;
+            tuntap.mtu = match config.mode {
+                Mode::Tun => ip_mtu,
+                Mode::Tap { .. } => ip_mtu + ETHERNET_HEADER_LEN,
+            };
 
-            Ok(TunTap { fd, mtu })
+            Ok(tuntap)
         }
     }
 }
@@ -143,10 +225,20 @@ pub struct TunTapDevice {
 }
 
 impl TunTapDevice {
-    /// Create a new TUN/TAP device.
+    /// Create a new TAP device.
     pub fn new(name: &str) -> io::Result<TunTapDevice> {
+        Self::with_config(name, &Config::default())
+    }
+
+    /// Create a new TUN device.
+    pub fn new_tun(name: &str) -> io::Result<TunTapDevice> {
+        Self::with_config(name, &Config::tun())
+    }
+
+    /// Create a new TUN/TAP device with the given configuration.
+    pub fn with_config(name: &str, config: &Config) -> io::Result<TunTapDevice> {
         Ok(Self {
-            device: Async::new(TunTap::new(name)?)?,
+            device: Async::new(TunTap::with_config(name, config)?)?,
         })
     }
 }
@@ -201,7 +293,10 @@ impl Driver for TunTapDevice {
     }
 
     fn hardware_address(&self) -> HardwareAddress {
-        HardwareAddress::Ethernet([0x02, 0x03, 0x04, 0x05, 0x06, 0x07])
+        match self.device.get_ref().mode {
+            Mode::Tun => HardwareAddress::Ip,
+            Mode::Tap { mac_address } => HardwareAddress::Ethernet(mac_address),
+        }
     }
 }
 
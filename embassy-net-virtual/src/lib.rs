@@ -0,0 +1,375 @@
+#![no_std]
+#![warn(missing_docs)]
+#![doc = include_str!("../README.md")]
+
+use core::cell::RefCell;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::Context;
+
+use embassy_net_driver::{Capabilities, Driver, HardwareAddress, LinkState};
+use embassy_sync::blocking_mutex::raw::RawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::waitqueue::WakerRegistration;
+use embassy_time::{Duration, Instant, Timer};
+use heapless::Vec;
+
+/// Behavior of the simulated network.
+#[derive(Debug, Clone, Copy)]
+pub struct Config {
+    /// Time between the transmission of a packet and its reception.
+    pub latency: Duration,
+    /// Maximum random delay added to the latency of each packet.
+    ///
+    /// Packets are received in the order they arrive in, so packets sent close together can be
+    /// reordered when this is longer than the time between them.
+    pub jitter: Duration,
+    /// Probability a packet is dropped, from 0.0 (never) to 1.0 (always).
+    pub loss: f32,
+    /// Seed of the random delays and drops.
+    pub seed: u64,
+}
+
+impl Config {
+    /// A perfect network: no latency, no jitter and no loss.
+    pub const fn new() -> Self {
+        Self {
+            latency: Duration::from_ticks(0),
+            jitter: Duration::from_ticks(0),
+            loss: 0.0,
+            seed: 0,
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A virtual Ethernet switch, connecting up to `PORTS` devices.
+///
+/// Frames are delivered to the device with their destination address, or to all other devices
+/// for broadcast, multicast and unknown destinations. Each device buffers up to `QUEUE` frames
+/// of up to `MTU` bytes, Ethernet header included: frames arriving at a full device are dropped,
+/// and counted by [`Driver::rx_dropped`].
+pub struct Switch<M: RawMutex, const PORTS: usize, const MTU: usize, const QUEUE: usize> {
+    state: Mutex<M, RefCell<State<PORTS, MTU, QUEUE>>>,
+}
+
+/// A loopback device: packets it transmits are received by itself.
+///
+/// It uses the IP medium. It buffers up to `QUEUE` packets of up to `MTU` bytes: packets
+/// transmitted while it's full are dropped.
+pub struct Loopback<M: RawMutex, const MTU: usize, const QUEUE: usize> {
+    switch: Switch<M, 1, MTU, QUEUE>,
+}
+
+/// A device of a [`Switch`] or [`Loopback`], implementing the `embassy-net` [`Driver`] trait.
+pub struct Device<'d, M: RawMutex, const PORTS: usize, const MTU: usize, const QUEUE: usize> {
+    switch: &'d Switch<M, PORTS, MTU, QUEUE>,
+    port: usize,
+}
+
+struct State<const PORTS: usize, const MTU: usize, const QUEUE: usize> {
+    config: Config,
+    random: u64,
+    loopback: bool,
+    ports: [Port<MTU, QUEUE>; PORTS],
+}
+
+struct Port<const MTU: usize, const QUEUE: usize> {
+    /// The hardware address of the device, once it's created.
+    hardware_address: Option<[u8; 6]>,
+    link_up: bool,
+    queue: Vec<Packet<MTU>, QUEUE>,
+    /// Frames dropped because the queue was full.
+    rx_dropped: u32,
+    waker: WakerRegistration,
+}
+
+struct Packet<const MTU: usize> {
+    /// When the packet can be received.
+    at: Instant,
+    len: usize,
+    buf: [u8; MTU],
+}
+
+impl<M: RawMutex, const PORTS: usize, const MTU: usize, const QUEUE: usize> Switch<M, PORTS, MTU, QUEUE> {
+    /// Create a new switch.
+    pub const fn new(config: Config) -> Self {
+        Self::new_inner(config, false)
+    }
+
+    const fn new_inner(config: Config, loopback: bool) -> Self {
+        Self {
+            state: Mutex::new(RefCell::new(State {
+                config,
+                random: seed(config.seed),
+                loopback,
+                ports: [const { Port::new() }; PORTS],
+            })),
+        }
+    }
+
+    /// Connect a new device to the switch, with the given Ethernet address.
+    ///
+    /// Its link is up.
+    ///
+    /// # Panics
+    ///
+    /// Panics if all `PORTS` ports of the switch are in use.
+    pub fn device(&self, hardware_address: [u8; 6]) -> Device<'_, M, PORTS, MTU, QUEUE> {
+        let port = self.with(|s| {
+            let Some(port) = s.ports.iter().position(|p| p.hardware_address.is_none()) else {
+                panic!("All {} ports of the switch are in use.", PORTS);
+            };
+            s.ports[port].hardware_address = Some(hardware_address);
+            port
+        });
+        Device { switch: self, port }
+    }
+
+    /// Change the behavior of the network.
+    ///
+    /// Frames already in flight keep the delay they got when transmitted.
+    pub fn set_config(&self, config: Config) {
+        self.with(|s| {
+            s.config = config;
+            s.random = seed(config.seed);
+        })
+    }
+
+    /// Plug or unplug the device connected to a port, see [`Device::port`].
+    ///
+    /// Frames in flight to a device are dropped when it's unplugged.
+    pub fn set_link_up(&self, port: usize, up: bool) {
+        self.with(|s| {
+            let port = &mut s.ports[port];
+            port.link_up = up;
+            if !up {
+                port.queue.clear();
+            }
+            port.waker.wake();
+        })
+    }
+
+    fn with<R>(&self, f: impl FnOnce(&mut State<PORTS, MTU, QUEUE>) -> R) -> R {
+        self.state.lock(|s| f(&mut s.borrow_mut()))
+    }
+}
+
+impl<M: RawMutex, const MTU: usize, const QUEUE: usize> Loopback<M, MTU, QUEUE> {
+    /// Create a new loopback device.
+    pub const fn new(config: Config) -> Self {
+        Self {
+            switch: Switch::new_inner(config, true),
+        }
+    }
+
+    /// Get the loopback device.
+    ///
+    /// # Panics
+    ///
+    /// Panics if it's called more than once.
+    pub fn device(&self) -> Device<'_, M, 1, MTU, QUEUE> {
+        self.switch.device([0; 6])
+    }
+
+    /// Change the behavior of the device.
+    ///
+    /// Packets already in flight keep the delay they got when transmitted.
+    pub fn set_config(&self, config: Config) {
+        self.switch.set_config(config)
+    }
+}
+
+impl<'d, M: RawMutex, const PORTS: usize, const MTU: usize, const QUEUE: usize> Device<'d, M, PORTS, MTU, QUEUE> {
+    /// The port of the switch this device is connected to.
+    pub fn port(&self) -> usize {
+        self.port
+    }
+
+    fn reborrow(&self) -> Device<'d, M, PORTS, MTU, QUEUE> {
+        Device {
+            switch: self.switch,
+            port: self.port,
+        }
+    }
+}
+
+impl<const PORTS: usize, const MTU: usize, const QUEUE: usize> State<PORTS, MTU, QUEUE> {
+    fn transmit(&mut self, from: usize, frame: &[u8]) {
+        if self.loopback {
+            self.deliver(from, frame);
+            return;
+        }
+        if !self.ports[from].link_up {
+            return;
+        }
+
+        let dst: [u8; 6] = frame[..6].try_into().unwrap();
+        let flood = dst[0] & 0x01 != 0 || !self.ports.iter().any(|p| p.hardware_address == Some(dst));
+        for port in 0..PORTS {
+            let p = &self.ports[port];
+            if port != from && p.link_up && (flood || p.hardware_address == Some(dst)) {
+                self.deliver(port, frame);
+            }
+        }
+    }
+
+    fn deliver(&mut self, port: usize, frame: &[u8]) {
+        if self.config.loss > 0.0 && (self.random() as f32 / u64::MAX as f32) < self.config.loss {
+            return;
+        }
+        let jitter = match self.config.jitter.as_ticks() {
+            0 => 0,
+            max => self.random() % (max + 1),
+        };
+        let at = Instant::now() + self.config.latency + Duration::from_ticks(jitter);
+
+        let port = &mut self.ports[port];
+        let mut packet = Packet {
+            at,
+            len: frame.len(),
+            buf: [0; MTU],
+        };
+        packet.buf[..frame.len()].copy_from_slice(frame);
+        match port.queue.push(packet) {
+            Ok(()) => port.waker.wake(),
+            Err(_) => port.rx_dropped = port.rx_dropped.wrapping_add(1),
+        }
+    }
+
+    fn random(&mut self) -> u64 {
+        // xorshift64
+        self.random ^= self.random << 13;
+        self.random ^= self.random >> 7;
+        self.random ^= self.random << 17;
+        self.random
+    }
+}
+
+impl<const MTU: usize, const QUEUE: usize> Port<MTU, QUEUE> {
+    const fn new() -> Self {
+        Self {
+            hardware_address: None,
+            link_up: true,
+            queue: Vec::new(),
+            rx_dropped: 0,
+            waker: WakerRegistration::new(),
+        }
+    }
+
+    /// The position in the queue of the next packet to receive, by arrival time then transmission order.
+    fn next(&self) -> Option<usize> {
+        self.queue.iter().enumerate().min_by_key(|(_, p)| p.at).map(|(i, _)| i)
+    }
+}
+
+const fn seed(seed: u64) -> u64 {
+    // The xorshift state must not be zero.
+    seed ^ 0x9E37_79B9_7F4A_7C15
+}
+
+impl<M: RawMutex, const PORTS: usize, const MTU: usize, const QUEUE: usize> Driver
+    for Device<'_, M, PORTS, MTU, QUEUE>
+{
+    type RxToken<'a>
+        = RxToken<'a, M, PORTS, MTU, QUEUE>
+    where
+        Self: 'a;
+    type TxToken<'a>
+        = TxToken<'a, M, PORTS, MTU, QUEUE>
+    where
+        Self: 'a;
+
+    fn receive(&mut self, cx: &mut Context) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+        let at = self.switch.with(|s| {
+            let port = &mut s.ports[self.port];
+            port.waker.register(cx.waker());
+            port.next().map(|i| port.queue[i].at)
+        })?;
+
+        if at > Instant::now() {
+            // Wake up when the packet arrives.
+            let _ = Pin::new(&mut Timer::at(at)).poll(cx);
+            return None;
+        }
+        Some((RxToken(self.reborrow()), TxToken(self.reborrow())))
+    }
+
+    fn transmit(&mut self, _cx: &mut Context) -> Option<Self::TxToken<'_>> {
+        Some(TxToken(self.reborrow()))
+    }
+
+    fn link_state(&mut self, cx: &mut Context) -> LinkState {
+        self.switch.with(|s| {
+            let port = &mut s.ports[self.port];
+            port.waker.register(cx.waker());
+            match port.link_up {
+                true => LinkState::Up,
+                false => LinkState::Down,
+            }
+        })
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        let mut caps = Capabilities::default();
+        caps.max_transmission_unit = MTU;
+        caps
+    }
+
+    fn hardware_address(&self) -> HardwareAddress {
+        self.switch.with(|s| match s.loopback {
+            true => HardwareAddress::Ip,
+            false => HardwareAddress::Ethernet(s.ports[self.port].hardware_address.unwrap()),
+        })
+    }
+
+    fn rx_dropped(&mut self) -> u32 {
+        self.switch.with(|s| s.ports[self.port].rx_dropped)
+    }
+}
+
+/// Receive token of a [`Device`].
+pub struct RxToken<'d, M: RawMutex, const PORTS: usize, const MTU: usize, const QUEUE: usize>(
+    Device<'d, M, PORTS, MTU, QUEUE>,
+);
+
+impl<M: RawMutex, const PORTS: usize, const MTU: usize, const QUEUE: usize> embassy_net_driver::RxToken
+    for RxToken<'_, M, PORTS, MTU, QUEUE>
+{
+    fn consume<R, F>(self, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        // The switch isn't locked while `f` runs, so it can transmit a reply.
+        let mut packet = self.0.switch.with(|s| {
+            let port = &mut s.ports[self.0.port];
+            let i = port.next().unwrap();
+            port.queue.remove(i)
+        });
+        f(&mut packet.buf[..packet.len])
+    }
+}
+
+/// Transmit token of a [`Device`].
+pub struct TxToken<'d, M: RawMutex, const PORTS: usize, const MTU: usize, const QUEUE: usize>(
+    Device<'d, M, PORTS, MTU, QUEUE>,
+);
+
+impl<M: RawMutex, const PORTS: usize, const MTU: usize, const QUEUE: usize> embassy_net_driver::TxToken
+    for TxToken<'_, M, PORTS, MTU, QUEUE>
+{
+    fn consume<R, F>(self, len: usize, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        let mut buf = [0; MTU];
+        let r = f(&mut buf[..len]);
+        self.0.switch.with(|s| s.transmit(self.0.port, &buf[..len]));
+        r
+    }
+}
@@ -0,0 +1,175 @@
+use core::future::poll_fn;
+use core::task::Poll;
+
+use embassy_futures::block_on;
+use embassy_futures::join::join;
+use embassy_futures::select::{select, Either};
+use embassy_net::udp::{PacketMetadata, UdpSocket};
+use embassy_net::{Ipv4Address, Ipv4Cidr, StackResources, StaticConfigV4};
+use embassy_net_driver::{Driver, RxToken, TxToken};
+use embassy_net_virtual::{Config, Device, Switch};
+use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+use embassy_time::{Duration, MockDriver};
+use serial_test::serial;
+
+const MTU: usize = 1514;
+const FRAMES: usize = 64;
+
+type TestSwitch = Switch<NoopRawMutex, 2, MTU, FRAMES>;
+type TestDevice<'d> = Device<'d, NoopRawMutex, 2, MTU, FRAMES>;
+
+const ADDR_A: [u8; 6] = [2, 0, 0, 0, 0, 1];
+const ADDR_B: [u8; 6] = [2, 0, 0, 0, 0, 2];
+
+/// Send an Ethernet frame to `dst`, with `seq` as payload.
+fn send(device: &mut TestDevice<'_>, dst: [u8; 6], seq: u8) {
+    block_on(poll_fn(|cx| {
+        let token = device.transmit(cx).unwrap();
+        token.consume(15, |buf| {
+            buf[..6].copy_from_slice(&dst);
+            buf[6..12].copy_from_slice(&ADDR_A);
+            buf[12..14].copy_from_slice(&0x88b5u16.to_be_bytes());
+            buf[14] = seq;
+        });
+        Poll::Ready(())
+    }))
+}
+
+/// Receive the payload of the next frame that has arrived, if any.
+fn recv(device: &mut TestDevice<'_>) -> Option<u8> {
+    block_on(poll_fn(|cx| {
+        Poll::Ready(device.receive(cx).map(|(token, _)| token.consume(|buf| buf[14])))
+    }))
+}
+
+fn recv_all(device: &mut TestDevice<'_>) -> Vec<u8> {
+    std::iter::from_fn(|| recv(device)).collect()
+}
+
+fn received_with_loss(loss: f32, seed: u64) -> Vec<u8> {
+    let switch = TestSwitch::new(Config {
+        loss,
+        seed,
+        ..Config::new()
+    });
+    let mut a = switch.device(ADDR_A);
+    let mut b = switch.device(ADDR_B);
+    for seq in 0..FRAMES as u8 {
+        send(&mut a, ADDR_B, seq);
+    }
+    recv_all(&mut b)
+}
+
+#[test]
+#[serial]
+fn loss() {
+    MockDriver::get().reset();
+
+    let all: Vec<u8> = (0..FRAMES as u8).collect();
+    assert_eq!(received_with_loss(0.0, 1), all);
+    assert_eq!(received_with_loss(1.0, 1), []);
+
+    let received = received_with_loss(0.5, 1);
+    assert!(received.len() > FRAMES / 4 && received.len() < FRAMES * 3 / 4);
+    // Drops don't reorder the other frames.
+    assert!(received.is_sorted());
+    // The same seed drops the same frames.
+    assert_eq!(received_with_loss(0.5, 1), received);
+    assert_ne!(received_with_loss(0.5, 2), received);
+}
+
+#[test]
+#[serial]
+fn full_queue_drops() {
+    MockDriver::get().reset();
+
+    let switch = TestSwitch::new(Config::new());
+    let mut a = switch.device(ADDR_A);
+    let mut b = switch.device(ADDR_B);
+    for seq in 0..FRAMES as u8 + 3 {
+        send(&mut a, ADDR_B, seq);
+    }
+    assert_eq!(b.rx_dropped(), 3);
+    assert_eq!(recv_all(&mut b), (0..FRAMES as u8).collect::<Vec<_>>());
+}
+
+#[test]
+#[serial]
+fn jitter_reorders() {
+    MockDriver::get().reset();
+
+    let switch = TestSwitch::new(Config {
+        latency: Duration::from_millis(5),
+        jitter: Duration::from_millis(10),
+        seed: 1,
+        ..Config::new()
+    });
+    let mut a = switch.device(ADDR_A);
+    let mut b = switch.device(ADDR_B);
+    for seq in 0..FRAMES as u8 {
+        send(&mut a, ADDR_B, seq);
+    }
+
+    // Nothing arrives before the latency.
+    MockDriver::get().advance(Duration::from_micros(4999));
+    assert_eq!(recv(&mut b), None);
+
+    MockDriver::get().advance(Duration::from_millis(11));
+    let received = recv_all(&mut b);
+    assert!(!received.is_sorted());
+    let mut sorted = received.clone();
+    sorted.sort();
+    assert_eq!(sorted, (0..FRAMES as u8).collect::<Vec<_>>());
+}
+
+#[test]
+#[serial]
+fn two_stacks() {
+    MockDriver::get().reset();
+
+    let switch = TestSwitch::new(Config::new());
+    let config = |n| {
+        embassy_net::Config::ipv4_static(StaticConfigV4 {
+            address: Ipv4Cidr::new(Ipv4Address::new(10, 0, 0, n), 24),
+            gateway: None,
+            dns_servers: heapless::Vec::new(),
+        })
+    };
+    let mut resources_a = StackResources::<2>::new();
+    let mut resources_b = StackResources::<2>::new();
+    let (stack_a, mut runner_a) = embassy_net::new(switch.device(ADDR_A), config(1), &mut resources_a, 1);
+    let (stack_b, mut runner_b) = embassy_net::new(switch.device(ADDR_B), config(2), &mut resources_b, 2);
+
+    let mut buffers = [[0u8; 256]; 4];
+    let mut meta = [[PacketMetadata::EMPTY; 4]; 4];
+    let [rx_a, tx_a, rx_b, tx_b] = &mut buffers;
+    let [rx_meta_a, tx_meta_a, rx_meta_b, tx_meta_b] = &mut meta;
+    let mut socket_a = UdpSocket::new(stack_a, rx_meta_a, rx_a, tx_meta_a, tx_a);
+    let mut socket_b = UdpSocket::new(stack_b, rx_meta_b, rx_b, tx_meta_b, tx_b);
+    socket_a.bind(1000).unwrap();
+    socket_b.bind(2000).unwrap();
+
+    let exchange = async {
+        stack_a.wait_config_up().await;
+        stack_b.wait_config_up().await;
+
+        socket_a
+            .send_to(b"ping", (Ipv4Address::new(10, 0, 0, 2), 2000))
+            .await
+            .unwrap();
+        let mut buf = [0; 16];
+        let (n, meta) = socket_b.recv_from(&mut buf).await.unwrap();
+        assert_eq!(&buf[..n], b"ping");
+        assert_eq!(meta.endpoint, (Ipv4Address::new(10, 0, 0, 1), 1000).into());
+
+        socket_b.send_to(b"pong", meta.endpoint).await.unwrap();
+        let (n, _) = socket_a.recv_from(&mut buf).await.unwrap();
+        assert_eq!(&buf[..n], b"pong");
+    };
+
+    let runners = join(runner_a.run(), runner_b.run());
+    match block_on(select(runners, exchange)) {
+        Either::First(_) => unreachable!(),
+        Either::Second(()) => {}
+    }
+}
@@ -0,0 +1,630 @@
+//! Minimal DHCPv4 server.
+//!
+//! This hands out addresses to the clients of a network where the device is the only host with
+//! a fixed address, such as a Wi-Fi access point. It implements the address allocation part of
+//! [RFC 2131](https://www.rfc-editor.org/rfc/rfc2131): clients can get, renew and release a
+//! lease, and receive the subnet mask, router and DNS server options. Relay agents are not
+//! supported.
+//!
+//! The interface the server runs on must have a static IPv4 configuration, which gives the server
+//! identifier and the subnet mask. Addresses are allocated from a pool of `N` consecutive addresses,
+//! skipping those that aren't usable by hosts of the subnet.
+//!
+//! ## Usage
+//!
+//! ```ignore
+//! use embassy_net::dhcp_server::{Config, DhcpServer};
+//! use embassy_net::udp::PacketMetadata;
+//!
+//! let config = Config {
+//!     pool_start: Ipv4Address::new(192, 168, 4, 100),
+//!     lease_time: Duration::from_secs(3600),
+//!     router: Some(Ipv4Address::new(192, 168, 4, 1)),
+//!     dns_servers: Vec::new(),
+//! };
+//!
+//! let mut rx_meta = [PacketMetadata::EMPTY; 4];
+//! let mut rx_buffer = [0; 2048];
+//! let mut tx_meta = [PacketMetadata::EMPTY; 4];
+//! let mut tx_buffer = [0; 2048];
+//! let mut server = DhcpServer::<8>::new(stack, config, &mut rx_meta, &mut rx_buffer, &mut tx_meta, &mut tx_buffer).unwrap();
+//! server.run().await;
+//! ```
+
+use embassy_time::{Duration, Instant};
+use heapless::Vec;
+use smoltcp::wire::{DhcpMessageType, DhcpPacket, DhcpRepr, DHCP_CLIENT_PORT, DHCP_SERVER_PORT};
+
+use crate::udp::{BindError, PacketMetadata, UdpSocket};
+use crate::{EthernetAddress, Interface, IpEndpoint, Ipv4Address, Ipv4Cidr, Stack};
+
+/// How long an offered address stays reserved for the client, waiting for its request.
+const OFFER_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Largest DHCP message handled. Clients must accept messages of this size.
+const MAX_MESSAGE_LEN: usize = 576;
+/// Minimum length of a BOOTP message, which some clients require.
+const MIN_MESSAGE_LEN: usize = 300;
+
+/// DHCP server configuration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Config {
+    /// First address of the pool. Addresses of the pool outside the subnet of the interface, and
+    /// the interface's own address, are never leased.
+    pub pool_start: Ipv4Address,
+    /// Duration of the leases.
+    pub lease_time: Duration,
+    /// Router option, the default gateway of the clients.
+    pub router: Option<Ipv4Address>,
+    /// DNS servers option.
+    pub dns_servers: Vec<Ipv4Address, 3>,
+}
+
+/// An address leased to a client.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Lease {
+    /// Leased address.
+    pub address: Ipv4Address,
+    /// Hardware address of the client.
+    pub hardware_address: EthernetAddress,
+    /// Instant at which the lease expires, unless it is renewed.
+    pub expires: Instant,
+}
+
+#[derive(Clone, Copy)]
+struct Entry {
+    /// Owner of the address, or `None` if a client declined it because it is already in use.
+    client: Option<EthernetAddress>,
+    expires: Instant,
+    /// Whether the address was acknowledged, or only offered.
+    bound: bool,
+}
+
+/// DHCPv4 server, with a pool of `N` addresses.
+pub struct DhcpServer<'d, const N: usize> {
+    iface: Interface<'d>,
+    socket: UdpSocket<'d>,
+    config: Config,
+    /// Entry `i` is for address `pool_start + i`. Expired entries are free.
+    leases: [Option<Entry>; N],
+}
+
+impl<'d, const N: usize> DhcpServer<'d, N> {
+    /// Create a new DHCP server on the default interface of the stack, with the buffers for its
+    /// UDP socket.
+    ///
+    /// This binds the socket to port 67. DHCP messages can be up to 576 bytes.
+    pub fn new(
+        stack: Stack<'d>,
+        config: Config,
+        rx_meta: &'d mut [PacketMetadata],
+        rx_buffer: &'d mut [u8],
+        tx_meta: &'d mut [PacketMetadata],
+        tx_buffer: &'d mut [u8],
+    ) -> Result<Self, BindError> {
+        let iface = stack.default_interface();
+        Self::new_on_interface(iface, config, rx_meta, rx_buffer, tx_meta, tx_buffer)
+    }
+
+    /// Create a new DHCP server on a network interface of the stack, with the buffers for its UDP
+    /// socket.
+    ///
+    /// The socket is bound to the interface, which must have room for it.
+    pub fn new_on_interface(
+        iface: Interface<'d>,
+        config: Config,
+        rx_meta: &'d mut [PacketMetadata],
+        rx_buffer: &'d mut [u8],
+        tx_meta: &'d mut [PacketMetadata],
+        tx_buffer: &'d mut [u8],
+    ) -> Result<Self, BindError> {
+        let mut socket = UdpSocket::new(iface.stack, rx_meta, rx_buffer, tx_meta, tx_buffer);
+        // Replies are broadcast on the interface, whatever the routes.
+        socket.bind_interface(iface.id()).map_err(|_| BindError::NoRoute)?;
+        socket.bind(DHCP_SERVER_PORT)?;
+        Ok(Self {
+            iface,
+            socket,
+            config,
+            leases: [None; N],
+        })
+    }
+
+    /// Iterate over the current leases.
+    pub fn leases(&self) -> impl Iterator<Item = Lease> + '_ {
+        let now = Instant::now();
+        self.leases
+            .iter()
+            .enumerate()
+            .filter_map(move |(i, entry)| match entry {
+                Some(Entry {
+                    client: Some(client),
+                    expires,
+                    bound: true,
+                }) if *expires > now => Some(Lease {
+                    address: self.pool_address(i),
+                    hardware_address: *client,
+                    expires: *expires,
+                }),
+                _ => None,
+            })
+    }
+
+    /// Run the server, answering the requests of clients forever.
+    pub async fn run(&mut self) -> ! {
+        let mut rx = [0u8; MAX_MESSAGE_LEN];
+        let mut tx = [0u8; MAX_MESSAGE_LEN];
+
+        loop {
+            // Messages larger than the buffer are ignored.
+            let Ok((n, _)) = self.socket.recv_from(&mut rx).await else {
+                continue;
+            };
+            let Ok(packet) = DhcpPacket::new_checked(&rx[..n]) else {
+                continue;
+            };
+            let Ok(request) = DhcpRepr::parse(&packet) else {
+                continue;
+            };
+            if request.relay_agent_ip != Ipv4Address::UNSPECIFIED {
+                continue;
+            }
+
+            let Some((reply, endpoint)) = self.handle(&request, Instant::now()) else {
+                continue;
+            };
+            let len = reply.buffer_len().max(MIN_MESSAGE_LEN);
+            tx[..len].fill(0);
+            let mut packet = DhcpPacket::new_unchecked(&mut tx[..len]);
+            if reply.emit(&mut packet).is_err() {
+                continue;
+            }
+            if let Err(e) = self.socket.send_to(&tx[..len], endpoint).await {
+                warn!("DHCP server: failed to send reply: {:?}", e);
+            }
+        }
+    }
+
+    /// Handle a request from a client. Returns the reply, and where to send it.
+    fn handle<'a>(&mut self, request: &DhcpRepr<'a>, now: Instant) -> Option<(DhcpRepr<'a>, IpEndpoint)> {
+        let server = self.iface.config_v4()?.address;
+        let client = request.client_hardware_address;
+
+        let (message_type, address) = match request.message_type {
+            DhcpMessageType::Discover => {
+                let index = self.allocate(client, request.requested_ip, server, now)?;
+                match &mut self.leases[index] {
+                    Some(entry) if entry.client == Some(client) && entry.expires > now => {
+                        entry.expires = entry.expires.max(now + OFFER_TIMEOUT);
+                    }
+                    entry => {
+                        *entry = Some(Entry {
+                            client: Some(client),
+                            expires: now + OFFER_TIMEOUT,
+                            bound: false,
+                        })
+                    }
+                }
+                (DhcpMessageType::Offer, self.pool_address(index))
+            }
+            DhcpMessageType::Request => {
+                if let Some(id) = request.server_identifier {
+                    if id != server.address() {
+                        // The client chose another server, free the address we offered.
+                        if let Some(index) = self.find(client, now) {
+                            if !self.leases[index].is_some_and(|e| e.bound) {
+                                self.leases[index] = None;
+                            }
+                        }
+                        return None;
+                    }
+                }
+
+                // The address is in the option when selecting an offer or rebooting, and in the
+                // header when renewing.
+                let address = request.requested_ip.unwrap_or(request.client_ip);
+                match self.pool_index(address) {
+                    Some(index) if self.is_available(index, client, server, now) => {
+                        debug!("DHCP server: leasing {} to {}", address, client);
+                        self.leases[index] = Some(Entry {
+                            client: Some(client),
+                            expires: now + self.config.lease_time,
+                            bound: true,
+                        });
+                        (DhcpMessageType::Ack, address)
+                    }
+                    _ => (DhcpMessageType::Nak, Ipv4Address::UNSPECIFIED),
+                }
+            }
+            DhcpMessageType::Decline => {
+                // The client found that the address is used by another host, don't offer it for
+                // a while.
+                let index = self.pool_index(request.requested_ip?)?;
+                if self.is_owned_by(index, client, now) {
+                    warn!("DHCP server: {} declined {}", client, self.pool_address(index));
+                    self.leases[index] = Some(Entry {
+                        client: None,
+                        expires: now + self.config.lease_time,
+                        bound: true,
+                    });
+                }
+                return None;
+            }
+            DhcpMessageType::Release => {
+                let index = self.pool_index(request.client_ip)?;
+                if self.is_owned_by(index, client, now) {
+                    debug!("DHCP server: {} released {}", client, request.client_ip);
+                    self.leases[index] = None;
+                }
+                return None;
+            }
+            _ => return None,
+        };
+
+        let mut reply = DhcpRepr {
+            message_type,
+            transaction_id: request.transaction_id,
+            secs: 0,
+            client_hardware_address: client,
+            client_ip: Ipv4Address::UNSPECIFIED,
+            your_ip: address,
+            server_ip: Ipv4Address::UNSPECIFIED,
+            router: None,
+            subnet_mask: None,
+            relay_agent_ip: Ipv4Address::UNSPECIFIED,
+            broadcast: request.broadcast,
+            requested_ip: None,
+            client_identifier: None,
+            server_identifier: Some(server.address()),
+            parameter_request_list: None,
+            dns_servers: None,
+            max_size: None,
+            lease_duration: None,
+            renew_duration: None,
+            rebind_duration: None,
+            additional_options: &[],
+        };
+        if message_type != DhcpMessageType::Nak {
+            reply.client_ip = request.client_ip;
+            reply.subnet_mask = Some(server.netmask());
+            reply.router = self.config.router;
+            reply.lease_duration = Some(self.config.lease_time.as_secs().min(u32::MAX as u64) as u32);
+            if !self.config.dns_servers.is_empty() {
+                reply.dns_servers = Some(self.config.dns_servers.clone());
+            }
+        }
+
+        // Clients without an address can't receive unicast before answering ARP requests, so
+        // reply by broadcast unless they are renewing a lease.
+        let destination = if message_type != DhcpMessageType::Nak && request.client_ip != Ipv4Address::UNSPECIFIED {
+            request.client_ip
+        } else {
+            Ipv4Address::BROADCAST
+        };
+        Some((reply, IpEndpoint::new(destination.into(), DHCP_CLIENT_PORT)))
+    }
+
+    /// Choose an address to offer to `client`.
+    ///
+    /// This is, in order of preference, the address it already has, the address it asked for,
+    /// a free address, or the address whose lease expired first.
+    fn allocate(
+        &self,
+        client: EthernetAddress,
+        requested: Option<Ipv4Address>,
+        server: Ipv4Cidr,
+        now: Instant,
+    ) -> Option<usize> {
+        if let Some(index) = self.find(client, now).filter(|&i| self.is_usable(i, server)) {
+            return Some(index);
+        }
+        if let Some(index) = requested.and_then(|a| self.pool_index(a)) {
+            if self.is_available(index, client, server, now) {
+                return Some(index);
+            }
+        }
+        if let Some(index) = (0..N).find(|&i| self.leases[i].is_none() && self.is_usable(i, server)) {
+            return Some(index);
+        }
+        self.leases
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| self.is_usable(*i, server))
+            .filter_map(|(i, entry)| entry.filter(|e| e.expires <= now).map(|e| (i, e.expires)))
+            .min_by_key(|(_, expires)| *expires)
+            .map(|(i, _)| i)
+    }
+
+    /// Find the unexpired entry of `client`.
+    fn find(&self, client: EthernetAddress, now: Instant) -> Option<usize> {
+        (0..N).find(|&i| self.is_owned_by(i, client, now))
+    }
+
+    fn is_owned_by(&self, index: usize, client: EthernetAddress, now: Instant) -> bool {
+        matches!(self.leases[index], Some(e) if e.client == Some(client) && e.expires > now)
+    }
+
+    /// Whether the address at `index` can be leased to `client`.
+    fn is_available(&self, index: usize, client: EthernetAddress, server: Ipv4Cidr, now: Instant) -> bool {
+        if !self.is_usable(index, server) {
+            return false;
+        }
+        match self.leases[index] {
+            None => true,
+            Some(e) => e.expires <= now || e.client == Some(client),
+        }
+    }
+
+    /// Whether the address at `index` can be used by a host of the server's subnet, other than the
+    /// server.
+    fn is_usable(&self, index: usize, server: Ipv4Cidr) -> bool {
+        let address = self.pool_address(index);
+        let network = server.network();
+        // /31 and /32 subnets have no network and broadcast addresses.
+        let reserved = match server.broadcast() {
+            Some(broadcast) => address == network.address() || address == broadcast,
+            None => false,
+        };
+        network.contains_addr(&address) && address != server.address() && !reserved
+    }
+
+    fn pool_address(&self, index: usize) -> Ipv4Address {
+        Ipv4Address::from_bits(self.config.pool_start.to_bits() + index as u32)
+    }
+
+    fn pool_index(&self, address: Ipv4Address) -> Option<usize> {
+        let index = address.to_bits().checked_sub(self.config.pool_start.to_bits())? as usize;
+        (index < N).then_some(index)
+    }
+}
+
+#[cfg(all(test, feature = "medium-ip"))]
+mod tests {
+    extern crate std;
+
+    use std::boxed::Box;
+    use std::vec;
+
+    use super::*;
+    use crate::tests::{config, NoTraffic};
+    use crate::StackResources;
+
+    const SERVER: Ipv4Address = Ipv4Address::new(192, 168, 4, 1);
+    const CLIENT: EthernetAddress = EthernetAddress([2, 0, 0, 0, 0, 1]);
+    const OTHER_CLIENT: EthernetAddress = EthernetAddress([2, 0, 0, 0, 0, 2]);
+    const LEASE_TIME: Duration = Duration::from_secs(3600);
+
+    fn dhcp_server<const N: usize>(pool_start: [u8; 4]) -> DhcpServer<'static, N> {
+        let resources = Box::leak(Box::new(StackResources::<2>::new()));
+        let (stack, _runner) = crate::new(NoTraffic, config(SERVER.octets(), 24, None), resources, 1);
+        let meta = || vec![PacketMetadata::EMPTY; 2].leak();
+        let buffer = || vec![0; MAX_MESSAGE_LEN].leak();
+        let config = Config {
+            pool_start: pool_start.into(),
+            lease_time: LEASE_TIME,
+            router: Some(SERVER),
+            dns_servers: Vec::new(),
+        };
+        unwrap!(DhcpServer::new(stack, config, meta(), buffer(), meta(), buffer()))
+    }
+
+    fn message(message_type: DhcpMessageType, client: EthernetAddress) -> DhcpRepr<'static> {
+        DhcpRepr {
+            message_type,
+            transaction_id: 0x1234,
+            secs: 0,
+            client_hardware_address: client,
+            client_ip: Ipv4Address::UNSPECIFIED,
+            your_ip: Ipv4Address::UNSPECIFIED,
+            server_ip: Ipv4Address::UNSPECIFIED,
+            router: None,
+            subnet_mask: None,
+            relay_agent_ip: Ipv4Address::UNSPECIFIED,
+            broadcast: false,
+            requested_ip: None,
+            client_identifier: None,
+            server_identifier: None,
+            parameter_request_list: None,
+            dns_servers: None,
+            max_size: None,
+            lease_duration: None,
+            renew_duration: None,
+            rebind_duration: None,
+            additional_options: &[],
+        }
+    }
+
+    fn discover(client: EthernetAddress) -> DhcpRepr<'static> {
+        message(DhcpMessageType::Discover, client)
+    }
+
+    /// Request sent when selecting the offer of `server`.
+    fn request(client: EthernetAddress, address: Ipv4Address, server: Ipv4Address) -> DhcpRepr<'static> {
+        let mut request = message(DhcpMessageType::Request, client);
+        request.requested_ip = Some(address);
+        request.server_identifier = Some(server);
+        request
+    }
+
+    /// Request sent when renewing a lease.
+    fn renew(client: EthernetAddress, address: Ipv4Address) -> DhcpRepr<'static> {
+        let mut request = message(DhcpMessageType::Request, client);
+        request.client_ip = address;
+        request
+    }
+
+    fn release(client: EthernetAddress, address: Ipv4Address) -> DhcpRepr<'static> {
+        let mut release = message(DhcpMessageType::Release, client);
+        release.client_ip = address;
+        release
+    }
+
+    fn broadcast() -> IpEndpoint {
+        IpEndpoint::new(Ipv4Address::BROADCAST.into(), DHCP_CLIENT_PORT)
+    }
+
+    #[test]
+    fn discover_request_release() {
+        let mut server = dhcp_server::<4>([192, 168, 4, 100]);
+        let address = Ipv4Address::new(192, 168, 4, 100);
+        let now = Instant::now();
+
+        let (offer, endpoint) = unwrap!(server.handle(&discover(CLIENT), now));
+        assert_eq!(offer.message_type, DhcpMessageType::Offer);
+        assert_eq!(offer.transaction_id, 0x1234);
+        assert_eq!(offer.client_hardware_address, CLIENT);
+        assert_eq!(offer.your_ip, address);
+        assert_eq!(offer.server_identifier, Some(SERVER));
+        assert_eq!(offer.subnet_mask, Some(Ipv4Address::new(255, 255, 255, 0)));
+        assert_eq!(offer.router, Some(SERVER));
+        assert_eq!(offer.lease_duration, Some(3600));
+        assert_eq!(endpoint, broadcast());
+        // Offers aren't leases.
+        assert_eq!(server.leases().count(), 0);
+
+        let (ack, endpoint) = unwrap!(server.handle(&request(CLIENT, address, SERVER), now));
+        assert_eq!(ack.message_type, DhcpMessageType::Ack);
+        assert_eq!(ack.your_ip, address);
+        assert_eq!(endpoint, broadcast());
+        let leases: vec::Vec<_> = server.leases().collect();
+        assert_eq!(
+            leases,
+            [Lease {
+                address,
+                hardware_address: CLIENT,
+                expires: now + LEASE_TIME,
+            }]
+        );
+
+        // Discovering again offers the same address.
+        let (offer, _) = unwrap!(server.handle(&discover(CLIENT), now));
+        assert_eq!(offer.your_ip, address);
+
+        // Renewing extends the lease, and the reply is sent to the client's address.
+        let later = now + Duration::from_secs(1800);
+        let (ack, endpoint) = unwrap!(server.handle(&renew(CLIENT, address), later));
+        assert_eq!(ack.message_type, DhcpMessageType::Ack);
+        assert_eq!(ack.client_ip, address);
+        assert_eq!(endpoint, IpEndpoint::new(address.into(), DHCP_CLIENT_PORT));
+        assert_eq!(unwrap!(server.leases().next()).expires, later + LEASE_TIME);
+
+        // Only the owner can release a lease.
+        assert!(server.handle(&release(OTHER_CLIENT, address), later).is_none());
+        assert_eq!(server.leases().count(), 1);
+        assert!(server.handle(&release(CLIENT, address), later).is_none());
+        assert_eq!(server.leases().count(), 0);
+    }
+
+    #[test]
+    fn clients_get_different_addresses() {
+        let mut server = dhcp_server::<4>([192, 168, 4, 100]);
+        let now = Instant::now();
+
+        let (offer, _) = unwrap!(server.handle(&discover(CLIENT), now));
+        assert_eq!(offer.your_ip, Ipv4Address::new(192, 168, 4, 100));
+        // The offered address is reserved.
+        let (offer, _) = unwrap!(server.handle(&discover(OTHER_CLIENT), now));
+        assert_eq!(offer.your_ip, Ipv4Address::new(192, 168, 4, 101));
+
+        // Clients get the address they ask for if it's free.
+        let mut discover = discover(EthernetAddress([2, 0, 0, 0, 0, 3]));
+        discover.requested_ip = Some(Ipv4Address::new(192, 168, 4, 103));
+        let (offer, _) = unwrap!(server.handle(&discover, now));
+        assert_eq!(offer.your_ip, Ipv4Address::new(192, 168, 4, 103));
+    }
+
+    #[test]
+    fn request_for_other_server() {
+        let mut server = dhcp_server::<1>([192, 168, 4, 100]);
+        let address = Ipv4Address::new(192, 168, 4, 100);
+        let now = Instant::now();
+
+        unwrap!(server.handle(&discover(CLIENT), now));
+        assert!(server.handle(&discover(OTHER_CLIENT), now).is_none());
+
+        // The client chose another server, so the offer is withdrawn.
+        let other_server = Ipv4Address::new(192, 168, 4, 2);
+        assert!(server
+            .handle(&request(CLIENT, Ipv4Address::new(192, 168, 4, 50), other_server), now)
+            .is_none());
+        let (offer, _) = unwrap!(server.handle(&discover(OTHER_CLIENT), now));
+        assert_eq!(offer.your_ip, address);
+    }
+
+    #[test]
+    fn nak() {
+        let mut server = dhcp_server::<2>([192, 168, 4, 100]);
+        let address = Ipv4Address::new(192, 168, 4, 100);
+        let now = Instant::now();
+
+        unwrap!(server.handle(&request(CLIENT, address, SERVER), now));
+
+        // Leased to another client.
+        let (nak, endpoint) = unwrap!(server.handle(&request(OTHER_CLIENT, address, SERVER), now));
+        assert_eq!(nak.message_type, DhcpMessageType::Nak);
+        assert_eq!(nak.your_ip, Ipv4Address::UNSPECIFIED);
+        assert_eq!(nak.lease_duration, None);
+        assert_eq!(endpoint, broadcast());
+
+        // Outside the pool, even when renewing.
+        let outside = Ipv4Address::new(192, 168, 4, 102);
+        let (nak, endpoint) = unwrap!(server.handle(&renew(OTHER_CLIENT, outside), now));
+        assert_eq!(nak.message_type, DhcpMessageType::Nak);
+        assert_eq!(endpoint, broadcast());
+        assert_eq!(server.leases().count(), 1);
+    }
+
+    #[test]
+    fn lease_expiry() {
+        let mut server = dhcp_server::<1>([192, 168, 4, 100]);
+        let address = Ipv4Address::new(192, 168, 4, 100);
+        let now = Instant::now();
+
+        unwrap!(server.handle(&request(CLIENT, address, SERVER), now));
+        assert!(server.handle(&discover(OTHER_CLIENT), now).is_none());
+        let before_expiry = now + LEASE_TIME - Duration::from_secs(1);
+        assert!(server.handle(&discover(OTHER_CLIENT), before_expiry).is_none());
+
+        // Once the lease expired, the address can go to another client.
+        let expired = now + LEASE_TIME;
+        let (offer, _) = unwrap!(server.handle(&discover(OTHER_CLIENT), expired));
+        assert_eq!(offer.your_ip, address);
+        // The first client can't renew its lease, the address is offered to someone else.
+        let (nak, _) = unwrap!(server.handle(&renew(CLIENT, address), expired));
+        assert_eq!(nak.message_type, DhcpMessageType::Nak);
+        let (ack, _) = unwrap!(server.handle(&request(OTHER_CLIENT, address, SERVER), expired));
+        assert_eq!(ack.message_type, DhcpMessageType::Ack);
+
+        // Unanswered offers expire sooner.
+        let mut server = dhcp_server::<1>([192, 168, 4, 100]);
+        unwrap!(server.handle(&discover(CLIENT), now));
+        assert!(server.handle(&discover(OTHER_CLIENT), now).is_none());
+        let (offer, _) = unwrap!(server.handle(&discover(OTHER_CLIENT), now + OFFER_TIMEOUT));
+        assert_eq!(offer.your_ip, address);
+    }
+
+    #[test]
+    fn unusable_addresses_skipped() {
+        let now = Instant::now();
+
+        // The network address and the server's address are skipped.
+        let mut server = dhcp_server::<3>([192, 168, 4, 0]);
+        let (offer, _) = unwrap!(server.handle(&discover(CLIENT), now));
+        assert_eq!(offer.your_ip, Ipv4Address::new(192, 168, 4, 2));
+        let (nak, _) = unwrap!(server.handle(&request(OTHER_CLIENT, SERVER, SERVER), now));
+        assert_eq!(nak.message_type, DhcpMessageType::Nak);
+        let mut discover_server = discover(OTHER_CLIENT);
+        discover_server.requested_ip = Some(SERVER);
+        assert!(server.handle(&discover_server, now).is_none());
+
+        // So are the broadcast address and addresses outside the subnet.
+        let mut server = dhcp_server::<3>([192, 168, 4, 254]);
+        let (offer, _) = unwrap!(server.handle(&discover(CLIENT), now));
+        assert_eq!(offer.your_ip, Ipv4Address::new(192, 168, 4, 254));
+        assert!(server.handle(&discover(OTHER_CLIENT), now).is_none());
+        let (nak, _) = unwrap!(server.handle(&request(OTHER_CLIENT, Ipv4Address::new(192, 168, 5, 0), SERVER), now));
+        assert_eq!(nak.message_type, DhcpMessageType::Nak);
+    }
+}
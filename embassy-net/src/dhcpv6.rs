@@ -0,0 +1,228 @@
+//! Stateless DHCPv6 client ([RFC 8415](https://www.rfc-editor.org/rfc/rfc8415) section 6.1).
+//!
+//! This only sends Information-request messages, to get the DNS servers of the network. The
+//! address itself comes from SLAAC.
+
+use core::mem::MaybeUninit;
+
+use embassy_time::{Duration, Instant};
+use heapless::Vec;
+use smoltcp::iface::{SocketHandle, SocketSet};
+use smoltcp::phy::PacketMeta;
+use smoltcp::socket::udp::{self, UdpMetadata};
+
+use crate::{IpEndpoint, Ipv6Address};
+
+const CLIENT_PORT: u16 = 546;
+const SERVER_PORT: u16 = 547;
+/// All_DHCP_Relay_Agents_and_Servers multicast address.
+const SERVERS_ADDRESS: Ipv6Address = Ipv6Address::new(0xff02, 0, 0, 0, 0, 0, 1, 2);
+
+const RX_BUFFER_LEN: usize = 512;
+const TX_BUFFER_LEN: usize = 64;
+
+const MSG_REPLY: u8 = 7;
+const MSG_INFORMATION_REQUEST: u8 = 11;
+const OPTION_ORO: u16 = 6;
+const OPTION_ELAPSED_TIME: u16 = 8;
+const OPTION_DNS_SERVERS: u16 = 23;
+const OPTION_INFORMATION_REFRESH_TIME: u16 = 32;
+
+/// Initial and maximum retransmission timeouts of Information-request messages.
+const INF_TIMEOUT: Duration = Duration::from_secs(1);
+const INF_MAX_RT: Duration = Duration::from_secs(3600);
+/// Default and minimum time before refreshing the information.
+const IRT_DEFAULT: u32 = 86400;
+const IRT_MINIMUM: u32 = 600;
+
+/// Buffers of the DHCPv6 socket.
+pub(crate) struct Resources {
+    rx_meta: MaybeUninit<[udp::PacketMetadata; 1]>,
+    rx_buffer: MaybeUninit<[u8; RX_BUFFER_LEN]>,
+    tx_meta: MaybeUninit<[udp::PacketMetadata; 1]>,
+    tx_buffer: MaybeUninit<[u8; TX_BUFFER_LEN]>,
+}
+
+impl Resources {
+    pub(crate) const fn new() -> Self {
+        Self {
+            rx_meta: MaybeUninit::uninit(),
+            rx_buffer: MaybeUninit::uninit(),
+            tx_meta: MaybeUninit::uninit(),
+            tx_buffer: MaybeUninit::uninit(),
+        }
+    }
+}
+
+enum State {
+    Idle,
+    Requesting {
+        started: Instant,
+        retransmit_at: Instant,
+        timeout: Duration,
+    },
+    Done {
+        refresh_at: Instant,
+    },
+}
+
+pub(crate) struct Client {
+    socket: SocketHandle,
+    state: State,
+    transaction_id: [u8; 3],
+    random: u64,
+    dns_servers: Vec<Ipv6Address, 3>,
+}
+
+impl Client {
+    pub(crate) fn new(sockets: &mut SocketSet<'static>, resources: &'static mut Resources, random_seed: u64) -> Self {
+        let rx_buffer = udp::PacketBuffer::new(
+            &mut resources.rx_meta.write([udp::PacketMetadata::EMPTY; 1])[..],
+            &mut resources.rx_buffer.write([0; RX_BUFFER_LEN])[..],
+        );
+        let tx_buffer = udp::PacketBuffer::new(
+            &mut resources.tx_meta.write([udp::PacketMetadata::EMPTY; 1])[..],
+            &mut resources.tx_buffer.write([0; TX_BUFFER_LEN])[..],
+        );
+        let mut socket = udp::Socket::new(rx_buffer, tx_buffer);
+        unwrap!(socket.bind(CLIENT_PORT));
+
+        Self {
+            socket: sockets.add(socket),
+            state: State::Idle,
+            transaction_id: [0; 3],
+            random: random_seed | 1,
+            dns_servers: Vec::new(),
+        }
+    }
+
+    pub(crate) fn remove(self, sockets: &mut SocketSet<'static>) {
+        sockets.remove(self.socket);
+    }
+
+    pub(crate) fn dns_servers(&self) -> impl Iterator<Item = &Ipv6Address> {
+        self.dns_servers.iter()
+    }
+
+    pub(crate) fn reset(&mut self) {
+        self.state = State::Idle;
+        self.dns_servers.clear();
+    }
+
+    /// Request the information, unless it was already requested.
+    pub(crate) fn start(&mut self, now: Instant) {
+        if matches!(self.state, State::Idle) {
+            self.request(now);
+        }
+    }
+
+    fn request(&mut self, now: Instant) {
+        // xorshift64
+        self.random ^= self.random << 13;
+        self.random ^= self.random >> 7;
+        self.random ^= self.random << 17;
+        self.transaction_id.copy_from_slice(&self.random.to_be_bytes()[..3]);
+
+        self.state = State::Requesting {
+            started: now,
+            retransmit_at: now,
+            timeout: INF_TIMEOUT,
+        };
+    }
+
+    pub(crate) fn poll(&mut self, sockets: &mut SocketSet<'static>, link_local: Ipv6Address, now: Instant) {
+        let socket = sockets.get_mut::<udp::Socket>(self.socket);
+
+        while let Ok((packet, _)) = socket.recv() {
+            if !matches!(self.state, State::Requesting { .. }) || packet.len() < 4 {
+                continue;
+            }
+            if packet[0] != MSG_REPLY || packet[1..4] != self.transaction_id {
+                continue;
+            }
+
+            let mut refresh_time = IRT_DEFAULT;
+            self.dns_servers.clear();
+            for (code, data) in options(&packet[4..]) {
+                match code {
+                    OPTION_DNS_SERVERS => {
+                        for server in data.chunks_exact(16) {
+                            let server = Ipv6Address::from(<[u8; 16]>::try_from(server).unwrap());
+                            let _ = self.dns_servers.push(server);
+                        }
+                    }
+                    OPTION_INFORMATION_REFRESH_TIME if data.len() == 4 => {
+                        refresh_time = u32::from_be_bytes(data.try_into().unwrap()).max(IRT_MINIMUM);
+                    }
+                    _ => {}
+                }
+            }
+            debug!("DHCPv6: got {} DNS servers", self.dns_servers.len());
+            self.state = State::Done {
+                refresh_at: now + Duration::from_secs(refresh_time as u64),
+            };
+        }
+
+        match self.state {
+            State::Done { refresh_at } if refresh_at <= now => self.request(now),
+            _ => {}
+        }
+
+        if let State::Requesting {
+            started,
+            retransmit_at,
+            timeout,
+        } = &mut self.state
+        {
+            if *retransmit_at > now {
+                return;
+            }
+
+            // Elapsed time is in hundredths of a second.
+            let elapsed = ((now - *started).as_millis() / 10).min(u16::MAX as u64) as u16;
+            let mut message = [0u8; 20];
+            message[0] = MSG_INFORMATION_REQUEST;
+            message[1..4].copy_from_slice(&self.transaction_id);
+            message[4..6].copy_from_slice(&OPTION_ELAPSED_TIME.to_be_bytes());
+            message[6..8].copy_from_slice(&2u16.to_be_bytes());
+            message[8..10].copy_from_slice(&elapsed.to_be_bytes());
+            message[10..12].copy_from_slice(&OPTION_ORO.to_be_bytes());
+            message[12..14].copy_from_slice(&4u16.to_be_bytes());
+            message[14..16].copy_from_slice(&OPTION_DNS_SERVERS.to_be_bytes());
+            message[16..18].copy_from_slice(&OPTION_INFORMATION_REFRESH_TIME.to_be_bytes());
+            let message = &message[..18];
+
+            let meta = UdpMetadata {
+                endpoint: IpEndpoint::new(SERVERS_ADDRESS.into(), SERVER_PORT),
+                local_address: Some(link_local.into()),
+                meta: PacketMeta::default(),
+            };
+            if socket.send_slice(message, meta).is_ok() {
+                debug!("DHCPv6: sending information request");
+                *retransmit_at = now + *timeout;
+                *timeout = (*timeout * 2).min(INF_MAX_RT);
+            }
+        }
+    }
+
+    pub(crate) fn poll_at(&self) -> Option<Instant> {
+        match self.state {
+            State::Idle => None,
+            State::Requesting { retransmit_at, .. } => Some(retransmit_at),
+            State::Done { refresh_at } => Some(refresh_at),
+        }
+    }
+}
+
+/// Iterate over DHCPv6 options, as code and data.
+fn options(mut options: &[u8]) -> impl Iterator<Item = (u16, &[u8])> {
+    core::iter::from_fn(move || {
+        let [c0, c1, l0, l1, ..] = *options else {
+            return None;
+        };
+        let len = u16::from_be_bytes([l0, l1]) as usize;
+        let data = options.get(4..4 + len)?;
+        options = &options[4 + len..];
+        Some((u16::from_be_bytes([c0, c1]), data))
+    })
+}
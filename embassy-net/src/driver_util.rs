@@ -4,6 +4,16 @@ use embassy_net_driver::{Capabilities, Checksum, Driver, RxToken, TxToken};
 use smoltcp::phy::{self, Medium};
 use smoltcp::time::Instant;
 
+/// Direction of a frame passed to a [`PacketHook`].
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Direction {
+    Rx,
+    Tx,
+}
+
+/// Called with every received frame, before smoltcp processes it, and with every transmitted frame.
+pub(crate) type PacketHook<'a> = &'a dyn Fn(Direction, &[u8]);
+
 pub(crate) struct DriverAdapter<'d, 'c, T>
 where
     T: Driver,
@@ -12,6 +22,10 @@ where
     pub cx: Option<&'d mut Context<'c>>,
     pub inner: &'d mut T,
     pub medium: Medium,
+    pub hook: Option<PacketHook<'d>>,
+    /// Counts the transmissions that failed because the driver had no buffer.
+    #[cfg(feature = "stats")]
+    pub tx_unavailable: Option<&'d core::cell::Cell<u32>>,
 }
 
 impl<'d, 'c, T> phy::Device for DriverAdapter<'d, 'c, T>
@@ -19,23 +33,30 @@ where
     T: Driver,
 {
     type RxToken<'a>
-        = RxTokenAdapter<T::RxToken<'a>>
+        = RxTokenAdapter<'a, T::RxToken<'a>>
     where
         Self: 'a;
     type TxToken<'a>
-        = TxTokenAdapter<T::TxToken<'a>>
+        = TxTokenAdapter<'a, T::TxToken<'a>>
     where
         Self: 'a;
 
     fn receive(&mut self, _timestamp: Instant) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+        let hook = self.hook;
         self.inner
             .receive(unwrap!(self.cx.as_deref_mut()))
-            .map(|(rx, tx)| (RxTokenAdapter(rx), TxTokenAdapter(tx)))
+            .map(|(rx, tx)| (RxTokenAdapter(rx, hook), TxTokenAdapter(tx, hook)))
     }
 
     /// Construct a transmit token.
     fn transmit(&mut self, _timestamp: Instant) -> Option<Self::TxToken<'_>> {
-        self.inner.transmit(unwrap!(self.cx.as_deref_mut())).map(TxTokenAdapter)
+        let hook = self.hook;
+        let tx = self.inner.transmit(unwrap!(self.cx.as_deref_mut()));
+        #[cfg(feature = "stats")]
+        if let (None, Some(counter)) = (&tx, self.tx_unavailable) {
+            counter.set(counter.get().wrapping_add(1));
+        }
+        tx.map(|tx| TxTokenAdapter(tx, hook))
     }
 
     /// Get a description of device capabilities.
@@ -70,11 +91,11 @@ where
     }
 }
 
-pub(crate) struct RxTokenAdapter<T>(T)
+pub(crate) struct RxTokenAdapter<'a, T>(T, Option<PacketHook<'a>>)
 where
     T: RxToken;
 
-impl<T> phy::RxToken for RxTokenAdapter<T>
+impl<T> phy::RxToken for RxTokenAdapter<'_, T>
 where
     T: RxToken,
 {
@@ -85,16 +106,19 @@ where
         self.0.consume(|buf| {
             #[cfg(feature = "packet-trace")]
             trace!("embassy device rx: {:02x}", buf);
+            if let Some(hook) = self.1 {
+                hook(Direction::Rx, buf);
+            }
             f(buf)
         })
     }
 }
 
-pub(crate) struct TxTokenAdapter<T>(T)
+pub(crate) struct TxTokenAdapter<'a, T>(T, Option<PacketHook<'a>>)
 where
     T: TxToken;
 
-impl<T> phy::TxToken for TxTokenAdapter<T>
+impl<T> phy::TxToken for TxTokenAdapter<'_, T>
 where
     T: TxToken,
 {
@@ -106,6 +130,9 @@ where
             let r = f(buf);
             #[cfg(feature = "packet-trace")]
             trace!("embassy device tx: {:02x}", buf);
+            if let Some(hook) = self.1 {
+                hook(Direction::Tx, buf);
+            }
             r
         })
     }
@@ -1,10 +1,11 @@
 //! ICMP sockets.
 
+use core::cell::Cell;
 use core::future::{poll_fn, Future};
 use core::mem;
 use core::task::{Context, Poll};
 
-use smoltcp::iface::{Interface, SocketHandle};
+use smoltcp::iface::Interface;
 pub use smoltcp::phy::ChecksumCapabilities;
 use smoltcp::socket::icmp;
 pub use smoltcp::socket::icmp::{Endpoint as IcmpEndpoint, PacketMetadata};
@@ -14,7 +15,7 @@ pub use smoltcp::wire::{Icmpv4Message, Icmpv4Packet, Icmpv4Repr};
 #[cfg(feature = "proto-ipv6")]
 pub use smoltcp::wire::{Icmpv6Message, Icmpv6Packet, Icmpv6Repr};
 
-use crate::Stack;
+use crate::{InterfaceFull, InterfaceId, SocketRef, Stack};
 
 /// Error returned by [`IcmpSocket::bind`].
 #[derive(PartialEq, Eq, Clone, Copy, Debug)]
@@ -49,9 +50,15 @@ pub enum RecvError {
 }
 
 /// An ICMP socket.
+///
+/// Unless it's bound to an interface or to an endpoint, the socket moves to the interface used to
+/// reach the destination of each packet it sends. Once bound to an endpoint, it stays on its
+/// interface, and sending to an address routed through another interface fails with
+/// [`SendError::NoRoute`].
 pub struct IcmpSocket<'a> {
     stack: Stack<'a>,
-    handle: SocketHandle,
+    /// Changed when sending moves the socket to another interface.
+    handle: Cell<SocketRef>,
 }
 
 impl<'a> IcmpSocket<'a> {
@@ -68,13 +75,16 @@ impl<'a> IcmpSocket<'a> {
             let rx_buffer: &'static mut [u8] = unsafe { mem::transmute(rx_buffer) };
             let tx_meta: &'static mut [PacketMetadata] = unsafe { mem::transmute(tx_meta) };
             let tx_buffer: &'static mut [u8] = unsafe { mem::transmute(tx_buffer) };
-            i.sockets.add(icmp::Socket::new(
+            i.add_socket(icmp::Socket::new(
                 icmp::PacketBuffer::new(rx_meta, rx_buffer),
                 icmp::PacketBuffer::new(tx_meta, tx_buffer),
             ))
         });
 
-        Self { stack, handle }
+        Self {
+            stack,
+            handle: Cell::new(handle),
+        }
     }
 
     /// Bind the socket to the given endpoint.
@@ -95,19 +105,52 @@ impl<'a> IcmpSocket<'a> {
         }
     }
 
+    /// Bind the socket to a network interface of the stack.
+    ///
+    /// The socket then only sends and receives through this interface, instead of the one picked
+    /// by the routing of the stack. Call this before binding the socket.
+    ///
+    /// Returns [`InterfaceFull`] if the interface has no room for the socket.
+    pub fn bind_interface(&mut self, iface: InterfaceId) -> Result<(), InterfaceFull> {
+        self.stack.with_mut(|i| i.bind_socket(self.handle.get_mut(), iface))
+    }
+
+    /// Get the network interface the socket sends and receives through.
+    pub fn interface(&self) -> InterfaceId {
+        self.handle.get().iface
+    }
+
     fn with<R>(&self, f: impl FnOnce(&icmp::Socket, &Interface) -> R) -> R {
-        self.stack.with(|i| {
-            let socket = i.sockets.get::<icmp::Socket>(self.handle);
-            f(socket, &i.iface)
-        })
+        self.stack.with(|i| i.with_socket(self.handle.get(), f))
     }
 
     fn with_mut<R>(&self, f: impl FnOnce(&mut icmp::Socket, &mut Interface) -> R) -> R {
+        self.stack.with_mut(|i| i.with_socket_mut(self.handle.get(), f))
+    }
+
+    /// Move the socket to the interface used to reach `remote`, unless it's bound to an interface.
+    /// The packets already queued are sent first, through the interface they were routed to.
+    ///
+    /// A socket bound to an endpoint isn't moved, as it would stop receiving on its interface.
+    fn poll_route(&self, remote: &IpAddress, cx: &mut Context<'_>) -> Poll<Result<(), SendError>> {
         self.stack.with_mut(|i| {
-            let socket = i.sockets.get_mut::<icmp::Socket>(self.handle);
-            let res = f(socket, &mut i.iface);
-            i.waker.wake();
-            res
+            let mut handle = self.handle.get();
+            if !i.is_rerouted(handle, remote) {
+                return Poll::Ready(Ok(()));
+            }
+            let (bound, queued) = i.with_socket_mut(handle, |s: &mut icmp::Socket, _| {
+                s.register_send_waker(cx.waker());
+                (s.is_open(), s.send_queue() > 0)
+            });
+            if bound {
+                return Poll::Ready(Err(SendError::NoRoute));
+            }
+            if queued {
+                return Poll::Pending;
+            }
+            let res = i.route_socket(&mut handle, remote).map_err(|_| SendError::NoRoute);
+            self.handle.set(handle);
+            Poll::Ready(res)
         })
     }
 
@@ -248,13 +291,20 @@ impl<'a> IcmpSocket<'a> {
     where
         T: Into<IpAddress>,
     {
+        let remote_endpoint: IpAddress = remote_endpoint.into();
+
         // Don't need to wake waker in `with_mut` if the buffer will never fit the icmp tx_buffer.
         let send_capacity_too_small = self.with(|s, _| s.payload_send_capacity() < buf.len());
         if send_capacity_too_small {
             return Poll::Ready(Err(SendError::PacketTooLarge));
         }
+        match self.poll_route(&remote_endpoint, cx) {
+            Poll::Ready(Ok(())) => {}
+            Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+            Poll::Pending => return Poll::Pending,
+        }
 
-        self.with_mut(|s, _| match s.send_slice(buf, remote_endpoint.into()) {
+        self.with_mut(|s, _| match s.send_slice(buf, remote_endpoint) {
             // Entire datagram has been sent
             Ok(()) => Poll::Ready(Ok(())),
             Err(icmp::SendError::BufferFull) => {
@@ -290,6 +340,11 @@ impl<'a> IcmpSocket<'a> {
         let mut f = Some(f);
         let remote_endpoint = remote_endpoint.into();
         poll_fn(move |cx| {
+            match self.poll_route(&remote_endpoint, cx) {
+                Poll::Ready(Ok(())) => {}
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Pending => return Poll::Pending,
+            }
             self.with_mut(|s, _| match s.send(size, remote_endpoint) {
                 Ok(buf) => Poll::Ready(Ok({ unwrap!(f.take())(buf) })),
                 Err(icmp::SendError::BufferFull) => {
@@ -366,7 +421,7 @@ impl<'a> IcmpSocket<'a> {
 
 impl Drop for IcmpSocket<'_> {
     fn drop(&mut self) {
-        self.stack.with_mut(|i| i.sockets.remove(self.handle));
+        self.stack.with_mut(|i| i.remove_socket(self.handle.get()));
     }
 }
 
@@ -566,6 +621,11 @@ pub mod ping {
             // Create the socket and set hop limit and bind it to the endpoint with the ident
             let mut socket = IcmpSocket::new(self.stack, self.rx_meta, self.rx_buffer, self.tx_meta, self.tx_buffer);
             socket.set_hop_limit(params.hop_limit);
+            // Stay on the interface used to reach the target, the reply is received there.
+            let iface = self.stack.route(params.target.unwrap());
+            if socket.bind_interface(iface).is_err() {
+                return Err(PingError::SocketSendError(SendError::NoRoute));
+            }
             if let Err(e) = socket.bind(IcmpEndpoint::Ident(self.ident)) {
                 return Err(PingError::SocketBindError(e));
             }
@@ -628,6 +688,11 @@ pub mod ping {
             // Create the socket and set hop limit and bind it to the endpoint with the ident
             let mut socket = IcmpSocket::new(self.stack, self.rx_meta, self.rx_buffer, self.tx_meta, self.tx_buffer);
             socket.set_hop_limit(params.hop_limit);
+            // Stay on the interface used to reach the target, the reply is received there.
+            let iface = self.stack.route(params.target.unwrap());
+            if socket.bind_interface(iface).is_err() {
+                return Err(PingError::SocketSendError(SendError::NoRoute));
+            }
             if let Err(e) = socket.bind(IcmpEndpoint::Ident(self.ident)) {
                 return Err(PingError::SocketBindError(e));
             }
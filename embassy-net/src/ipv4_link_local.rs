@@ -0,0 +1,287 @@
+//! IPv4 link-local address autoconfiguration ([RFC 3927](https://www.rfc-editor.org/rfc/rfc3927)).
+//!
+//! Picks an address in 169.254.1.0 - 169.254.254.255, checks with ARP probes that nobody else on
+//! the link uses it, announces it, and then defends it against other hosts claiming it.
+
+use embassy_time::{Duration, Instant};
+use heapless::Vec;
+use smoltcp::phy::{Device, TxToken};
+use smoltcp::wire::{
+    ArpOperation, ArpPacket, ArpRepr, EthernetAddress, EthernetFrame, EthernetProtocol, EthernetRepr, Ipv4Address,
+    Ipv4Cidr,
+};
+
+use crate::time::instant_to_smoltcp;
+use crate::StaticConfigV4;
+
+const PROBE_WAIT: Duration = Duration::from_secs(1);
+const PROBE_NUM: u8 = 3;
+const PROBE_MIN: Duration = Duration::from_secs(1);
+const PROBE_MAX: Duration = Duration::from_secs(2);
+const ANNOUNCE_WAIT: Duration = Duration::from_secs(2);
+const ANNOUNCE_NUM: u8 = 2;
+const ANNOUNCE_INTERVAL: Duration = Duration::from_secs(2);
+const MAX_CONFLICTS: u8 = 10;
+const RATE_LIMIT_INTERVAL: Duration = Duration::from_secs(60);
+const DEFEND_INTERVAL: Duration = Duration::from_secs(10);
+
+#[derive(Clone, Copy)]
+enum State {
+    Disabled,
+    /// Waiting before probing a new candidate address.
+    Waiting {
+        until: Instant,
+    },
+    Probing {
+        sent: u8,
+        next: Instant,
+    },
+    /// The address is claimed and in use from here on.
+    Announcing {
+        sent: u8,
+        next: Instant,
+    },
+    Bound {
+        last_defense: Option<Instant>,
+    },
+}
+
+pub(crate) struct LinkLocal {
+    state: State,
+    hardware_address: EthernetAddress,
+    address: Ipv4Address,
+    /// Generator of candidate addresses, seeded from the hardware address so the same host picks
+    /// the same addresses every time.
+    address_random: u64,
+    /// Generator of the random delays.
+    random: u64,
+    conflicts: u8,
+    /// An ARP packet conflicting with our address was received since the last poll.
+    conflict: bool,
+}
+
+impl LinkLocal {
+    pub(crate) fn new(hardware_address: EthernetAddress, random_seed: u64) -> Self {
+        let mut seed = [0; 8];
+        seed[2..].copy_from_slice(&hardware_address.0);
+        let seed = u64::from_be_bytes(seed);
+
+        let mut this = Self {
+            state: State::Disabled,
+            hardware_address,
+            address: Ipv4Address::UNSPECIFIED,
+            address_random: seed | 1,
+            random: (random_seed ^ seed) | 1,
+            conflicts: 0,
+            conflict: false,
+        };
+        this.next_address();
+        this
+    }
+
+    /// Start claiming an address, or stop using it.
+    pub(crate) fn set_enabled(&mut self, enabled: bool, now: Instant) {
+        match (enabled, self.state) {
+            (true, State::Disabled) => {
+                self.conflicts = 0;
+                self.wait(now);
+            }
+            (false, State::Disabled) => {}
+            (false, _) => self.state = State::Disabled,
+            (true, _) => {}
+        }
+    }
+
+    /// The address configuration, once the address has been claimed.
+    pub(crate) fn config(&self) -> Option<StaticConfigV4> {
+        match self.state {
+            State::Announcing { .. } | State::Bound { .. } => Some(StaticConfigV4 {
+                address: Ipv4Cidr::new(self.address, 16),
+                gateway: None,
+                dns_servers: Vec::new(),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Look for other hosts using our address in a received frame.
+    pub(crate) fn receive(&mut self, frame: &[u8]) {
+        let Ok(frame) = EthernetFrame::new_checked(frame) else {
+            return;
+        };
+        if frame.ethertype() != EthernetProtocol::Arp {
+            return;
+        }
+        let Ok(ArpRepr::EthernetIpv4 {
+            operation,
+            source_hardware_addr,
+            source_protocol_addr,
+            target_protocol_addr,
+            ..
+        }) = ArpPacket::new_checked(frame.payload()).and_then(|p| ArpRepr::parse(&p))
+        else {
+            return;
+        };
+        if source_hardware_addr == self.hardware_address {
+            return;
+        }
+
+        self.conflict |= match self.state {
+            State::Probing { .. } => {
+                source_protocol_addr == self.address
+                    // Another host probing for the same address.
+                    || (operation == ArpOperation::Request
+                        && source_protocol_addr.is_unspecified()
+                        && target_protocol_addr == self.address)
+            }
+            State::Announcing { .. } | State::Bound { .. } => source_protocol_addr == self.address,
+            State::Disabled | State::Waiting { .. } => false,
+        };
+    }
+
+    pub(crate) fn poll<D: Device>(&mut self, device: &mut D, now: Instant) {
+        if core::mem::take(&mut self.conflict) {
+            match self.state {
+                State::Probing { .. } => {
+                    debug!("IPv4 link-local: {} is in use", self.address);
+                    self.restart(now);
+                }
+                State::Announcing { .. } | State::Bound { last_defense: None } => self.defend(device, now),
+                State::Bound {
+                    last_defense: Some(last),
+                } if now - last >= DEFEND_INTERVAL => self.defend(device, now),
+                State::Bound { .. } => {
+                    info!("IPv4 link-local: lost {} to another host", self.address);
+                    self.restart(now);
+                }
+                State::Disabled | State::Waiting { .. } => {}
+            }
+        }
+
+        match self.state {
+            State::Waiting { until } if until <= now => {
+                debug!("IPv4 link-local: probing {}", self.address);
+                self.state = State::Probing { sent: 0, next: now };
+            }
+            _ => {}
+        }
+
+        match self.state {
+            State::Probing { sent, next } if next <= now => {
+                if sent == PROBE_NUM {
+                    debug!("IPv4 link-local: claimed {}", self.address);
+                    self.state = State::Announcing { sent: 0, next: now };
+                } else if self.send(device, now, Ipv4Address::UNSPECIFIED) {
+                    let next = if sent + 1 == PROBE_NUM {
+                        ANNOUNCE_WAIT
+                    } else {
+                        PROBE_MIN + self.random_delay(PROBE_MAX - PROBE_MIN)
+                    };
+                    self.state = State::Probing {
+                        sent: sent + 1,
+                        next: now + next,
+                    };
+                }
+            }
+            _ => {}
+        }
+
+        match self.state {
+            State::Announcing { sent, next } if next <= now => {
+                if self.send(device, now, self.address) {
+                    self.state = if sent + 1 == ANNOUNCE_NUM {
+                        self.conflicts = 0;
+                        State::Bound { last_defense: None }
+                    } else {
+                        State::Announcing {
+                            sent: sent + 1,
+                            next: now + ANNOUNCE_INTERVAL,
+                        }
+                    };
+                }
+            }
+            _ => {}
+        }
+    }
+
+    pub(crate) fn poll_at(&self) -> Option<Instant> {
+        match self.state {
+            State::Waiting { until } => Some(until),
+            State::Probing { next, .. } | State::Announcing { next, .. } => Some(next),
+            State::Disabled | State::Bound { .. } => None,
+        }
+    }
+
+    /// Send a single announcement, and keep the address.
+    fn defend<D: Device>(&mut self, device: &mut D, now: Instant) {
+        debug!("IPv4 link-local: defending {}", self.address);
+        self.send(device, now, self.address);
+        if let State::Bound { last_defense } = &mut self.state {
+            *last_defense = Some(now);
+        }
+    }
+
+    /// Give up the address and start over with a new one.
+    fn restart(&mut self, now: Instant) {
+        self.conflicts = self.conflicts.saturating_add(1);
+        self.next_address();
+        if self.conflicts >= MAX_CONFLICTS {
+            self.state = State::Waiting {
+                until: now + RATE_LIMIT_INTERVAL,
+            };
+        } else {
+            self.wait(now);
+        }
+    }
+
+    fn wait(&mut self, now: Instant) {
+        self.state = State::Waiting {
+            until: now + self.random_delay(PROBE_WAIT),
+        };
+    }
+
+    fn next_address(&mut self) {
+        // The first and last 256 addresses are reserved.
+        let n = (xorshift(&mut self.address_random) % 0xfe00) as u16;
+        let [hi, lo] = n.to_be_bytes();
+        self.address = Ipv4Address::new(169, 254, hi + 1, lo);
+    }
+
+    fn random_delay(&mut self, max: Duration) -> Duration {
+        Duration::from_ticks(xorshift(&mut self.random) % (max.as_ticks() + 1))
+    }
+
+    /// Broadcast an ARP request for our address: a probe when `source` is unspecified, an
+    /// announcement when it's our address.
+    fn send<D: Device>(&mut self, device: &mut D, now: Instant, source: Ipv4Address) -> bool {
+        let Some(token) = device.transmit(instant_to_smoltcp(now)) else {
+            return false;
+        };
+
+        let ethernet = EthernetRepr {
+            src_addr: self.hardware_address,
+            dst_addr: EthernetAddress::BROADCAST,
+            ethertype: EthernetProtocol::Arp,
+        };
+        let arp = ArpRepr::EthernetIpv4 {
+            operation: ArpOperation::Request,
+            source_hardware_addr: self.hardware_address,
+            source_protocol_addr: source,
+            target_hardware_addr: EthernetAddress([0; 6]),
+            target_protocol_addr: self.address,
+        };
+        token.consume(ethernet.buffer_len() + arp.buffer_len(), |buf| {
+            let mut frame = EthernetFrame::new_unchecked(buf);
+            ethernet.emit(&mut frame);
+            arp.emit(&mut ArpPacket::new_unchecked(frame.payload_mut()));
+        });
+        true
+    }
+}
+
+fn xorshift(state: &mut u64) -> u64 {
+    *state ^= *state << 13;
+    *state ^= *state >> 7;
+    *state ^= *state << 17;
+    *state
+}
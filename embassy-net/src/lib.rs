@@ -12,13 +12,31 @@ compile_error!("You must enable at least one of the following features: proto-ip
 // This mod MUST go first, so that the others see its macros.
 pub(crate) mod fmt;
 
+#[cfg(feature = "dhcp-server")]
+pub mod dhcp_server;
+#[cfg(feature = "dhcpv6")]
+mod dhcpv6;
 #[cfg(feature = "dns")]
 pub mod dns;
 mod driver_util;
 #[cfg(feature = "icmp")]
 pub mod icmp;
+#[cfg(feature = "ipv4-link-local")]
+mod ipv4_link_local;
+#[cfg(feature = "mdns-responder")]
+pub mod mdns;
+#[cfg(feature = "pcap")]
+pub mod pcap;
+#[cfg(any(feature = "tcp", feature = "udp"))]
+mod pool;
 #[cfg(feature = "raw")]
 pub mod raw;
+#[cfg(feature = "slaac")]
+mod slaac;
+#[cfg(feature = "sntp")]
+pub mod sntp;
+#[cfg(feature = "stats")]
+pub mod stats;
 #[cfg(feature = "tcp")]
 pub mod tcp;
 mod time;
@@ -40,12 +58,13 @@ use heapless::Vec;
 pub use smoltcp::config::DNS_MAX_SERVER_COUNT;
 #[cfg(feature = "multicast")]
 pub use smoltcp::iface::MulticastError;
-#[cfg(any(feature = "dns", feature = "dhcpv4"))]
-use smoltcp::iface::SocketHandle;
-use smoltcp::iface::{Interface, SocketSet, SocketStorage};
+pub use smoltcp::iface::RouteTableFull;
+use smoltcp::iface::{SocketHandle, SocketSet, SocketStorage};
 use smoltcp::phy::Medium;
 #[cfg(feature = "dhcpv4")]
 use smoltcp::socket::dhcpv4::{self, RetryConfig};
+#[cfg(any(feature = "tcp", feature = "udp", feature = "raw", feature = "icmp"))]
+use smoltcp::socket::AnySocket;
 #[cfg(feature = "medium-ethernet")]
 pub use smoltcp::wire::EthernetAddress;
 #[cfg(any(feature = "medium-ethernet", feature = "medium-ieee802154", feature = "medium-ip"))]
@@ -60,7 +79,7 @@ pub use smoltcp::wire::{Ipv4Address, Ipv4Cidr};
 #[cfg(feature = "proto-ipv6")]
 pub use smoltcp::wire::{Ipv6Address, Ipv6Cidr};
 
-use crate::driver_util::DriverAdapter;
+use crate::driver_util::{Direction, DriverAdapter};
 use crate::time::{instant_from_smoltcp, instant_to_smoltcp};
 
 const LOCAL_PORT_MIN: u16 = 1025;
@@ -69,15 +88,30 @@ const LOCAL_PORT_MAX: u16 = 65535;
 const MAX_QUERIES: usize = 4;
 #[cfg(feature = "dhcpv4-hostname")]
 const MAX_HOSTNAME_LEN: usize = 32;
+/// Maximum number of network interfaces in a stack, including the default interface.
+pub const MAX_INTERFACES: usize = 4;
+const MAX_ROUTES: usize = 4;
 
 /// Memory resources needed for a network stack.
 pub struct StackResources<const SOCK: usize> {
-    sockets: MaybeUninit<[SocketStorage<'static>; SOCK]>,
+    interface: InterfaceResources<SOCK>,
     inner: MaybeUninit<RefCell<Inner>>,
     #[cfg(feature = "dns")]
     queries: MaybeUninit<[Option<dns::DnsQuery>; MAX_QUERIES]>,
+}
+
+/// Memory resources needed for an additional network interface.
+///
+/// See [`Stack::add_interface`].
+pub struct InterfaceResources<const SOCK: usize> {
+    sockets: MaybeUninit<[SocketStorage<'static>; SOCK]>,
+    inner: MaybeUninit<InterfaceInner>,
     #[cfg(feature = "dhcpv4-hostname")]
     hostname: HostnameResources,
+    #[cfg(feature = "slaac")]
+    slaac: slaac::Resources,
+    #[cfg(all(feature = "stats", any(feature = "tcp", feature = "udp")))]
+    socket_stats: MaybeUninit<[Option<stats::SocketEntry>; SOCK]>,
 }
 
 #[cfg(feature = "dhcpv4-hostname")]
@@ -90,19 +124,45 @@ impl<const SOCK: usize> StackResources<SOCK> {
     /// Create a new set of stack resources.
     pub const fn new() -> Self {
         Self {
-            sockets: MaybeUninit::uninit(),
+            interface: InterfaceResources::new(),
             inner: MaybeUninit::uninit(),
             #[cfg(feature = "dns")]
             queries: MaybeUninit::uninit(),
+        }
+    }
+}
+
+impl<const SOCK: usize> InterfaceResources<SOCK> {
+    /// Create a new set of interface resources.
+    pub const fn new() -> Self {
+        Self {
+            sockets: MaybeUninit::uninit(),
+            inner: MaybeUninit::uninit(),
             #[cfg(feature = "dhcpv4-hostname")]
             hostname: HostnameResources {
                 option: MaybeUninit::uninit(),
                 data: MaybeUninit::uninit(),
             },
+            #[cfg(feature = "slaac")]
+            slaac: slaac::Resources::new(),
+            #[cfg(all(feature = "stats", any(feature = "tcp", feature = "udp")))]
+            socket_stats: MaybeUninit::uninit(),
         }
     }
 }
 
+impl<const SOCK: usize> Default for StackResources<SOCK> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const SOCK: usize> Default for InterfaceResources<SOCK> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// Static IP address configuration.
 #[cfg(feature = "proto-ipv4")]
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -150,6 +210,12 @@ pub struct DhcpConfig {
     /// Our hostname. This will be sent to the DHCP server as Option 12.
     #[cfg(feature = "dhcpv4-hostname")]
     pub hostname: Option<heapless::String<MAX_HOSTNAME_LEN>>,
+    /// Use an IPv4 link-local address while no lease is acquired.
+    ///
+    /// The link-local address is claimed alongside the DHCP requests, and dropped as soon as a
+    /// lease arrives. See [`ConfigV4::LinkLocal`].
+    #[cfg(feature = "ipv4-link-local")]
+    pub link_local_fallback: bool,
 }
 
 #[cfg(feature = "dhcpv4")]
@@ -163,10 +229,25 @@ impl Default for DhcpConfig {
             client_port: smoltcp::wire::DHCP_CLIENT_PORT,
             #[cfg(feature = "dhcpv4-hostname")]
             hostname: None,
+            #[cfg(feature = "ipv4-link-local")]
+            link_local_fallback: false,
         }
     }
 }
 
+/// IPv6 stateless address autoconfiguration.
+#[cfg(feature = "slaac")]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct SlaacConfig {
+    /// Interface identifier of the link-local and global addresses.
+    ///
+    /// If not set, it's derived from the hardware address, or from the random seed on `Ip` medium
+    /// interfaces. Set it to an identifier negotiated by the link layer, such as the one
+    /// negotiated by PPP's IPV6CP.
+    pub interface_identifier: Option<[u8; 8]>,
+}
+
 /// Network stack configuration.
 #[derive(Debug, Clone, Default)]
 #[non_exhaustive]
@@ -190,6 +271,16 @@ impl Config {
         }
     }
 
+    /// IPv4 configuration with a link-local address.
+    #[cfg(feature = "ipv4-link-local")]
+    pub const fn ipv4_link_local() -> Self {
+        Self {
+            ipv4: ConfigV4::LinkLocal,
+            #[cfg(feature = "proto-ipv6")]
+            ipv6: ConfigV6::None,
+        }
+    }
+
     /// IPv6 configuration with static addressing.
     #[cfg(feature = "proto-ipv6")]
     pub const fn ipv6_static(config: StaticConfigV6) -> Self {
@@ -200,6 +291,26 @@ impl Config {
         }
     }
 
+    /// IPv6 configuration with stateless address autoconfiguration.
+    #[cfg(feature = "slaac")]
+    pub const fn slaac(config: SlaacConfig) -> Self {
+        Self {
+            #[cfg(feature = "proto-ipv4")]
+            ipv4: ConfigV4::None,
+            ipv6: ConfigV6::Slaac(config),
+        }
+    }
+
+    /// IPv6 configuration with stateless address autoconfiguration and stateless DHCPv6.
+    #[cfg(feature = "dhcpv6")]
+    pub const fn dhcpv6(config: SlaacConfig) -> Self {
+        Self {
+            #[cfg(feature = "proto-ipv4")]
+            ipv4: ConfigV4::None,
+            ipv6: ConfigV6::Dhcpv6(config),
+        }
+    }
+
     /// IPv4 configuration with dynamic addressing.
     ///
     /// # Example
@@ -229,6 +340,28 @@ pub enum ConfigV4 {
     /// Use DHCP to obtain an IP address configuration.
     #[cfg(feature = "dhcpv4")]
     Dhcp(DhcpConfig),
+    /// Use a link-local address in 169.254.0.0/16 (RFC 3927).
+    ///
+    /// The address is picked at random, checked with ARP probes to not be used by another host
+    /// on the link, and defended once claimed. There is no gateway and no DNS servers, so only
+    /// hosts on the same link can be reached.
+    #[cfg(feature = "ipv4-link-local")]
+    LinkLocal,
+}
+
+/// Source of the current IPv4 configuration, see [`Stack::config_v4_source`].
+#[cfg(feature = "proto-ipv4")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ConfigSourceV4 {
+    /// Static configuration.
+    Static,
+    /// Lease from a DHCP server.
+    #[cfg(feature = "dhcpv4")]
+    Dhcp,
+    /// Link-local address.
+    #[cfg(feature = "ipv4-link-local")]
+    LinkLocal,
 }
 
 /// Network stack IPv6 configuration.
@@ -240,6 +373,24 @@ pub enum ConfigV6 {
     None,
     /// Use a static IPv6 address configuration.
     Static(StaticConfigV6),
+    /// Use stateless address autoconfiguration (SLAAC) to obtain an IPv6 address configuration.
+    ///
+    /// The address is derived from the first autonomous /64 prefix advertised by routers, the
+    /// default gateway is the advertising router, and DNS servers come from RDNSS options.
+    ///
+    /// A link-local address is also assigned to the interface. When IPv4 is also used, this needs
+    /// room for 3 addresses: set `SMOLTCP_IFACE_MAX_ADDR_COUNT=3` in your environment.
+    ///
+    /// This uses one socket slot of the [`StackResources`].
+    #[cfg(feature = "slaac")]
+    Slaac(SlaacConfig),
+    /// Like [`ConfigV6::Slaac`], and also get DNS servers from a stateless DHCPv6 server when
+    /// routers advertise one with the "managed" or "other configuration" flag. Addresses are not
+    /// leased from DHCPv6 servers.
+    ///
+    /// This uses two socket slots of the [`StackResources`].
+    #[cfg(feature = "dhcpv6")]
+    Dhcpv6(SlaacConfig),
 }
 
 /// Network stack runner.
@@ -248,6 +399,9 @@ pub enum ConfigV6 {
 pub struct Runner<'d, D: Driver> {
     driver: D,
     stack: Stack<'d>,
+    iface: InterfaceId,
+    #[cfg(feature = "pcap")]
+    pcap: Option<pcap::Pcap<'d>>,
 }
 
 /// Network stack handle
@@ -259,28 +413,89 @@ pub struct Stack<'d> {
     inner: &'d RefCell<Inner>,
 }
 
+/// Identifier of a network interface of a [`Stack`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct InterfaceId(usize);
+
+impl InterfaceId {
+    /// The interface created along with the stack by [`new`].
+    ///
+    /// Sockets are created on this interface, and stay on it unless they are bound to or routed
+    /// through another interface.
+    pub const DEFAULT: Self = Self(0);
+}
+
+/// Error returned when a socket is moved to an interface that has no room for it.
+///
+/// Each interface holds as many sockets as its [`StackResources`] or [`InterfaceResources`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct InterfaceFull;
+
+/// Handle to a network interface of a [`Stack`].
+///
+/// Get it with [`Stack::interface`] or [`Stack::add_interface`]. It's `Copy`, so you can pass it
+/// by value instead of by reference.
+#[derive(Copy, Clone)]
+pub struct Interface<'d> {
+    stack: Stack<'d>,
+    id: InterfaceId,
+}
+
 pub(crate) struct Inner {
-    pub(crate) sockets: SocketSet<'static>, // Lifetime type-erased.
-    pub(crate) iface: Interface,
+    /// The interfaces of the stack, starting with the default interface. Lifetime type-erased.
+    interfaces: Vec<&'static mut InterfaceInner, MAX_INTERFACES>,
+    routes: Vec<(IpCidr, InterfaceId), MAX_ROUTES>,
+    next_local_port: u16,
+    #[cfg(feature = "dns")]
+    dns_waker: WakerRegistration,
+}
+
+/// A socket in the socket set of one of the interfaces.
+#[cfg(any(feature = "tcp", feature = "udp", feature = "raw", feature = "icmp"))]
+#[derive(Copy, Clone)]
+pub(crate) struct SocketRef {
+    pub(crate) iface: InterfaceId,
+    handle: SocketHandle,
+    /// Bound to its interface by the user, so routing doesn't move it.
+    bound: bool,
+}
+
+struct InterfaceInner {
+    sockets: SocketSet<'static>, // Lifetime type-erased.
+    /// Number of sockets `sockets` has room for.
+    socket_capacity: usize,
+    iface: smoltcp::iface::Interface,
     /// Waker used for triggering polls.
-    pub(crate) waker: WakerRegistration,
+    waker: WakerRegistration,
     /// Waker used for waiting for link up or config up.
     state_waker: WakerRegistration,
     hardware_address: HardwareAddress,
-    next_local_port: u16,
     link_up: bool,
     #[cfg(feature = "proto-ipv4")]
     static_v4: Option<StaticConfigV4>,
+    #[cfg(feature = "proto-ipv4")]
+    source_v4: ConfigSourceV4,
     #[cfg(feature = "proto-ipv6")]
     static_v6: Option<StaticConfigV6>,
     #[cfg(feature = "dhcpv4")]
     dhcp_socket: Option<SocketHandle>,
+    #[cfg(feature = "ipv4-link-local")]
+    link_local: Option<ipv4_link_local::LinkLocal>,
+    /// The DNS socket, in the socket set of the default interface.
     #[cfg(feature = "dns")]
-    dns_socket: SocketHandle,
-    #[cfg(feature = "dns")]
-    dns_waker: WakerRegistration,
+    dns_socket: Option<SocketHandle>,
     #[cfg(feature = "dhcpv4-hostname")]
     hostname: *mut HostnameResources,
+    #[cfg(feature = "slaac")]
+    slaac: Option<slaac::Slaac>,
+    #[cfg(feature = "slaac")]
+    slaac_resources: *mut slaac::Resources,
+    #[cfg(any(feature = "slaac", feature = "ipv4-link-local"))]
+    random_seed: u64,
+    #[cfg(feature = "stats")]
+    counters: stats::Counters,
 }
 
 fn _assert_covariant<'a, 'b: 'a>(x: Stack<'b>) -> Stack<'a> {
@@ -289,74 +504,128 @@ fn _assert_covariant<'a, 'b: 'a>(x: Stack<'b>) -> Stack<'a> {
 
 /// Create a new network stack.
 pub fn new<'d, D: Driver, const SOCK: usize>(
-    mut driver: D,
+    driver: D,
     config: Config,
     resources: &'d mut StackResources<SOCK>,
     random_seed: u64,
 ) -> (Stack<'d>, Runner<'d, D>) {
-    let (hardware_address, medium) = to_smoltcp_hardware_address(driver.hardware_address());
-    let mut iface_cfg = smoltcp::iface::Config::new(hardware_address);
-    iface_cfg.random_seed = random_seed;
-
-    let iface = Interface::new(
-        iface_cfg,
-        &mut DriverAdapter {
-            inner: &mut driver,
-            cx: None,
-            medium,
-        },
-        instant_to_smoltcp(Instant::now()),
-    );
-
-    unsafe fn transmute_slice<T>(x: &mut [T]) -> &'static mut [T] {
-        core::mem::transmute(x)
-    }
-
-    let sockets = resources.sockets.write([SocketStorage::EMPTY; SOCK]);
-    #[allow(unused_mut)]
-    let mut sockets: SocketSet<'static> = SocketSet::new(unsafe { transmute_slice(sockets) });
-
     let next_local_port = (random_seed % (LOCAL_PORT_MAX - LOCAL_PORT_MIN) as u64) as u16 + LOCAL_PORT_MIN;
 
+    let (driver, iface) = InterfaceInner::new(driver, &mut resources.interface, random_seed);
+
     #[cfg(feature = "dns")]
-    let dns_socket = sockets.add(dns::Socket::new(
-        &[],
-        managed::ManagedSlice::Borrowed(unsafe {
-            transmute_slice(resources.queries.write([const { None }; MAX_QUERIES]))
-        }),
-    ));
-
-    let mut inner = Inner {
-        sockets,
-        iface,
-        waker: WakerRegistration::new(),
-        state_waker: WakerRegistration::new(),
+    {
+        iface.dns_socket = Some(iface.sockets.add(dns::Socket::new(
+            &[],
+            managed::ManagedSlice::Borrowed(unsafe {
+                transmute_slice(resources.queries.write([const { None }; MAX_QUERIES]))
+            }),
+        )));
+    }
+    iface.set_config(config);
+
+    let mut interfaces = Vec::new();
+    unwrap!(interfaces.push(iface).ok());
+    let inner = Inner {
+        interfaces,
+        routes: Vec::new(),
         next_local_port,
-        hardware_address,
-        link_up: false,
-        #[cfg(feature = "proto-ipv4")]
-        static_v4: None,
-        #[cfg(feature = "proto-ipv6")]
-        static_v6: None,
-        #[cfg(feature = "dhcpv4")]
-        dhcp_socket: None,
-        #[cfg(feature = "dns")]
-        dns_socket,
         #[cfg(feature = "dns")]
         dns_waker: WakerRegistration::new(),
-        #[cfg(feature = "dhcpv4-hostname")]
-        hostname: &mut resources.hostname,
     };
 
-    #[cfg(feature = "proto-ipv4")]
-    inner.set_config_v4(config.ipv4);
-    #[cfg(feature = "proto-ipv6")]
-    inner.set_config_v6(config.ipv6);
-    inner.apply_static_config();
-
     let inner = &*resources.inner.write(RefCell::new(inner));
     let stack = Stack { inner };
-    (stack, Runner { driver, stack })
+    (
+        stack,
+        Runner {
+            driver,
+            stack,
+            iface: InterfaceId::DEFAULT,
+            #[cfg(feature = "pcap")]
+            pcap: None,
+        },
+    )
+}
+
+unsafe fn transmute_slice<T>(x: &mut [T]) -> &'static mut [T] {
+    core::mem::transmute(x)
+}
+
+impl InterfaceInner {
+    fn new<D: Driver, const SOCK: usize>(
+        mut driver: D,
+        resources: &mut InterfaceResources<SOCK>,
+        random_seed: u64,
+    ) -> (D, &'static mut Self) {
+        let (hardware_address, medium) = to_smoltcp_hardware_address(driver.hardware_address());
+        let mut iface_cfg = smoltcp::iface::Config::new(hardware_address);
+        iface_cfg.random_seed = random_seed;
+
+        let iface = smoltcp::iface::Interface::new(
+            iface_cfg,
+            &mut DriverAdapter {
+                inner: &mut driver,
+                cx: None,
+                medium,
+                hook: None,
+                #[cfg(feature = "stats")]
+                tx_unavailable: None,
+            },
+            instant_to_smoltcp(Instant::now()),
+        );
+
+        let sockets = resources.sockets.write([SocketStorage::EMPTY; SOCK]);
+        let sockets: SocketSet<'static> = SocketSet::new(unsafe { transmute_slice(sockets) });
+
+        let inner = resources.inner.write(InterfaceInner {
+            sockets,
+            socket_capacity: SOCK,
+            iface,
+            waker: WakerRegistration::new(),
+            state_waker: WakerRegistration::new(),
+            hardware_address,
+            link_up: false,
+            #[cfg(feature = "proto-ipv4")]
+            static_v4: None,
+            #[cfg(feature = "proto-ipv4")]
+            source_v4: ConfigSourceV4::Static,
+            #[cfg(feature = "proto-ipv6")]
+            static_v6: None,
+            #[cfg(feature = "dhcpv4")]
+            dhcp_socket: None,
+            #[cfg(feature = "ipv4-link-local")]
+            link_local: None,
+            #[cfg(feature = "dns")]
+            dns_socket: None,
+            #[cfg(feature = "dhcpv4-hostname")]
+            hostname: &mut resources.hostname,
+            #[cfg(feature = "slaac")]
+            slaac: None,
+            #[cfg(feature = "slaac")]
+            slaac_resources: &mut resources.slaac,
+            #[cfg(any(feature = "slaac", feature = "ipv4-link-local"))]
+            random_seed,
+            #[cfg(feature = "stats")]
+            counters: stats::Counters::new(
+                #[cfg(any(feature = "tcp", feature = "udp"))]
+                unsafe {
+                    transmute_slice(resources.socket_stats.write([const { None }; SOCK]))
+                },
+            ),
+        });
+
+        // safety: the stack borrows the resources for as long as it exists.
+        (driver, unsafe { &mut *(inner as *mut Self) })
+    }
+
+    fn set_config(&mut self, config: Config) {
+        #[cfg(feature = "proto-ipv4")]
+        self.set_config_v4(config.ipv4);
+        #[cfg(feature = "proto-ipv6")]
+        self.set_config_v6(config.ipv6);
+        self.apply_static_config();
+    }
 }
 
 fn to_smoltcp_hardware_address(addr: driver::HardwareAddress) -> (HardwareAddress, Medium) {
@@ -388,51 +657,110 @@ impl<'d> Stack<'d> {
         f(&mut self.inner.borrow_mut())
     }
 
+    /// Add a network interface to the stack.
+    ///
+    /// The interface has its own configuration, and its own link and configuration state, see
+    /// [`Interface`]. The [`Runner`] of the interface must be run in a background task, like the
+    /// one returned by [`new`].
+    ///
+    /// Sockets are created on the default interface. They are moved to this interface when bound
+    /// to it with their `bind_interface` method, when bound to one of its addresses, or when
+    /// connecting to an address routed through it, see [`Stack::route`]. `resources` holds the
+    /// sockets moved to this interface, and the DHCP and SLAAC sockets of its configuration.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the stack already has [`MAX_INTERFACES`] interfaces.
+    pub fn add_interface<D: Driver, const SOCK: usize>(
+        &self,
+        driver: D,
+        config: Config,
+        resources: &'d mut InterfaceResources<SOCK>,
+        random_seed: u64,
+    ) -> (Interface<'d>, Runner<'d, D>) {
+        let (driver, iface) = InterfaceInner::new(driver, resources, random_seed);
+        iface.set_config(config);
+        let id = self.with_mut(|i| {
+            let id = InterfaceId(i.interfaces.len());
+            if i.interfaces.push(iface).is_err() {
+                panic!("Too many interfaces, the maximum is {}.", MAX_INTERFACES);
+            }
+            id
+        });
+
+        let runner = Runner {
+            driver,
+            stack: *self,
+            iface: id,
+            #[cfg(feature = "pcap")]
+            pcap: None,
+        };
+        (self.interface(id), runner)
+    }
+
+    /// Get a network interface of the stack.
+    pub fn interface(&self, id: InterfaceId) -> Interface<'d> {
+        Interface { stack: *self, id }
+    }
+
+    /// Get the default network interface, created by [`new`].
+    ///
+    /// The methods of the stack that are about a single interface, like
+    /// [`Stack::config_v4`], use this interface.
+    pub fn default_interface(&self) -> Interface<'d> {
+        self.interface(InterfaceId::DEFAULT)
+    }
+
+    /// Route the addresses of `cidr` through an interface.
+    ///
+    /// Routes only pick the interface: the next hop is then the address itself when it's on the
+    /// interface's subnet, or the default gateway of the interface. A route with the same `cidr`
+    /// is replaced.
+    pub fn add_route(&self, cidr: IpCidr, iface: InterfaceId) -> Result<(), RouteTableFull> {
+        self.with_mut(|i| {
+            i.routes.retain(|(c, _)| *c != cidr);
+            i.routes.push((cidr, iface)).map_err(|_| RouteTableFull)
+        })
+    }
+
+    /// Remove a route added with [`Stack::add_route`].
+    pub fn remove_route(&self, cidr: IpCidr) {
+        self.with_mut(|i| i.routes.retain(|(c, _)| *c != cidr))
+    }
+
+    /// Get the interface used to reach an address.
+    ///
+    /// This is the most specific match of the routes added with [`Stack::add_route`] and the
+    /// subnets of the interfaces. When nothing matches, this is the first interface that is up
+    /// with a default gateway for the address family, and the default interface otherwise.
+    pub fn route(&self, addr: IpAddress) -> InterfaceId {
+        self.with(|i| i.route(&addr))
+    }
+
     /// Get the hardware address of the network interface.
     pub fn hardware_address(&self) -> HardwareAddress {
-        self.with(|i| i.hardware_address)
+        self.default_interface().hardware_address()
     }
 
     /// Check whether the link is up.
     pub fn is_link_up(&self) -> bool {
-        self.with(|i| i.link_up)
+        self.default_interface().is_link_up()
     }
 
     /// Check whether the network stack has a valid IP configuration.
     /// This is true if the network stack has a static IP configuration or if DHCP has completed
     pub fn is_config_up(&self) -> bool {
-        let v4_up;
-        let v6_up;
-
-        #[cfg(feature = "proto-ipv4")]
-        {
-            v4_up = self.config_v4().is_some();
-        }
-        #[cfg(not(feature = "proto-ipv4"))]
-        {
-            v4_up = false;
-        }
-
-        #[cfg(feature = "proto-ipv6")]
-        {
-            v6_up = self.config_v6().is_some();
-        }
-        #[cfg(not(feature = "proto-ipv6"))]
-        {
-            v6_up = false;
-        }
-
-        v4_up || v6_up
+        self.default_interface().is_config_up()
     }
 
     /// Wait for the network device to obtain a link signal.
     pub async fn wait_link_up(&self) {
-        self.wait(|| self.is_link_up()).await
+        self.default_interface().wait_link_up().await
     }
 
     /// Wait for the network device to lose link signal.
     pub async fn wait_link_down(&self) {
-        self.wait(|| !self.is_link_up()).await
+        self.default_interface().wait_link_down().await
     }
 
     /// Wait for the network stack to obtain a valid IP configuration.
@@ -465,66 +793,53 @@ impl<'d> Stack<'d> {
     /// // ...
     /// ```
     pub async fn wait_config_up(&self) {
-        self.wait(|| self.is_config_up()).await
+        self.default_interface().wait_config_up().await
     }
 
     /// Wait for the network stack to lose a valid IP configuration.
     pub async fn wait_config_down(&self) {
-        self.wait(|| !self.is_config_up()).await
-    }
-
-    fn wait<'a>(&'a self, mut predicate: impl FnMut() -> bool + 'a) -> impl Future<Output = ()> + 'a {
-        poll_fn(move |cx| {
-            if predicate() {
-                Poll::Ready(())
-            } else {
-                // If the config is not up, we register a waker that is woken up
-                // when a config is applied (static or DHCP).
-                trace!("Waiting for config up");
-
-                self.with_mut(|i| {
-                    i.state_waker.register(cx.waker());
-                });
-
-                Poll::Pending
-            }
-        })
+        self.default_interface().wait_config_down().await
     }
 
     /// Get the current IPv4 configuration.
     ///
     /// If using DHCP, this will be None if DHCP hasn't been able to
-    /// acquire an IP address, or Some if it has.
+    /// acquire an IP address, or Some if it has. Use [`Stack::config_v4_source`]
+    /// to know where the configuration comes from.
     #[cfg(feature = "proto-ipv4")]
     pub fn config_v4(&self) -> Option<StaticConfigV4> {
-        self.with(|i| i.static_v4.clone())
+        self.default_interface().config_v4()
+    }
+
+    /// Get the source of the current IPv4 configuration.
+    ///
+    /// This is None when there is no IPv4 configuration, like [`Stack::config_v4`].
+    #[cfg(feature = "proto-ipv4")]
+    pub fn config_v4_source(&self) -> Option<ConfigSourceV4> {
+        self.default_interface().config_v4_source()
     }
 
     /// Get the current IPv6 configuration.
     #[cfg(feature = "proto-ipv6")]
     pub fn config_v6(&self) -> Option<StaticConfigV6> {
-        self.with(|i| i.static_v6.clone())
+        self.default_interface().config_v6()
     }
 
     /// Set the IPv4 configuration.
     #[cfg(feature = "proto-ipv4")]
     pub fn set_config_v4(&self, config: ConfigV4) {
-        self.with_mut(|i| {
-            i.set_config_v4(config);
-            i.apply_static_config();
-        })
+        self.default_interface().set_config_v4(config)
     }
 
     /// Set the IPv6 configuration.
     #[cfg(feature = "proto-ipv6")]
     pub fn set_config_v6(&self, config: ConfigV6) {
-        self.with_mut(|i| {
-            i.set_config_v6(config);
-            i.apply_static_config();
-        })
+        self.default_interface().set_config_v6(config)
     }
 
     /// Make a query for a given name and return the corresponding IP addresses.
+    ///
+    /// Queries are sent through the default interface, to the DNS servers of its configuration.
     #[cfg(feature = "dns")]
     pub async fn dns_query(
         &self,
@@ -550,10 +865,11 @@ impl<'d> Stack<'d> {
 
         let query = poll_fn(|cx| {
             self.with_mut(|i| {
-                let socket = i.sockets.get_mut::<dns::Socket>(i.dns_socket);
-                match socket.start_query(i.iface.context(), name, qtype) {
+                let iface = &mut *i.interfaces[InterfaceId::DEFAULT.0];
+                let socket = iface.sockets.get_mut::<dns::Socket>(unwrap!(iface.dns_socket));
+                match socket.start_query(iface.iface.context(), name, qtype) {
                     Ok(handle) => {
-                        i.waker.wake();
+                        iface.waker.wake();
                         Poll::Ready(Ok(handle))
                     }
                     Err(dns::StartQueryError::NoFreeSlot) => {
@@ -591,16 +907,18 @@ impl<'d> Stack<'d> {
 
         let drop = OnDrop::new(|| {
             self.with_mut(|i| {
-                let socket = i.sockets.get_mut::<dns::Socket>(i.dns_socket);
+                let iface = &mut *i.interfaces[InterfaceId::DEFAULT.0];
+                let socket = iface.sockets.get_mut::<dns::Socket>(unwrap!(iface.dns_socket));
                 socket.cancel_query(query);
-                i.waker.wake();
+                iface.waker.wake();
                 i.dns_waker.wake();
             })
         });
 
         let res = poll_fn(|cx| {
             self.with_mut(|i| {
-                let socket = i.sockets.get_mut::<dns::Socket>(i.dns_socket);
+                let iface = &mut *i.interfaces[InterfaceId::DEFAULT.0];
+                let socket = iface.sockets.get_mut::<dns::Socket>(unwrap!(iface.dns_socket));
                 match socket.get_query_result(query) {
                     Ok(addrs) => {
                         i.dns_waker.wake();
@@ -629,20 +947,193 @@ impl<'d> Stack<'d> {
 impl<'d> Stack<'d> {
     /// Join a multicast group.
     pub fn join_multicast_group(&self, addr: impl Into<IpAddress>) -> Result<(), MulticastError> {
-        self.with_mut(|i| i.iface.join_multicast_group(addr))
+        self.default_interface().join_multicast_group(addr)
     }
 
     /// Leave a multicast group.
     pub fn leave_multicast_group(&self, addr: impl Into<IpAddress>) -> Result<(), MulticastError> {
-        self.with_mut(|i| i.iface.leave_multicast_group(addr))
+        self.default_interface().leave_multicast_group(addr)
     }
 
     /// Get whether the network stack has joined the given multicast group.
+    pub fn has_multicast_group(&self, addr: impl Into<IpAddress>) -> bool {
+        self.default_interface().has_multicast_group(addr)
+    }
+}
+
+#[cfg(feature = "stats")]
+impl Stack<'_> {
+    /// Get the statistics of the stack, summed over all its interfaces.
+    ///
+    /// See the [`stats`] module.
+    pub fn stats(&self) -> stats::Stats {
+        self.with(|i| {
+            let mut stats = stats::Stats::default();
+            for iface in &i.interfaces {
+                stats.add(&iface.counters.stats(&iface.sockets));
+            }
+            stats
+        })
+    }
+}
+
+impl<'d> Interface<'d> {
+    fn with<R>(&self, f: impl FnOnce(&InterfaceInner) -> R) -> R {
+        self.stack.with(|i| f(i.interfaces[self.id.0]))
+    }
+
+    fn with_mut<R>(&self, f: impl FnOnce(&mut InterfaceInner) -> R) -> R {
+        self.stack.with_mut(|i| f(i.interfaces[self.id.0]))
+    }
+
+    /// Get the identifier of the interface.
+    pub fn id(&self) -> InterfaceId {
+        self.id
+    }
+
+    /// Get the hardware address of the interface.
+    pub fn hardware_address(&self) -> HardwareAddress {
+        self.with(|i| i.hardware_address)
+    }
+
+    /// Check whether the link is up.
+    pub fn is_link_up(&self) -> bool {
+        self.with(|i| i.link_up)
+    }
+
+    /// Check whether the interface has a valid IP configuration.
+    pub fn is_config_up(&self) -> bool {
+        let v4_up;
+        let v6_up;
+
+        #[cfg(feature = "proto-ipv4")]
+        {
+            v4_up = self.config_v4().is_some();
+        }
+        #[cfg(not(feature = "proto-ipv4"))]
+        {
+            v4_up = false;
+        }
+
+        #[cfg(feature = "proto-ipv6")]
+        {
+            v6_up = self.config_v6().is_some();
+        }
+        #[cfg(not(feature = "proto-ipv6"))]
+        {
+            v6_up = false;
+        }
+
+        v4_up || v6_up
+    }
+
+    /// Wait for the network device to obtain a link signal.
+    pub async fn wait_link_up(&self) {
+        self.wait(|| self.is_link_up()).await
+    }
+
+    /// Wait for the network device to lose link signal.
+    pub async fn wait_link_down(&self) {
+        self.wait(|| !self.is_link_up()).await
+    }
+
+    /// Wait for the interface to obtain a valid IP configuration.
+    ///
+    /// See [`Stack::wait_config_up`].
+    pub async fn wait_config_up(&self) {
+        self.wait(|| self.is_config_up()).await
+    }
+
+    /// Wait for the interface to lose a valid IP configuration.
+    pub async fn wait_config_down(&self) {
+        self.wait(|| !self.is_config_up()).await
+    }
+
+    fn wait<'a>(&'a self, mut predicate: impl FnMut() -> bool + 'a) -> impl Future<Output = ()> + 'a {
+        poll_fn(move |cx| {
+            if predicate() {
+                Poll::Ready(())
+            } else {
+                // If the config is not up, we register a waker that is woken up
+                // when a config is applied (static or DHCP).
+                trace!("Waiting for config up");
+
+                self.with_mut(|i| {
+                    i.state_waker.register(cx.waker());
+                });
+
+                Poll::Pending
+            }
+        })
+    }
+
+    /// Get the current IPv4 configuration.
+    ///
+    /// See [`Stack::config_v4`].
+    #[cfg(feature = "proto-ipv4")]
+    pub fn config_v4(&self) -> Option<StaticConfigV4> {
+        self.with(|i| i.static_v4.clone())
+    }
+
+    /// Get the source of the current IPv4 configuration.
+    #[cfg(feature = "proto-ipv4")]
+    pub fn config_v4_source(&self) -> Option<ConfigSourceV4> {
+        self.with(|i| i.static_v4.as_ref().map(|_| i.source_v4))
+    }
+
+    /// Get the current IPv6 configuration.
+    #[cfg(feature = "proto-ipv6")]
+    pub fn config_v6(&self) -> Option<StaticConfigV6> {
+        self.with(|i| i.static_v6.clone())
+    }
+
+    /// Set the IPv4 configuration.
+    #[cfg(feature = "proto-ipv4")]
+    pub fn set_config_v4(&self, config: ConfigV4) {
+        self.with_mut(|i| {
+            i.set_config_v4(config);
+            i.apply_static_config();
+        })
+    }
+
+    /// Set the IPv6 configuration.
+    #[cfg(feature = "proto-ipv6")]
+    pub fn set_config_v6(&self, config: ConfigV6) {
+        self.with_mut(|i| {
+            i.set_config_v6(config);
+            i.apply_static_config();
+        })
+    }
+}
+
+#[cfg(feature = "multicast")]
+impl<'d> Interface<'d> {
+    /// Join a multicast group on this interface.
+    pub fn join_multicast_group(&self, addr: impl Into<IpAddress>) -> Result<(), MulticastError> {
+        self.with_mut(|i| i.iface.join_multicast_group(addr))
+    }
+
+    /// Leave a multicast group on this interface.
+    pub fn leave_multicast_group(&self, addr: impl Into<IpAddress>) -> Result<(), MulticastError> {
+        self.with_mut(|i| i.iface.leave_multicast_group(addr))
+    }
+
+    /// Get whether this interface has joined the given multicast group.
     pub fn has_multicast_group(&self, addr: impl Into<IpAddress>) -> bool {
         self.with(|i| i.iface.has_multicast_group(addr))
     }
 }
 
+#[cfg(feature = "stats")]
+impl Interface<'_> {
+    /// Get the statistics of this interface.
+    ///
+    /// See the [`stats`] module.
+    pub fn stats(&self) -> stats::Stats {
+        self.with(|i| i.counters.stats(&i.sockets))
+    }
+}
+
 impl Inner {
     #[allow(clippy::absurd_extreme_comparisons)]
     pub fn get_local_port(&mut self) -> u16 {
@@ -651,15 +1142,208 @@ impl Inner {
         res
     }
 
+    /// Add a socket to the default interface.
+    #[cfg(any(feature = "tcp", feature = "udp", feature = "raw", feature = "icmp"))]
+    pub(crate) fn add_socket<T: AnySocket<'static>>(&mut self, socket: T) -> SocketRef {
+        let iface = &mut *self.interfaces[InterfaceId::DEFAULT.0];
+        let handle = iface.sockets.add(socket);
+        #[cfg(all(feature = "stats", any(feature = "tcp", feature = "udp")))]
+        iface.counters.add_socket(&iface.sockets, handle);
+        SocketRef {
+            iface: InterfaceId::DEFAULT,
+            handle,
+            bound: false,
+        }
+    }
+
+    #[cfg(any(feature = "tcp", feature = "udp", feature = "raw", feature = "icmp"))]
+    pub(crate) fn remove_socket(&mut self, socket: SocketRef) {
+        let iface = &mut *self.interfaces[socket.iface.0];
+        iface.sockets.remove(socket.handle);
+        #[cfg(all(feature = "stats", any(feature = "tcp", feature = "udp")))]
+        iface.counters.remove_socket(socket.handle);
+    }
+
+    #[cfg(all(feature = "stats", any(feature = "tcp", feature = "udp")))]
+    pub(crate) fn socket_stats(&self, socket: SocketRef) -> stats::SocketStats {
+        self.interfaces[socket.iface.0].counters.socket_stats(socket.handle)
+    }
+
+    #[cfg(any(feature = "tcp", feature = "udp", feature = "icmp"))]
+    pub(crate) fn with_socket<T: AnySocket<'static>, R>(
+        &self,
+        socket: SocketRef,
+        f: impl FnOnce(&T, &smoltcp::iface::Interface) -> R,
+    ) -> R {
+        let iface = &self.interfaces[socket.iface.0];
+        f(iface.sockets.get::<T>(socket.handle), &iface.iface)
+    }
+
+    /// Access a socket, and wake its interface to poll it.
+    #[cfg(any(feature = "tcp", feature = "udp", feature = "raw", feature = "icmp"))]
+    pub(crate) fn with_socket_mut<T: AnySocket<'static>, R>(
+        &mut self,
+        socket: SocketRef,
+        f: impl FnOnce(&mut T, &mut smoltcp::iface::Interface) -> R,
+    ) -> R {
+        let iface = &mut *self.interfaces[socket.iface.0];
+        let res = f(iface.sockets.get_mut::<T>(socket.handle), &mut iface.iface);
+        iface.waker.wake();
+        res
+    }
+
+    /// Bind a socket to an interface, so that it isn't moved by routing.
+    #[cfg(any(feature = "tcp", feature = "udp", feature = "raw", feature = "icmp"))]
+    pub(crate) fn bind_socket(&mut self, socket: &mut SocketRef, iface: InterfaceId) -> Result<(), InterfaceFull> {
+        self.move_socket(socket, iface)?;
+        socket.bound = true;
+        Ok(())
+    }
+
+    /// Move a socket to the interface used to reach `remote`, unless it's bound.
+    #[cfg(any(feature = "tcp", feature = "raw", feature = "icmp"))]
+    pub(crate) fn route_socket(&mut self, socket: &mut SocketRef, remote: &IpAddress) -> Result<(), InterfaceFull> {
+        if socket.bound {
+            return Ok(());
+        }
+        let iface = self.route(remote);
+        self.move_socket(socket, iface)
+    }
+
+    /// Move a socket to the interface that has the `local` address, unless it's bound.
+    #[cfg(any(feature = "tcp", feature = "udp"))]
+    pub(crate) fn route_socket_local(
+        &mut self,
+        socket: &mut SocketRef,
+        local: &IpAddress,
+    ) -> Result<(), InterfaceFull> {
+        if socket.bound {
+            return Ok(());
+        }
+        let iface = self
+            .interfaces
+            .iter()
+            .position(|i| i.iface.ip_addrs().iter().any(|a| a.address() == *local));
+        match iface {
+            Some(iface) => self.move_socket(socket, InterfaceId(iface)),
+            None => Ok(()),
+        }
+    }
+
+    /// Whether `remote` is routed through another interface than the socket's, unless it's bound.
+    #[cfg(any(feature = "udp", feature = "raw", feature = "icmp"))]
+    pub(crate) fn is_rerouted(&self, socket: SocketRef, remote: &IpAddress) -> bool {
+        !socket.bound && self.route(remote) != socket.iface
+    }
+
+    #[cfg(any(feature = "tcp", feature = "udp", feature = "raw", feature = "icmp"))]
+    fn move_socket(&mut self, socket: &mut SocketRef, iface: InterfaceId) -> Result<(), InterfaceFull> {
+        if socket.iface == iface {
+            return Ok(());
+        }
+        let target = &self.interfaces[iface.0];
+        if target.sockets.iter().count() >= target.socket_capacity {
+            return Err(InterfaceFull);
+        }
+
+        let moved = self.interfaces[socket.iface.0].sockets.remove(socket.handle);
+        #[cfg(all(feature = "stats", any(feature = "tcp", feature = "udp")))]
+        let entry = self.interfaces[socket.iface.0].counters.remove_socket(socket.handle);
+        let sockets = &mut self.interfaces[iface.0].sockets;
+        socket.handle = match moved {
+            #[cfg(feature = "tcp")]
+            smoltcp::socket::Socket::Tcp(s) => sockets.add(s),
+            #[cfg(feature = "udp")]
+            smoltcp::socket::Socket::Udp(s) => sockets.add(s),
+            #[cfg(feature = "raw")]
+            smoltcp::socket::Socket::Raw(s) => sockets.add(s),
+            #[cfg(feature = "icmp")]
+            smoltcp::socket::Socket::Icmp(s) => sockets.add(s),
+            #[allow(unreachable_patterns)]
+            _ => unreachable!(),
+        };
+        #[cfg(all(feature = "stats", any(feature = "tcp", feature = "udp")))]
+        if let Some(entry) = entry {
+            self.interfaces[iface.0].counters.move_socket(entry, socket.handle);
+        }
+        socket.iface = iface;
+        self.interfaces[iface.0].waker.wake();
+        Ok(())
+    }
+
+    fn route(&self, addr: &IpAddress) -> InterfaceId {
+        // Most specific match of the routes and the subnets of the interfaces.
+        let subnets = self
+            .interfaces
+            .iter()
+            .enumerate()
+            .flat_map(|(n, i)| i.iface.ip_addrs().iter().map(move |cidr| (*cidr, InterfaceId(n))));
+        let best = self
+            .routes
+            .iter()
+            .copied()
+            .chain(subnets)
+            .filter(|(cidr, _)| cidr.contains_addr(addr))
+            .max_by_key(|(cidr, _)| cidr.prefix_len());
+        if let Some((_, iface)) = best {
+            return iface;
+        }
+
+        self.interfaces
+            .iter()
+            .position(|i| i.link_up && i.has_gateway(addr))
+            .map_or(InterfaceId::DEFAULT, InterfaceId)
+    }
+}
+
+impl InterfaceInner {
+    fn has_gateway(&self, addr: &IpAddress) -> bool {
+        match addr {
+            #[cfg(feature = "proto-ipv4")]
+            IpAddress::Ipv4(_) => self.static_v4.as_ref().is_some_and(|c| c.gateway.is_some()),
+            #[cfg(feature = "proto-ipv6")]
+            IpAddress::Ipv6(_) => self.static_v6.as_ref().is_some_and(|c| c.gateway.is_some()),
+            #[allow(unreachable_patterns)]
+            _ => false,
+        }
+    }
+
     #[cfg(feature = "proto-ipv4")]
-    pub fn set_config_v4(&mut self, config: ConfigV4) {
+    fn set_config_v4(&mut self, config: ConfigV4) {
         // Handle static config.
         self.static_v4 = match config.clone() {
             ConfigV4::None => None,
             #[cfg(feature = "dhcpv4")]
             ConfigV4::Dhcp(_) => None,
             ConfigV4::Static(c) => Some(c),
+            #[cfg(feature = "ipv4-link-local")]
+            ConfigV4::LinkLocal => None,
         };
+        self.source_v4 = ConfigSourceV4::Static;
+
+        // Handle link-local config.
+        #[cfg(feature = "ipv4-link-local")]
+        {
+            let enabled = match &config {
+                ConfigV4::LinkLocal => true,
+                #[cfg(feature = "dhcpv4")]
+                ConfigV4::Dhcp(c) => c.link_local_fallback,
+                _ => false,
+            };
+            self.link_local = match self.hardware_address {
+                HardwareAddress::Ethernet(addr) if enabled => {
+                    let mut link_local = ipv4_link_local::LinkLocal::new(addr, self.random_seed);
+                    link_local.set_enabled(self.link_up, Instant::now());
+                    Some(link_local)
+                }
+                _ => {
+                    if enabled {
+                        warn!("IPv4 link-local addresses need an Ethernet medium.");
+                    }
+                    None
+                }
+            };
+        }
 
         // Handle DHCP config.
         #[cfg(feature = "dhcpv4")]
@@ -711,11 +1395,45 @@ impl Inner {
     }
 
     #[cfg(feature = "proto-ipv6")]
-    pub fn set_config_v6(&mut self, config: ConfigV6) {
-        self.static_v6 = match config {
+    fn set_config_v6(&mut self, config: ConfigV6) {
+        // Handle static config.
+        self.static_v6 = match config.clone() {
             ConfigV6::None => None,
             ConfigV6::Static(c) => Some(c),
+            #[cfg(feature = "slaac")]
+            ConfigV6::Slaac(_) => None,
+            #[cfg(feature = "dhcpv6")]
+            ConfigV6::Dhcpv6(_) => None,
         };
+
+        // Handle autoconfiguration. Recreate its sockets, which resets it.
+        #[cfg(feature = "slaac")]
+        {
+            if let Some(slaac) = self.slaac.take() {
+                slaac.remove(&mut self.sockets);
+            }
+            let slaac_config = match config {
+                ConfigV6::Slaac(c) => Some((c, false)),
+                #[cfg(feature = "dhcpv6")]
+                ConfigV6::Dhcpv6(c) => Some((c, true)),
+                _ => None,
+            };
+            if let Some((slaac_config, dhcpv6)) = slaac_config {
+                // safety: the previous sockets were just removed, so they no longer hold references
+                // to the resources, which live for as long as the stack exists.
+                let resources = unsafe { &mut *self.slaac_resources };
+                let mut slaac = slaac::Slaac::new(
+                    &mut self.sockets,
+                    resources,
+                    &slaac_config,
+                    self.hardware_address,
+                    self.random_seed,
+                    dhcpv6,
+                );
+                slaac.reset(self.link_up);
+                self.slaac = Some(slaac);
+            }
+        }
     }
 
     fn apply_static_config(&mut self) {
@@ -761,6 +1479,13 @@ impl Inner {
             info!("IPv6: DOWN");
         }
 
+        #[cfg(feature = "slaac")]
+        if let Some(slaac) = &self.slaac {
+            if addrs.push(IpCidr::Ipv6(slaac.link_local())).is_err() {
+                warn!("No room for the IPv6 link-local address, increase SMOLTCP_IFACE_MAX_ADDR_COUNT.");
+            }
+        }
+
         // Apply addresses
         self.iface.update_ip_addrs(|a| *a = addrs);
 
@@ -780,7 +1505,7 @@ impl Inner {
 
         // Apply DNS servers
         #[cfg(feature = "dns")]
-        if !dns_servers.is_empty() {
+        if let (Some(dns_socket), false) = (self.dns_socket, dns_servers.is_empty()) {
             let count = if dns_servers.len() > DNS_MAX_SERVER_COUNT {
                 warn!("Number of DNS servers exceeds DNS_MAX_SERVER_COUNT, truncating list.");
                 DNS_MAX_SERVER_COUNT
@@ -788,14 +1513,14 @@ impl Inner {
                 dns_servers.len()
             };
             self.sockets
-                .get_mut::<smoltcp::socket::dns::Socket>(self.dns_socket)
+                .get_mut::<smoltcp::socket::dns::Socket>(dns_socket)
                 .update_servers(&dns_servers[..count]);
         }
 
         self.state_waker.wake();
     }
 
-    fn poll<D: Driver>(&mut self, cx: &mut Context<'_>, driver: &mut D) {
+    fn poll<D: Driver>(&mut self, cx: &mut Context<'_>, driver: &mut D, hook: &dyn Fn(Direction, &[u8])) {
         self.waker.register(cx.waker());
 
         let (_hardware_addr, medium) = to_smoltcp_hardware_address(driver.hardware_address());
@@ -815,14 +1540,39 @@ impl Inner {
             }
         }
 
+        #[cfg(feature = "stats")]
+        let checksum = driver.capabilities().checksum;
+        #[cfg(all(feature = "stats", any(feature = "tcp", feature = "udp")))]
+        self.counters.update_sockets(&self.sockets);
+
+        #[cfg(feature = "ipv4-link-local")]
+        let link_local = RefCell::new(self.link_local.as_mut());
+        let iface_hook = |direction, frame: &[u8]| {
+            #[cfg(feature = "ipv4-link-local")]
+            if let (Direction::Rx, Some(link_local)) = (direction, &mut *link_local.borrow_mut()) {
+                link_local.receive(frame);
+            }
+            #[cfg(feature = "stats")]
+            self.counters.frame(direction, frame, medium, &checksum);
+            hook(direction, frame);
+        };
+
         let timestamp = instant_to_smoltcp(Instant::now());
         let mut smoldev = DriverAdapter {
             cx: Some(cx),
             inner: driver,
             medium,
+            hook: Some(&iface_hook),
+            #[cfg(feature = "stats")]
+            tx_unavailable: Some(&self.counters.tx_unavailable),
         };
         self.iface.poll(timestamp, &mut smoldev, &mut self.sockets);
 
+        #[cfg(all(feature = "stats", any(feature = "tcp", feature = "udp")))]
+        self.counters.update_sockets(&self.sockets);
+        #[cfg(feature = "stats")]
+        self.counters.rx_dropped.set(driver.rx_dropped());
+
         // Update link up
         let old_link_up = self.link_up;
         self.link_up = driver.link_state(cx) == LinkState::Up;
@@ -853,6 +1603,7 @@ impl Inner {
                             gateway: config.router,
                             dns_servers: config.dns_servers,
                         });
+                        self.source_v4 = ConfigSourceV4::Dhcp;
                         true
                     }
                 }
@@ -868,6 +1619,73 @@ impl Inner {
             }
         }
 
+        #[cfg(feature = "ipv4-link-local")]
+        if let Some(link_local) = &mut self.link_local {
+            // A DHCP lease takes precedence over the link-local address.
+            #[cfg(feature = "dhcpv4")]
+            let leased = self.static_v4.is_some() && self.source_v4 == ConfigSourceV4::Dhcp;
+            #[cfg(not(feature = "dhcpv4"))]
+            let leased = false;
+
+            let now = Instant::now();
+            if old_link_up != self.link_up {
+                link_local.set_enabled(false, now);
+            }
+            link_local.set_enabled(self.link_up && !leased, now);
+            let link_local_hook = |direction, frame: &[u8]| {
+                #[cfg(feature = "stats")]
+                self.counters.frame(direction, frame, medium, &checksum);
+                hook(direction, frame);
+            };
+            link_local.poll(
+                &mut DriverAdapter {
+                    cx: Some(cx),
+                    inner: driver,
+                    medium,
+                    hook: Some(&link_local_hook),
+                    #[cfg(feature = "stats")]
+                    tx_unavailable: Some(&self.counters.tx_unavailable),
+                },
+                now,
+            );
+            if let Some(poll_at) = link_local.poll_at() {
+                let t = pin!(Timer::at(poll_at));
+                if t.poll(cx).is_ready() {
+                    cx.waker().wake_by_ref();
+                }
+            }
+            if !leased {
+                let config = link_local.config();
+                if config != self.static_v4 {
+                    self.static_v4 = config;
+                    self.source_v4 = ConfigSourceV4::LinkLocal;
+                    self.apply_static_config();
+                }
+            }
+        }
+
+        #[cfg(feature = "slaac")]
+        if let Some(slaac) = &mut self.slaac {
+            if old_link_up != self.link_up {
+                slaac.reset(self.link_up);
+            }
+            if self.link_up {
+                slaac.poll(&mut self.sockets);
+            }
+            let config = slaac.config();
+            if let Some(poll_at) = slaac.poll_at() {
+                let t = pin!(Timer::at(poll_at));
+                if t.poll(cx).is_ready() {
+                    cx.waker().wake_by_ref();
+                }
+            }
+            if config != self.static_v6 {
+                self.static_v6 = config;
+                self.apply_static_config();
+            }
+        }
+
+        // Packets queued by the sockets above make this immediate.
         if let Some(poll_at) = self.iface.poll_at(timestamp, &mut self.sockets) {
             let t = pin!(Timer::at(instant_from_smoltcp(poll_at)));
             if t.poll(cx).is_ready() {
@@ -883,10 +1701,247 @@ impl<'d, D: Driver> Runner<'d, D> {
     /// You must call this in a background task, to process network events.
     pub async fn run(&mut self) -> ! {
         poll_fn(|cx| {
-            self.stack.with_mut(|i| i.poll(cx, &mut self.driver));
+            let hook = |_direction, _frame: &[u8]| {
+                #[cfg(feature = "pcap")]
+                if let Some(pcap) = &self.pcap {
+                    pcap.packet(_direction, _frame);
+                }
+            };
+            self.stack
+                .with_mut(|i| i.interfaces[self.iface.0].poll(cx, &mut self.driver, &hook));
             Poll::<()>::Pending
         })
         .await;
         unreachable!()
     }
+
+    /// Capture the frames received and transmitted by the interface to `sink`, or stop capturing
+    /// with `None`.
+    ///
+    /// See the [`pcap`] module.
+    #[cfg(feature = "pcap")]
+    pub fn set_pcap_sink(&mut self, sink: Option<&'d dyn pcap::PcapSink>) {
+        let (_, medium) = to_smoltcp_hardware_address(self.driver.hardware_address());
+        self.pcap = sink.map(|sink| pcap::Pcap::new(sink, medium));
+    }
+}
+
+#[cfg(all(test, feature = "proto-ipv4", feature = "medium-ip", feature = "udp"))]
+pub(crate) mod tests {
+    extern crate std;
+
+    use embassy_futures::block_on;
+
+    use super::*;
+    use crate::udp::{PacketMetadata, SendError, UdpSocket};
+
+    /// Driver without traffic, for tests that don't send or receive.
+    pub(crate) struct NoTraffic;
+
+    pub(crate) struct Token;
+
+    impl driver::RxToken for Token {
+        fn consume<R, F: FnOnce(&mut [u8]) -> R>(self, _f: F) -> R {
+            unreachable!()
+        }
+    }
+
+    impl driver::TxToken for Token {
+        fn consume<R, F: FnOnce(&mut [u8]) -> R>(self, _len: usize, _f: F) -> R {
+            unreachable!()
+        }
+    }
+
+    impl Driver for NoTraffic {
+        type RxToken<'a> = Token;
+        type TxToken<'a> = Token;
+
+        fn receive(&mut self, _cx: &mut Context) -> Option<(Token, Token)> {
+            None
+        }
+        fn transmit(&mut self, _cx: &mut Context) -> Option<Token> {
+            None
+        }
+        fn link_state(&mut self, _cx: &mut Context) -> LinkState {
+            LinkState::Down
+        }
+        fn capabilities(&self) -> driver::Capabilities {
+            let mut caps = driver::Capabilities::default();
+            caps.max_transmission_unit = 1500;
+            caps
+        }
+        fn hardware_address(&self) -> driver::HardwareAddress {
+            driver::HardwareAddress::Ip
+        }
+    }
+
+    pub(crate) fn config(address: [u8; 4], prefix_len: u8, gateway: Option<[u8; 4]>) -> Config {
+        Config::ipv4_static(StaticConfigV4 {
+            address: Ipv4Cidr::new(address.into(), prefix_len),
+            gateway: gateway.map(Into::into),
+            dns_servers: Vec::new(),
+        })
+    }
+
+    fn set_link_up(stack: Stack<'_>, iface: InterfaceId) {
+        stack.with_mut(|i| i.interfaces[iface.0].link_up = true);
+    }
+
+    fn cidr(address: [u8; 4], prefix_len: u8) -> IpCidr {
+        Ipv4Cidr::new(address.into(), prefix_len).into()
+    }
+
+    fn addr(address: [u8; 4]) -> IpAddress {
+        Ipv4Address::from(address).into()
+    }
+
+    #[test]
+    fn route_longest_prefix() {
+        let mut resources = StackResources::<2>::new();
+        let mut resources1 = InterfaceResources::<2>::new();
+        let (stack, _runner) = new(NoTraffic, config([192, 168, 1, 2], 24, None), &mut resources, 1);
+        let (iface1, _runner1) = stack.add_interface(NoTraffic, config([10, 0, 0, 2], 8, None), &mut resources1, 2);
+        let iface1 = iface1.id();
+
+        // Subnets of the interfaces.
+        assert!(stack.route(addr([192, 168, 1, 9])) == InterfaceId::DEFAULT);
+        assert!(stack.route(addr([10, 2, 0, 1])) == iface1);
+
+        // A route is used when it's more specific than the subnet, and the other way around.
+        unwrap!(stack.add_route(cidr([10, 1, 0, 0], 16), InterfaceId::DEFAULT));
+        unwrap!(stack.add_route(cidr([192, 168, 0, 0], 16), iface1));
+        assert!(stack.route(addr([10, 1, 2, 3])) == InterfaceId::DEFAULT);
+        assert!(stack.route(addr([10, 2, 0, 1])) == iface1);
+        assert!(stack.route(addr([192, 168, 1, 9])) == InterfaceId::DEFAULT);
+        assert!(stack.route(addr([192, 168, 2, 9])) == iface1);
+    }
+
+    #[test]
+    fn route_default() {
+        let mut resources = StackResources::<2>::new();
+        let mut resources1 = InterfaceResources::<2>::new();
+        let (stack, _runner) = new(NoTraffic, config([192, 168, 1, 2], 24, None), &mut resources, 1);
+        let (iface1, _runner1) = stack.add_interface(
+            NoTraffic,
+            config([10, 0, 0, 2], 8, Some([10, 0, 0, 1])),
+            &mut resources1,
+            2,
+        );
+        let iface1 = iface1.id();
+
+        // The default interface is used until an interface with a gateway is up.
+        assert!(stack.route(addr([8, 8, 8, 8])) == InterfaceId::DEFAULT);
+        set_link_up(stack, InterfaceId::DEFAULT);
+        assert!(stack.route(addr([8, 8, 8, 8])) == InterfaceId::DEFAULT);
+        set_link_up(stack, iface1);
+        assert!(stack.route(addr([8, 8, 8, 8])) == iface1);
+
+        // The first interface with a gateway wins.
+        stack
+            .default_interface()
+            .set_config_v4(config([192, 168, 1, 2], 24, Some([192, 168, 1, 1])).ipv4);
+        assert!(stack.route(addr([8, 8, 8, 8])) == InterfaceId::DEFAULT);
+    }
+
+    #[test]
+    fn remove_route() {
+        let mut resources = StackResources::<2>::new();
+        let mut resources1 = InterfaceResources::<2>::new();
+        let (stack, _runner) = new(NoTraffic, config([192, 168, 1, 2], 24, None), &mut resources, 1);
+        let (iface1, _runner1) = stack.add_interface(NoTraffic, config([10, 0, 0, 2], 8, None), &mut resources1, 2);
+        let iface1 = iface1.id();
+
+        unwrap!(stack.add_route(cidr([172, 16, 0, 0], 12), iface1));
+        assert!(stack.route(addr([172, 16, 1, 1])) == iface1);
+
+        // The route with the same CIDR is replaced.
+        unwrap!(stack.add_route(cidr([172, 16, 0, 0], 12), InterfaceId::DEFAULT));
+        unwrap!(stack.add_route(cidr([172, 16, 0, 0], 16), iface1));
+        assert!(stack.route(addr([172, 17, 1, 1])) == InterfaceId::DEFAULT);
+        assert!(stack.route(addr([172, 16, 1, 1])) == iface1);
+
+        stack.remove_route(cidr([172, 16, 0, 0], 16));
+        assert!(stack.route(addr([172, 16, 1, 1])) == InterfaceId::DEFAULT);
+        stack.remove_route(cidr([172, 16, 0, 0], 12));
+        stack.remove_route(cidr([172, 16, 0, 0], 12));
+
+        for n in 0..MAX_ROUTES as u8 {
+            unwrap!(stack.add_route(cidr([172, 16, n, 0], 24), iface1));
+        }
+        assert!(stack.add_route(cidr([172, 17, 0, 0], 24), iface1) == Err(RouteTableFull));
+        // Replacing a route doesn't need room.
+        unwrap!(stack.add_route(cidr([172, 16, 0, 0], 24), InterfaceId::DEFAULT));
+    }
+
+    #[test]
+    #[cfg(any(feature = "tcp", feature = "raw", feature = "icmp"))]
+    fn route_socket() {
+        let mut resources = StackResources::<3>::new();
+        let mut resources1 = InterfaceResources::<1>::new();
+        let (stack, _runner) = new(NoTraffic, config([192, 168, 1, 2], 24, None), &mut resources, 1);
+        let (iface1, _runner1) = stack.add_interface(NoTraffic, config([10, 0, 0, 2], 8, None), &mut resources1, 2);
+        let iface1 = iface1.id();
+
+        let udp_socket = || {
+            let buffer = || {
+                let meta = std::vec![PacketMetadata::EMPTY; 1].leak();
+                smoltcp::socket::udp::PacketBuffer::new(meta, std::vec![0; 64].leak())
+            };
+            smoltcp::socket::udp::Socket::new(buffer(), buffer())
+        };
+        stack.with_mut(|i| {
+            let mut socket = i.add_socket(udp_socket());
+            let mut other = i.add_socket(udp_socket());
+
+            // Moved to the interface used to reach the remote address.
+            assert!(i.is_rerouted(socket, &addr([10, 1, 1, 1])));
+            unwrap!(i.route_socket(&mut socket, &addr([10, 1, 1, 1])));
+            assert!(socket.iface == iface1);
+            assert!(!i.is_rerouted(socket, &addr([10, 1, 1, 1])));
+
+            // The interface is full.
+            assert!(i.route_socket(&mut other, &addr([10, 1, 1, 1])) == Err(InterfaceFull));
+            assert!(other.iface == InterfaceId::DEFAULT);
+
+            // A bound socket isn't moved.
+            unwrap!(i.bind_socket(&mut socket, InterfaceId::DEFAULT));
+            assert!(!i.is_rerouted(socket, &addr([10, 1, 1, 1])));
+            unwrap!(i.route_socket(&mut socket, &addr([10, 1, 1, 1])));
+            assert!(socket.iface == InterfaceId::DEFAULT);
+
+            // There's room again.
+            unwrap!(i.route_socket(&mut other, &addr([10, 1, 1, 1])));
+            assert!(other.iface == iface1);
+
+            i.remove_socket(socket);
+            i.remove_socket(other);
+        });
+    }
+
+    #[test]
+    fn udp_send_not_moved() {
+        let mut resources = StackResources::<2>::new();
+        let mut resources1 = InterfaceResources::<2>::new();
+        let (stack, _runner) = new(NoTraffic, config([192, 168, 1, 2], 24, None), &mut resources, 1);
+        let (iface1, _runner1) = stack.add_interface(NoTraffic, config([10, 0, 0, 2], 8, None), &mut resources1, 2);
+        let iface1 = iface1.id();
+
+        let mut rx_meta = [PacketMetadata::EMPTY; 1];
+        let mut rx_buffer = [0; 64];
+        let mut tx_meta = [PacketMetadata::EMPTY; 2];
+        let mut tx_buffer = [0; 64];
+        let mut socket = UdpSocket::new(stack, &mut rx_meta, &mut rx_buffer, &mut tx_meta, &mut tx_buffer);
+        unwrap!(socket.bind(1234));
+
+        // Sending through another interface would stop the socket from receiving on its own.
+        let remote = IpEndpoint::new(addr([10, 1, 1, 1]), 1234);
+        assert!(block_on(socket.send_to(b"hello", remote)) == Err(SendError::NoRoute));
+        assert!(socket.interface() == InterfaceId::DEFAULT);
+        unwrap!(block_on(socket.send_to(b"hello", (addr([192, 168, 1, 9]), 1234))));
+
+        // Unless it's bound to that interface.
+        unwrap!(socket.bind_interface(iface1));
+        unwrap!(block_on(socket.send_to(b"hello", remote)));
+        assert!(socket.interface() == iface1);
+    }
 }
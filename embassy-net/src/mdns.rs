@@ -0,0 +1,1411 @@
+//! mDNS and DNS-SD responder.
+//!
+//! This makes the device discoverable on the local network as `<hostname>.local`
+//! ([RFC 6762](https://www.rfc-editor.org/rfc/rfc6762)), and advertises its services with DNS-based
+//! service discovery ([RFC 6763](https://www.rfc-editor.org/rfc/rfc6763)).
+//!
+//! The responder answers A and AAAA queries for the hostname with the addresses of its interface,
+//! and PTR, SRV and TXT queries for the configured services. Before answering, it probes the
+//! network to check that no other device uses the same names, then announces its records. It keeps
+//! watching for conflicting records afterwards, and announces the new addresses when those of the
+//! interface change.
+//!
+//! Resolving `.local` names of other devices is done by the `mdns` feature, through
+//! [`Stack::dns_query`](crate::Stack::dns_query).
+//!
+//! ## Usage
+//!
+//! ```ignore
+//! use embassy_net::mdns::{Config, Responder, Service};
+//! use embassy_net::udp::PacketMetadata;
+//!
+//! static SERVICES: [Service; 1] = [Service {
+//!     instance: "Device 1234",
+//!     service_type: "_http._tcp",
+//!     port: 80,
+//!     txt: &["path=/"],
+//! }];
+//!
+//! let config = Config {
+//!     hostname: "device-1234",
+//!     services: &SERVICES,
+//! };
+//!
+//! let mut rx_meta = [PacketMetadata::EMPTY; 4];
+//! let mut rx_buffer = [0; 1024];
+//! let mut tx_meta = [PacketMetadata::EMPTY; 4];
+//! let mut tx_buffer = [0; 1024];
+//! let mut responder = Responder::new(stack, config, &mut rx_meta, &mut rx_buffer, &mut tx_meta, &mut tx_buffer).unwrap();
+//! let error = responder.run().await;
+//! ```
+
+use core::cmp::Ordering;
+use core::iter;
+use core::ops::Range;
+
+use embassy_time::{Duration, Instant, Timer, WithTimeout};
+use heapless::Vec;
+
+use crate::udp::{BindError, PacketMetadata, UdpSocket};
+#[cfg(feature = "proto-ipv4")]
+use crate::Ipv4Address;
+#[cfg(feature = "proto-ipv6")]
+use crate::Ipv6Address;
+use crate::{Interface, IpEndpoint, MulticastError, Stack};
+
+/// UDP port used by mDNS.
+pub const MDNS_PORT: u16 = 5353;
+
+#[cfg(feature = "proto-ipv4")]
+const MDNS_GROUP_V4: Ipv4Address = Ipv4Address::new(224, 0, 0, 251);
+#[cfg(feature = "proto-ipv6")]
+const MDNS_GROUP_V6: Ipv6Address = Ipv6Address::new(0xff02, 0, 0, 0, 0, 0, 0, 0xfb);
+
+/// Largest packet sent or received. Larger queries are ignored.
+const MAX_PACKET_LEN: usize = 512;
+/// Most records in a single response.
+const MAX_RECORDS: usize = 16;
+
+const PROBE_COUNT: usize = 3;
+const PROBE_INTERVAL: Duration = Duration::from_millis(250);
+const ANNOUNCE_COUNT: usize = 2;
+const ANNOUNCE_INTERVAL: Duration = Duration::from_secs(1);
+/// Delay before probing again after losing a simultaneous probe tiebreak.
+const TIEBREAK_DELAY: Duration = Duration::from_secs(1);
+/// How often the addresses of the interface are checked for changes.
+const ADDRESS_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// TTL of records containing a hostname, as recommended by RFC 6762.
+const HOST_TTL: u32 = 120;
+/// TTL of other records, as recommended by RFC 6762.
+const OTHER_TTL: u32 = 4500;
+/// Maximum TTL in responses to legacy unicast queries.
+const LEGACY_TTL: u32 = 10;
+
+#[cfg(feature = "proto-ipv4")]
+const TYPE_A: u16 = 1;
+const TYPE_PTR: u16 = 12;
+const TYPE_TXT: u16 = 16;
+#[cfg(feature = "proto-ipv6")]
+const TYPE_AAAA: u16 = 28;
+const TYPE_SRV: u16 = 33;
+const TYPE_ANY: u16 = 255;
+const CLASS_IN: u16 = 1;
+const CLASS_ANY: u16 = 255;
+/// In questions, the unicast-response bit of the class field.
+const CLASS_UNICAST_RESPONSE: u16 = 0x8000;
+/// In records, the cache-flush bit of the class field.
+const CLASS_CACHE_FLUSH: u16 = 0x8000;
+const FLAG_RESPONSE: u16 = 0x8000;
+const FLAG_AUTHORITATIVE: u16 = 0x0400;
+const OPCODE_MASK: u16 = 0x7800;
+
+/// Error returned by [`Responder`].
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// The UDP socket could not be bound to port 5353.
+    Bind(BindError),
+    /// The mDNS multicast group could not be joined.
+    Multicast(MulticastError),
+    /// Another device answered a probe for the hostname or a service instance name. Choose
+    /// another name and try again.
+    Conflict,
+}
+
+/// A service advertised with DNS-SD.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Service<'a> {
+    /// Instance name, displayed to users, such as `Living room printer`. It can contain any
+    /// character, including spaces and dots.
+    pub instance: &'a str,
+    /// Service type and protocol, such as `_http._tcp`.
+    pub service_type: &'a str,
+    /// Port the service is listening on.
+    pub port: u16,
+    /// TXT record entries, such as `path=/`. Each entry must be at most 255 bytes.
+    pub txt: &'a [&'a str],
+}
+
+/// Responder configuration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Config<'a> {
+    /// Hostname, without the `.local` suffix.
+    pub hostname: &'a str,
+    /// Services to advertise.
+    pub services: &'a [Service<'a>],
+}
+
+/// mDNS and DNS-SD responder.
+pub struct Responder<'d> {
+    iface: Interface<'d>,
+    socket: UdpSocket<'d>,
+    config: Config<'d>,
+}
+
+impl<'d> Responder<'d> {
+    /// Create a new responder on the default interface of the stack, with the buffers for its UDP
+    /// socket.
+    ///
+    /// This binds the socket to port 5353 and joins the mDNS multicast groups. Responses can be
+    /// up to 512 bytes.
+    pub fn new(
+        stack: Stack<'d>,
+        config: Config<'d>,
+        rx_meta: &'d mut [PacketMetadata],
+        rx_buffer: &'d mut [u8],
+        tx_meta: &'d mut [PacketMetadata],
+        tx_buffer: &'d mut [u8],
+    ) -> Result<Self, Error> {
+        let iface = stack.default_interface();
+        Self::new_on_interface(iface, config, rx_meta, rx_buffer, tx_meta, tx_buffer)
+    }
+
+    /// Create a new responder on a network interface of the stack, with the buffers for its UDP
+    /// socket.
+    ///
+    /// The socket is bound to the interface, which must have room for it. Run one responder per
+    /// interface to be discoverable on several networks.
+    pub fn new_on_interface(
+        iface: Interface<'d>,
+        config: Config<'d>,
+        rx_meta: &'d mut [PacketMetadata],
+        rx_buffer: &'d mut [u8],
+        tx_meta: &'d mut [PacketMetadata],
+        tx_buffer: &'d mut [u8],
+    ) -> Result<Self, Error> {
+        let mut socket = UdpSocket::new(iface.stack, rx_meta, rx_buffer, tx_meta, tx_buffer);
+        // Multicast packets are sent on the interface, whatever the routes.
+        socket
+            .bind_interface(iface.id())
+            .map_err(|_| Error::Bind(BindError::NoRoute))?;
+        socket.bind(MDNS_PORT).map_err(Error::Bind)?;
+        // Packets with a lower hop limit must be ignored by receivers.
+        socket.set_hop_limit(Some(255));
+
+        #[cfg(feature = "proto-ipv4")]
+        iface.join_multicast_group(MDNS_GROUP_V4).map_err(Error::Multicast)?;
+        #[cfg(feature = "proto-ipv6")]
+        iface.join_multicast_group(MDNS_GROUP_V6).map_err(Error::Multicast)?;
+
+        Ok(Self { iface, socket, config })
+    }
+
+    /// Run the responder.
+    ///
+    /// This waits for the interface to be configured, probes for conflicts and announces the records,
+    /// then answers queries forever. When a conflicting record is seen after probing, the names are
+    /// probed again. It only returns if a conflict is detected while probing.
+    pub async fn run(&mut self) -> Error {
+        self.iface.wait_config_up().await;
+
+        loop {
+            if let Err(e) = self.probe().await {
+                return e;
+            }
+            self.announce().await;
+            self.respond().await;
+        }
+    }
+
+    /// Send probes for our unique names, and wait for conflicting answers.
+    async fn probe(&mut self) -> Result<(), Error> {
+        let mut tx = [0u8; MAX_PACKET_LEN];
+        let mut rx = [0u8; MAX_PACKET_LEN];
+
+        'probe: loop {
+            for _ in 0..PROBE_COUNT {
+                let len = self.write_probe(&mut tx);
+                self.send_multicast(&tx[..len]).await;
+
+                let deadline = Instant::now() + PROBE_INTERVAL;
+                while let Ok(received) = self.socket.recv_from(&mut rx).with_deadline(deadline).await {
+                    let Ok((n, meta)) = received else {
+                        continue;
+                    };
+                    if self.is_conflict(&rx[..n], &tx[..len]) {
+                        warn!("mDNS: name conflict with {}", meta.endpoint);
+                        return Err(Error::Conflict);
+                    }
+                    if self.tiebreak(&rx[..n], &tx[..len]) == Ordering::Less {
+                        // Another device is probing for the same names at the same time, and wins.
+                        // If it's gone when we probe again, we get the names.
+                        debug!("mDNS: lost probe tiebreak against {}", meta.endpoint);
+                        Timer::after(TIEBREAK_DELAY).await;
+                        continue 'probe;
+                    }
+                }
+            }
+            return Ok(());
+        }
+    }
+
+    async fn announce(&mut self) {
+        let mut tx = [0u8; MAX_PACKET_LEN];
+        let mut records = self.host_records();
+        for service in self.config.services {
+            push_unique(&mut records, Record::ServiceType(service));
+            push_unique(&mut records, Record::Ptr(service));
+            push_unique(&mut records, Record::Srv(service));
+            push_unique(&mut records, Record::Txt(service));
+        }
+
+        for i in 0..ANNOUNCE_COUNT {
+            if i != 0 {
+                Timer::after(ANNOUNCE_INTERVAL).await;
+            }
+            let w = Writer {
+                buf: &mut tx,
+                len: HEADER_LEN,
+            };
+            let len = self.write_response(w, 0, 0, &records, &[], false);
+            self.send_multicast(&tx[..len]).await;
+        }
+    }
+
+    /// Answer queries, until a record conflicting with ours is received.
+    async fn respond(&mut self) {
+        let mut rx = [0u8; MAX_PACKET_LEN];
+        let mut tx = [0u8; MAX_PACKET_LEN];
+
+        let mut addresses = self.host_records();
+        let mut next_check = Instant::now() + ADDRESS_CHECK_INTERVAL;
+        loop {
+            let Ok(received) = self.socket.recv_from(&mut rx).with_deadline(next_check).await else {
+                // Records whose data changes must be announced again (RFC 6762 section 8.4).
+                let current = self.host_records();
+                if current != addresses {
+                    debug!("mDNS: addresses changed, announcing");
+                    self.announce().await;
+                    addresses = current;
+                }
+                next_check = Instant::now() + ADDRESS_CHECK_INTERVAL;
+                continue;
+            };
+            let Ok((n, meta)) = received else {
+                continue;
+            };
+
+            // Conflicts can happen at any time, such as when a device with the same name joins
+            // the network (RFC 6762 section 9).
+            let probe_len = self.write_probe(&mut tx);
+            if self.is_conflict(&rx[..n], &tx[..probe_len]) {
+                warn!("mDNS: name conflict with {}, probing again", meta.endpoint);
+                return;
+            }
+
+            let Some((len, unicast)) = self.answer(&rx[..n], meta.endpoint, &mut tx) else {
+                continue;
+            };
+
+            if unicast {
+                if let Err(e) = self.socket.send_to(&tx[..len], meta.endpoint).await {
+                    warn!("mDNS: failed to send response: {:?}", e);
+                }
+            } else {
+                self.send_multicast(&tx[..len]).await;
+            }
+        }
+    }
+
+    async fn send_multicast(&self, packet: &[u8]) {
+        #[cfg(feature = "proto-ipv4")]
+        if self.iface.config_v4().is_some() {
+            let endpoint = IpEndpoint::new(MDNS_GROUP_V4.into(), MDNS_PORT);
+            if let Err(e) = self.socket.send_to(packet, endpoint).await {
+                warn!("mDNS: failed to send packet: {:?}", e);
+            }
+        }
+        #[cfg(feature = "proto-ipv6")]
+        if self.iface.config_v6().is_some() {
+            let endpoint = IpEndpoint::new(MDNS_GROUP_V6.into(), MDNS_PORT);
+            if let Err(e) = self.socket.send_to(packet, endpoint).await {
+                warn!("mDNS: failed to send packet: {:?}", e);
+            }
+        }
+    }
+
+    fn host_name(&self) -> Name<'d> {
+        Name {
+            label: None,
+            dotted: self.config.hostname,
+        }
+    }
+
+    /// Names that must be unique on the network.
+    fn unique_names(&self) -> impl Iterator<Item = Name<'d>> + '_ {
+        iter::once(self.host_name()).chain(self.config.services.iter().map(Name::instance))
+    }
+
+    /// Address records of the hostname.
+    fn host_records(&self) -> Vec<Record<'d>, MAX_RECORDS> {
+        let mut records = Vec::new();
+        self.push_host_records(&mut records);
+        records
+    }
+
+    fn push_host_records(&self, records: &mut Vec<Record<'d>, MAX_RECORDS>) {
+        #[cfg(feature = "proto-ipv4")]
+        if let Some(config) = self.iface.config_v4() {
+            push_unique(records, Record::A(config.address.address()));
+        }
+        #[cfg(feature = "proto-ipv6")]
+        if let Some(config) = self.iface.config_v6() {
+            push_unique(records, Record::Aaaa(config.address.address()));
+        }
+    }
+
+    /// Write a probe query, with our records in the authority section. Returns its length.
+    fn write_probe(&self, buf: &mut [u8]) -> usize {
+        let mut w = Writer { buf, len: HEADER_LEN };
+
+        let mut questions = 0;
+        for name in self.unique_names() {
+            if w.question(name, TYPE_ANY, CLASS_IN | CLASS_UNICAST_RESPONSE).is_some() {
+                questions += 1;
+            }
+        }
+
+        let mut records = self.host_records();
+        for service in self.config.services {
+            push_unique(&mut records, Record::Srv(service));
+            push_unique(&mut records, Record::Txt(service));
+        }
+        let mut authority = 0;
+        for record in &records {
+            if w.record(record, self.host_name(), record.ttl(), false).is_some() {
+                authority += 1;
+            }
+        }
+
+        w.header(0, 0, questions, 0, authority, 0);
+        w.len
+    }
+
+    /// Whether a packet is a response with a record for one of our unique names, which is not
+    /// identical to one of our records. `probe` is our probe, written by `write_probe`.
+    fn is_conflict(&self, packet: &[u8], probe: &[u8]) -> bool {
+        let Some(header) = Header::parse(packet) else {
+            return false;
+        };
+        if header.flags & FLAG_RESPONSE == 0 {
+            return false;
+        }
+        let Some(pos) = skip_questions(packet, header.questions) else {
+            return false;
+        };
+
+        records(packet, pos, header.records())
+            .filter(|record| self.unique_names().any(|name| record.name_is(&name)))
+            .any(|record| !authority_records(probe).any(|ours| ours.is_identical(&record)))
+    }
+
+    /// Compare the records of a probe from another device with ours, for the first name we both
+    /// probe for (RFC 6762 section 8.2). `Less` means that the other device wins, and `Equal` that
+    /// it isn't probing for our names.
+    fn tiebreak(&self, packet: &[u8], probe: &[u8]) -> Ordering {
+        let Some(header) = Header::parse(packet) else {
+            return Ordering::Equal;
+        };
+        if header.flags & (FLAG_RESPONSE | OPCODE_MASK) != 0 {
+            return Ordering::Equal;
+        }
+
+        for name in self.unique_names() {
+            let sorted = |packet| {
+                let mut records: Vec<RawRecord<'_>, MAX_RECORDS> = authority_records(packet)
+                    .filter(|record| record.name_is(&name))
+                    .take(MAX_RECORDS)
+                    .collect();
+                records.sort_unstable_by(RawRecord::cmp_data);
+                records
+            };
+            let theirs = sorted(packet);
+            if theirs.is_empty() {
+                continue;
+            }
+            let ours = sorted(probe);
+
+            let ordering = ours
+                .iter()
+                .zip(&theirs)
+                .map(|(a, b)| a.cmp_data(b))
+                .find(|o| o.is_ne())
+                .unwrap_or(ours.len().cmp(&theirs.len()));
+            if ordering.is_ne() {
+                return ordering;
+            }
+        }
+        Ordering::Equal
+    }
+
+    /// Write the response to a query, if any. Returns its length, and whether it must be sent by
+    /// unicast to the sender of the query.
+    fn answer(&self, packet: &[u8], source: IpEndpoint, buf: &mut [u8]) -> Option<(usize, bool)> {
+        let header = Header::parse(packet)?;
+        if header.flags & (FLAG_RESPONSE | OPCODE_MASK) != 0 {
+            return None;
+        }
+        // Queries not sent from port 5353 come from simple resolvers that expect a regular DNS
+        // response.
+        let legacy = source.port != MDNS_PORT;
+
+        let mut answers: Vec<Record<'d>, MAX_RECORDS> = Vec::new();
+        let mut unicast = true;
+        let mut pos = HEADER_LEN;
+        for _ in 0..header.questions {
+            let end = skip_name(packet, pos)?;
+            let qtype = read_u16(packet, end)?;
+            let qclass = read_u16(packet, end + 2)?;
+            unicast &= qclass & CLASS_UNICAST_RESPONSE != 0;
+            if matches!(qclass & !CLASS_UNICAST_RESPONSE, CLASS_IN | CLASS_ANY) {
+                self.push_answers(packet, pos, qtype, &mut answers);
+            }
+            pos = end + 4;
+        }
+
+        // Known-answer suppression (RFC 6762 section 7.1): leave out the answers the querier lists
+        // with at least half of their TTL left. Each answer is written to `buf` to compare it.
+        answers.retain(|answer| {
+            let mut w = Writer { buf: &mut *buf, len: 0 };
+            if w.record(answer, self.host_name(), answer.ttl(), false).is_none() {
+                return true;
+            }
+            let len = w.len;
+            let Some((ours, _)) = RawRecord::parse(&buf[..len], 0) else {
+                return true;
+            };
+            !records(packet, pos, header.answers as u32)
+                .any(|known| known.ttl >= answer.ttl() / 2 && known.is_identical(&ours))
+        });
+        if answers.is_empty() {
+            return None;
+        }
+
+        // Records the querier will likely need next.
+        let mut additional: Vec<Record<'d>, MAX_RECORDS> = Vec::new();
+        for answer in &answers {
+            match answer {
+                Record::Ptr(service) => {
+                    push_unique(&mut additional, Record::Srv(service));
+                    push_unique(&mut additional, Record::Txt(service));
+                    self.push_host_records(&mut additional);
+                }
+                Record::Srv(_) => self.push_host_records(&mut additional),
+                _ => {}
+            }
+        }
+        additional.retain(|r| !answers.contains(r));
+
+        let len = if legacy {
+            // Echo the ID and questions. The questions start at the same offset in both packets,
+            // so compressed names in them stay valid.
+            let questions = packet.get(HEADER_LEN..pos)?;
+            buf.get_mut(HEADER_LEN..pos)?.copy_from_slice(questions);
+            let w = Writer { buf, len: pos };
+            self.write_response(w, header.id, header.questions, &answers, &[], true)
+        } else {
+            let w = Writer { buf, len: HEADER_LEN };
+            self.write_response(w, 0, 0, &answers, &additional, false)
+        };
+        Some((len, legacy || unicast))
+    }
+
+    /// Add the records matching a question to `answers`.
+    fn push_answers(&self, packet: &[u8], name_pos: usize, qtype: u16, answers: &mut Vec<Record<'d>, MAX_RECORDS>) {
+        let any = qtype == TYPE_ANY;
+
+        if name_eq(packet, name_pos, &self.host_name()) {
+            #[cfg(feature = "proto-ipv4")]
+            if any || qtype == TYPE_A {
+                if let Some(config) = self.iface.config_v4() {
+                    push_unique(answers, Record::A(config.address.address()));
+                }
+            }
+            #[cfg(feature = "proto-ipv6")]
+            if any || qtype == TYPE_AAAA {
+                if let Some(config) = self.iface.config_v6() {
+                    push_unique(answers, Record::Aaaa(config.address.address()));
+                }
+            }
+        }
+
+        for service in self.config.services {
+            if any || qtype == TYPE_PTR {
+                if name_eq(packet, name_pos, &Name::SERVICES) {
+                    push_unique(answers, Record::ServiceType(service));
+                }
+                if name_eq(packet, name_pos, &Name::service_type(service)) {
+                    push_unique(answers, Record::Ptr(service));
+                }
+            }
+            if (any || qtype == TYPE_SRV || qtype == TYPE_TXT) && name_eq(packet, name_pos, &Name::instance(service)) {
+                if qtype != TYPE_TXT {
+                    push_unique(answers, Record::Srv(service));
+                }
+                if qtype != TYPE_SRV {
+                    push_unique(answers, Record::Txt(service));
+                }
+            }
+        }
+    }
+
+    /// Finish a response, after `questions` questions already written to `w`. Records that don't
+    /// fit are left out. Returns the length of the response.
+    fn write_response(
+        &self,
+        mut w: Writer<'_>,
+        id: u16,
+        questions: u16,
+        answers: &[Record<'d>],
+        additional: &[Record<'d>],
+        legacy: bool,
+    ) -> usize {
+        let mut counts = [0u16; 2];
+        'sections: for (records, count) in [answers, additional].into_iter().zip(&mut counts) {
+            for record in records {
+                let ttl = if legacy {
+                    record.ttl().min(LEGACY_TTL)
+                } else {
+                    record.ttl()
+                };
+                // Caches must replace their records for our unique names, rather than add to them.
+                let cache_flush = !legacy && record.is_unique();
+                if w.record(record, self.host_name(), ttl, cache_flush).is_none() {
+                    break 'sections;
+                }
+                *count += 1;
+            }
+        }
+
+        w.header(
+            id,
+            FLAG_RESPONSE | FLAG_AUTHORITATIVE,
+            questions,
+            counts[0],
+            0,
+            counts[1],
+        );
+        w.len
+    }
+}
+
+impl Drop for Responder<'_> {
+    fn drop(&mut self) {
+        #[cfg(feature = "proto-ipv4")]
+        let _ = self.iface.leave_multicast_group(MDNS_GROUP_V4);
+        #[cfg(feature = "proto-ipv6")]
+        let _ = self.iface.leave_multicast_group(MDNS_GROUP_V6);
+    }
+}
+
+fn push_unique<'a>(records: &mut Vec<Record<'a>, MAX_RECORDS>, record: Record<'a>) {
+    if !records.contains(&record) {
+        // Records that don't fit are left out of the response.
+        let _ = records.push(record);
+    }
+}
+
+/// A domain name in the `.local` domain.
+#[derive(Clone, Copy)]
+struct Name<'a> {
+    /// Leading label, which may contain dots.
+    label: Option<&'a str>,
+    /// Labels separated by dots, before `.local`.
+    dotted: &'a str,
+}
+
+impl<'a> Name<'a> {
+    /// Name queried to list the service types of the network.
+    const SERVICES: Name<'static> = Name {
+        label: None,
+        dotted: "_services._dns-sd._udp",
+    };
+
+    fn service_type(service: &Service<'a>) -> Self {
+        Self {
+            label: None,
+            dotted: service.service_type,
+        }
+    }
+
+    fn instance(service: &Service<'a>) -> Self {
+        Self {
+            label: Some(service.instance),
+            dotted: service.service_type,
+        }
+    }
+
+    fn labels(&self) -> impl Iterator<Item = &'a str> {
+        self.label
+            .into_iter()
+            .chain(self.dotted.split('.'))
+            .chain(iter::once("local"))
+    }
+}
+
+/// A resource record we can answer with.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Record<'a> {
+    #[cfg(feature = "proto-ipv4")]
+    A(Ipv4Address),
+    #[cfg(feature = "proto-ipv6")]
+    Aaaa(Ipv6Address),
+    /// PTR from `_services._dns-sd._udp.local` to the service type.
+    ServiceType(&'a Service<'a>),
+    /// PTR from the service type to the service instance.
+    Ptr(&'a Service<'a>),
+    Srv(&'a Service<'a>),
+    Txt(&'a Service<'a>),
+}
+
+impl Record<'_> {
+    fn ttl(&self) -> u32 {
+        match self {
+            Record::ServiceType(_) | Record::Ptr(_) | Record::Txt(_) => OTHER_TTL,
+            _ => HOST_TTL,
+        }
+    }
+
+    /// Whether the record's name must be unique. PTR records are shared with other devices.
+    fn is_unique(&self) -> bool {
+        !matches!(self, Record::ServiceType(_) | Record::Ptr(_))
+    }
+}
+
+const HEADER_LEN: usize = 12;
+
+struct Header {
+    id: u16,
+    flags: u16,
+    questions: u16,
+    answers: u16,
+    authority: u16,
+    additional: u16,
+}
+
+impl Header {
+    fn parse(packet: &[u8]) -> Option<Self> {
+        Some(Self {
+            id: read_u16(packet, 0)?,
+            flags: read_u16(packet, 2)?,
+            questions: read_u16(packet, 4)?,
+            answers: read_u16(packet, 6)?,
+            authority: read_u16(packet, 8)?,
+            additional: read_u16(packet, 10)?,
+        })
+    }
+
+    fn records(&self) -> u32 {
+        self.answers as u32 + self.authority as u32 + self.additional as u32
+    }
+}
+
+/// A resource record in a packet.
+struct RawRecord<'p> {
+    packet: &'p [u8],
+    /// Position of the name.
+    name: usize,
+    rtype: u16,
+    /// Class, without the cache-flush bit.
+    class: u16,
+    ttl: u32,
+    rdata: Range<usize>,
+}
+
+impl<'p> RawRecord<'p> {
+    /// Parse the record at `pos`. Returns it and the position after it.
+    fn parse(packet: &'p [u8], pos: usize) -> Option<(Self, usize)> {
+        let end = skip_name(packet, pos)?;
+        let rdlength = read_u16(packet, end + 8)?;
+        let rdata = end + 10..end + 10 + rdlength as usize;
+        if rdata.end > packet.len() {
+            return None;
+        }
+        let record = Self {
+            packet,
+            name: pos,
+            rtype: read_u16(packet, end)?,
+            class: read_u16(packet, end + 2)? & !CLASS_CACHE_FLUSH,
+            ttl: read_u32(packet, end + 4)?,
+            rdata: rdata.clone(),
+        };
+        Some((record, rdata.end))
+    }
+
+    fn name_is(&self, name: &Name<'_>) -> bool {
+        name_eq(self.packet, self.name, name)
+    }
+
+    /// The rdata, with compressed names expanded.
+    fn rdata(&self) -> impl Iterator<Item = u8> + 'p {
+        let start = self.rdata.start;
+        // Names in the rdata of the records we answer with, after the priority, weight and port of
+        // SRV records.
+        let (fixed, name) = match self.rtype {
+            TYPE_SRV => (6.min(self.rdata.len()), true),
+            TYPE_PTR => (0, true),
+            _ => (self.rdata.len(), false),
+        };
+        let packet = self.packet;
+        packet[start..start + fixed]
+            .iter()
+            .copied()
+            .chain(name.then(|| name_bytes(packet, start + fixed)).into_iter().flatten())
+    }
+
+    /// Order of records with the same name, used for probe tiebreaking: by class, type, then
+    /// rdata.
+    fn cmp_data(&self, other: &RawRecord<'_>) -> Ordering {
+        self.class
+            .cmp(&other.class)
+            .then(self.rtype.cmp(&other.rtype))
+            .then_with(|| self.rdata().cmp(other.rdata()))
+    }
+
+    /// Whether both records have the same name, type, class and data. The TTL isn't compared.
+    fn is_identical(&self, other: &RawRecord<'_>) -> bool {
+        let mut a = labels(self.packet, self.name);
+        let mut b = labels(other.packet, other.name);
+        loop {
+            match (a.next(), b.next()) {
+                (None, None) => break,
+                (Some(x), Some(y)) if x.eq_ignore_ascii_case(y) => {}
+                _ => return false,
+            }
+        }
+        self.cmp_data(other).is_eq()
+    }
+}
+
+/// Iterate over `count` records starting at `pos`, stopping at the first malformed one.
+fn records(packet: &[u8], mut pos: usize, mut count: u32) -> impl Iterator<Item = RawRecord<'_>> {
+    iter::from_fn(move || {
+        count = count.checked_sub(1)?;
+        let (record, next) = RawRecord::parse(packet, pos)?;
+        pos = next;
+        Some(record)
+    })
+}
+
+/// Records of the authority section of a packet.
+fn authority_records(packet: &[u8]) -> impl Iterator<Item = RawRecord<'_>> {
+    let section = Header::parse(packet).and_then(|header| {
+        let mut pos = skip_questions(packet, header.questions)?;
+        for _ in 0..header.answers {
+            pos = RawRecord::parse(packet, pos)?.1;
+        }
+        Some((pos, header.authority))
+    });
+    section
+        .into_iter()
+        .flat_map(move |(pos, count)| records(packet, pos, count as u32))
+}
+
+fn read_u16(packet: &[u8], pos: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(packet.get(pos..pos + 2)?.try_into().unwrap()))
+}
+
+fn read_u32(packet: &[u8], pos: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(packet.get(pos..pos + 4)?.try_into().unwrap()))
+}
+
+/// Return the position after the name at `pos`.
+fn skip_name(packet: &[u8], mut pos: usize) -> Option<usize> {
+    loop {
+        let len = *packet.get(pos)?;
+        match len {
+            0 => return Some(pos + 1),
+            // Compression pointer, which ends the name.
+            l if l & 0xc0 == 0xc0 => return packet.get(pos + 1).map(|_| pos + 2),
+            l if l & 0xc0 == 0 => pos += 1 + l as usize,
+            _ => return None,
+        }
+    }
+}
+
+fn skip_questions(packet: &[u8], count: u16) -> Option<usize> {
+    let mut pos = HEADER_LEN;
+    for _ in 0..count {
+        pos = skip_name(packet, pos)? + 4;
+    }
+    Some(pos)
+}
+
+/// Labels of the name at `pos`, following compression pointers. Malformed names are cut short.
+fn labels(packet: &[u8], mut pos: usize) -> impl Iterator<Item = &[u8]> {
+    // Bound the number of compression pointers followed, in case they form a loop.
+    let mut jumps = 0;
+    iter::from_fn(move || loop {
+        let len = *packet.get(pos)?;
+        match len {
+            0 => return None,
+            l if l & 0xc0 == 0xc0 => {
+                let low = *packet.get(pos + 1)?;
+                jumps += 1;
+                if jumps > 16 {
+                    return None;
+                }
+                pos = ((l & 0x3f) as usize) << 8 | low as usize;
+            }
+            l if l & 0xc0 == 0 => {
+                let label = packet.get(pos + 1..pos + 1 + l as usize)?;
+                pos += 1 + l as usize;
+                return Some(label);
+            }
+            _ => return None,
+        }
+    })
+}
+
+/// Wire format of the name at `pos`, with compression pointers followed.
+fn name_bytes(packet: &[u8], pos: usize) -> impl Iterator<Item = u8> + '_ {
+    labels(packet, pos)
+        .flat_map(|label| iter::once(label.len() as u8).chain(label.iter().copied()))
+        .chain(iter::once(0))
+}
+
+/// Whether the name at `pos`, which may be compressed, is `name`. Comparison is case-insensitive.
+fn name_eq(packet: &[u8], mut pos: usize, name: &Name<'_>) -> bool {
+    let mut labels = name.labels();
+    // Bound the number of compression pointers followed, in case they form a loop.
+    let mut jumps = 0;
+    loop {
+        let Some(&len) = packet.get(pos) else {
+            return false;
+        };
+        match len {
+            0 => return labels.next().is_none(),
+            l if l & 0xc0 == 0xc0 => {
+                let Some(&low) = packet.get(pos + 1) else {
+                    return false;
+                };
+                jumps += 1;
+                if jumps > 16 {
+                    return false;
+                }
+                pos = ((l & 0x3f) as usize) << 8 | low as usize;
+            }
+            l if l & 0xc0 == 0 => {
+                let Some(label) = packet.get(pos + 1..pos + 1 + l as usize) else {
+                    return false;
+                };
+                match labels.next() {
+                    Some(ours) if ours.as_bytes().eq_ignore_ascii_case(label) => {}
+                    _ => return false,
+                }
+                pos += 1 + l as usize;
+            }
+            _ => return false,
+        }
+    }
+}
+
+/// Writes a DNS message. Methods return `None` and leave the message unchanged if it is full.
+struct Writer<'b> {
+    buf: &'b mut [u8],
+    len: usize,
+}
+
+impl Writer<'_> {
+    fn header(&mut self, id: u16, flags: u16, questions: u16, answers: u16, authority: u16, additional: u16) {
+        for (i, value) in [id, flags, questions, answers, authority, additional]
+            .into_iter()
+            .enumerate()
+        {
+            self.buf[i * 2..i * 2 + 2].copy_from_slice(&value.to_be_bytes());
+        }
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) -> Option<()> {
+        self.buf
+            .get_mut(self.len..self.len + bytes.len())?
+            .copy_from_slice(bytes);
+        self.len += bytes.len();
+        Some(())
+    }
+
+    fn u16(&mut self, value: u16) -> Option<()> {
+        self.bytes(&value.to_be_bytes())
+    }
+
+    fn u32(&mut self, value: u32) -> Option<()> {
+        self.bytes(&value.to_be_bytes())
+    }
+
+    /// Write a string prefixed with its length, as used for labels and TXT entries.
+    fn string(&mut self, s: &str, max_len: usize) -> Option<()> {
+        if s.len() > max_len {
+            return None;
+        }
+        self.bytes(&[s.len() as u8])?;
+        self.bytes(s.as_bytes())
+    }
+
+    fn name(&mut self, name: Name<'_>) -> Option<()> {
+        for label in name.labels() {
+            if label.is_empty() {
+                return None;
+            }
+            self.string(label, 63)?;
+        }
+        self.bytes(&[0])
+    }
+
+    fn question(&mut self, name: Name<'_>, qtype: u16, qclass: u16) -> Option<()> {
+        self.atomic(|w| {
+            w.name(name)?;
+            w.u16(qtype)?;
+            w.u16(qclass)
+        })
+    }
+
+    fn record(&mut self, record: &Record<'_>, host: Name<'_>, ttl: u32, cache_flush: bool) -> Option<()> {
+        let (name, rtype) = match record {
+            #[cfg(feature = "proto-ipv4")]
+            Record::A(_) => (host, TYPE_A),
+            #[cfg(feature = "proto-ipv6")]
+            Record::Aaaa(_) => (host, TYPE_AAAA),
+            Record::ServiceType(_) => (Name::SERVICES, TYPE_PTR),
+            Record::Ptr(service) => (Name::service_type(service), TYPE_PTR),
+            Record::Srv(service) => (Name::instance(service), TYPE_SRV),
+            Record::Txt(service) => (Name::instance(service), TYPE_TXT),
+        };
+        let class = if cache_flush {
+            CLASS_IN | CLASS_CACHE_FLUSH
+        } else {
+            CLASS_IN
+        };
+
+        self.atomic(|w| {
+            w.name(name)?;
+            w.u16(rtype)?;
+            w.u16(class)?;
+            w.u32(ttl)?;
+            let len_pos = w.len;
+            w.u16(0)?;
+
+            match record {
+                #[cfg(feature = "proto-ipv4")]
+                Record::A(addr) => w.bytes(&addr.octets())?,
+                #[cfg(feature = "proto-ipv6")]
+                Record::Aaaa(addr) => w.bytes(&addr.octets())?,
+                Record::ServiceType(service) => w.name(Name::service_type(service))?,
+                Record::Ptr(service) => w.name(Name::instance(service))?,
+                Record::Srv(service) => {
+                    // Priority and weight.
+                    w.u16(0)?;
+                    w.u16(0)?;
+                    w.u16(service.port)?;
+                    w.name(host)?;
+                }
+                Record::Txt(service) => {
+                    // A TXT record can't be empty, it has a single empty string instead.
+                    if service.txt.is_empty() {
+                        w.bytes(&[0])?;
+                    }
+                    for entry in service.txt {
+                        w.string(entry, 255)?;
+                    }
+                }
+            }
+
+            let rdlength = (w.len - len_pos - 2) as u16;
+            w.buf[len_pos..len_pos + 2].copy_from_slice(&rdlength.to_be_bytes());
+            Some(())
+        })
+    }
+
+    /// Run `f`, undoing its writes if it fails.
+    fn atomic(&mut self, f: impl FnOnce(&mut Self) -> Option<()>) -> Option<()> {
+        let start = self.len;
+        let res = f(self);
+        if res.is_none() {
+            self.len = start;
+        }
+        res
+    }
+}
+
+#[cfg(all(test, feature = "proto-ipv4", feature = "medium-ip"))]
+mod tests {
+    extern crate std;
+
+    use std::boxed::Box;
+    use std::string::String;
+    use std::vec;
+    use std::vec::Vec;
+
+    use super::*;
+    use crate::tests::{config, NoTraffic};
+    use crate::StackResources;
+
+    static SERVICES: [Service; 1] = [Service {
+        instance: "Living room",
+        service_type: "_http._tcp",
+        port: 8080,
+        txt: &["path=/"],
+    }];
+
+    static OTHER_SERVICE: Service = Service {
+        instance: "Kitchen",
+        service_type: "_http._tcp",
+        port: 80,
+        txt: &[],
+    };
+
+    const HOST: Name<'static> = Name {
+        label: None,
+        dotted: "device",
+    };
+
+    fn name(dotted: &str) -> Name<'_> {
+        Name { label: None, dotted }
+    }
+
+    /// A header, then `device.local` and `_http._tcp.local`, whose `local` label is a pointer to
+    /// the first name. Returns the packet and the positions of the names.
+    fn compressed_names() -> (Vec<u8>, usize, usize) {
+        let mut packet = vec![0; HEADER_LEN];
+        let host = packet.len();
+        packet.extend_from_slice(b"\x06device\x05local\x00");
+        let service = packet.len();
+        packet.extend_from_slice(b"\x05_http\x04_tcp\xc0");
+        packet.push((host + 7) as u8);
+        (packet, host, service)
+    }
+
+    fn with_names(names: &[u8]) -> Vec<u8> {
+        let mut packet = vec![0; HEADER_LEN];
+        packet.extend_from_slice(names);
+        packet
+    }
+
+    #[test]
+    fn compressed_name() {
+        let (packet, host, service) = compressed_names();
+
+        assert!(name_eq(&packet, host, &HOST));
+        assert!(name_eq(&packet, host, &name("DEVICE")));
+        assert!(!name_eq(&packet, host, &name("device2")));
+        assert!(!name_eq(&packet, host, &name("device.local")));
+        assert!(name_eq(&packet, service, &name("_http._tcp")));
+        assert!(!name_eq(&packet, service, &Name::instance(&SERVICES[0])));
+
+        assert_eq!(skip_name(&packet, host), Some(service));
+        assert_eq!(skip_name(&packet, service), Some(packet.len()));
+        assert_eq!(
+            labels(&packet, service).collect::<Vec<_>>(),
+            [&b"_http"[..], &b"_tcp"[..], &b"local"[..]]
+        );
+        assert_eq!(
+            name_bytes(&packet, service).collect::<Vec<_>>(),
+            b"\x05_http\x04_tcp\x05local\x00"
+        );
+    }
+
+    #[test]
+    fn instance_label_with_dots() {
+        let service = Service {
+            instance: "Printer 2.0",
+            ..SERVICES[0]
+        };
+        let mut buf = [0; 64];
+        let mut w = Writer { buf: &mut buf, len: 0 };
+        unwrap!(w.name(Name::instance(&service)));
+        let len = w.len;
+
+        assert_eq!(&buf[..len], b"\x0bPrinter 2.0\x05_http\x04_tcp\x05local\x00");
+        assert!(name_eq(&buf, 0, &Name::instance(&service)));
+        assert!(!name_eq(&buf, 0, &name("Printer 2.0._http._tcp")));
+    }
+
+    #[test]
+    fn truncated_name() {
+        // Label longer than the rest of the packet.
+        let packet = with_names(b"\x06device\x05loc");
+        assert_eq!(skip_name(&packet, HEADER_LEN), None);
+        assert!(!name_eq(&packet, HEADER_LEN, &HOST));
+        assert_eq!(labels(&packet, HEADER_LEN).count(), 1);
+
+        // Compression pointer cut in half.
+        let packet = with_names(b"\x06device\xc0");
+        assert_eq!(skip_name(&packet, HEADER_LEN), None);
+        assert!(!name_eq(&packet, HEADER_LEN, &HOST));
+        assert_eq!(labels(&packet, HEADER_LEN).count(), 1);
+
+        // Compression pointer past the end of the packet.
+        let packet = with_names(b"\x06device\xc0\xff");
+        assert_eq!(skip_name(&packet, HEADER_LEN), Some(packet.len()));
+        assert!(!name_eq(&packet, HEADER_LEN, &HOST));
+        assert_eq!(labels(&packet, HEADER_LEN).count(), 1);
+
+        // Reserved label type.
+        let packet = with_names(b"\x46device\x05local\x00");
+        assert_eq!(skip_name(&packet, HEADER_LEN), None);
+        assert!(!name_eq(&packet, HEADER_LEN, &name("")));
+        assert_eq!(labels(&packet, HEADER_LEN).count(), 0);
+
+        assert_eq!(
+            RawRecord::parse(&with_names(b"\x00\x00\x01\x00"), HEADER_LEN).map(|_| ()),
+            None
+        );
+    }
+
+    #[test]
+    fn looping_name() {
+        // Pointer to itself.
+        let packet = with_names(b"\xc0\x0c");
+        assert!(!name_eq(&packet, HEADER_LEN, &HOST));
+        assert_eq!(labels(&packet, HEADER_LEN).count(), 0);
+        assert_eq!(skip_name(&packet, HEADER_LEN), Some(packet.len()));
+
+        // `a` followed by a pointer back to it, which would be an infinite name.
+        let packet = with_names(b"\x01a\xc0\x0c");
+        assert!(labels(&packet, HEADER_LEN).count() <= 17);
+        let long: String = "a.".repeat(40) + "a";
+        assert!(!name_eq(&packet, HEADER_LEN, &name(&long)));
+        assert!(name_bytes(&packet, HEADER_LEN).count() < MAX_PACKET_LEN);
+
+        // Records with such names are parsed, and compared in bounded time.
+        let rest = [0, 1, 0, 1, 0, 0, 0, 120, 0, 4, 10, 0, 0, 1];
+        let mut looping = packet;
+        looping.extend_from_slice(&rest);
+        let (record, end) = unwrap!(RawRecord::parse(&looping, HEADER_LEN));
+        assert_eq!(end, looping.len());
+        assert!(!record.name_is(&name(&long)));
+        let mut plain = with_names(b"\x01a\x05local\x00");
+        plain.extend_from_slice(&rest);
+        let (other, _) = unwrap!(RawRecord::parse(&plain, HEADER_LEN));
+        assert!(!record.is_identical(&other));
+        assert!(!other.is_identical(&record));
+    }
+
+    #[test]
+    fn write_records() {
+        let mut buf = [0; 128];
+
+        let mut w = Writer { buf: &mut buf, len: 0 };
+        unwrap!(w.record(&Record::Srv(&SERVICES[0]), HOST, HOST_TTL, true));
+        let len = w.len;
+        let (record, end) = unwrap!(RawRecord::parse(&buf[..len], 0));
+        assert_eq!(end, len);
+        assert!(record.name_is(&Name::instance(&SERVICES[0])));
+        assert_eq!(record.rtype, TYPE_SRV);
+        assert_eq!(record.class, CLASS_IN);
+        assert_eq!(record.ttl, HOST_TTL);
+        let class_pos = unwrap!(skip_name(&buf, 0)) + 2;
+        assert_eq!(read_u16(&buf, class_pos), Some(CLASS_IN | CLASS_CACHE_FLUSH));
+        let rdata: Vec<u8> = record.rdata().collect();
+        assert_eq!(rdata, b"\x00\x00\x00\x00\x1f\x90\x06device\x05local\x00");
+
+        let mut w = Writer { buf: &mut buf, len: 0 };
+        unwrap!(w.record(&Record::Txt(&SERVICES[0]), HOST, OTHER_TTL, false));
+        let len = w.len;
+        let (record, _) = unwrap!(RawRecord::parse(&buf[..len], 0));
+        assert_eq!(&buf[record.rdata], b"\x06path=/");
+
+        // An empty TXT record has a single empty string.
+        let mut w = Writer { buf: &mut buf, len: 0 };
+        unwrap!(w.record(&Record::Txt(&OTHER_SERVICE), HOST, OTHER_TTL, false));
+        let len = w.len;
+        let (record, _) = unwrap!(RawRecord::parse(&buf[..len], 0));
+        assert_eq!(&buf[record.rdata], b"\x00");
+
+        let mut w = Writer { buf: &mut buf, len: 0 };
+        unwrap!(w.record(&Record::A(Ipv4Address::new(192, 168, 1, 2)), HOST, HOST_TTL, true));
+        let len = w.len;
+        let (record, _) = unwrap!(RawRecord::parse(&buf[..len], 0));
+        assert!(record.name_is(&HOST));
+        assert_eq!(record.rtype, TYPE_A);
+        assert_eq!(&buf[record.rdata], [192, 168, 1, 2]);
+    }
+
+    #[test]
+    fn write_records_full() {
+        // Records that don't fit are not written at all.
+        let mut buf = [0xaa; 40];
+        let mut w = Writer { buf: &mut buf, len: 10 };
+        assert!(w.record(&Record::Srv(&SERVICES[0]), HOST, HOST_TTL, true).is_none());
+        assert_eq!(w.len, 10);
+
+        // Labels are at most 63 bytes.
+        let long: String = "x".repeat(64);
+        let service = Service {
+            instance: &long,
+            ..SERVICES[0]
+        };
+        let mut buf = [0; MAX_PACKET_LEN];
+        let mut w = Writer { buf: &mut buf, len: 0 };
+        assert!(w.record(&Record::Srv(&service), HOST, HOST_TTL, true).is_none());
+        assert!(w.question(Name::instance(&service), TYPE_ANY, CLASS_IN).is_none());
+        assert_eq!(w.len, 0);
+    }
+
+    fn responder(
+        address: [u8; 4],
+        hostname: &'static str,
+        services: &'static [Service<'static>],
+    ) -> Responder<'static> {
+        let resources = Box::leak(Box::new(StackResources::<3>::new()));
+        let (stack, _runner) = crate::new(NoTraffic, config(address, 24, None), resources, 1);
+        let meta = || vec![PacketMetadata::EMPTY; 2].leak();
+        let buffer = || vec![0; MAX_PACKET_LEN].leak();
+        let config = Config { hostname, services };
+        unwrap!(Responder::new(stack, config, meta(), buffer(), meta(), buffer()))
+    }
+
+    fn probe(responder: &Responder<'_>) -> Vec<u8> {
+        let mut buf = vec![0; MAX_PACKET_LEN];
+        let len = responder.write_probe(&mut buf);
+        buf.truncate(len);
+        buf
+    }
+
+    fn response(responder: &Responder<'_>, records: &[Record<'_>]) -> Vec<u8> {
+        let mut buf = vec![0; MAX_PACKET_LEN];
+        let w = Writer {
+            buf: &mut buf,
+            len: HEADER_LEN,
+        };
+        let len = responder.write_response(w, 0, 0, records, &[], false);
+        buf.truncate(len);
+        buf
+    }
+
+    /// A query with one question, and the known answers of the querier.
+    fn query(id: u16, question: Name<'_>, qtype: u16, qclass: u16, known: &[(Record<'_>, u32)]) -> Vec<u8> {
+        let mut buf = vec![0; MAX_PACKET_LEN];
+        let mut w = Writer {
+            buf: &mut buf,
+            len: HEADER_LEN,
+        };
+        unwrap!(w.question(question, qtype, qclass));
+        for (record, ttl) in known {
+            unwrap!(w.record(record, HOST, *ttl, false));
+        }
+        let len = w.len;
+        w.header(id, 0, 1, known.len() as u16, 0, 0);
+        buf.truncate(len);
+        buf
+    }
+
+    #[test]
+    fn probe_conflict() {
+        let ours = responder([192, 168, 1, 2], "device", &SERVICES);
+        let theirs = responder([192, 168, 1, 3], "device", &SERVICES);
+        let other = responder([192, 168, 1, 4], "other", &[]);
+        let our_probe = probe(&ours);
+
+        // Another device answers for our hostname, with another address.
+        let answer = response(&theirs, &theirs.host_records());
+        assert!(ours.is_conflict(&answer, &our_probe));
+
+        // Our own records, such as our announcements received back, don't conflict.
+        let announcement = response(
+            &ours,
+            &[Record::A(Ipv4Address::new(192, 168, 1, 2)), Record::Srv(&SERVICES[0])],
+        );
+        assert!(!ours.is_conflict(&announcement, &our_probe));
+        // Records for other names don't conflict.
+        assert!(!ours.is_conflict(&response(&other, &other.host_records()), &our_probe));
+        // Neither do queries, such as the probes of another device.
+        assert!(!ours.is_conflict(&probe(&theirs), &our_probe));
+
+        // The service instance name is unique too: another device with another hostname can't
+        // use it.
+        let srv = response(&other, &[Record::Srv(&SERVICES[0])]);
+        assert!(ours.is_conflict(&srv, &our_probe));
+        // PTR records are shared.
+        let ptr = response(&other, &[Record::Ptr(&SERVICES[0])]);
+        assert!(!ours.is_conflict(&ptr, &our_probe));
+    }
+
+    #[test]
+    fn probe_tiebreak() {
+        let lower = responder([192, 168, 1, 2], "device", &SERVICES);
+        let higher = responder([192, 168, 1, 3], "device", &SERVICES);
+        let other = responder([192, 168, 1, 4], "other", &[]);
+        let (lower_probe, higher_probe) = (probe(&lower), probe(&higher));
+
+        // The lexicographically later records win.
+        assert_eq!(lower.tiebreak(&higher_probe, &lower_probe), Ordering::Less);
+        assert_eq!(higher.tiebreak(&lower_probe, &higher_probe), Ordering::Greater);
+
+        // Identical probes, such as our own received back, are no conflict.
+        assert_eq!(lower.tiebreak(&lower_probe, &lower_probe), Ordering::Equal);
+        // Neither are probes for other names, or responses.
+        assert_eq!(lower.tiebreak(&probe(&other), &lower_probe), Ordering::Equal);
+        let answer = response(&higher, &higher.host_records());
+        assert_eq!(lower.tiebreak(&answer, &lower_probe), Ordering::Equal);
+
+        // Only the records of the first name that differs are compared.
+        let renamed = responder([192, 168, 1, 2], "device", core::slice::from_ref(&OTHER_SERVICE));
+        assert_eq!(lower.tiebreak(&probe(&renamed), &lower_probe), Ordering::Equal);
+    }
+
+    #[test]
+    fn known_answer_suppression() {
+        let responder = responder([192, 168, 1, 2], "device", &SERVICES);
+        let service_type = Name::service_type(&SERVICES[0]);
+        let source = IpEndpoint::new(Ipv4Address::new(192, 168, 1, 9).into(), MDNS_PORT);
+        let mut buf = [0; MAX_PACKET_LEN];
+
+        // Answered by multicast, with the records the querier needs next.
+        let packet = query(0, service_type, TYPE_PTR, CLASS_IN, &[]);
+        let (len, unicast) = unwrap!(responder.answer(&packet, source, &mut buf));
+        assert!(!unicast);
+        let header = unwrap!(Header::parse(&buf[..len]));
+        assert_eq!(header.flags, FLAG_RESPONSE | FLAG_AUTHORITATIVE);
+        assert_eq!((header.questions, header.answers), (0, 1));
+        // SRV, TXT and A.
+        assert_eq!(header.additional, 3);
+
+        // The querier already knows the answer.
+        let known = [(Record::Ptr(&SERVICES[0]), OTHER_TTL)];
+        let packet = query(0, service_type, TYPE_PTR, CLASS_IN, &known);
+        assert!(responder.answer(&packet, source, &mut buf).is_none());
+
+        // Answered again when less than half of the TTL is left.
+        let known = [(Record::Ptr(&SERVICES[0]), OTHER_TTL / 2 - 1)];
+        let packet = query(0, service_type, TYPE_PTR, CLASS_IN, &known);
+        assert!(responder.answer(&packet, source, &mut buf).is_some());
+
+        // The known answers of other devices don't suppress ours.
+        let known = [(Record::Ptr(&OTHER_SERVICE), OTHER_TTL)];
+        let packet = query(0, service_type, TYPE_PTR, CLASS_IN, &known);
+        assert!(responder.answer(&packet, source, &mut buf).is_some());
+
+        // Queries for other names aren't answered.
+        let packet = query(0, name("_ipp._tcp"), TYPE_PTR, CLASS_IN, &[]);
+        assert!(responder.answer(&packet, source, &mut buf).is_none());
+    }
+
+    #[test]
+    fn unicast_and_legacy_queries() {
+        let responder = responder([192, 168, 1, 2], "device", &SERVICES);
+        let source = IpEndpoint::new(Ipv4Address::new(192, 168, 1, 9).into(), MDNS_PORT);
+        let mut buf = [0; MAX_PACKET_LEN];
+
+        let packet = query(0, HOST, TYPE_A, CLASS_IN | CLASS_UNICAST_RESPONSE, &[]);
+        let (_, unicast) = unwrap!(responder.answer(&packet, source, &mut buf));
+        assert!(unicast);
+
+        // Queries from another port get a regular DNS response, with the ID and question echoed.
+        let legacy_source = IpEndpoint::new(source.addr, 12345);
+        let packet = query(0x1234, HOST, TYPE_A, CLASS_IN, &[]);
+        let (len, unicast) = unwrap!(responder.answer(&packet, legacy_source, &mut buf));
+        assert!(unicast);
+        let header = unwrap!(Header::parse(&buf[..len]));
+        assert_eq!((header.id, header.questions, header.answers), (0x1234, 1, 1));
+        assert_eq!(buf[HEADER_LEN..packet.len()], packet[HEADER_LEN..]);
+        let (record, _) = unwrap!(RawRecord::parse(&buf[..len], packet.len()));
+        assert!(record.ttl <= LEGACY_TTL);
+        // No cache-flush bit in legacy responses.
+        let class_pos = unwrap!(skip_name(&buf, packet.len())) + 2;
+        assert_eq!(read_u16(&buf, class_pos), Some(CLASS_IN));
+        assert_eq!(&buf[record.rdata], [192, 168, 1, 2]);
+    }
+}
@@ -0,0 +1,156 @@
+//! Packet capture in the [pcapng](https://www.ietf.org/archive/id/draft-ietf-opsawg-pcapng-02.html) format.
+//!
+//! Set a [`PcapSink`] on the [`Runner`](crate::Runner) of an interface with
+//! [`Runner::set_pcap_sink`](crate::Runner::set_pcap_sink), and every frame it receives or
+//! transmits is written to the sink, with a timestamp and its direction. The capture can be
+//! opened directly in Wireshark.
+//!
+//! Timestamps are the time since boot, from [`embassy_time::Instant`].
+//!
+//! ## Usage
+//!
+//! A [`Pipe`] is a sink, so the capture can be streamed to a UART or USB endpoint from another
+//! task:
+//!
+//! ```ignore
+//! static CAPTURE: Pipe<CriticalSectionRawMutex, 4096> = Pipe::new();
+//!
+//! runner.set_pcap_sink(Some(&CAPTURE));
+//!
+//! // In another task:
+//! let mut buf = [0; 64];
+//! loop {
+//!     let n = CAPTURE.read(&mut buf).await;
+//!     uart.write_all(&buf[..n]).await.unwrap();
+//! }
+//! ```
+
+use core::cell::Cell;
+
+use embassy_sync::blocking_mutex::raw::RawMutex;
+use embassy_sync::pipe::Pipe;
+use embassy_time::Instant;
+use smoltcp::phy::Medium;
+
+use crate::driver_util::Direction;
+
+/// Destination of a packet capture.
+pub trait PcapSink {
+    /// Write a block of the capture, made of the concatenation of `parts`.
+    ///
+    /// Blocks must be written whole or not at all, so the capture stays readable: return
+    /// `false` to drop the block, when it doesn't fit.
+    fn write(&self, parts: &[&[u8]]) -> bool;
+}
+
+impl<M: RawMutex, const N: usize> PcapSink for Pipe<M, N> {
+    fn write(&self, parts: &[&[u8]]) -> bool {
+        let len: usize = parts.iter().map(|p| p.len()).sum();
+        if self.free_capacity() < len {
+            return false;
+        }
+        for mut part in parts.iter().copied() {
+            while !part.is_empty() {
+                match self.try_write(part) {
+                    Ok(n) => part = &part[n..],
+                    // Only when other writers fill the pipe at the same time.
+                    Err(_) => return false,
+                }
+            }
+        }
+        true
+    }
+}
+
+const BLOCK_SECTION_HEADER: u32 = 0x0A0D_0D0A;
+const BLOCK_INTERFACE_DESCRIPTION: u32 = 0x0000_0001;
+const BLOCK_ENHANCED_PACKET: u32 = 0x0000_0006;
+const BYTE_ORDER_MAGIC: u32 = 0x1A2B_3C4D;
+const OPTION_END: u16 = 0;
+const OPTION_EPB_FLAGS: u16 = 2;
+
+/// Writes the frames of an interface to a [`PcapSink`].
+pub(crate) struct Pcap<'d> {
+    sink: &'d dyn PcapSink,
+    link_type: u16,
+    /// Whether the section and interface headers were written.
+    started: Cell<bool>,
+}
+
+impl<'d> Pcap<'d> {
+    pub(crate) fn new(sink: &'d dyn PcapSink, medium: Medium) -> Self {
+        let link_type = match medium {
+            #[cfg(feature = "medium-ethernet")]
+            Medium::Ethernet => 1,
+            #[cfg(feature = "medium-ip")]
+            Medium::Ip => 101,
+            #[cfg(feature = "medium-ieee802154")]
+            Medium::Ieee802154 => 230,
+        };
+        Self {
+            sink,
+            link_type,
+            started: Cell::new(false),
+        }
+    }
+
+    pub(crate) fn packet(&self, direction: Direction, frame: &[u8]) {
+        if !self.started.get() {
+            if !self.write_headers() {
+                return;
+            }
+            self.started.set(true);
+        }
+
+        let padding = frame.len().next_multiple_of(4) - frame.len();
+        let total_len = (44 + frame.len() + padding) as u32;
+        let timestamp = Instant::now().as_micros();
+
+        let mut header = [0; 28];
+        header[0..4].copy_from_slice(&BLOCK_ENHANCED_PACKET.to_le_bytes());
+        header[4..8].copy_from_slice(&total_len.to_le_bytes());
+        // Interface ID 0
+        header[12..16].copy_from_slice(&((timestamp >> 32) as u32).to_le_bytes());
+        header[16..20].copy_from_slice(&(timestamp as u32).to_le_bytes());
+        header[20..24].copy_from_slice(&(frame.len() as u32).to_le_bytes());
+        header[24..28].copy_from_slice(&(frame.len() as u32).to_le_bytes());
+
+        let flags: u32 = match direction {
+            Direction::Rx => 0b01,
+            Direction::Tx => 0b10,
+        };
+        // Padding of the frame, options, and the length again.
+        let mut trailer = [0; 19];
+        let options = &mut trailer[padding..];
+        options[0..2].copy_from_slice(&OPTION_EPB_FLAGS.to_le_bytes());
+        options[2..4].copy_from_slice(&4u16.to_le_bytes());
+        options[4..8].copy_from_slice(&flags.to_le_bytes());
+        options[8..10].copy_from_slice(&OPTION_END.to_le_bytes());
+        options[12..16].copy_from_slice(&total_len.to_le_bytes());
+
+        self.sink.write(&[&header, frame, &trailer[..padding + 16]]);
+    }
+
+    fn write_headers(&self) -> bool {
+        let mut headers = [0; 48];
+        let (section, interface) = headers.split_at_mut(28);
+
+        section[0..4].copy_from_slice(&BLOCK_SECTION_HEADER.to_le_bytes());
+        section[4..8].copy_from_slice(&28u32.to_le_bytes());
+        section[8..12].copy_from_slice(&BYTE_ORDER_MAGIC.to_le_bytes());
+        // Version 1.0
+        section[12..14].copy_from_slice(&1u16.to_le_bytes());
+        // Unknown section length
+        section[16..24].copy_from_slice(&(-1i64).to_le_bytes());
+        section[24..28].copy_from_slice(&28u32.to_le_bytes());
+
+        // Timestamps are in microseconds, the default resolution, so no options are needed.
+        interface[0..4].copy_from_slice(&BLOCK_INTERFACE_DESCRIPTION.to_le_bytes());
+        interface[4..8].copy_from_slice(&20u32.to_le_bytes());
+        interface[8..10].copy_from_slice(&self.link_type.to_le_bytes());
+        // No snapshot length limit
+        interface[16..20].copy_from_slice(&20u32.to_le_bytes());
+
+        self.sink.write(&[&headers])
+    }
+}
@@ -0,0 +1,56 @@
+//! Fixed-size pool of socket buffers, for the `embedded-nal-async` clients.
+
+use core::cell::{Cell, RefCell, UnsafeCell};
+use core::mem::MaybeUninit;
+use core::ptr::NonNull;
+#[cfg(feature = "tcp")]
+use core::task::Waker;
+
+use embassy_sync::waitqueue::WakerRegistration;
+
+pub(crate) struct Pool<T, const N: usize> {
+    used: [Cell<bool>; N],
+    data: [UnsafeCell<MaybeUninit<T>>; N],
+    /// Woken when an item is freed.
+    waker: RefCell<WakerRegistration>,
+}
+
+impl<T, const N: usize> Pool<T, N> {
+    pub(crate) const fn new() -> Self {
+        Self {
+            used: [const { Cell::new(false) }; N],
+            data: [const { UnsafeCell::new(MaybeUninit::uninit()) }; N],
+            waker: RefCell::new(WakerRegistration::new()),
+        }
+    }
+}
+
+impl<T, const N: usize> Pool<T, N> {
+    pub(crate) fn alloc(&self) -> Option<NonNull<T>> {
+        for n in 0..N {
+            // this can't race because Pool is not Sync.
+            if !self.used[n].get() {
+                self.used[n].set(true);
+                let p = self.data[n].get() as *mut T;
+                return Some(unsafe { NonNull::new_unchecked(p) });
+            }
+        }
+        None
+    }
+
+    /// safety: p must be a pointer obtained from self.alloc that hasn't been freed yet.
+    pub(crate) unsafe fn free(&self, p: NonNull<T>) {
+        let origin = self.data.as_ptr() as *mut T;
+        let n = p.as_ptr().offset_from(origin);
+        assert!(n >= 0);
+        assert!((n as usize) < N);
+        self.used[n as usize].set(false);
+        self.waker.borrow_mut().wake();
+    }
+
+    /// Register a waker, woken when an item is freed.
+    #[cfg(feature = "tcp")]
+    pub(crate) fn register_waker(&self, waker: &Waker) {
+        self.waker.borrow_mut().register(waker);
+    }
+}
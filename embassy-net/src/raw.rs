@@ -1,16 +1,22 @@
 //! Raw sockets.
 
+use core::cell::Cell;
 use core::future::{poll_fn, Future};
 use core::mem;
 use core::task::{Context, Poll};
 
 use embassy_net_driver::Driver;
-use smoltcp::iface::{Interface, SocketHandle};
+use smoltcp::iface::Interface;
 use smoltcp::socket::raw;
 pub use smoltcp::socket::raw::PacketMetadata;
+use smoltcp::wire::IpAddress;
+#[cfg(feature = "proto-ipv4")]
+use smoltcp::wire::Ipv4Packet;
+#[cfg(feature = "proto-ipv6")]
+use smoltcp::wire::Ipv6Packet;
 pub use smoltcp::wire::{IpProtocol, IpVersion};
 
-use crate::Stack;
+use crate::{InterfaceFull, InterfaceId, SocketRef, Stack};
 
 /// Error returned by [`RawSocket::recv`] and [`RawSocket::send`].
 #[derive(PartialEq, Eq, Clone, Copy, Debug)]
@@ -21,9 +27,13 @@ pub enum RecvError {
 }
 
 /// An Raw socket.
+///
+/// Unless it's bound to an interface, the socket moves to the interface used to reach the
+/// destination of each packet it sends, and then receives on that interface only.
 pub struct RawSocket<'a> {
     stack: Stack<'a>,
-    handle: SocketHandle,
+    /// Changed when sending moves the socket to another interface.
+    handle: Cell<SocketRef>,
 }
 
 impl<'a> RawSocket<'a> {
@@ -42,7 +52,7 @@ impl<'a> RawSocket<'a> {
             let rx_buffer: &'static mut [u8] = unsafe { mem::transmute(rx_buffer) };
             let tx_meta: &'static mut [PacketMetadata] = unsafe { mem::transmute(tx_meta) };
             let tx_buffer: &'static mut [u8] = unsafe { mem::transmute(tx_buffer) };
-            i.sockets.add(raw::Socket::new(
+            i.add_socket(raw::Socket::new(
                 ip_version,
                 ip_protocol,
                 raw::PacketBuffer::new(rx_meta, rx_buffer),
@@ -50,15 +60,56 @@ impl<'a> RawSocket<'a> {
             ))
         });
 
-        Self { stack, handle }
+        Self {
+            stack,
+            handle: Cell::new(handle),
+        }
+    }
+
+    /// Bind the socket to a network interface of the stack.
+    ///
+    /// The socket then only sends and receives through this interface, instead of the one picked
+    /// by the routing of the stack.
+    ///
+    /// Returns [`InterfaceFull`] if the interface has no room for the socket.
+    pub fn bind_interface(&mut self, iface: InterfaceId) -> Result<(), InterfaceFull> {
+        self.stack.with_mut(|i| i.bind_socket(self.handle.get_mut(), iface))
+    }
+
+    /// Get the network interface the socket sends and receives through.
+    pub fn interface(&self) -> InterfaceId {
+        self.handle.get().iface
     }
 
     fn with_mut<R>(&self, f: impl FnOnce(&mut raw::Socket, &mut Interface) -> R) -> R {
+        self.stack.with_mut(|i| i.with_socket_mut(self.handle.get(), f))
+    }
+
+    /// Move the socket to the interface used to reach the destination of the IP packet `buf`,
+    /// unless it's bound to an interface. The packets already queued are sent first, through the
+    /// interface they were routed to. If the interface has no room for the socket, it stays where
+    /// it is.
+    fn poll_route(&self, buf: &[u8], cx: &mut Context<'_>) -> Poll<()> {
+        let Some(remote) = destination(buf) else {
+            return Poll::Ready(());
+        };
         self.stack.with_mut(|i| {
-            let socket = i.sockets.get_mut::<raw::Socket>(self.handle);
-            let res = f(socket, &mut i.iface);
-            i.waker.wake();
-            res
+            let mut handle = self.handle.get();
+            if !i.is_rerouted(handle, &remote) {
+                return Poll::Ready(());
+            }
+            let queued = i.with_socket_mut(handle, |s: &mut raw::Socket, _| {
+                s.register_send_waker(cx.waker());
+                s.send_queue() > 0
+            });
+            if queued {
+                return Poll::Pending;
+            }
+            if i.route_socket(&mut handle, &remote).is_err() {
+                warn!("raw socket: no room on the interface routing {}", remote);
+            }
+            self.handle.set(handle);
+            Poll::Ready(())
         })
     }
 
@@ -152,6 +203,9 @@ impl<'a> RawSocket<'a> {
     /// When the socket's send buffer is full, this method will return `Poll::Pending`
     /// and register the current task to be notified when the buffer has space available.
     pub fn poll_send(&self, buf: &[u8], cx: &mut Context<'_>) -> Poll<()> {
+        if self.poll_route(buf, cx).is_pending() {
+            return Poll::Pending;
+        }
         self.with_mut(|s, _| match s.send_slice(buf) {
             // Entire datagram has been sent
             Ok(()) => Poll::Ready(()),
@@ -179,9 +233,21 @@ impl<'a> RawSocket<'a> {
     }
 }
 
+/// Destination address of an IP packet.
+fn destination(packet: &[u8]) -> Option<IpAddress> {
+    match IpVersion::of_packet(packet).ok()? {
+        #[cfg(feature = "proto-ipv4")]
+        IpVersion::Ipv4 => Some(Ipv4Packet::new_checked(packet).ok()?.dst_addr().into()),
+        #[cfg(feature = "proto-ipv6")]
+        IpVersion::Ipv6 => Some(Ipv6Packet::new_checked(packet).ok()?.dst_addr().into()),
+        #[allow(unreachable_patterns)]
+        _ => None,
+    }
+}
+
 impl Drop for RawSocket<'_> {
     fn drop(&mut self) {
-        self.stack.with_mut(|i| i.sockets.remove(self.handle));
+        self.stack.with_mut(|i| i.remove_socket(self.handle.get()));
     }
 }
 
@@ -0,0 +1,445 @@
+//! IPv6 stateless address autoconfiguration ([RFC 4862](https://www.rfc-editor.org/rfc/rfc4862)).
+//!
+//! This solicits Router Advertisements on a raw ICMPv6 socket, and derives the configuration
+//! from them: a global address from the first autonomous /64 prefix, the advertising router as
+//! default gateway, and DNS servers from RDNSS options ([RFC 8106](https://www.rfc-editor.org/rfc/rfc8106)).
+//! Duplicate address detection is not done.
+
+use core::mem::MaybeUninit;
+
+use embassy_time::{Duration, Instant};
+use heapless::Vec;
+use smoltcp::iface::{SocketHandle, SocketSet};
+use smoltcp::socket::raw;
+use smoltcp::wire::{
+    Icmpv6Message, Icmpv6Packet, IpProtocol, IpVersion, Ipv6Packet, Ipv6Repr, IPV6_HEADER_LEN,
+    IPV6_LINK_LOCAL_ALL_ROUTERS,
+};
+
+#[cfg(feature = "dhcpv6")]
+use crate::dhcpv6;
+use crate::{HardwareAddress, Ipv6Address, Ipv6Cidr, SlaacConfig, StaticConfigV6};
+
+const RX_BUFFER_LEN: usize = 1024;
+const TX_BUFFER_LEN: usize = IPV6_HEADER_LEN + 16;
+
+/// Initial interval between Router Solicitations. It doubles after each one, as recommended by
+/// RFC 7559.
+const SOLICITATION_INTERVAL: Duration = Duration::from_secs(4);
+const MAX_SOLICITATION_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Remaining lifetime below which the lifetime of the address can't be shortened by an
+/// unauthenticated Router Advertisement, see RFC 4862 section 5.5.3.
+const MIN_VALID_LIFETIME: Duration = Duration::from_secs(2 * 3600);
+
+#[cfg(feature = "medium-ethernet")]
+const NDISC_OPTION_SOURCE_LINK_LAYER_ADDRESS: u8 = 1;
+const NDISC_OPTION_PREFIX_INFORMATION: u8 = 3;
+const NDISC_OPTION_RDNSS: u8 = 25;
+const PREFIX_FLAG_AUTONOMOUS: u8 = 0x40;
+#[cfg(feature = "dhcpv6")]
+const RA_FLAG_MANAGED: u8 = 0x80;
+#[cfg(feature = "dhcpv6")]
+const RA_FLAG_OTHER: u8 = 0x40;
+const INFINITE_LIFETIME: u32 = u32::MAX;
+
+/// Buffers of the autoconfiguration sockets.
+pub(crate) struct Resources {
+    rx_meta: MaybeUninit<[raw::PacketMetadata; 2]>,
+    rx_buffer: MaybeUninit<[u8; RX_BUFFER_LEN]>,
+    tx_meta: MaybeUninit<[raw::PacketMetadata; 1]>,
+    tx_buffer: MaybeUninit<[u8; TX_BUFFER_LEN]>,
+    #[cfg(feature = "dhcpv6")]
+    dhcpv6: dhcpv6::Resources,
+}
+
+impl Resources {
+    pub(crate) const fn new() -> Self {
+        Self {
+            rx_meta: MaybeUninit::uninit(),
+            rx_buffer: MaybeUninit::uninit(),
+            tx_meta: MaybeUninit::uninit(),
+            tx_buffer: MaybeUninit::uninit(),
+            #[cfg(feature = "dhcpv6")]
+            dhcpv6: dhcpv6::Resources::new(),
+        }
+    }
+}
+
+/// Autoconfiguration state of the interface.
+pub(crate) struct Slaac {
+    socket: SocketHandle,
+    #[cfg(feature = "dhcpv6")]
+    dhcpv6: Option<dhcpv6::Client>,
+    link_local: Ipv6Address,
+    /// Source link-layer address option of solicitations.
+    source_lladdr: Option<[u8; 8]>,
+    next_solicitation: Option<Instant>,
+    solicitation_interval: Duration,
+    /// Global address, and when it becomes invalid.
+    address: Option<(Ipv6Address, Instant)>,
+    /// Default router, and when it stops being one.
+    router: Option<(Ipv6Address, Instant)>,
+    /// Recursive DNS servers, and when they expire.
+    dns_servers: Vec<(Ipv6Address, Instant), 3>,
+}
+
+impl Slaac {
+    /// Create the autoconfiguration sockets in `sockets`, using `resources` for their buffers.
+    ///
+    /// With `dhcpv6`, DNS servers are also requested with stateless DHCPv6 when routers ask to.
+    pub(crate) fn new(
+        sockets: &mut SocketSet<'static>,
+        resources: &'static mut Resources,
+        config: &SlaacConfig,
+        hardware_address: HardwareAddress,
+        random_seed: u64,
+        #[allow(unused)] dhcpv6: bool,
+    ) -> Self {
+        let rx_buffer = raw::PacketBuffer::new(
+            &mut resources.rx_meta.write([raw::PacketMetadata::EMPTY; 2])[..],
+            &mut resources.rx_buffer.write([0; RX_BUFFER_LEN])[..],
+        );
+        let tx_buffer = raw::PacketBuffer::new(
+            &mut resources.tx_meta.write([raw::PacketMetadata::EMPTY; 1])[..],
+            &mut resources.tx_buffer.write([0; TX_BUFFER_LEN])[..],
+        );
+        let socket = sockets.add(raw::Socket::new(
+            IpVersion::Ipv6,
+            IpProtocol::Icmpv6,
+            rx_buffer,
+            tx_buffer,
+        ));
+
+        let mut link_local = [0; 16];
+        link_local[..2].copy_from_slice(&[0xfe, 0x80]);
+        let iid = config
+            .interface_identifier
+            .unwrap_or_else(|| interface_identifier(hardware_address, random_seed));
+        link_local[8..].copy_from_slice(&iid);
+
+        Self {
+            socket,
+            #[cfg(feature = "dhcpv6")]
+            dhcpv6: dhcpv6.then(|| dhcpv6::Client::new(sockets, &mut resources.dhcpv6, random_seed)),
+            link_local: Ipv6Address::from(link_local),
+            source_lladdr: source_lladdr_option(hardware_address),
+            next_solicitation: None,
+            solicitation_interval: SOLICITATION_INTERVAL,
+            address: None,
+            router: None,
+            dns_servers: Vec::new(),
+        }
+    }
+
+    /// Remove the sockets from `sockets`.
+    pub(crate) fn remove(self, sockets: &mut SocketSet<'static>) {
+        sockets.remove(self.socket);
+        #[cfg(feature = "dhcpv6")]
+        if let Some(dhcpv6) = self.dhcpv6 {
+            dhcpv6.remove(sockets);
+        }
+    }
+
+    /// Link-local address, which must be assigned to the interface.
+    pub(crate) fn link_local(&self) -> Ipv6Cidr {
+        Ipv6Cidr::new(self.link_local, 64)
+    }
+
+    /// Forget the configuration, and start soliciting routers again.
+    pub(crate) fn reset(&mut self, link_up: bool) {
+        self.next_solicitation = link_up.then(Instant::now);
+        self.solicitation_interval = SOLICITATION_INTERVAL;
+        self.address = None;
+        self.router = None;
+        self.dns_servers.clear();
+        #[cfg(feature = "dhcpv6")]
+        if let Some(dhcpv6) = &mut self.dhcpv6 {
+            dhcpv6.reset();
+        }
+    }
+
+    /// Current configuration, if an address was obtained.
+    pub(crate) fn config(&self) -> Option<StaticConfigV6> {
+        let (address, _) = self.address?;
+        let mut dns_servers: Vec<Ipv6Address, 3> = Vec::new();
+        #[cfg(feature = "dhcpv6")]
+        let dhcpv6_servers = self.dhcpv6.iter().flat_map(|c| c.dns_servers());
+        #[cfg(not(feature = "dhcpv6"))]
+        let dhcpv6_servers = core::iter::empty();
+        for server in self.dns_servers.iter().map(|(s, _)| s).chain(dhcpv6_servers) {
+            if !dns_servers.contains(server) {
+                let _ = dns_servers.push(*server);
+            }
+        }
+
+        Some(StaticConfigV6 {
+            address: Ipv6Cidr::new(address, 64),
+            gateway: self.router.map(|(router, _)| router),
+            dns_servers,
+        })
+    }
+
+    /// Process received Router Advertisements, send solicitations and expire the configuration.
+    pub(crate) fn poll(&mut self, sockets: &mut SocketSet<'static>) {
+        let now = Instant::now();
+
+        let socket = sockets.get_mut::<raw::Socket>(self.socket);
+        while let Ok(packet) = socket.recv() {
+            if let Some((router, advert)) = parse_router_advert(packet) {
+                self.process_router_advert(router, advert, now);
+            }
+        }
+
+        if self.next_solicitation.is_some_and(|t| t <= now) && self.send_solicitation(socket).is_some() {
+            self.next_solicitation = Some(now + self.solicitation_interval);
+            self.solicitation_interval = (self.solicitation_interval * 2).min(MAX_SOLICITATION_INTERVAL);
+        }
+
+        if self.address.is_some_and(|(_, valid_until)| valid_until <= now) {
+            debug!("SLAAC: address expired");
+            self.address = None;
+        }
+        if self.router.is_some_and(|(_, valid_until)| valid_until <= now) {
+            self.router = None;
+        }
+        self.dns_servers.retain(|(_, valid_until)| *valid_until > now);
+
+        #[cfg(feature = "dhcpv6")]
+        if let Some(dhcpv6) = &mut self.dhcpv6 {
+            dhcpv6.poll(sockets, self.link_local, now);
+        }
+    }
+
+    /// When [`poll`](Self::poll) must be called next, at the latest.
+    pub(crate) fn poll_at(&self) -> Option<Instant> {
+        let expirations = [
+            self.next_solicitation,
+            self.address.map(|(_, t)| t),
+            self.router.map(|(_, t)| t),
+            self.dns_servers.iter().map(|(_, t)| *t).min(),
+            #[cfg(feature = "dhcpv6")]
+            self.dhcpv6.as_ref().and_then(|c| c.poll_at()),
+        ];
+        expirations.into_iter().flatten().min()
+    }
+
+    fn send_solicitation(&self, socket: &mut raw::Socket<'static>) -> Option<()> {
+        let mut message: Vec<u8, 16> = Vec::new();
+        // Type, code, checksum and reserved field.
+        unwrap!(message.extend_from_slice(&[Icmpv6Message::RouterSolicit.into(), 0, 0, 0, 0, 0, 0, 0]));
+        if let Some(option) = &self.source_lladdr {
+            unwrap!(message.extend_from_slice(option));
+        }
+
+        let ip_repr = Ipv6Repr {
+            src_addr: self.link_local,
+            dst_addr: IPV6_LINK_LOCAL_ALL_ROUTERS,
+            next_header: IpProtocol::Icmpv6,
+            payload_len: message.len(),
+            hop_limit: 255,
+        };
+
+        let buf = socket.send(IPV6_HEADER_LEN + message.len()).ok()?;
+        let mut packet = Ipv6Packet::new_unchecked(&mut *buf);
+        ip_repr.emit(&mut packet);
+        let payload = packet.payload_mut();
+        payload.copy_from_slice(&message);
+        Icmpv6Packet::new_unchecked(payload).fill_checksum(&ip_repr.src_addr, &ip_repr.dst_addr);
+        debug!("SLAAC: sending router solicitation");
+        Some(())
+    }
+
+    fn process_router_advert(&mut self, router: Ipv6Address, advert: RouterAdvert<'_>, now: Instant) {
+        // A router answered, stop soliciting.
+        self.next_solicitation = None;
+
+        if advert.router_lifetime == 0 {
+            if self.router.is_some_and(|(r, _)| r == router) {
+                self.router = None;
+            }
+        } else if self.router.is_none_or(|(r, _)| r == router) {
+            self.router = Some((router, now + Duration::from_secs(advert.router_lifetime as u64)));
+        }
+
+        for (kind, data) in advert.options() {
+            match kind {
+                NDISC_OPTION_PREFIX_INFORMATION => self.process_prefix(data, now),
+                NDISC_OPTION_RDNSS => self.process_rdnss(data, now),
+                _ => {}
+            }
+        }
+
+        #[cfg(feature = "dhcpv6")]
+        if let Some(dhcpv6) = &mut self.dhcpv6 {
+            if advert.flags & (RA_FLAG_MANAGED | RA_FLAG_OTHER) != 0 {
+                dhcpv6.start(now);
+            }
+        }
+    }
+
+    /// Process a Prefix Information option, without its type and length.
+    fn process_prefix(&mut self, data: &[u8], now: Instant) {
+        if data.len() < 30 {
+            return;
+        }
+        let prefix_len = data[0];
+        let flags = data[1];
+        let valid = u32::from_be_bytes(data[2..6].try_into().unwrap());
+        let preferred = u32::from_be_bytes(data[6..10].try_into().unwrap());
+        let mut address: [u8; 16] = data[14..30].try_into().unwrap();
+
+        // The interface identifier is 64 bits, so only /64 prefixes can be used.
+        if flags & PREFIX_FLAG_AUTONOMOUS == 0 || prefix_len != 64 || preferred > valid {
+            return;
+        }
+        if address[..8] == self.link_local.octets()[..8] {
+            return;
+        }
+        address[8..].copy_from_slice(&self.link_local.octets()[8..]);
+        let address = Ipv6Address::from(address);
+
+        let valid_until = lifetime_to_instant(valid, now);
+        match self.address {
+            None if valid != 0 => {
+                debug!("SLAAC: got address {}", address);
+                self.address = Some((address, valid_until));
+            }
+            Some((current, current_until)) if current == address => {
+                // Don't let a spoofed advertisement invalidate the address quickly.
+                let remaining = current_until.saturating_duration_since(now);
+                if valid_until > now + MIN_VALID_LIFETIME || valid_until > current_until {
+                    self.address = Some((address, valid_until));
+                } else if remaining > MIN_VALID_LIFETIME {
+                    self.address = Some((address, now + MIN_VALID_LIFETIME));
+                }
+            }
+            // Only one global address is configured.
+            _ => {}
+        }
+    }
+
+    /// Process a Recursive DNS Server option, without its type and length.
+    fn process_rdnss(&mut self, data: &[u8], now: Instant) {
+        if data.len() < 22 {
+            return;
+        }
+        let lifetime = u32::from_be_bytes(data[2..6].try_into().unwrap());
+        let valid_until = lifetime_to_instant(lifetime, now);
+        for server in data[6..].chunks_exact(16) {
+            let server = Ipv6Address::from(<[u8; 16]>::try_from(server).unwrap());
+            match self.dns_servers.iter_mut().find(|(s, _)| *s == server) {
+                Some(entry) => entry.1 = valid_until,
+                None if lifetime != 0 => {
+                    let _ = self.dns_servers.push((server, valid_until));
+                }
+                None => {}
+            }
+        }
+        self.dns_servers.retain(|(_, valid_until)| *valid_until > now);
+    }
+}
+
+struct RouterAdvert<'a> {
+    #[cfg(feature = "dhcpv6")]
+    flags: u8,
+    router_lifetime: u16,
+    options: &'a [u8],
+}
+
+impl<'a> RouterAdvert<'a> {
+    /// Iterate over the options, as type and data without the type and length.
+    fn options(&self) -> impl Iterator<Item = (u8, &'a [u8])> {
+        let mut options = self.options;
+        core::iter::from_fn(move || {
+            let [kind, len, ..] = *options else {
+                return None;
+            };
+            let len = len as usize * 8;
+            if len == 0 || len > options.len() {
+                return None;
+            }
+            let (option, rest) = options.split_at(len);
+            options = rest;
+            Some((kind, &option[2..]))
+        })
+    }
+}
+
+/// Parse a Router Advertisement from a raw IPv6 packet. Returns the router address with it.
+fn parse_router_advert(packet: &[u8]) -> Option<(Ipv6Address, RouterAdvert<'_>)> {
+    let ip_packet = Ipv6Packet::new_checked(packet).ok()?;
+    let ip_repr = Ipv6Repr::parse(&ip_packet).ok()?;
+    // Advertisements must come from a link-local address, and not have crossed a router.
+    if ip_repr.next_header != IpProtocol::Icmpv6
+        || ip_repr.hop_limit != 255
+        || ip_repr.src_addr.segments()[0] & 0xffc0 != 0xfe80
+    {
+        return None;
+    }
+
+    let payload = &packet[IPV6_HEADER_LEN..IPV6_HEADER_LEN + ip_repr.payload_len];
+    let icmp_packet = Icmpv6Packet::new_checked(payload).ok()?;
+    if icmp_packet.msg_type() != Icmpv6Message::RouterAdvert
+        || icmp_packet.msg_code() != 0
+        || payload.len() < 16
+        || !icmp_packet.verify_checksum(&ip_repr.src_addr, &ip_repr.dst_addr)
+    {
+        return None;
+    }
+
+    let advert = RouterAdvert {
+        #[cfg(feature = "dhcpv6")]
+        flags: payload[5],
+        router_lifetime: u16::from_be_bytes([payload[6], payload[7]]),
+        options: &payload[16..],
+    };
+    Some((ip_repr.src_addr, advert))
+}
+
+fn source_lladdr_option(hardware_address: HardwareAddress) -> Option<[u8; 8]> {
+    match hardware_address {
+        #[cfg(feature = "medium-ethernet")]
+        HardwareAddress::Ethernet(addr) => {
+            let mut option = [NDISC_OPTION_SOURCE_LINK_LAYER_ADDRESS, 1, 0, 0, 0, 0, 0, 0];
+            option[2..].copy_from_slice(addr.as_bytes());
+            Some(option)
+        }
+        // Other link layers don't need the option, or have a different format for it.
+        #[allow(unreachable_patterns)]
+        _ => None,
+    }
+}
+
+fn lifetime_to_instant(lifetime: u32, now: Instant) -> Instant {
+    if lifetime == INFINITE_LIFETIME {
+        Instant::MAX
+    } else {
+        now.checked_add(Duration::from_secs(lifetime as u64))
+            .unwrap_or(Instant::MAX)
+    }
+}
+
+/// Interface identifier of the addresses: the modified EUI-64 of the hardware address if it has
+/// one, or a random one otherwise.
+fn interface_identifier(hardware_address: HardwareAddress, random_seed: u64) -> [u8; 8] {
+    match hardware_address {
+        #[cfg(feature = "medium-ethernet")]
+        HardwareAddress::Ethernet(addr) => {
+            let mac = addr.0;
+            [mac[0] ^ 0x02, mac[1], mac[2], 0xff, 0xfe, mac[3], mac[4], mac[5]]
+        }
+        #[cfg(feature = "medium-ieee802154")]
+        HardwareAddress::Ieee802154(smoltcp::wire::Ieee802154Address::Extended(mut addr)) => {
+            addr[0] ^= 0x02;
+            addr
+        }
+        #[allow(unreachable_patterns)]
+        _ => {
+            let mut iid = random_seed.to_be_bytes();
+            // Clear the universal/local bit, the identifier is not globally unique.
+            iid[0] &= !0x02;
+            iid
+        }
+    }
+}
@@ -0,0 +1,470 @@
+//! SNTP client, to get the wall-clock time from an NTP server.
+//!
+//! This implements the client side of SNTPv4 ([RFC 4330](https://www.rfc-editor.org/rfc/rfc4330)).
+//! Each query measures the offset between the local wall clock and the server's clock, and the
+//! round-trip delay of the request.
+//!
+//! ## Usage
+//!
+//! ```ignore
+//! use embassy_net::sntp::SntpClient;
+//! use embassy_net::udp::PacketMetadata;
+//!
+//! let mut rx_meta = [PacketMetadata::EMPTY; 2];
+//! let mut rx_buffer = [0; 128];
+//! let mut tx_meta = [PacketMetadata::EMPTY; 2];
+//! let mut tx_buffer = [0; 128];
+//!
+//! let mut client = SntpClient::new(stack, &mut rx_meta, &mut rx_buffer, &mut tx_meta, &mut tx_buffer, seed).unwrap();
+//!
+//! // Either query once...
+//! let measurement = client.query("pool.ntp.org").await.unwrap();
+//! info!("UTC time: {}", measurement.timestamp);
+//!
+//! // ...or keep `embassy_time::SystemTime` in sync forever.
+//! client.run("pool.ntp.org", Duration::from_secs(1024)).await;
+//! ```
+
+use embassy_time::{Duration, Instant, SystemTime, Timer, UnixTimestamp, WithTimeout};
+
+use crate::dns::{self, DnsQueryType};
+use crate::udp::{BindError, PacketMetadata, SendError, UdpSocket};
+use crate::{IpAddress, IpEndpoint, Stack};
+
+/// UDP port of NTP servers.
+pub const NTP_PORT: u16 = 123;
+
+/// Offsets larger than this are corrected by stepping the wall clock instead of slewing it,
+/// in [`Measurement::apply`].
+pub const STEP_THRESHOLD: Duration = Duration::from_millis(128);
+
+/// Time to wait before retrying after a failed query in [`SntpClient::run`].
+const RETRY_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Seconds between the NTP epoch (1900-01-01) and the Unix epoch (1970-01-01).
+const NTP_UNIX_OFFSET: u64 = 2_208_988_800;
+
+const PACKET_LEN: usize = 48;
+const MAX_PACKET_LEN: usize = 68;
+/// Leap indicator 0 (no warning), version 4, mode 3 (client).
+const REQUEST_HEADER: u8 = 0b00_100_011;
+const MODE_SERVER: u8 = 4;
+const LEAP_UNSYNCHRONIZED: u8 = 3;
+
+/// Error returned by [`SntpClient`].
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// The server name could not be resolved.
+    Dns(dns::Error),
+    /// The server name resolved to no address.
+    NoAddress,
+    /// The request could not be sent.
+    Send(SendError),
+    /// The server did not respond in time.
+    Timeout,
+    /// The server's clock is not synchronized.
+    Unsynchronized,
+    /// The server sent a "kiss-o'-death" packet, asking the client to stop or slow down. The
+    /// four ASCII characters are the reason code, such as `RATE` or `DENY`.
+    KissOfDeath([u8; 4]),
+}
+
+/// Result of a successful SNTP query.
+///
+/// The measured time is anchored to the [`Instant`] at which the response was received.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Measurement {
+    /// Address of the server that answered.
+    pub server: IpAddress,
+    /// Stratum of the server: 1 for a server with a reference clock, 2 for a server synchronized
+    /// to a stratum 1 server, and so on.
+    pub stratum: u8,
+    /// Instant at which the response was received.
+    pub instant: Instant,
+    /// UTC time at [`instant`](Measurement::instant), according to the server.
+    pub timestamp: UnixTimestamp,
+    /// Round-trip delay of the request, excluding the processing time of the server.
+    pub delay: Duration,
+    /// Offset of the server's clock from [`SystemTime`], in microseconds, or `None` if the system
+    /// time has not been set. Positive if the local clock is late.
+    pub offset: Option<i64>,
+}
+
+impl Measurement {
+    /// Update [`SystemTime`] with this measurement.
+    ///
+    /// If the system time has not been set yet, it is set to the measured time. Otherwise, the
+    /// offset is corrected by slewing the clock if it is smaller than [`STEP_THRESHOLD`], or
+    /// by stepping it if it is larger.
+    pub fn apply(&self) {
+        match self.offset {
+            None => SystemTime::set_at(self.instant, self.timestamp),
+            Some(offset) if offset.unsigned_abs() > STEP_THRESHOLD.as_micros() => SystemTime::step(offset),
+            Some(offset) => SystemTime::slew(offset),
+        }
+    }
+}
+
+/// SNTP client.
+///
+/// The client owns a UDP socket bound to a dynamic local port, which is used for all queries.
+pub struct SntpClient<'d> {
+    stack: Stack<'d>,
+    socket: UdpSocket<'d>,
+    timeout: Duration,
+    /// Generator of the transmit timestamps of the requests.
+    random: u64,
+}
+
+impl<'d> SntpClient<'d> {
+    /// Create a new SNTP client, with the buffers for its UDP socket.
+    ///
+    /// The buffers must be large enough for at least one NTP packet each, which is 48 bytes for
+    /// requests and up to 68 bytes for responses.
+    ///
+    /// `random_seed` seeds the random transmit timestamps of the requests, which the responses
+    /// must echo. It should come from a hardware random number generator.
+    pub fn new(
+        stack: Stack<'d>,
+        rx_meta: &'d mut [PacketMetadata],
+        rx_buffer: &'d mut [u8],
+        tx_meta: &'d mut [PacketMetadata],
+        tx_buffer: &'d mut [u8],
+        random_seed: u64,
+    ) -> Result<Self, BindError> {
+        let mut socket = UdpSocket::new(stack, rx_meta, rx_buffer, tx_meta, tx_buffer);
+        socket.bind(0)?;
+        Ok(Self {
+            stack,
+            socket,
+            timeout: Duration::from_secs(5),
+            random: random_seed | 1,
+        })
+    }
+
+    /// Set how long to wait for the response to a query. Defaults to 5 seconds.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// Resolve `server` with [`Stack::dns_query`] and query the first address returned.
+    ///
+    /// `server` can also be an IP address. IPv4 addresses are used if the `proto-ipv4` feature is
+    /// enabled, IPv6 addresses otherwise.
+    pub async fn query(&mut self, server: &str) -> Result<Measurement, Error> {
+        let addr = self.resolve(server).await?;
+        self.query_addr(addr).await
+    }
+
+    /// Query the NTP server at `addr`.
+    pub async fn query_addr(&mut self, addr: IpAddress) -> Result<Measurement, Error> {
+        let server = IpEndpoint::new(addr, NTP_PORT);
+
+        // Send and receive through the interface the server is reached through.
+        self.socket
+            .bind_interface(self.stack.route(addr))
+            .map_err(|_| Error::Send(SendError::NoRoute))?;
+
+        // The transmit timestamp of the request is echoed by the server, and used to match the
+        // response. The server doesn't interpret it, so it's random: the local time would tell
+        // the uptime of the device, and make responses easy to spoof
+        // (draft-ietf-ntp-data-minimization).
+        let nonce = xorshift(&mut self.random).to_be_bytes();
+
+        let mut request = [0u8; PACKET_LEN];
+        request[0] = REQUEST_HEADER;
+        request[40..48].copy_from_slice(&nonce);
+        let sent = Instant::now();
+        self.socket.send_to(&request, server).await.map_err(Error::Send)?;
+
+        // Leave room for the optional key identifier and message digest.
+        let mut response = [0u8; MAX_PACKET_LEN];
+        let received = async {
+            loop {
+                // Ignore unrelated packets, and responses to previous requests that timed out.
+                if let Ok((n, meta)) = self.socket.recv_from(&mut response).await {
+                    let instant = Instant::now();
+                    if meta.endpoint == server && n >= PACKET_LEN && response[24..32] == nonce {
+                        break instant;
+                    }
+                }
+            }
+        }
+        .with_timeout(self.timeout)
+        .await
+        .map_err(|_| Error::Timeout)?;
+
+        let (stratum, server_received, server_sent) = parse_response(&response)?;
+        let (timestamp, delay) = server_time(sent, received, server_received, server_sent);
+        let offset = SystemTime::from_instant(received).map(|local| offset(timestamp, local));
+
+        Ok(Measurement {
+            server: addr,
+            stratum,
+            instant: received,
+            timestamp,
+            delay,
+            offset,
+        })
+    }
+
+    /// Keep [`SystemTime`] synchronized with `server`, querying it every `interval`.
+    ///
+    /// Each successful query is [applied](Measurement::apply) to the system time. Failed queries
+    /// are retried after 10 seconds, or after `interval` if the server asked to slow down.
+    pub async fn run(&mut self, server: &str, interval: Duration) -> ! {
+        loop {
+            match self.query(server).await {
+                Ok(measurement) => {
+                    debug!(
+                        "SNTP: time {}, delay {} us",
+                        measurement.timestamp.as_micros(),
+                        measurement.delay.as_micros()
+                    );
+                    measurement.apply();
+                    Timer::after(interval).await;
+                }
+                Err(Error::KissOfDeath(code)) => {
+                    warn!("SNTP: server sent kiss-o'-death {:?}", code);
+                    Timer::after(interval).await;
+                }
+                Err(e) => {
+                    warn!("SNTP: query failed: {:?}", e);
+                    Timer::after(RETRY_INTERVAL).await;
+                }
+            }
+        }
+    }
+
+    async fn resolve(&self, server: &str) -> Result<IpAddress, Error> {
+        #[cfg(feature = "proto-ipv4")]
+        let qtype = DnsQueryType::A;
+        #[cfg(not(feature = "proto-ipv4"))]
+        let qtype = DnsQueryType::Aaaa;
+
+        let addrs = self.stack.dns_query(server, qtype).await.map_err(Error::Dns)?;
+        addrs.first().copied().ok_or(Error::NoAddress)
+    }
+}
+
+/// Parse the stratum, and the receive and transmit timestamps of a response.
+fn parse_response(response: &[u8]) -> Result<(u8, UnixTimestamp, UnixTimestamp), Error> {
+    let header = response[0];
+    let stratum = response[1];
+    if header & 0b111 != MODE_SERVER {
+        // Not a valid response, treat it as no response.
+        return Err(Error::Timeout);
+    }
+    if stratum == 0 {
+        return Err(Error::KissOfDeath(response[12..16].try_into().unwrap()));
+    }
+    if header >> 6 == LEAP_UNSYNCHRONIZED {
+        return Err(Error::Unsynchronized);
+    }
+
+    let server_received = read_timestamp(&response[32..40]);
+    let server_sent = read_timestamp(&response[40..48]);
+    match (server_received, server_sent) {
+        (Some(server_received), Some(server_sent)) => Ok((stratum, server_received, server_sent)),
+        _ => Err(Error::Unsynchronized),
+    }
+}
+
+/// Compute the server's time when the response was received, and the round-trip delay of the
+/// request, from the local instants it was sent and received and the server's timestamps.
+///
+/// Both ways are assumed to take the same time. Timestamps in the wrong order, such as a server
+/// processing the request for longer than the round trip, give a zero delay.
+fn server_time(
+    sent: Instant,
+    received: Instant,
+    server_received: UnixTimestamp,
+    server_sent: UnixTimestamp,
+) -> (UnixTimestamp, Duration) {
+    // Time spent by the request on the network, both ways.
+    let processing = server_sent.checked_duration_since(server_received).unwrap_or_default();
+    let delay = (received - sent).checked_sub(processing).unwrap_or_default();
+    (server_sent + delay / 2, delay)
+}
+
+/// Offset of the server's time from the local time, in microseconds. Positive if the local clock
+/// is late.
+fn offset(server: UnixTimestamp, local: UnixTimestamp) -> i64 {
+    server.as_micros() as i64 - local.as_micros() as i64
+}
+
+fn xorshift(state: &mut u64) -> u64 {
+    *state ^= *state << 13;
+    *state ^= *state >> 7;
+    *state ^= *state << 17;
+    *state
+}
+
+/// Parse an NTP timestamp. Returns `None` for the zero timestamp, which means "unknown", and
+/// for times before the Unix epoch.
+///
+/// Timestamps with the most significant bit cleared are assumed to be after the NTP era
+/// rollover in 2036, as recommended by RFC 4330.
+fn read_timestamp(bytes: &[u8]) -> Option<UnixTimestamp> {
+    let secs = u32::from_be_bytes(bytes[0..4].try_into().unwrap()) as u64;
+    let fraction = u32::from_be_bytes(bytes[4..8].try_into().unwrap()) as u64;
+    if secs == 0 && fraction == 0 {
+        return None;
+    }
+
+    let secs = if secs & 0x8000_0000 == 0 {
+        secs + (1 << 32)
+    } else {
+        secs
+    };
+    let micros = (fraction * 1_000_000) >> 32;
+    let unix_secs = secs.checked_sub(NTP_UNIX_OFFSET)?;
+    Some(UnixTimestamp::from_micros(unix_secs * 1_000_000 + micros))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 2024-01-01T00:00:00Z.
+    const UNIX_2024: u64 = 1_704_067_200;
+
+    fn ntp_timestamp(secs: u32, fraction: u32) -> [u8; 8] {
+        let mut bytes = [0; 8];
+        bytes[..4].copy_from_slice(&secs.to_be_bytes());
+        bytes[4..].copy_from_slice(&fraction.to_be_bytes());
+        bytes
+    }
+
+    fn response(header: u8, stratum: u8, received: [u8; 8], sent: [u8; 8]) -> [u8; PACKET_LEN] {
+        let mut response = [0; PACKET_LEN];
+        response[0] = header;
+        response[1] = stratum;
+        response[32..40].copy_from_slice(&received);
+        response[40..48].copy_from_slice(&sent);
+        response
+    }
+
+    #[test]
+    fn timestamp() {
+        let secs = (UNIX_2024 + NTP_UNIX_OFFSET) as u32;
+        assert_eq!(
+            read_timestamp(&ntp_timestamp(secs, 0)),
+            Some(UnixTimestamp::from_secs(UNIX_2024))
+        );
+        // Half a second.
+        assert_eq!(
+            read_timestamp(&ntp_timestamp(secs, 0x8000_0000)),
+            Some(UnixTimestamp::from_micros(UNIX_2024 * 1_000_000 + 500_000))
+        );
+        assert_eq!(
+            read_timestamp(&ntp_timestamp(NTP_UNIX_OFFSET as u32, 0)),
+            Some(UnixTimestamp::from_secs(0))
+        );
+
+        // Zero means unknown.
+        assert_eq!(read_timestamp(&ntp_timestamp(0, 0)), None);
+        // Times between 1968 and the Unix epoch can't be represented.
+        assert_eq!(read_timestamp(&ntp_timestamp(0x8000_0000, 0)), None);
+        assert_eq!(read_timestamp(&ntp_timestamp(NTP_UNIX_OFFSET as u32 - 1, 0)), None);
+    }
+
+    #[test]
+    fn timestamp_era_wrap() {
+        // The era rolls over on 2036-02-07T06:28:16Z.
+        let rollover = (1 << 32) - NTP_UNIX_OFFSET;
+        assert_eq!(
+            read_timestamp(&ntp_timestamp(u32::MAX, 0)),
+            Some(UnixTimestamp::from_secs(rollover - 1))
+        );
+        assert_eq!(
+            read_timestamp(&ntp_timestamp(0, 1)),
+            Some(UnixTimestamp::from_secs(rollover))
+        );
+        assert_eq!(
+            read_timestamp(&ntp_timestamp(1, 0)),
+            Some(UnixTimestamp::from_secs(rollover + 1))
+        );
+        // The last second of the next era that is assumed, in 2104.
+        assert_eq!(
+            read_timestamp(&ntp_timestamp(0x7fff_ffff, 0)),
+            Some(UnixTimestamp::from_secs(rollover + 0x7fff_ffff))
+        );
+    }
+
+    #[test]
+    fn response_parsing() {
+        let secs = (UNIX_2024 + NTP_UNIX_OFFSET) as u32;
+        let (received, sent) = (ntp_timestamp(secs, 0), ntp_timestamp(secs + 1, 0));
+        // Leap indicator 0, version 4, mode 4.
+        let header = 0b00_100_100;
+
+        assert_eq!(
+            parse_response(&response(header, 2, received, sent)),
+            Ok((
+                2,
+                UnixTimestamp::from_secs(UNIX_2024),
+                UnixTimestamp::from_secs(UNIX_2024 + 1)
+            ))
+        );
+
+        // Not a server response, such as our own request.
+        let request = response(REQUEST_HEADER, 2, received, sent);
+        assert_eq!(parse_response(&request), Err(Error::Timeout));
+
+        let mut kiss = response(header, 0, received, sent);
+        kiss[12..16].copy_from_slice(b"RATE");
+        assert_eq!(parse_response(&kiss), Err(Error::KissOfDeath(*b"RATE")));
+
+        let unsynchronized = response(0b11_100_100, 2, received, sent);
+        assert_eq!(parse_response(&unsynchronized), Err(Error::Unsynchronized));
+        let unknown = response(header, 2, received, ntp_timestamp(0, 0));
+        assert_eq!(parse_response(&unknown), Err(Error::Unsynchronized));
+    }
+
+    #[test]
+    fn delay_and_offset() {
+        let server_received = UnixTimestamp::from_secs(UNIX_2024);
+        let server_sent = server_received + Duration::from_millis(20);
+        let sent = Instant::from_secs(10);
+        let received = sent + Duration::from_millis(100);
+
+        // 100 ms round trip, of which 20 ms in the server, so 40 ms each way.
+        let (timestamp, delay) = server_time(sent, received, server_received, server_sent);
+        assert_eq!(delay, Duration::from_millis(80));
+        assert_eq!(timestamp, server_sent + Duration::from_millis(40));
+
+        // Local clock late, then early.
+        let local = timestamp - Duration::from_millis(250);
+        assert_eq!(offset(timestamp, local), 250_000);
+        let local = timestamp + Duration::from_secs(3);
+        assert_eq!(offset(timestamp, local), -3_000_000);
+        assert_eq!(offset(timestamp, timestamp), 0);
+    }
+
+    #[test]
+    fn timestamps_out_of_order() {
+        let server_received = UnixTimestamp::from_secs(UNIX_2024);
+        let sent = Instant::from_secs(10);
+        let received = sent + Duration::from_millis(100);
+
+        // The server transmitted before receiving: no processing time.
+        let server_sent = server_received - Duration::from_millis(5);
+        let (timestamp, delay) = server_time(sent, received, server_received, server_sent);
+        assert_eq!(delay, Duration::from_millis(100));
+        assert_eq!(timestamp, server_sent + Duration::from_millis(50));
+
+        // The server processed the request for longer than the round trip.
+        let server_sent = server_received + Duration::from_millis(150);
+        let (timestamp, delay) = server_time(sent, received, server_received, server_sent);
+        assert_eq!(delay, Duration::from_ticks(0));
+        assert_eq!(timestamp, server_sent);
+
+        // Sent and received in the same tick.
+        let (timestamp, delay) = server_time(sent, sent, server_received, server_received);
+        assert_eq!(delay, Duration::from_ticks(0));
+        assert_eq!(timestamp, server_received);
+    }
+}
@@ -0,0 +1,614 @@
+//! Interface and socket statistics.
+//!
+//! The counters of an interface are updated for every frame it receives or transmits, and
+//! read with [`Interface::stats`](crate::Interface::stats), or [`Stack::stats`](crate::Stack::stats)
+//! for the sum over all the interfaces. Sockets have their own counters, see
+//! [`TcpSocket::stats`](crate::tcp::TcpSocket::stats) and
+//! [`UdpSocket::stats`](crate::udp::UdpSocket::stats).
+//!
+//! The frames are matched with sockets from their headers. The checksums of received frames are
+//! verified only when the driver leaves it to the stack, see its
+//! [`ChecksumCapabilities`], to count the frames smoltcp
+//! drops because of them.
+//!
+//! All counters start at zero, and wrap around on overflow.
+
+use core::cell::{Cell, RefCell};
+
+#[cfg(any(feature = "medium-ethernet", feature = "medium-ip"))]
+use embassy_net_driver::Checksum;
+use embassy_net_driver::ChecksumCapabilities;
+#[cfg(any(feature = "tcp", feature = "udp"))]
+use smoltcp::iface::SocketHandle;
+use smoltcp::iface::SocketSet;
+use smoltcp::phy::Medium;
+#[cfg(feature = "tcp")]
+use smoltcp::socket::tcp;
+#[cfg(feature = "udp")]
+use smoltcp::socket::udp;
+#[cfg(any(feature = "tcp", feature = "udp"))]
+use smoltcp::socket::Socket;
+#[cfg(all(feature = "proto-ipv4", any(feature = "medium-ethernet", feature = "medium-ip")))]
+use smoltcp::wire::Icmpv4Packet;
+#[cfg(all(feature = "proto-ipv6", any(feature = "medium-ethernet", feature = "medium-ip")))]
+use smoltcp::wire::Icmpv6Packet;
+#[cfg(any(feature = "tcp", feature = "udp"))]
+use smoltcp::wire::IpListenEndpoint;
+#[cfg(all(feature = "proto-ipv4", any(feature = "medium-ethernet", feature = "medium-ip")))]
+use smoltcp::wire::Ipv4Packet;
+#[cfg(all(feature = "proto-ipv6", any(feature = "medium-ethernet", feature = "medium-ip")))]
+use smoltcp::wire::Ipv6Packet;
+#[cfg(all(feature = "proto-ipv4", feature = "medium-ethernet"))]
+use smoltcp::wire::{ArpOperation, ArpPacket, ArpRepr};
+#[cfg(feature = "medium-ethernet")]
+use smoltcp::wire::{EthernetFrame, EthernetProtocol};
+#[cfg(any(feature = "medium-ethernet", feature = "medium-ip"))]
+use smoltcp::wire::{IpAddress, IpProtocol, TcpPacket, UdpPacket};
+#[cfg(feature = "tcp")]
+use smoltcp::wire::{IpEndpoint, TcpSeqNumber};
+
+use crate::driver_util::Direction;
+
+/// Statistics of a network interface, or of all the interfaces of a stack.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Stats {
+    /// Frames received from the driver.
+    pub rx_packets: u32,
+    /// Bytes of the frames received from the driver, link-layer headers included.
+    pub rx_bytes: u32,
+    /// Frames transmitted to the driver.
+    pub tx_packets: u32,
+    /// Bytes of the frames transmitted to the driver, link-layer headers included.
+    pub tx_bytes: u32,
+    /// Frames that couldn't be transmitted when they were ready, because the driver had no
+    /// transmit buffer available.
+    ///
+    /// TCP segments are transmitted again later, but UDP datagrams are dropped.
+    pub tx_unavailable: u32,
+    /// Received frames the driver dropped because it had no receive buffer available, as
+    /// reported by [`Driver::rx_dropped`](embassy_net_driver::Driver::rx_dropped).
+    ///
+    /// This is updated when the interface is polled, and stays zero with drivers that don't
+    /// count these drops.
+    pub rx_dropped: u32,
+    /// Received frames dropped because of an invalid IPv4, TCP, UDP or ICMP checksum.
+    ///
+    /// Only checksums that the stack verifies are counted, see the [`ChecksumCapabilities`] of
+    /// the driver.
+    pub rx_checksum_errors: u32,
+    /// Destinations missing from the neighbor cache: ARP requests and NDP neighbor solicitations
+    /// transmitted to resolve their hardware address.
+    pub neighbor_misses: u32,
+    /// TCP segments transmitted again.
+    pub tcp_retransmits: u32,
+    /// Sockets in use, including the internal sockets of DHCP, DNS and SLAAC.
+    pub sockets: u32,
+}
+
+impl Stats {
+    pub(crate) fn add(&mut self, other: &Stats) {
+        self.rx_packets = self.rx_packets.wrapping_add(other.rx_packets);
+        self.rx_bytes = self.rx_bytes.wrapping_add(other.rx_bytes);
+        self.tx_packets = self.tx_packets.wrapping_add(other.tx_packets);
+        self.tx_bytes = self.tx_bytes.wrapping_add(other.tx_bytes);
+        self.tx_unavailable = self.tx_unavailable.wrapping_add(other.tx_unavailable);
+        self.rx_dropped = self.rx_dropped.wrapping_add(other.rx_dropped);
+        self.rx_checksum_errors = self.rx_checksum_errors.wrapping_add(other.rx_checksum_errors);
+        self.neighbor_misses = self.neighbor_misses.wrapping_add(other.neighbor_misses);
+        self.tcp_retransmits = self.tcp_retransmits.wrapping_add(other.tcp_retransmits);
+        self.sockets = self.sockets.wrapping_add(other.sockets);
+    }
+}
+
+/// Statistics of a TCP or UDP socket.
+///
+/// These count the segments and datagrams of the socket seen by its interface, and their
+/// payload bytes: retransmitted TCP segments are counted again, and UDP datagrams that are
+/// dropped because the receive buffer of the socket is full are counted too.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SocketStats {
+    /// Segments or datagrams received.
+    pub rx_packets: u32,
+    /// Payload bytes received.
+    pub rx_bytes: u32,
+    /// Segments or datagrams transmitted.
+    pub tx_packets: u32,
+    /// Payload bytes transmitted.
+    pub tx_bytes: u32,
+    /// TCP segments transmitted again. Always zero for UDP sockets.
+    pub retransmits: u32,
+}
+
+impl SocketStats {
+    #[cfg(all(
+        any(feature = "tcp", feature = "udp"),
+        any(feature = "medium-ethernet", feature = "medium-ip")
+    ))]
+    fn count(&mut self, direction: Direction, len: usize) {
+        let (packets, bytes) = match direction {
+            Direction::Rx => (&mut self.rx_packets, &mut self.rx_bytes),
+            Direction::Tx => (&mut self.tx_packets, &mut self.tx_bytes),
+        };
+        *packets = packets.wrapping_add(1);
+        *bytes = bytes.wrapping_add(len as u32);
+    }
+}
+
+/// The counters of an interface.
+pub(crate) struct Counters {
+    /// Counted by the driver adapter, separately from the frames.
+    pub(crate) tx_unavailable: Cell<u32>,
+    /// Counted by the driver, and read after every poll.
+    pub(crate) rx_dropped: Cell<u32>,
+    inner: RefCell<CountersInner>,
+}
+
+struct CountersInner {
+    stats: Stats,
+    /// The sockets of the interface, with their counters.
+    #[cfg(any(feature = "tcp", feature = "udp"))]
+    sockets: &'static mut [Option<SocketEntry>],
+}
+
+#[cfg(any(feature = "tcp", feature = "udp"))]
+pub(crate) struct SocketEntry {
+    handle: SocketHandle,
+    endpoints: Endpoints,
+    /// End of the highest TCP segment transmitted.
+    #[cfg(feature = "tcp")]
+    snd_max: Option<TcpSeqNumber>,
+    stats: SocketStats,
+}
+
+/// Endpoints of a socket, updated before and after every poll of the interface.
+#[cfg(any(feature = "tcp", feature = "udp"))]
+#[derive(PartialEq)]
+enum Endpoints {
+    #[cfg(feature = "tcp")]
+    Tcp {
+        listen: IpListenEndpoint,
+        local: Option<IpEndpoint>,
+        remote: Option<IpEndpoint>,
+    },
+    #[cfg(feature = "udp")]
+    Udp(IpListenEndpoint),
+}
+
+impl Counters {
+    pub(crate) fn new(
+        #[cfg(any(feature = "tcp", feature = "udp"))] sockets: &'static mut [Option<SocketEntry>],
+    ) -> Self {
+        Self {
+            tx_unavailable: Cell::new(0),
+            rx_dropped: Cell::new(0),
+            inner: RefCell::new(CountersInner {
+                stats: Stats::default(),
+                #[cfg(any(feature = "tcp", feature = "udp"))]
+                sockets,
+            }),
+        }
+    }
+
+    pub(crate) fn stats(&self, sockets: &SocketSet<'_>) -> Stats {
+        let mut stats = self.inner.borrow().stats;
+        stats.tx_unavailable = self.tx_unavailable.get();
+        stats.rx_dropped = self.rx_dropped.get();
+        stats.sockets = sockets.iter().count() as u32;
+        stats
+    }
+
+    #[cfg(any(feature = "tcp", feature = "udp"))]
+    pub(crate) fn socket_stats(&self, handle: SocketHandle) -> SocketStats {
+        let inner = self.inner.borrow();
+        let entry = inner.sockets.iter().flatten().find(|e| e.handle == handle);
+        entry.map(|e| e.stats).unwrap_or_default()
+    }
+
+    /// Start counting for a socket just added to the socket set.
+    #[cfg(any(feature = "tcp", feature = "udp"))]
+    pub(crate) fn add_socket(&self, sockets: &SocketSet<'_>, handle: SocketHandle) {
+        let Some((_, socket)) = sockets.iter().find(|(h, _)| *h == handle) else {
+            return;
+        };
+        let endpoints = match socket {
+            #[cfg(feature = "tcp")]
+            Socket::Tcp(s) => tcp_endpoints(s),
+            #[cfg(feature = "udp")]
+            Socket::Udp(s) => Endpoints::Udp(s.endpoint()),
+            #[allow(unreachable_patterns)]
+            _ => return,
+        };
+        self.insert(SocketEntry {
+            handle,
+            endpoints,
+            #[cfg(feature = "tcp")]
+            snd_max: None,
+            stats: SocketStats::default(),
+        });
+    }
+
+    /// Stop counting for a socket, returning its entry to move it to another interface.
+    #[cfg(any(feature = "tcp", feature = "udp"))]
+    pub(crate) fn remove_socket(&self, handle: SocketHandle) -> Option<SocketEntry> {
+        let mut inner = self.inner.borrow_mut();
+        let slot = inner
+            .sockets
+            .iter_mut()
+            .find(|e| e.as_ref().is_some_and(|e| e.handle == handle))?;
+        slot.take()
+    }
+
+    /// Keep counting for a socket moved from another interface.
+    #[cfg(any(feature = "tcp", feature = "udp"))]
+    pub(crate) fn move_socket(&self, mut entry: SocketEntry, handle: SocketHandle) {
+        entry.handle = handle;
+        self.insert(entry);
+    }
+
+    #[cfg(any(feature = "tcp", feature = "udp"))]
+    fn insert(&self, entry: SocketEntry) {
+        let mut inner = self.inner.borrow_mut();
+        // There's one entry per slot of the socket set, so there's always room.
+        if let Some(slot) = inner.sockets.iter_mut().find(|e| e.is_none()) {
+            *slot = Some(entry);
+        }
+    }
+
+    /// Update the endpoints of the sockets, to match frames with them.
+    ///
+    /// This is called before a poll of the interface, for the connections opened since the last
+    /// one, and after it, for those the poll closed or accepted.
+    #[cfg(any(feature = "tcp", feature = "udp"))]
+    pub(crate) fn update_sockets(&self, sockets: &SocketSet<'_>) {
+        for entry in self.inner.borrow_mut().sockets.iter_mut().flatten() {
+            let endpoints = match entry.endpoints {
+                #[cfg(feature = "tcp")]
+                Endpoints::Tcp { .. } => tcp_endpoints(sockets.get::<tcp::Socket>(entry.handle)),
+                #[cfg(feature = "udp")]
+                Endpoints::Udp(_) => Endpoints::Udp(sockets.get::<udp::Socket>(entry.handle).endpoint()),
+            };
+            if endpoints != entry.endpoints {
+                // A new connection, unless the socket was listening or closed.
+                #[cfg(feature = "tcp")]
+                if matches!(entry.endpoints, Endpoints::Tcp { remote: Some(_), .. }) {
+                    entry.snd_max = None;
+                }
+                entry.endpoints = endpoints;
+            }
+        }
+    }
+
+    /// Count a frame received or transmitted by the interface.
+    #[cfg_attr(
+        not(any(feature = "medium-ethernet", feature = "medium-ip")),
+        allow(unused_variables)
+    )]
+    pub(crate) fn frame(&self, direction: Direction, frame: &[u8], medium: Medium, checksum: &ChecksumCapabilities) {
+        let mut inner = self.inner.borrow_mut();
+        let stats = &mut inner.stats;
+        let len = frame.len() as u32;
+        match direction {
+            Direction::Rx => {
+                stats.rx_packets = stats.rx_packets.wrapping_add(1);
+                stats.rx_bytes = stats.rx_bytes.wrapping_add(len);
+            }
+            Direction::Tx => {
+                stats.tx_packets = stats.tx_packets.wrapping_add(1);
+                stats.tx_bytes = stats.tx_bytes.wrapping_add(len);
+            }
+        }
+
+        #[cfg(any(feature = "medium-ethernet", feature = "medium-ip"))]
+        inner.packet(direction, frame, medium, checksum);
+    }
+}
+
+/// Whether the stack verifies received checksums with this setting, rather than the device.
+#[cfg(any(feature = "medium-ethernet", feature = "medium-ip"))]
+fn verify(checksum: Checksum) -> bool {
+    matches!(checksum, Checksum::Both | Checksum::Rx)
+}
+
+#[cfg(feature = "tcp")]
+fn tcp_endpoints(socket: &tcp::Socket) -> Endpoints {
+    Endpoints::Tcp {
+        listen: socket.listen_endpoint(),
+        local: socket.local_endpoint(),
+        remote: socket.remote_endpoint(),
+    }
+}
+
+#[cfg(any(feature = "medium-ethernet", feature = "medium-ip"))]
+impl CountersInner {
+    fn packet(&mut self, direction: Direction, frame: &[u8], medium: Medium, checksum: &ChecksumCapabilities) {
+        let packet = match medium {
+            #[cfg(feature = "medium-ethernet")]
+            Medium::Ethernet => {
+                let Ok(frame) = EthernetFrame::new_checked(frame) else {
+                    return;
+                };
+                match frame.ethertype() {
+                    #[cfg(feature = "proto-ipv4")]
+                    EthernetProtocol::Arp => {
+                        if direction == Direction::Tx {
+                            self.arp(frame.payload());
+                        }
+                        return;
+                    }
+                    EthernetProtocol::Ipv4 | EthernetProtocol::Ipv6 => frame.payload(),
+                    _ => return,
+                }
+            }
+            #[cfg(feature = "medium-ip")]
+            Medium::Ip => frame,
+            // 6LoWPAN frames are compressed.
+            #[allow(unreachable_patterns)]
+            _ => return,
+        };
+        match packet.first().map(|b| b >> 4) {
+            #[cfg(feature = "proto-ipv4")]
+            Some(4) => self.ipv4(direction, packet, checksum),
+            #[cfg(feature = "proto-ipv6")]
+            Some(6) => self.ipv6(direction, packet, checksum),
+            _ => {}
+        }
+    }
+
+    /// An ARP packet transmitted by the interface.
+    #[cfg(all(feature = "proto-ipv4", feature = "medium-ethernet"))]
+    fn arp(&mut self, packet: &[u8]) {
+        let Ok(ArpRepr::EthernetIpv4 {
+            operation: ArpOperation::Request,
+            source_protocol_addr,
+            target_protocol_addr,
+            ..
+        }) = ArpPacket::new_checked(packet).and_then(|p| ArpRepr::parse(&p))
+        else {
+            return;
+        };
+        // Probes and announcements of link-local addresses aren't lookups.
+        if !source_protocol_addr.is_unspecified() && source_protocol_addr != target_protocol_addr {
+            self.stats.neighbor_misses = self.stats.neighbor_misses.wrapping_add(1);
+        }
+    }
+
+    #[cfg(feature = "proto-ipv4")]
+    fn ipv4(&mut self, direction: Direction, packet: &[u8], checksum: &ChecksumCapabilities) {
+        let Ok(packet) = Ipv4Packet::new_checked(packet) else {
+            return;
+        };
+        if direction == Direction::Rx && verify(checksum.ipv4) && !packet.verify_checksum() {
+            self.checksum_error();
+            return;
+        }
+        // The transport header is only in the first fragment, and its checksum covers all of them.
+        if packet.more_frags() || packet.frag_offset() != 0 {
+            return;
+        }
+        let (src, dst) = (packet.src_addr().into(), packet.dst_addr().into());
+        self.transport(direction, packet.next_header(), src, dst, packet.payload(), checksum);
+    }
+
+    #[cfg(feature = "proto-ipv6")]
+    fn ipv6(&mut self, direction: Direction, packet: &[u8], checksum: &ChecksumCapabilities) {
+        let Ok(packet) = Ipv6Packet::new_checked(packet) else {
+            return;
+        };
+        let (src, dst) = (packet.src_addr(), packet.dst_addr());
+        match packet.next_header() {
+            IpProtocol::Icmpv6 => match direction {
+                Direction::Rx => {
+                    let valid = Icmpv6Packet::new_checked(packet.payload())
+                        .is_ok_and(|p| !verify(checksum.icmpv6) || p.verify_checksum(&src, &dst));
+                    if !valid {
+                        self.checksum_error();
+                    }
+                }
+                // Neighbor solicitations, except for duplicate address detection.
+                Direction::Tx => {
+                    if packet.payload().first() == Some(&135) && !src.is_unspecified() {
+                        self.stats.neighbor_misses = self.stats.neighbor_misses.wrapping_add(1);
+                    }
+                }
+            },
+            protocol => self.transport(direction, protocol, src.into(), dst.into(), packet.payload(), checksum),
+        }
+    }
+
+    fn transport(
+        &mut self,
+        direction: Direction,
+        protocol: IpProtocol,
+        src: IpAddress,
+        dst: IpAddress,
+        payload: &[u8],
+        checksum: &ChecksumCapabilities,
+    ) {
+        let rx_verify = |checksum| direction == Direction::Rx && verify(checksum);
+        match protocol {
+            IpProtocol::Tcp => {
+                let Ok(segment) = TcpPacket::new_checked(payload) else {
+                    return;
+                };
+                if rx_verify(checksum.tcp) && !segment.verify_checksum(&src, &dst) {
+                    self.checksum_error();
+                } else {
+                    #[cfg(feature = "tcp")]
+                    self.tcp(direction, src, dst, &segment);
+                }
+            }
+            IpProtocol::Udp => {
+                let Ok(datagram) = UdpPacket::new_checked(payload) else {
+                    return;
+                };
+                if rx_verify(checksum.udp) && !datagram.verify_checksum(&src, &dst) {
+                    self.checksum_error();
+                } else {
+                    #[cfg(feature = "udp")]
+                    self.udp(direction, src, dst, &datagram);
+                }
+            }
+            #[cfg(feature = "proto-ipv4")]
+            IpProtocol::Icmp => {
+                if rx_verify(checksum.icmpv4) && !Icmpv4Packet::new_checked(payload).is_ok_and(|p| p.verify_checksum())
+                {
+                    self.checksum_error();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    #[cfg(feature = "tcp")]
+    fn tcp(&mut self, direction: Direction, src: IpAddress, dst: IpAddress, segment: &TcpPacket<&[u8]>) {
+        let src = IpEndpoint::new(src, segment.src_port());
+        let dst = IpEndpoint::new(dst, segment.dst_port());
+        let (local, remote) = match direction {
+            Direction::Rx => (dst, src),
+            Direction::Tx => (src, dst),
+        };
+        let connection = |e: &&mut SocketEntry| matches!(e.endpoints, Endpoints::Tcp { local: Some(l), remote: Some(r), .. } if l == local && r == remote);
+        let entry = match self.sockets.iter_mut().flatten().find(connection) {
+            Some(entry) => entry,
+            // A connection request. smoltcp gives it to the first listening socket in the socket set
+            // that accepts it, which gets its endpoints right away so that the rest of the handshake,
+            // in the same poll, is counted too.
+            None if direction == Direction::Rx && segment.syn() && !segment.ack() => {
+                let listener = self
+                    .sockets
+                    .iter_mut()
+                    .flatten()
+                    .filter(|e| {
+                        matches!(e.endpoints, Endpoints::Tcp { listen, remote: None, .. }
+                            if listen.port == local.port && listen.addr.is_none_or(|a| a == local.addr))
+                    })
+                    .min_by_key(|e| e.handle);
+                let Some(entry) = listener else {
+                    return;
+                };
+                #[allow(irrefutable_let_patterns)]
+                if let Endpoints::Tcp {
+                    local: l, remote: r, ..
+                } = &mut entry.endpoints
+                {
+                    *l = Some(local);
+                    *r = Some(remote);
+                }
+                entry
+            }
+            None => return,
+        };
+
+        let len = segment.payload().len();
+        entry.stats.count(direction, len);
+
+        let seq_len = len + segment.syn() as usize + segment.fin() as usize;
+        if direction == Direction::Tx && seq_len > 0 {
+            let seq = segment.seq_number();
+            let end = seq + seq_len;
+            match entry.snd_max {
+                Some(max) if seq < max => {
+                    entry.stats.retransmits = entry.stats.retransmits.wrapping_add(1);
+                    self.stats.tcp_retransmits = self.stats.tcp_retransmits.wrapping_add(1);
+                    if end > max {
+                        entry.snd_max = Some(end);
+                    }
+                }
+                _ => entry.snd_max = Some(end),
+            }
+        }
+    }
+
+    #[cfg(feature = "udp")]
+    fn udp(&mut self, direction: Direction, src: IpAddress, dst: IpAddress, datagram: &UdpPacket<&[u8]>) {
+        let (addr, port) = match direction {
+            Direction::Rx => (dst, datagram.dst_port()),
+            Direction::Tx => (src, datagram.src_port()),
+        };
+        let entry = self.sockets.iter_mut().flatten().find(|e| match e.endpoints {
+            Endpoints::Udp(endpoint) => endpoint.port == port && endpoint.addr.is_none_or(|a| a == addr),
+            #[allow(unreachable_patterns)]
+            _ => false,
+        });
+        if let Some(entry) = entry {
+            entry.stats.count(direction, datagram.payload().len());
+        }
+    }
+    fn checksum_error(&mut self) {
+        self.stats.rx_checksum_errors = self.stats.rx_checksum_errors.wrapping_add(1);
+    }
+}
+
+#[cfg(all(test, feature = "proto-ipv4", feature = "medium-ip", feature = "udp"))]
+mod tests {
+    extern crate std;
+
+    use std::vec;
+    use std::vec::Vec;
+
+    use smoltcp::wire::{Ipv4Address, Ipv4Repr, UdpRepr};
+
+    use super::*;
+
+    fn udp_packet(payload: &[u8]) -> Vec<u8> {
+        let (src, dst) = (Ipv4Address::new(10, 0, 0, 1), Ipv4Address::new(10, 0, 0, 2));
+        let ip = Ipv4Repr {
+            src_addr: src,
+            dst_addr: dst,
+            next_header: IpProtocol::Udp,
+            payload_len: 8 + payload.len(),
+            hop_limit: 64,
+        };
+        let udp = UdpRepr {
+            src_port: 1000,
+            dst_port: 2000,
+        };
+        let caps = smoltcp::phy::ChecksumCapabilities::default();
+        let mut buf = vec![0; ip.buffer_len() + ip.payload_len];
+        ip.emit(&mut Ipv4Packet::new_unchecked(&mut buf[..]), &caps);
+        udp.emit(
+            &mut UdpPacket::new_unchecked(&mut buf[ip.buffer_len()..]),
+            &src.into(),
+            &dst.into(),
+            payload.len(),
+            |b| b.copy_from_slice(payload),
+            &caps,
+        );
+        buf
+    }
+
+    fn checksum_errors(counters: &Counters) -> u32 {
+        counters.inner.borrow().stats.rx_checksum_errors
+    }
+
+    #[test]
+    fn checksum_errors_are_counted() {
+        let counters = Counters::new(&mut []);
+        let verified = ChecksumCapabilities::default();
+
+        let mut packet = udp_packet(b"hello");
+        counters.frame(Direction::Rx, &packet, Medium::Ip, &verified);
+        assert_eq!(checksum_errors(&counters), 0);
+
+        // Invalid UDP checksum.
+        *packet.last_mut().unwrap() ^= 1;
+        counters.frame(Direction::Rx, &packet, Medium::Ip, &verified);
+        assert_eq!(checksum_errors(&counters), 1);
+
+        // Not verified when the device does it, nor when transmitting.
+        let mut offloaded = ChecksumCapabilities::default();
+        offloaded.udp = Checksum::None;
+        counters.frame(Direction::Rx, &packet, Medium::Ip, &offloaded);
+        counters.frame(Direction::Tx, &packet, Medium::Ip, &verified);
+        assert_eq!(checksum_errors(&counters), 1);
+
+        // Invalid IPv4 header checksum.
+        packet[8] ^= 1;
+        counters.frame(Direction::Rx, &packet, Medium::Ip, &offloaded);
+        assert_eq!(checksum_errors(&counters), 2);
+
+        assert_eq!(counters.inner.borrow().stats.rx_packets, 4);
+    }
+}
@@ -2,24 +2,24 @@
 //!
 //! # Listening
 //!
-//! `embassy-net` does not have a `TcpListener`. Instead, individual `TcpSocket`s can be put into
-//! listening mode by calling [`TcpSocket::accept`].
+//! Individual `TcpSocket`s can be put into listening mode by calling [`TcpSocket::accept`].
 //!
 //! Incoming connections when no socket is listening are rejected. To accept many incoming
-//! connections, create many sockets and put them all into listening mode.
+//! connections, create many sockets and put them all into listening mode, or use a
+//! [`TcpListener`], which keeps a pool of sockets listening on the same port.
 
 use core::future::{poll_fn, Future};
 use core::mem;
 use core::task::{Context, Poll};
 
 use embassy_time::Duration;
-use smoltcp::iface::{Interface, SocketHandle};
+use smoltcp::iface::Interface;
 use smoltcp::socket::tcp;
 pub use smoltcp::socket::tcp::State;
 use smoltcp::wire::{IpEndpoint, IpListenEndpoint};
 
 use crate::time::duration_to_smoltcp;
-use crate::Stack;
+use crate::{InterfaceFull, InterfaceId, SocketRef, Stack};
 
 /// Error returned by TcpSocket read/write functions.
 #[derive(PartialEq, Eq, Clone, Copy, Debug)]
@@ -55,6 +55,9 @@ pub enum AcceptError {
     InvalidPort,
     /// The remote host rejected the connection with a RST packet.
     ConnectionReset,
+    /// The interface that has the local address, or the interface of a
+    /// [`TcpListener`], has no room for the socket.
+    NoRoute,
 }
 
 /// A TCP socket.
@@ -179,7 +182,7 @@ impl<'a> TcpSocket<'a> {
         let handle = stack.with_mut(|i| {
             let rx_buffer: &'static mut [u8] = unsafe { mem::transmute(rx_buffer) };
             let tx_buffer: &'static mut [u8] = unsafe { mem::transmute(tx_buffer) };
-            i.sockets.add(tcp::Socket::new(
+            i.add_socket(tcp::Socket::new(
                 tcp::SocketBuffer::new(rx_buffer),
                 tcp::SocketBuffer::new(tx_buffer),
             ))
@@ -239,12 +242,44 @@ impl<'a> TcpSocket<'a> {
         (TcpReader { io: self.io }, TcpWriter { io: self.io })
     }
 
+    /// Bind the socket to a network interface of the stack.
+    ///
+    /// The socket then only sends and receives through this interface, instead of the one picked
+    /// by the routing of the stack. Call this before connecting the socket or accepting a
+    /// connection.
+    ///
+    /// Returns [`InterfaceFull`] if the interface has no room for the socket.
+    pub fn bind_interface(&mut self, iface: InterfaceId) -> Result<(), InterfaceFull> {
+        self.io.stack.with_mut(|i| i.bind_socket(&mut self.io.handle, iface))
+    }
+
+    /// Get the network interface the socket sends and receives through.
+    pub fn interface(&self) -> InterfaceId {
+        self.io.handle.iface
+    }
+
+    /// Get the statistics of the socket.
+    ///
+    /// They count all the connections of the socket. See the [`stats`](crate::stats) module.
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> crate::stats::SocketStats {
+        self.io.stack.with(|i| i.socket_stats(self.io.handle))
+    }
+
     /// Connect to a remote host.
+    ///
+    /// Unless the socket is bound to an interface, it's moved to the one used to reach the remote
+    /// host. Returns [`ConnectError::NoRoute`] if that interface has no room for the socket.
     pub async fn connect<T>(&mut self, remote_endpoint: T) -> Result<(), ConnectError>
     where
         T: Into<IpEndpoint>,
     {
-        let local_port = self.io.stack.with_mut(|i| i.get_local_port());
+        let remote_endpoint = remote_endpoint.into();
+        let local_port = self.io.stack.with_mut(|i| {
+            i.route_socket(&mut self.io.handle, &remote_endpoint.addr)
+                .map_err(|_| ConnectError::NoRoute)?;
+            Ok(i.get_local_port())
+        })?;
 
         match {
             self.io
@@ -272,15 +307,15 @@ impl<'a> TcpSocket<'a> {
     /// Accept a connection from a remote host.
     ///
     /// This function puts the socket in listening mode, and waits until a connection is received.
+    ///
+    /// With a local address, the socket listens on the interface that has it, unless the socket is
+    /// bound to an interface. Without one, it only listens on the interface it's on: the default
+    /// interface, or the one it's bound to.
     pub async fn accept<T>(&mut self, local_endpoint: T) -> Result<(), AcceptError>
     where
         T: Into<IpListenEndpoint>,
     {
-        match self.io.with_mut(|s, _| s.listen(local_endpoint)) {
-            Ok(()) => {}
-            Err(tcp::ListenError::InvalidState) => return Err(AcceptError::InvalidState),
-            Err(tcp::ListenError::Unaddressable) => return Err(AcceptError::InvalidPort),
-        }
+        self.listen(local_endpoint.into())?;
 
         poll_fn(|cx| {
             self.io.with_mut(|s, _| match s.state() {
@@ -294,6 +329,21 @@ impl<'a> TcpSocket<'a> {
         .await
     }
 
+    fn listen(&mut self, local_endpoint: IpListenEndpoint) -> Result<(), AcceptError> {
+        if let Some(addr) = local_endpoint.addr {
+            self.io
+                .stack
+                .with_mut(|i| i.route_socket_local(&mut self.io.handle, &addr))
+                .map_err(|_| AcceptError::NoRoute)?;
+        }
+
+        match self.io.with_mut(|s, _| s.listen(local_endpoint)) {
+            Ok(()) => Ok(()),
+            Err(tcp::ListenError::InvalidState) => Err(AcceptError::InvalidState),
+            Err(tcp::ListenError::Unaddressable) => Err(AcceptError::InvalidPort),
+        }
+    }
+
     /// Wait until the socket becomes readable.
     ///
     /// A socket becomes readable when the receive half of the full-duplex connection is open
@@ -451,7 +501,7 @@ impl<'a> TcpSocket<'a> {
 
 impl<'a> Drop for TcpSocket<'a> {
     fn drop(&mut self) {
-        self.io.stack.with_mut(|i| i.sockets.remove(self.io.handle));
+        self.io.stack.with_mut(|i| i.remove_socket(self.io.handle));
     }
 }
 
@@ -470,24 +520,16 @@ fn _assert_covariant_writer<'a, 'b: 'a>(x: TcpWriter<'b>) -> TcpWriter<'a> {
 #[derive(Copy, Clone)]
 struct TcpIo<'a> {
     stack: Stack<'a>,
-    handle: SocketHandle,
+    handle: SocketRef,
 }
 
 impl<'d> TcpIo<'d> {
     fn with<R>(&self, f: impl FnOnce(&tcp::Socket, &Interface) -> R) -> R {
-        self.stack.with(|i| {
-            let socket = i.sockets.get::<tcp::Socket>(self.handle);
-            f(socket, &i.iface)
-        })
+        self.stack.with(|i| i.with_socket(self.handle, f))
     }
 
     fn with_mut<R>(&self, f: impl FnOnce(&mut tcp::Socket, &mut Interface) -> R) -> R {
-        self.stack.with_mut(|i| {
-            let socket = i.sockets.get_mut::<tcp::Socket>(self.handle);
-            let res = f(socket, &mut i.iface);
-            i.waker.wake();
-            res
-        })
+        self.stack.with_mut(|i| i.with_socket_mut(self.handle, f))
     }
 
     fn poll_read_ready(&self, cx: &mut Context<'_>) -> Poll<()> {
@@ -744,14 +786,15 @@ mod embedded_io_impls {
     }
 }
 
+pub use server::TcpListener;
+
 /// TCP client compatible with `embedded-nal-async` traits.
 pub mod client {
-    use core::cell::{Cell, UnsafeCell};
-    use core::mem::MaybeUninit;
     use core::net::IpAddr;
     use core::ptr::NonNull;
 
     use super::*;
+    use crate::pool::Pool;
 
     /// TCP client connection pool compatible with `embedded-nal-async` traits.
     ///
@@ -813,15 +856,15 @@ pub mod client {
         }
     }
 
-    /// Opened TCP connection in a [`TcpClient`].
+    /// Opened TCP connection in a [`TcpClient`] or [`TcpListener`].
     pub struct TcpConnection<'d, const N: usize, const TX_SZ: usize, const RX_SZ: usize> {
-        socket: TcpSocket<'d>,
+        pub(super) socket: TcpSocket<'d>,
         state: &'d TcpClientState<N, TX_SZ, RX_SZ>,
         bufs: NonNull<([u8; TX_SZ], [u8; RX_SZ])>,
     }
 
     impl<'d, const N: usize, const TX_SZ: usize, const RX_SZ: usize> TcpConnection<'d, N, TX_SZ, RX_SZ> {
-        fn new(stack: Stack<'d>, state: &'d TcpClientState<N, TX_SZ, RX_SZ>) -> Result<Self, Error> {
+        pub(super) fn new(stack: Stack<'d>, state: &'d TcpClientState<N, TX_SZ, RX_SZ>) -> Result<Self, Error> {
             let mut bufs = state.pool.alloc().ok_or(Error::ConnectionReset)?;
             Ok(Self {
                 socket: unsafe { TcpSocket::new(stack, &mut bufs.as_mut().1, &mut bufs.as_mut().0) },
@@ -829,6 +872,13 @@ pub mod client {
                 bufs,
             })
         }
+
+        /// Get the remote endpoint of the connection.
+        ///
+        /// Returns `None` if the connection is closed.
+        pub fn remote_endpoint(&self) -> Option<IpEndpoint> {
+            self.socket.remote_endpoint()
+        }
     }
 
     impl<'d, const N: usize, const TX_SZ: usize, const RX_SZ: usize> Drop for TcpConnection<'d, N, TX_SZ, RX_SZ> {
@@ -868,7 +918,7 @@ pub mod client {
 
     /// State for TcpClient
     pub struct TcpClientState<const N: usize, const TX_SZ: usize, const RX_SZ: usize> {
-        pool: Pool<([u8; TX_SZ], [u8; RX_SZ]), N>,
+        pub(super) pool: Pool<([u8; TX_SZ], [u8; RX_SZ]), N>,
     }
 
     impl<const N: usize, const TX_SZ: usize, const RX_SZ: usize> TcpClientState<N, TX_SZ, RX_SZ> {
@@ -877,44 +927,159 @@ pub mod client {
             Self { pool: Pool::new() }
         }
     }
+}
 
-    struct Pool<T, const N: usize> {
-        used: [Cell<bool>; N],
-        data: [UnsafeCell<MaybeUninit<T>>; N],
+/// TCP server accepting connections with a pool of sockets.
+pub mod server {
+    use super::client::{TcpClientState, TcpConnection};
+    use super::*;
+
+    /// TCP server accepting connections with a pool of sockets, all listening on the same port.
+    ///
+    /// Every free socket of the pool is kept listening, so up to N clients can connect at once, even
+    /// when the application doesn't call [`accept`](Self::accept) for a while.
+    ///
+    /// The pool is a [`TcpClientState`], which can be shared with a [`TcpClient`](super::client::TcpClient).
+    pub struct TcpListener<'d, const N: usize, const TX_SZ: usize = 1024, const RX_SZ: usize = 1024> {
+        stack: Stack<'d>,
+        state: &'d TcpClientState<N, TX_SZ, RX_SZ>,
+        /// Interface the sockets are bound to, if any.
+        iface: Option<InterfaceId>,
+        local_endpoint: IpListenEndpoint,
+        socket_timeout: Option<Duration>,
+        /// Sockets listening, or connected and not accepted yet.
+        backlog: heapless::Vec<TcpConnection<'d, N, TX_SZ, RX_SZ>, N>,
     }
 
-    impl<T, const N: usize> Pool<T, N> {
-        const VALUE: Cell<bool> = Cell::new(false);
-        const UNINIT: UnsafeCell<MaybeUninit<T>> = UnsafeCell::new(MaybeUninit::uninit());
+    impl<'d, const N: usize, const TX_SZ: usize, const RX_SZ: usize> TcpListener<'d, N, TX_SZ, RX_SZ> {
+        /// Create a new `TcpListener`, and start listening on `local_endpoint`.
+        ///
+        /// With a local address, the listener accepts connections on the interface that has it.
+        /// Without one, it accepts them on the default interface, see
+        /// [`new_on_interface`](Self::new_on_interface) for the other interfaces.
+        ///
+        /// Returns [`AcceptError::InvalidPort`] if the port is 0.
+        pub fn new<T>(
+            stack: Stack<'d>,
+            state: &'d TcpClientState<N, TX_SZ, RX_SZ>,
+            local_endpoint: T,
+        ) -> Result<Self, AcceptError>
+        where
+            T: Into<IpListenEndpoint>,
+        {
+            Self::with_interface(stack, None, state, local_endpoint.into())
+        }
+
+        /// Create a new `TcpListener` accepting connections on a network interface of the stack,
+        /// and start listening on `local_endpoint`.
+        ///
+        /// Returns [`AcceptError::NoRoute`] if the interface has no room for the sockets.
+        pub fn new_on_interface<T>(
+            iface: crate::Interface<'d>,
+            state: &'d TcpClientState<N, TX_SZ, RX_SZ>,
+            local_endpoint: T,
+        ) -> Result<Self, AcceptError>
+        where
+            T: Into<IpListenEndpoint>,
+        {
+            Self::with_interface(iface.stack, Some(iface.id()), state, local_endpoint.into())
+        }
 
-        const fn new() -> Self {
-            Self {
-                used: [Self::VALUE; N],
-                data: [Self::UNINIT; N],
-            }
+        fn with_interface(
+            stack: Stack<'d>,
+            iface: Option<InterfaceId>,
+            state: &'d TcpClientState<N, TX_SZ, RX_SZ>,
+            local_endpoint: IpListenEndpoint,
+        ) -> Result<Self, AcceptError> {
+            let mut listener = Self {
+                stack,
+                state,
+                iface,
+                local_endpoint,
+                socket_timeout: None,
+                backlog: heapless::Vec::new(),
+            };
+            listener.listen()?;
+            Ok(listener)
         }
-    }
 
-    impl<T, const N: usize> Pool<T, N> {
-        fn alloc(&self) -> Option<NonNull<T>> {
-            for n in 0..N {
-                // this can't race because Pool is not Sync.
-                if !self.used[n].get() {
-                    self.used[n].set(true);
-                    let p = self.data[n].get() as *mut T;
-                    return Some(unsafe { NonNull::new_unchecked(p) });
+        /// Set the timeout for each connection accepted by this `TcpListener`.
+        ///
+        /// If the timeout is set, the socket will be closed if no data is received for the
+        /// specified duration.
+        pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+            self.socket_timeout = timeout;
+        }
+
+        /// Get the local endpoint the listener accepts connections on.
+        pub fn local_endpoint(&self) -> IpListenEndpoint {
+            self.local_endpoint
+        }
+
+        /// Wait for a connection, and accept it.
+        ///
+        /// If all the sockets of the pool are in use by connections, this waits for one of them
+        /// to be dropped.
+        pub async fn accept(&mut self) -> Result<TcpConnection<'d, N, TX_SZ, RX_SZ>, AcceptError> {
+            let mut connection = poll_fn(|cx| {
+                // Woken when a connection is dropped and frees its socket.
+                self.state.pool.register_waker(cx.waker());
+                loop {
+                    if let Err(e) = self.listen() {
+                        return Poll::Ready(Err(e));
+                    }
+
+                    let mut closed = false;
+                    let mut i = 0;
+                    while i < self.backlog.len() {
+                        match self.backlog[i].socket.state() {
+                            tcp::State::Listen | tcp::State::SynReceived => i += 1,
+                            // Timed out before being established: drop it, to listen again.
+                            tcp::State::Closed => {
+                                self.backlog.remove(i);
+                                closed = true;
+                            }
+                            _ => return Poll::Ready(Ok(self.backlog.remove(i))),
+                        }
+                    }
+                    if !closed {
+                        break;
+                    }
                 }
-            }
-            None
+
+                for c in &self.backlog {
+                    c.socket.io.with_mut(|s, _| s.register_send_waker(cx.waker()));
+                }
+                Poll::Pending
+            })
+            .await?;
+
+            // Replace the socket right away, so connections aren't refused in the meantime.
+            self.listen()?;
+            connection.socket.set_timeout(self.socket_timeout);
+            Ok(connection)
         }
 
-        /// safety: p must be a pointer obtained from self.alloc that hasn't been freed yet.
-        unsafe fn free(&self, p: NonNull<T>) {
-            let origin = self.data.as_ptr() as *mut T;
-            let n = p.as_ptr().offset_from(origin);
-            assert!(n >= 0);
-            assert!((n as usize) < N);
-            self.used[n as usize].set(false);
+        /// Put the free sockets of the pool in listening mode.
+        fn listen(&mut self) -> Result<(), AcceptError> {
+            while !self.backlog.is_full() {
+                let Ok(mut connection) = TcpConnection::new(self.stack, self.state) else {
+                    break;
+                };
+                if let Some(iface) = self.iface {
+                    if connection.socket.bind_interface(iface).is_err() {
+                        // Listen with the sockets the interface has room for.
+                        match self.backlog.is_empty() {
+                            true => return Err(AcceptError::NoRoute),
+                            false => break,
+                        }
+                    }
+                }
+                connection.socket.listen(self.local_endpoint)?;
+                // Can't fail, there's room.
+                let _ = self.backlog.push(connection);
+            }
+            Ok(())
         }
     }
 }
@@ -4,12 +4,12 @@ use core::future::{poll_fn, Future};
 use core::mem;
 use core::task::{Context, Poll};
 
-use smoltcp::iface::{Interface, SocketHandle};
+use smoltcp::iface::Interface;
 use smoltcp::socket::udp;
 pub use smoltcp::socket::udp::{PacketMetadata, UdpMetadata};
-use smoltcp::wire::IpListenEndpoint;
+use smoltcp::wire::{IpAddress, IpListenEndpoint};
 
-use crate::Stack;
+use crate::{InterfaceFull, InterfaceId, SocketRef, Stack};
 
 /// Error returned by [`UdpSocket::bind`].
 #[derive(PartialEq, Eq, Clone, Copy, Debug)]
@@ -42,9 +42,13 @@ pub enum RecvError {
 }
 
 /// An UDP socket.
+///
+/// The socket sends and receives through a single interface: the default interface, the one it's
+/// bound to with [`UdpSocket::bind_interface`], or the one that has its local address. Sending to
+/// an address routed through another interface fails with [`SendError::NoRoute`].
 pub struct UdpSocket<'a> {
     stack: Stack<'a>,
-    handle: SocketHandle,
+    handle: SocketRef,
 }
 
 impl<'a> UdpSocket<'a> {
@@ -61,7 +65,7 @@ impl<'a> UdpSocket<'a> {
             let rx_buffer: &'static mut [u8] = unsafe { mem::transmute(rx_buffer) };
             let tx_meta: &'static mut [PacketMetadata] = unsafe { mem::transmute(tx_meta) };
             let tx_buffer: &'static mut [u8] = unsafe { mem::transmute(tx_buffer) };
-            i.sockets.add(udp::Socket::new(
+            i.add_socket(udp::Socket::new(
                 udp::PacketBuffer::new(rx_meta, rx_buffer),
                 udp::PacketBuffer::new(tx_meta, tx_buffer),
             ))
@@ -71,6 +75,10 @@ impl<'a> UdpSocket<'a> {
     }
 
     /// Bind the socket to a local endpoint.
+    ///
+    /// With a local address, the socket moves to the interface that has it, unless the socket is
+    /// bound to an interface. Without one, it only receives on the interface it's on: the default
+    /// interface, or the one it's bound to.
     pub fn bind<T>(&mut self, endpoint: T) -> Result<(), BindError>
     where
         T: Into<IpListenEndpoint>,
@@ -82,6 +90,12 @@ impl<'a> UdpSocket<'a> {
             endpoint.port = self.stack.with_mut(|i| i.get_local_port());
         }
 
+        if let Some(addr) = endpoint.addr {
+            self.stack
+                .with_mut(|i| i.route_socket_local(&mut self.handle, &addr))
+                .map_err(|_| BindError::NoRoute)?;
+        }
+
         match self.with_mut(|s, _| s.bind(endpoint)) {
             Ok(()) => Ok(()),
             Err(udp::BindError::InvalidState) => Err(BindError::InvalidState),
@@ -89,20 +103,44 @@ impl<'a> UdpSocket<'a> {
         }
     }
 
+    /// Bind the socket to a network interface of the stack.
+    ///
+    /// The socket then only sends and receives through this interface, instead of the one picked
+    /// by the routing of the stack. Call this before binding the socket to a local address.
+    ///
+    /// Returns [`InterfaceFull`] if the interface has no room for the socket.
+    pub fn bind_interface(&mut self, iface: InterfaceId) -> Result<(), InterfaceFull> {
+        self.stack.with_mut(|i| i.bind_socket(&mut self.handle, iface))
+    }
+
+    /// Get the network interface the socket sends and receives through.
+    pub fn interface(&self) -> InterfaceId {
+        self.handle.iface
+    }
+
+    /// Get the statistics of the socket.
+    ///
+    /// See the [`stats`](crate::stats) module.
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> crate::stats::SocketStats {
+        self.stack.with(|i| i.socket_stats(self.handle))
+    }
+
     fn with<R>(&self, f: impl FnOnce(&udp::Socket, &Interface) -> R) -> R {
-        self.stack.with(|i| {
-            let socket = i.sockets.get::<udp::Socket>(self.handle);
-            f(socket, &i.iface)
-        })
+        self.stack.with(|i| i.with_socket(self.handle, f))
     }
 
     fn with_mut<R>(&self, f: impl FnOnce(&mut udp::Socket, &mut Interface) -> R) -> R {
-        self.stack.with_mut(|i| {
-            let socket = i.sockets.get_mut::<udp::Socket>(self.handle);
-            let res = f(socket, &mut i.iface);
-            i.waker.wake();
-            res
-        })
+        self.stack.with_mut(|i| i.with_socket_mut(self.handle, f))
+    }
+
+    /// Check that `remote` is reached through the interface of the socket, unless it's bound to an
+    /// interface. The socket isn't moved to another interface: it would stop receiving on this one.
+    fn check_route(&self, remote: &IpAddress) -> Result<(), SendError> {
+        match self.stack.with(|i| i.is_rerouted(self.handle, remote)) {
+            true => Err(SendError::NoRoute),
+            false => Ok(()),
+        }
     }
 
     /// Wait until the socket becomes readable.
@@ -228,7 +266,8 @@ impl<'a> UdpSocket<'a> {
     ///
     /// If the socket's send buffer is too small to fit `buf`, this method will return `Err(SendError::PacketTooLarge)`
     ///
-    /// When the remote endpoint is not reachable, this method will return `Err(SendError::NoRoute)`
+    /// When the remote endpoint is not reachable, or is routed through another interface than the
+    /// socket's, this method will return `Err(SendError::NoRoute)`
     pub async fn send_to<T>(&self, buf: &[u8], remote_endpoint: T) -> Result<(), SendError>
     where
         T: Into<UdpMetadata>,
@@ -251,11 +290,16 @@ impl<'a> UdpSocket<'a> {
     where
         T: Into<UdpMetadata>,
     {
+        let remote_endpoint: UdpMetadata = remote_endpoint.into();
+
         // Don't need to wake waker in `with_mut` if the buffer will never fit the udp tx_buffer.
         let send_capacity_too_small = self.with(|s, _| s.payload_send_capacity() < buf.len());
         if send_capacity_too_small {
             return Poll::Ready(Err(SendError::PacketTooLarge));
         }
+        if let Err(e) = self.check_route(&remote_endpoint.endpoint.addr) {
+            return Poll::Ready(Err(e));
+        }
 
         self.with_mut(|s, _| match s.send_slice(buf, remote_endpoint) {
             // Entire datagram has been sent
@@ -282,7 +326,8 @@ impl<'a> UdpSocket<'a> {
     ///
     /// If the socket's send buffer is too small to fit `size`, this method will return `Err(SendError::PacketTooLarge)`
     ///
-    /// When the remote endpoint is not reachable, this method will return `Err(SendError::NoRoute)`
+    /// When the remote endpoint is not reachable, or is routed through another interface than the
+    /// socket's, this method will return `Err(SendError::NoRoute)`
     pub async fn send_to_with<T, F, R>(&mut self, size: usize, remote_endpoint: T, f: F) -> Result<R, SendError>
     where
         T: Into<UdpMetadata> + Copy,
@@ -294,8 +339,12 @@ impl<'a> UdpSocket<'a> {
             return Err(SendError::PacketTooLarge);
         }
 
+        let remote_endpoint: UdpMetadata = remote_endpoint.into();
         let mut f = Some(f);
         poll_fn(move |cx| {
+            if let Err(e) = self.check_route(&remote_endpoint.endpoint.addr) {
+                return Poll::Ready(Err(e));
+            }
             self.with_mut(|s, _| {
                 match s.send(size, remote_endpoint) {
                     Ok(buffer) => Poll::Ready(Ok(unwrap!(f.take())(buffer))),
@@ -387,10 +436,290 @@ impl<'a> UdpSocket<'a> {
 
 impl Drop for UdpSocket<'_> {
     fn drop(&mut self) {
-        self.stack.with_mut(|i| i.sockets.remove(self.handle));
+        self.stack.with_mut(|i| i.remove_socket(self.handle));
     }
 }
 
 fn _assert_covariant<'a, 'b: 'a>(x: UdpSocket<'b>) -> UdpSocket<'a> {
     x
 }
+
+/// UDP sockets from a pool, compatible with the `embedded-nal-async` traits.
+pub mod client {
+    use core::net::{IpAddr, SocketAddr};
+    use core::ptr::{addr_of_mut, NonNull};
+
+    use smoltcp::wire::{IpAddress, IpEndpoint};
+
+    use super::*;
+    use crate::pool::Pool;
+
+    /// Error returned by [`UdpClient`] and its sockets.
+    #[derive(PartialEq, Eq, Clone, Copy, Debug)]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+    pub enum Error {
+        /// All the sockets of the pool are in use.
+        NoFreeSocket,
+        /// The local address isn't an address of the stack, or the one of the socket.
+        AddressNotAvailable,
+        /// The socket couldn't be bound.
+        Bind(BindError),
+        /// The datagram couldn't be sent.
+        Send(SendError),
+    }
+
+    impl embedded_io_async::Error for Error {
+        fn kind(&self) -> embedded_io_async::ErrorKind {
+            match self {
+                Error::NoFreeSocket => embedded_io_async::ErrorKind::OutOfMemory,
+                Error::AddressNotAvailable => embedded_io_async::ErrorKind::AddrNotAvailable,
+                Error::Bind(BindError::InvalidState) => embedded_io_async::ErrorKind::Other,
+                Error::Bind(BindError::NoRoute) => embedded_io_async::ErrorKind::AddrNotAvailable,
+                Error::Send(SendError::NoRoute) => embedded_io_async::ErrorKind::NotConnected,
+                Error::Send(SendError::SocketNotBound) => embedded_io_async::ErrorKind::NotConnected,
+                Error::Send(SendError::PacketTooLarge) => embedded_io_async::ErrorKind::InvalidInput,
+            }
+        }
+    }
+
+    /// UDP socket pool compatible with `embedded-nal-async` traits.
+    ///
+    /// The pool is capable of managing up to N sockets, with tx and rx buffers according to TX_SZ and
+    /// RX_SZ, each holding up to M datagrams.
+    pub struct UdpClient<'d, const N: usize, const TX_SZ: usize = 1024, const RX_SZ: usize = 1024, const M: usize = 4> {
+        stack: Stack<'d>,
+        state: &'d UdpClientState<N, TX_SZ, RX_SZ, M>,
+    }
+
+    impl<'d, const N: usize, const TX_SZ: usize, const RX_SZ: usize, const M: usize> UdpClient<'d, N, TX_SZ, RX_SZ, M> {
+        /// Create a new `UdpClient`.
+        pub fn new(stack: Stack<'d>, state: &'d UdpClientState<N, TX_SZ, RX_SZ, M>) -> Self {
+            Self { stack, state }
+        }
+
+        /// Allocate a socket from the pool, and bind it to `local`.
+        fn bind(&self, local: IpListenEndpoint) -> Result<PooledSocket<'d, N, TX_SZ, RX_SZ, M>, Error> {
+            let mut socket = PooledSocket::new(self.stack, self.state)?;
+            socket.socket.bind(local).map_err(Error::Bind)?;
+            Ok(socket)
+        }
+    }
+
+    impl<'d, const N: usize, const TX_SZ: usize, const RX_SZ: usize, const M: usize> embedded_nal_async::UdpStack
+        for UdpClient<'d, N, TX_SZ, RX_SZ, M>
+    {
+        type Error = Error;
+        type Connected = UdpConnection<'d, N, TX_SZ, RX_SZ, M>;
+        type UniquelyBound = UdpBoundSocket<'d, N, TX_SZ, RX_SZ, M>;
+        type MultiplyBound = UdpBoundSocket<'d, N, TX_SZ, RX_SZ, M>;
+
+        async fn connect_from(
+            &self,
+            local: SocketAddr,
+            remote: SocketAddr,
+        ) -> Result<(SocketAddr, Self::Connected), Self::Error> {
+            let remote = to_endpoint(remote);
+            let mut local = to_endpoint(local);
+            if local.addr.is_unspecified() {
+                // The source address of the interface the remote is reached through.
+                local.addr = self
+                    .stack
+                    .with(|i| {
+                        let iface = i.route(&remote.addr);
+                        i.interfaces[iface.0].iface.get_source_address(&remote.addr)
+                    })
+                    .ok_or(Error::AddressNotAvailable)?;
+            }
+
+            let socket = self.bind(local.into())?;
+            local.port = socket.socket.endpoint().port;
+            Ok((to_socket_addr(local), UdpConnection { socket, remote }))
+        }
+
+        async fn bind_single(&self, local: SocketAddr) -> Result<(SocketAddr, Self::UniquelyBound), Self::Error> {
+            let mut local = to_endpoint(local);
+            if local.addr.is_unspecified() {
+                // An address of the default interface.
+                local.addr = self
+                    .stack
+                    .with(|i| {
+                        let addrs = i.interfaces[InterfaceId::DEFAULT.0].iface.ip_addrs();
+                        let mut addrs = addrs.iter().map(|cidr| cidr.address());
+                        addrs.find(|addr| addr.version() == local.addr.version())
+                    })
+                    .ok_or(Error::AddressNotAvailable)?;
+            }
+
+            let socket = self.bind(local.into())?;
+            local.port = socket.socket.endpoint().port;
+            Ok((to_socket_addr(local), UdpBoundSocket { socket }))
+        }
+
+        async fn bind_multiple(&self, local: SocketAddr) -> Result<Self::MultiplyBound, Self::Error> {
+            let local = to_endpoint(local);
+            let local = IpListenEndpoint {
+                addr: (!local.addr.is_unspecified()).then_some(local.addr),
+                port: local.port,
+            };
+            Ok(UdpBoundSocket {
+                socket: self.bind(local)?,
+            })
+        }
+    }
+
+    /// UDP socket of a [`UdpClient`], with a fixed remote address.
+    ///
+    /// Datagrams received from other addresses are dropped.
+    pub struct UdpConnection<'d, const N: usize, const TX_SZ: usize, const RX_SZ: usize, const M: usize> {
+        socket: PooledSocket<'d, N, TX_SZ, RX_SZ, M>,
+        remote: IpEndpoint,
+    }
+
+    impl<const N: usize, const TX_SZ: usize, const RX_SZ: usize, const M: usize> embedded_nal_async::ConnectedUdp
+        for UdpConnection<'_, N, TX_SZ, RX_SZ, M>
+    {
+        type Error = Error;
+
+        async fn send(&mut self, data: &[u8]) -> Result<(), Self::Error> {
+            self.socket.socket.send_to(data, self.remote).await.map_err(Error::Send)
+        }
+
+        async fn receive_into(&mut self, buffer: &mut [u8]) -> Result<usize, Self::Error> {
+            loop {
+                let (n, meta) = self.socket.receive_into(buffer).await;
+                if meta.endpoint == self.remote {
+                    return Ok(n);
+                }
+            }
+        }
+    }
+
+    /// UDP socket of a [`UdpClient`], bound to a local address or port.
+    pub struct UdpBoundSocket<'d, const N: usize, const TX_SZ: usize, const RX_SZ: usize, const M: usize> {
+        socket: PooledSocket<'d, N, TX_SZ, RX_SZ, M>,
+    }
+
+    impl<const N: usize, const TX_SZ: usize, const RX_SZ: usize, const M: usize> embedded_nal_async::UnconnectedUdp
+        for UdpBoundSocket<'_, N, TX_SZ, RX_SZ, M>
+    {
+        type Error = Error;
+
+        async fn send(&mut self, local: SocketAddr, remote: SocketAddr, data: &[u8]) -> Result<(), Self::Error> {
+            let local = to_endpoint(local);
+            let bound = self.socket.socket.endpoint();
+            if local.port != 0 && local.port != bound.port {
+                return Err(Error::AddressNotAvailable);
+            }
+            let mut meta = UdpMetadata::from(to_endpoint(remote));
+            if !local.addr.is_unspecified() {
+                if bound.addr.is_some_and(|addr| addr != local.addr) {
+                    return Err(Error::AddressNotAvailable);
+                }
+                meta.local_address = Some(local.addr);
+            }
+            self.socket.socket.send_to(data, meta).await.map_err(Error::Send)
+        }
+
+        async fn receive_into(&mut self, buffer: &mut [u8]) -> Result<(usize, SocketAddr, SocketAddr), Self::Error> {
+            let (n, meta) = self.socket.receive_into(buffer).await;
+            let bound = self.socket.socket.endpoint();
+            // Received datagrams always have their destination address.
+            let local = IpEndpoint::new(unwrap!(meta.local_address.or(bound.addr)), bound.port);
+            Ok((n, to_socket_addr(local), to_socket_addr(meta.endpoint)))
+        }
+    }
+
+    struct PooledSocket<'d, const N: usize, const TX_SZ: usize, const RX_SZ: usize, const M: usize> {
+        socket: UdpSocket<'d>,
+        state: &'d UdpClientState<N, TX_SZ, RX_SZ, M>,
+        bufs: NonNull<Buffers<TX_SZ, RX_SZ, M>>,
+    }
+
+    impl<'d, const N: usize, const TX_SZ: usize, const RX_SZ: usize, const M: usize> PooledSocket<'d, N, TX_SZ, RX_SZ, M> {
+        fn new(stack: Stack<'d>, state: &'d UdpClientState<N, TX_SZ, RX_SZ, M>) -> Result<Self, Error> {
+            let bufs = state.pool.alloc().ok_or(Error::NoFreeSocket)?;
+            let socket = unsafe {
+                let b = bufs.as_ptr();
+                addr_of_mut!((*b).rx_meta).write([PacketMetadata::EMPTY; M]);
+                addr_of_mut!((*b).tx_meta).write([PacketMetadata::EMPTY; M]);
+                UdpSocket::new(
+                    stack,
+                    &mut (*b).rx_meta,
+                    &mut (*b).rx_buffer,
+                    &mut (*b).tx_meta,
+                    &mut (*b).tx_buffer,
+                )
+            };
+            Ok(Self { socket, state, bufs })
+        }
+
+        /// Receive a datagram, truncated to the size of `buffer`.
+        ///
+        /// Returns the size of the whole datagram.
+        async fn receive_into(&mut self, buffer: &mut [u8]) -> (usize, UdpMetadata) {
+            self.socket
+                .recv_from_with(|data, meta| {
+                    let n = data.len().min(buffer.len());
+                    buffer[..n].copy_from_slice(&data[..n]);
+                    (data.len(), meta)
+                })
+                .await
+        }
+    }
+
+    impl<const N: usize, const TX_SZ: usize, const RX_SZ: usize, const M: usize> Drop
+        for PooledSocket<'_, N, TX_SZ, RX_SZ, M>
+    {
+        fn drop(&mut self) {
+            unsafe {
+                self.socket.close();
+                self.state.pool.free(self.bufs);
+            }
+        }
+    }
+
+    struct Buffers<const TX_SZ: usize, const RX_SZ: usize, const M: usize> {
+        rx_meta: [PacketMetadata; M],
+        rx_buffer: [u8; RX_SZ],
+        tx_meta: [PacketMetadata; M],
+        tx_buffer: [u8; TX_SZ],
+    }
+
+    /// State for UdpClient
+    pub struct UdpClientState<const N: usize, const TX_SZ: usize, const RX_SZ: usize, const M: usize> {
+        pool: Pool<Buffers<TX_SZ, RX_SZ, M>, N>,
+    }
+
+    impl<const N: usize, const TX_SZ: usize, const RX_SZ: usize, const M: usize> UdpClientState<N, TX_SZ, RX_SZ, M> {
+        /// Create a new `UdpClientState`.
+        pub const fn new() -> Self {
+            Self { pool: Pool::new() }
+        }
+    }
+
+    impl<const N: usize, const TX_SZ: usize, const RX_SZ: usize, const M: usize> Default
+        for UdpClientState<N, TX_SZ, RX_SZ, M>
+    {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    fn to_endpoint(addr: SocketAddr) -> IpEndpoint {
+        let ip = match addr.ip() {
+            #[cfg(feature = "proto-ipv4")]
+            IpAddr::V4(addr) => IpAddress::Ipv4(addr),
+            #[cfg(not(feature = "proto-ipv4"))]
+            IpAddr::V4(_) => panic!("ipv4 support not enabled"),
+            #[cfg(feature = "proto-ipv6")]
+            IpAddr::V6(addr) => IpAddress::Ipv6(addr),
+            #[cfg(not(feature = "proto-ipv6"))]
+            IpAddr::V6(_) => panic!("ipv6 support not enabled"),
+        };
+        IpEndpoint::new(ip, addr.port())
+    }
+
+    fn to_socket_addr(endpoint: IpEndpoint) -> SocketAddr {
+        SocketAddr::new(endpoint.addr.into(), endpoint.port)
+    }
+}
@@ -43,6 +43,35 @@ pub struct DateTime {
     pub second: u8,
 }
 
+impl From<DateTime> for embassy_time::DateTime {
+    fn from(dt: DateTime) -> Self {
+        Self {
+            year: dt.year,
+            month: dt.month,
+            day: dt.day,
+            hour: dt.hour,
+            minute: dt.minute,
+            second: dt.second,
+        }
+    }
+}
+
+impl From<embassy_time::DateTime> for DateTime {
+    /// The day of the week is computed from the date. The fields aren't checked, setting the RTC to an
+    /// invalid date fails.
+    fn from(dt: embassy_time::DateTime) -> Self {
+        Self {
+            year: dt.year,
+            month: dt.month,
+            day: dt.day,
+            day_of_week: dt.day_of_week().into(),
+            hour: dt.hour,
+            minute: dt.minute,
+            second: dt.second,
+        }
+    }
+}
+
 /// A day of the week
 #[repr(u8)]
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Ord, PartialOrd, Hash)]
@@ -57,6 +86,34 @@ pub enum DayOfWeek {
     Saturday = 6,
 }
 
+impl From<embassy_time::DayOfWeek> for DayOfWeek {
+    fn from(dotw: embassy_time::DayOfWeek) -> Self {
+        match dotw {
+            embassy_time::DayOfWeek::Monday => DayOfWeek::Monday,
+            embassy_time::DayOfWeek::Tuesday => DayOfWeek::Tuesday,
+            embassy_time::DayOfWeek::Wednesday => DayOfWeek::Wednesday,
+            embassy_time::DayOfWeek::Thursday => DayOfWeek::Thursday,
+            embassy_time::DayOfWeek::Friday => DayOfWeek::Friday,
+            embassy_time::DayOfWeek::Saturday => DayOfWeek::Saturday,
+            embassy_time::DayOfWeek::Sunday => DayOfWeek::Sunday,
+        }
+    }
+}
+
+impl From<DayOfWeek> for embassy_time::DayOfWeek {
+    fn from(dotw: DayOfWeek) -> Self {
+        match dotw {
+            DayOfWeek::Monday => embassy_time::DayOfWeek::Monday,
+            DayOfWeek::Tuesday => embassy_time::DayOfWeek::Tuesday,
+            DayOfWeek::Wednesday => embassy_time::DayOfWeek::Wednesday,
+            DayOfWeek::Thursday => embassy_time::DayOfWeek::Thursday,
+            DayOfWeek::Friday => embassy_time::DayOfWeek::Friday,
+            DayOfWeek::Saturday => embassy_time::DayOfWeek::Saturday,
+            DayOfWeek::Sunday => embassy_time::DayOfWeek::Sunday,
+        }
+    }
+}
+
 fn day_of_week_from_u8(v: u8) -> Result<DayOfWeek, Error> {
     Ok(match v {
         0 => DayOfWeek::Sunday,
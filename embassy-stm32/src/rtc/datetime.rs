@@ -159,6 +159,39 @@ impl From<DateTime> for chrono::NaiveDateTime {
     }
 }
 
+#[cfg(feature = "time")]
+impl From<DateTime> for embassy_time::DateTime {
+    /// The microseconds are dropped.
+    fn from(date_time: DateTime) -> Self {
+        Self {
+            year: date_time.year,
+            month: date_time.month,
+            day: date_time.day,
+            hour: date_time.hour,
+            minute: date_time.minute,
+            second: date_time.second,
+        }
+    }
+}
+
+#[cfg(feature = "time")]
+impl TryFrom<embassy_time::DateTime> for DateTime {
+    type Error = Error;
+
+    fn try_from(date_time: embassy_time::DateTime) -> Result<Self, Self::Error> {
+        Self::from(
+            date_time.year,
+            date_time.month,
+            date_time.day,
+            date_time.day_of_week().into(),
+            date_time.hour,
+            date_time.minute,
+            date_time.second,
+            0,
+        )
+    }
+}
+
 /// A day of the week
 #[repr(u8)]
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Ord, PartialOrd, Hash)]
@@ -196,6 +229,36 @@ impl From<DayOfWeek> for chrono::Weekday {
     }
 }
 
+#[cfg(feature = "time")]
+impl From<embassy_time::DayOfWeek> for DayOfWeek {
+    fn from(weekday: embassy_time::DayOfWeek) -> Self {
+        match weekday {
+            embassy_time::DayOfWeek::Monday => DayOfWeek::Monday,
+            embassy_time::DayOfWeek::Tuesday => DayOfWeek::Tuesday,
+            embassy_time::DayOfWeek::Wednesday => DayOfWeek::Wednesday,
+            embassy_time::DayOfWeek::Thursday => DayOfWeek::Thursday,
+            embassy_time::DayOfWeek::Friday => DayOfWeek::Friday,
+            embassy_time::DayOfWeek::Saturday => DayOfWeek::Saturday,
+            embassy_time::DayOfWeek::Sunday => DayOfWeek::Sunday,
+        }
+    }
+}
+
+#[cfg(feature = "time")]
+impl From<DayOfWeek> for embassy_time::DayOfWeek {
+    fn from(weekday: DayOfWeek) -> Self {
+        match weekday {
+            DayOfWeek::Monday => embassy_time::DayOfWeek::Monday,
+            DayOfWeek::Tuesday => embassy_time::DayOfWeek::Tuesday,
+            DayOfWeek::Wednesday => embassy_time::DayOfWeek::Wednesday,
+            DayOfWeek::Thursday => embassy_time::DayOfWeek::Thursday,
+            DayOfWeek::Friday => embassy_time::DayOfWeek::Friday,
+            DayOfWeek::Saturday => embassy_time::DayOfWeek::Saturday,
+            DayOfWeek::Sunday => embassy_time::DayOfWeek::Sunday,
+        }
+    }
+}
+
 pub(super) const fn day_of_week_from_u8(v: u8) -> Result<DayOfWeek, Error> {
     Ok(match v {
         1 => DayOfWeek::Monday,
@@ -0,0 +1,199 @@
+//! A synchronization primitive for making a fixed number of tasks wait for each other.
+use core::cell::RefCell;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+use crate::blocking_mutex::raw::RawMutex;
+use crate::blocking_mutex::Mutex;
+use crate::waitqueue::MultiWakerRegistration;
+
+/// A barrier enables `N` tasks to synchronize the beginning of some computation.
+///
+/// Each task calls [`Barrier::wait`], which will wait until all `N` tasks have called it. Once the
+/// last task arrives, all waiting tasks are released at once and the barrier is reset, so it can be
+/// reused for the next phase.
+///
+/// ```
+/// use futures_executor::block_on;
+/// use embassy_sync::barrier::Barrier;
+/// use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+///
+/// static BARRIER: Barrier<CriticalSectionRawMutex, 1> = Barrier::new();
+///
+/// let f = async {
+///     // With a single participant, the caller is always the leader.
+///     assert!(BARRIER.wait().await.is_leader());
+///     assert!(BARRIER.wait().await.is_leader());
+/// };
+/// block_on(f);
+/// ```
+pub struct Barrier<M: RawMutex, const N: usize> {
+    state: Mutex<M, RefCell<BarrierState<N>>>,
+}
+
+struct BarrierState<const N: usize> {
+    /// Number of tasks that arrived in the current generation.
+    count: usize,
+    /// Incremented each time the barrier releases its waiters.
+    generation: usize,
+    wakers: MultiWakerRegistration<N>,
+}
+
+impl<M: RawMutex, const N: usize> Barrier<M, N> {
+    /// Create a new `Barrier` for `N` tasks.
+    pub const fn new() -> Self {
+        Self {
+            state: Mutex::new(RefCell::new(BarrierState {
+                count: 0,
+                generation: 0,
+                wakers: MultiWakerRegistration::new(),
+            })),
+        }
+    }
+
+    /// Wait until all `N` tasks have reached the barrier.
+    ///
+    /// Exactly one of the released tasks, the last one to arrive, is designated as the
+    /// leader, see [`BarrierWaitResult::is_leader`].
+    ///
+    /// If the returned future is dropped before the barrier is released, the task no longer
+    /// counts as having arrived.
+    pub fn wait(&self) -> impl Future<Output = BarrierWaitResult> + '_ {
+        BarrierWait {
+            barrier: self,
+            generation: None,
+        }
+    }
+
+    /// Returns the number of tasks currently waiting at the barrier.
+    pub fn waiting(&self) -> usize {
+        self.state.lock(|s| s.borrow().count)
+    }
+}
+
+impl<M: RawMutex, const N: usize> Default for Barrier<M, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The result of [`Barrier::wait`].
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct BarrierWaitResult {
+    leader: bool,
+}
+
+impl BarrierWaitResult {
+    /// Returns `true` if this task was the last to arrive and thereby released the barrier.
+    pub fn is_leader(&self) -> bool {
+        self.leader
+    }
+}
+
+struct BarrierWait<'a, M: RawMutex, const N: usize> {
+    barrier: &'a Barrier<M, N>,
+    /// The generation this task arrived in, once it has arrived.
+    generation: Option<usize>,
+}
+
+impl<M: RawMutex, const N: usize> Future for BarrierWait<'_, M, N> {
+    type Output = BarrierWaitResult;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let generation = self.generation;
+        let (generation, result) = self.barrier.state.lock(|s| {
+            let mut s = s.borrow_mut();
+            match generation {
+                Some(g) if g != s.generation => (None, Poll::Ready(BarrierWaitResult { leader: false })),
+                Some(g) => {
+                    s.wakers.register(cx.waker());
+                    (Some(g), Poll::Pending)
+                }
+                None => {
+                    s.count += 1;
+                    if s.count >= N {
+                        s.count = 0;
+                        s.generation = s.generation.wrapping_add(1);
+                        s.wakers.wake();
+                        (None, Poll::Ready(BarrierWaitResult { leader: true }))
+                    } else {
+                        s.wakers.register(cx.waker());
+                        (Some(s.generation), Poll::Pending)
+                    }
+                }
+            }
+        });
+        self.generation = generation;
+        result
+    }
+}
+
+impl<M: RawMutex, const N: usize> Drop for BarrierWait<'_, M, N> {
+    fn drop(&mut self) {
+        if let Some(g) = self.generation {
+            self.barrier.state.lock(|s| {
+                let mut s = s.borrow_mut();
+                if s.generation == g {
+                    s.count -= 1;
+                }
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::pin::pin;
+    use core::task::Poll;
+
+    use futures_util::poll;
+
+    use super::*;
+    use crate::blocking_mutex::raw::NoopRawMutex;
+
+    #[futures_test::test]
+    async fn releases_all_waiters() {
+        let barrier = Barrier::<NoopRawMutex, 3>::new();
+
+        let mut a = pin!(barrier.wait());
+        let mut b = pin!(barrier.wait());
+        assert!(poll!(a.as_mut()).is_pending());
+        assert!(poll!(b.as_mut()).is_pending());
+        assert_eq!(barrier.waiting(), 2);
+
+        assert!(barrier.wait().await.is_leader());
+        assert_eq!(barrier.waiting(), 0);
+
+        assert_eq!(poll!(a.as_mut()), Poll::Ready(BarrierWaitResult { leader: false }));
+        assert_eq!(poll!(b.as_mut()), Poll::Ready(BarrierWaitResult { leader: false }));
+    }
+
+    #[futures_test::test]
+    async fn reusable() {
+        let barrier = Barrier::<NoopRawMutex, 2>::new();
+
+        for _ in 0..3 {
+            let mut a = pin!(barrier.wait());
+            assert!(poll!(a.as_mut()).is_pending());
+            assert!(barrier.wait().await.is_leader());
+            assert!(poll!(a.as_mut()).is_ready());
+        }
+    }
+
+    #[futures_test::test]
+    async fn cancelled_waiter_is_not_counted() {
+        let barrier = Barrier::<NoopRawMutex, 2>::new();
+
+        {
+            let mut a = pin!(barrier.wait());
+            assert!(poll!(a.as_mut()).is_pending());
+            assert_eq!(barrier.waiting(), 1);
+        }
+        assert_eq!(barrier.waiting(), 0);
+
+        let mut b = pin!(barrier.wait());
+        assert!(poll!(b.as_mut()).is_pending());
+    }
+}
@@ -458,6 +458,123 @@ impl<'ch, M: RawMutex, T, const N: usize> From<SendFuture<'ch, M, T, N>> for Dyn
     }
 }
 
+/// A set of [`DynamicReceiver`]s that can be received from as one.
+///
+/// [`ChannelSet::receive`] waits for whichever channel has a value first, and returns the
+/// value together with the index of the channel in the set. Channels are checked in
+/// round-robin order, starting after the channel that produced the previous value, so one busy
+/// channel cannot starve the others.
+///
+/// Note that each [`Channel`] stores the waker of a single receiving task. Receiving from a channel in
+/// the set from a different task at the same time works, but causes the two tasks to wake each other
+/// repeatedly.
+///
+/// ```
+/// use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+/// use embassy_sync::channel::{Channel, ChannelSet};
+///
+/// let a = Channel::<NoopRawMutex, u32, 2>::new();
+/// let b = Channel::<NoopRawMutex, u32, 2>::new();
+/// let receivers = [a.dyn_receiver(), b.dyn_receiver()];
+/// let mut set = ChannelSet::new(&receivers);
+///
+/// b.try_send(1).unwrap();
+/// assert_eq!(set.try_receive(), Ok((1, 1)));
+/// ```
+pub struct ChannelSet<'a, T> {
+    receivers: &'a [DynamicReceiver<'a, T>],
+    next: usize,
+}
+
+impl<'a, T> ChannelSet<'a, T> {
+    /// Create a new set of the given receivers.
+    pub const fn new(receivers: &'a [DynamicReceiver<'a, T>]) -> Self {
+        Self { receivers, next: 0 }
+    }
+
+    /// Receive the next value from any channel in the set.
+    ///
+    /// Returns the index of the channel in the set along with the value.
+    pub fn receive(&mut self) -> ChannelSetReceiveFuture<'_, 'a, T> {
+        ChannelSetReceiveFuture { set: self }
+    }
+
+    /// Attempt to immediately receive the next value from any channel in the set.
+    ///
+    /// Returns the index of the channel in the set along with the value.
+    pub fn try_receive(&mut self) -> Result<(usize, T), TryReceiveError> {
+        self.select(|r| r.try_receive().ok()).ok_or(TryReceiveError::Empty)
+    }
+
+    /// Poll the channels in the set for the next item.
+    ///
+    /// Returns the index of the channel in the set along with the value.
+    pub fn poll_receive(&mut self, cx: &mut Context<'_>) -> Poll<(usize, T)> {
+        match self.select(|r| match r.poll_receive(cx) {
+            Poll::Ready(v) => Some(v),
+            Poll::Pending => None,
+        }) {
+            Some(v) => Poll::Ready(v),
+            None => Poll::Pending,
+        }
+    }
+
+    /// Returns the receivers in the set.
+    pub fn receivers(&self) -> &'a [DynamicReceiver<'a, T>] {
+        self.receivers
+    }
+
+    fn select(&mut self, mut f: impl FnMut(&DynamicReceiver<'a, T>) -> Option<T>) -> Option<(usize, T)> {
+        let len = self.receivers.len();
+        for i in 0..len {
+            let index = (self.next + i) % len;
+            if let Some(v) = f(&self.receivers[index]) {
+                self.next = (index + 1) % len;
+                return Some((index, v));
+            }
+        }
+        None
+    }
+}
+
+/// Future returned by [`ChannelSet::receive`].
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct ChannelSetReceiveFuture<'s, 'a, T> {
+    set: &'s mut ChannelSet<'a, T>,
+}
+
+impl<T> Future for ChannelSetReceiveFuture<'_, '_, T> {
+    type Output = (usize, T);
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.set.poll_receive(cx)
+    }
+}
+
+/// Receive the next value from whichever of the `receivers` has one available first.
+///
+/// Returns the index of the receiver in the slice along with the value. Receivers are checked
+/// in slice order; use a [`ChannelSet`] to receive repeatedly with round-robin fairness.
+pub fn select_recv<'a, T>(receivers: &'a [DynamicReceiver<'a, T>]) -> SelectRecvFuture<'a, T> {
+    SelectRecvFuture {
+        set: ChannelSet::new(receivers),
+    }
+}
+
+/// Future returned by [`select_recv`].
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct SelectRecvFuture<'a, T> {
+    set: ChannelSet<'a, T>,
+}
+
+impl<T> Future for SelectRecvFuture<'_, T> {
+    type Output = (usize, T);
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.set.poll_receive(cx)
+    }
+}
+
 pub(crate) trait DynamicChannel<T> {
     fn try_send_with_context(&self, message: T, cx: Option<&mut Context<'_>>) -> Result<(), TrySendError<T>>;
 
@@ -884,6 +1001,52 @@ mod tests {
         assert_eq!(r.try_receive().unwrap(), 1);
     }
 
+    #[test]
+    fn channel_set_round_robin() {
+        let a = Channel::<NoopRawMutex, u32, 3>::new();
+        let b = Channel::<NoopRawMutex, u32, 3>::new();
+        let c = Channel::<NoopRawMutex, u32, 3>::new();
+        let receivers = [a.dyn_receiver(), b.dyn_receiver(), c.dyn_receiver()];
+        let mut set = ChannelSet::new(&receivers);
+
+        assert_eq!(set.try_receive(), Err(TryReceiveError::Empty));
+
+        for i in 0..3 {
+            a.try_send(i).unwrap();
+        }
+        c.try_send(10).unwrap();
+
+        // `a` is busy, but `c` still gets its turn.
+        assert_eq!(set.try_receive(), Ok((0, 0)));
+        assert_eq!(set.try_receive(), Ok((2, 10)));
+        assert_eq!(set.try_receive(), Ok((0, 1)));
+        assert_eq!(set.try_receive(), Ok((0, 2)));
+        assert_eq!(set.try_receive(), Err(TryReceiveError::Empty));
+    }
+
+    #[futures_test::test]
+    async fn channel_set_receive_waits() {
+        let executor = ThreadPool::new().unwrap();
+
+        static A: StaticCell<Channel<CriticalSectionRawMutex, u32, 3>> = StaticCell::new();
+        static B: StaticCell<Channel<CriticalSectionRawMutex, u32, 3>> = StaticCell::new();
+        let a = &*A.init(Channel::new());
+        let b = &*B.init(Channel::new());
+        let receivers = [a.dyn_receiver(), b.dyn_receiver()];
+        let mut set = ChannelSet::new(&receivers);
+
+        assert!(executor
+            .spawn(async move {
+                Delay::new(Duration::from_millis(50)).await;
+                assert!(b.try_send(7).is_ok());
+            })
+            .is_ok());
+        assert_eq!(set.receive().await, (1, 7));
+
+        assert!(a.try_send(3).is_ok());
+        assert_eq!(select_recv(&receivers).await, (0, 3));
+    }
+
     #[futures_test::test]
     async fn receiver_receives_given_try_send_async() {
         let executor = ThreadPool::new().unwrap();
@@ -0,0 +1,171 @@
+//! A synchronization primitive for waiting until a number of events have happened.
+use core::cell::RefCell;
+use core::future::{poll_fn, Future};
+use core::task::{Context, Poll};
+
+use crate::blocking_mutex::raw::RawMutex;
+use crate::blocking_mutex::Mutex;
+use crate::waitqueue::MultiWakerRegistration;
+
+/// A count-down latch.
+///
+/// The latch is created with an initial count. Tasks call [`CountDownLatch::count_down`] to decrement
+/// it, and any number of tasks can [`wait`](CountDownLatch::wait) until the count reaches zero. Once the
+/// count is zero, the latch stays released until it is [`reset`](CountDownLatch::reset).
+///
+/// Up to `N` waiting tasks are tracked individually. Additional waiters are still supported, but will
+/// cause all waiting tasks to be woken spuriously when the waker storage overflows.
+///
+/// ```
+/// use futures_executor::block_on;
+/// use embassy_sync::latch::CountDownLatch;
+/// use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+///
+/// static STARTUP: CountDownLatch<CriticalSectionRawMutex, 4> = CountDownLatch::new(2);
+///
+/// let f = async {
+///     STARTUP.count_down(); // radio ready
+///     STARTUP.count_down(); // sensors ready
+///     STARTUP.wait().await;
+/// };
+/// block_on(f);
+/// ```
+pub struct CountDownLatch<M: RawMutex, const N: usize> {
+    state: Mutex<M, RefCell<LatchState<N>>>,
+}
+
+struct LatchState<const N: usize> {
+    count: usize,
+    wakers: MultiWakerRegistration<N>,
+}
+
+impl<M: RawMutex, const N: usize> CountDownLatch<M, N> {
+    /// Create a new `CountDownLatch` with the given initial count.
+    pub const fn new(count: usize) -> Self {
+        Self {
+            state: Mutex::new(RefCell::new(LatchState {
+                count,
+                wakers: MultiWakerRegistration::new(),
+            })),
+        }
+    }
+
+    /// Decrement the count by one, releasing all waiters if it reaches zero.
+    ///
+    /// Does nothing if the count is already zero.
+    pub fn count_down(&self) {
+        self.state.lock(|s| {
+            let mut s = s.borrow_mut();
+            if s.count > 0 {
+                s.count -= 1;
+                if s.count == 0 {
+                    s.wakers.wake();
+                }
+            }
+        })
+    }
+
+    /// Returns the current count.
+    pub fn count(&self) -> usize {
+        self.state.lock(|s| s.borrow().count)
+    }
+
+    /// Returns `true` if the count has reached zero.
+    pub fn is_released(&self) -> bool {
+        self.count() == 0
+    }
+
+    /// Set the count to a new value.
+    ///
+    /// Setting the count to zero releases all waiters.
+    pub fn reset(&self, count: usize) {
+        self.state.lock(|s| {
+            let mut s = s.borrow_mut();
+            s.count = count;
+            if count == 0 {
+                s.wakers.wake();
+            }
+        })
+    }
+
+    /// Wait until the count reaches zero.
+    pub fn wait(&self) -> impl Future<Output = ()> + '_ {
+        poll_fn(|cx| self.poll_wait(cx))
+    }
+
+    /// Poll the latch, registering the waker if the count has not reached zero yet.
+    pub fn poll_wait(&self, cx: &mut Context<'_>) -> Poll<()> {
+        self.state.lock(|s| {
+            let mut s = s.borrow_mut();
+            if s.count == 0 {
+                Poll::Ready(())
+            } else {
+                s.wakers.register(cx.waker());
+                Poll::Pending
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::pin::pin;
+
+    use futures_util::poll;
+
+    use super::*;
+    use crate::blocking_mutex::raw::NoopRawMutex;
+
+    #[futures_test::test]
+    async fn releases_when_zero() {
+        let latch = CountDownLatch::<NoopRawMutex, 2>::new(2);
+
+        let mut a = pin!(latch.wait());
+        let mut b = pin!(latch.wait());
+        assert!(poll!(a.as_mut()).is_pending());
+        assert!(poll!(b.as_mut()).is_pending());
+
+        latch.count_down();
+        assert_eq!(latch.count(), 1);
+        assert!(poll!(a.as_mut()).is_pending());
+
+        latch.count_down();
+        assert!(latch.is_released());
+        assert!(poll!(a.as_mut()).is_ready());
+        assert!(poll!(b.as_mut()).is_ready());
+
+        // Stays released.
+        latch.count_down();
+        latch.wait().await;
+    }
+
+    #[futures_test::test]
+    async fn more_waiters_than_slots() {
+        let latch = CountDownLatch::<NoopRawMutex, 1>::new(1);
+
+        let mut a = pin!(latch.wait());
+        let mut b = pin!(latch.wait());
+        let mut c = pin!(latch.wait());
+        assert!(poll!(a.as_mut()).is_pending());
+        assert!(poll!(b.as_mut()).is_pending());
+        assert!(poll!(c.as_mut()).is_pending());
+
+        latch.count_down();
+        assert!(poll!(a.as_mut()).is_ready());
+        assert!(poll!(b.as_mut()).is_ready());
+        assert!(poll!(c.as_mut()).is_ready());
+    }
+
+    #[futures_test::test]
+    async fn reset() {
+        let latch = CountDownLatch::<NoopRawMutex, 2>::new(0);
+        latch.wait().await;
+
+        latch.reset(1);
+        let mut a = pin!(latch.wait());
+        assert!(poll!(a.as_mut()).is_pending());
+
+        latch.reset(0);
+        assert!(poll!(a.as_mut()).is_ready());
+    }
+}
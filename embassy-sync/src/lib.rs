@@ -10,14 +10,17 @@ pub(crate) mod fmt;
 // internal use
 mod ring_buffer;
 
+pub mod barrier;
 pub mod blocking_mutex;
 pub mod channel;
+pub mod latch;
 pub mod lazy_lock;
 pub mod mutex;
 pub mod once_lock;
 pub mod pipe;
 pub mod priority_channel;
 pub mod pubsub;
+pub mod rwlock;
 pub mod semaphore;
 pub mod signal;
 pub mod waitqueue;
@@ -0,0 +1,802 @@
+//! Async read-write lock.
+//!
+//! This module provides a read-write lock that can be used to synchronize data between asynchronous tasks.
+use core::cell::{RefCell, UnsafeCell};
+use core::future::Future;
+use core::ops::{Deref, DerefMut};
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use core::{fmt, mem};
+
+use crate::blocking_mutex::raw::RawMutex;
+use crate::blocking_mutex::Mutex as BlockingMutex;
+use crate::waitqueue::{MultiWakerRegistration, WakerRegistration};
+
+/// Error returned by [`RwLock::try_read`] and [`RwLock::try_write`]
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct TryLockError;
+
+struct State<const N: usize> {
+    /// Number of active readers.
+    readers: usize,
+    /// Whether a writer currently holds the lock.
+    writer: bool,
+    /// Number of writers waiting to acquire the lock.
+    writers_waiting: usize,
+    read_wakers: MultiWakerRegistration<N>,
+    write_waker: WakerRegistration,
+}
+
+impl<const N: usize> State<N> {
+    fn can_read(&self) -> bool {
+        !self.writer && self.writers_waiting == 0
+    }
+
+    fn can_write(&self) -> bool {
+        !self.writer && self.readers == 0
+    }
+
+    fn unlock_read(&mut self) {
+        self.readers -= 1;
+        if self.readers == 0 {
+            self.write_waker.wake();
+        }
+    }
+
+    fn unlock_write(&mut self) {
+        self.writer = false;
+        if self.writers_waiting > 0 {
+            self.write_waker.wake();
+        } else {
+            self.read_wakers.wake();
+        }
+    }
+}
+
+/// Async read-write lock.
+///
+/// The lock allows any number of readers or at most one writer at any point in time.
+///
+/// The lock prefers writers: once a writer is waiting, new readers are held back until
+/// that writer has acquired and released the lock. This ensures a steady stream of readers
+/// cannot starve writers.
+///
+/// `N` is the maximum number of readers that can wait for the lock at the same time without
+/// spurious wakeups. If more readers are waiting, all of them are woken when another one has to be
+/// registered, and those still blocked register again.
+///
+/// The lock is generic over a blocking [`RawMutex`](crate::blocking_mutex::raw::RawMutex).
+/// The raw mutex is used to guard access to the internal lock state. It is held for very
+/// short periods only, while locking and unlocking. It is *not* held for the entire time
+/// the async RwLock is locked.
+///
+/// Which implementation you select depends on the context in which you're using the lock.
+///
+/// Use [`CriticalSectionRawMutex`](crate::blocking_mutex::raw::CriticalSectionRawMutex) when data can be shared between threads and interrupts.
+///
+/// Use [`NoopRawMutex`](crate::blocking_mutex::raw::NoopRawMutex) when data is only shared between tasks running on the same executor.
+///
+/// Use [`ThreadModeRawMutex`](crate::blocking_mutex::raw::ThreadModeRawMutex) when data is shared between tasks running on the same executor but you want a singleton.
+///
+pub struct RwLock<M, T, const N: usize>
+where
+    M: RawMutex,
+    T: ?Sized,
+{
+    state: BlockingMutex<M, RefCell<State<N>>>,
+    inner: UnsafeCell<T>,
+}
+
+unsafe impl<M: RawMutex + Send, T: ?Sized + Send, const N: usize> Send for RwLock<M, T, N> {}
+unsafe impl<M: RawMutex + Sync, T: ?Sized + Send + Sync, const N: usize> Sync for RwLock<M, T, N> {}
+
+/// Async read-write lock.
+impl<M, T, const N: usize> RwLock<M, T, N>
+where
+    M: RawMutex,
+{
+    /// Create a new read-write lock with the given value.
+    pub const fn new(value: T) -> Self {
+        Self {
+            inner: UnsafeCell::new(value),
+            state: BlockingMutex::new(RefCell::new(State {
+                readers: 0,
+                writer: false,
+                writers_waiting: 0,
+                read_wakers: MultiWakerRegistration::new(),
+                write_waker: WakerRegistration::new(),
+            })),
+        }
+    }
+}
+
+impl<M, T, const N: usize> RwLock<M, T, N>
+where
+    M: RawMutex,
+    T: ?Sized,
+{
+    /// Lock the read-write lock for reading.
+    ///
+    /// This will wait until no writer holds or is waiting for the lock.
+    pub fn read(&self) -> impl Future<Output = RwLockReadGuard<'_, M, T, N>> {
+        core::future::poll_fn(|cx| {
+            let ready = self.state.lock(|s| {
+                let mut s = s.borrow_mut();
+                if s.can_read() {
+                    s.readers += 1;
+                    true
+                } else {
+                    s.read_wakers.register(cx.waker());
+                    false
+                }
+            });
+
+            if ready {
+                Poll::Ready(RwLockReadGuard { rwlock: self })
+            } else {
+                Poll::Pending
+            }
+        })
+    }
+
+    /// Lock the read-write lock for writing.
+    ///
+    /// This will wait until all readers and any other writer have released the lock.
+    pub fn write(&self) -> impl Future<Output = RwLockWriteGuard<'_, M, T, N>> {
+        WriteFuture {
+            rwlock: self,
+            waiting: false,
+        }
+    }
+
+    /// Attempt to immediately lock the read-write lock for reading.
+    ///
+    /// If a writer holds or is waiting for the lock, this will return an error instead of waiting.
+    pub fn try_read(&self) -> Result<RwLockReadGuard<'_, M, T, N>, TryLockError> {
+        self.state.lock(|s| {
+            let mut s = s.borrow_mut();
+            if s.can_read() {
+                s.readers += 1;
+                Ok(())
+            } else {
+                Err(TryLockError)
+            }
+        })?;
+
+        Ok(RwLockReadGuard { rwlock: self })
+    }
+
+    /// Attempt to immediately lock the read-write lock for writing.
+    ///
+    /// If the lock is already held, this will return an error instead of waiting.
+    pub fn try_write(&self) -> Result<RwLockWriteGuard<'_, M, T, N>, TryLockError> {
+        self.state.lock(|s| {
+            let mut s = s.borrow_mut();
+            if s.can_write() {
+                s.writer = true;
+                Ok(())
+            } else {
+                Err(TryLockError)
+            }
+        })?;
+
+        Ok(RwLockWriteGuard { rwlock: self })
+    }
+
+    /// Consumes this read-write lock, returning the underlying data.
+    pub fn into_inner(self) -> T
+    where
+        T: Sized,
+    {
+        self.inner.into_inner()
+    }
+
+    /// Returns a mutable reference to the underlying data.
+    ///
+    /// Since this call borrows the RwLock mutably, no actual locking needs to
+    /// take place -- the mutable borrow statically guarantees no locks exist.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.inner.get_mut()
+    }
+}
+
+impl<M: RawMutex, T, const N: usize> From<T> for RwLock<M, T, N> {
+    fn from(from: T) -> Self {
+        Self::new(from)
+    }
+}
+
+impl<M, T, const N: usize> Default for RwLock<M, T, N>
+where
+    M: RawMutex,
+    T: Default,
+{
+    fn default() -> Self {
+        Self::new(Default::default())
+    }
+}
+
+impl<M, T, const N: usize> fmt::Debug for RwLock<M, T, N>
+where
+    M: RawMutex,
+    T: ?Sized + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut d = f.debug_struct("RwLock");
+        match self.try_read() {
+            Ok(value) => {
+                d.field("inner", &&*value);
+            }
+            Err(TryLockError) => {
+                d.field("inner", &format_args!("<locked>"));
+            }
+        }
+
+        d.finish_non_exhaustive()
+    }
+}
+
+/// Future returned by [`RwLock::write`].
+///
+/// While pending, it is counted as a waiting writer so that new readers are held back.
+struct WriteFuture<'a, M, T, const N: usize>
+where
+    M: RawMutex,
+    T: ?Sized,
+{
+    rwlock: &'a RwLock<M, T, N>,
+    waiting: bool,
+}
+
+impl<'a, M, T, const N: usize> Future for WriteFuture<'a, M, T, N>
+where
+    M: RawMutex,
+    T: ?Sized,
+{
+    type Output = RwLockWriteGuard<'a, M, T, N>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let waiting = self.waiting;
+        let ready = self.rwlock.state.lock(|s| {
+            let mut s = s.borrow_mut();
+            if s.can_write() {
+                s.writer = true;
+                if waiting {
+                    s.writers_waiting -= 1;
+                }
+                true
+            } else {
+                if !waiting {
+                    s.writers_waiting += 1;
+                }
+                s.write_waker.register(cx.waker());
+                false
+            }
+        });
+
+        if ready {
+            self.waiting = false;
+            Poll::Ready(RwLockWriteGuard { rwlock: self.rwlock })
+        } else {
+            self.waiting = true;
+            Poll::Pending
+        }
+    }
+}
+
+impl<M, T, const N: usize> Drop for WriteFuture<'_, M, T, N>
+where
+    M: RawMutex,
+    T: ?Sized,
+{
+    fn drop(&mut self) {
+        if self.waiting {
+            self.rwlock.state.lock(|s| {
+                let mut s = unwrap!(s.try_borrow_mut());
+                s.writers_waiting -= 1;
+                if s.writers_waiting == 0 {
+                    // Readers held back by this writer may proceed now.
+                    s.read_wakers.wake();
+                } else {
+                    // We may have consumed a wakeup meant for another writer.
+                    s.write_waker.wake();
+                }
+            })
+        }
+    }
+}
+
+/// Async read-write lock read guard.
+///
+/// Owning an instance of this type indicates having
+/// successfully locked the read-write lock for reading, and grants shared access to the contents.
+///
+/// Dropping it releases the read lock.
+#[clippy::has_significant_drop]
+#[must_use = "if unused the RwLock will immediately unlock"]
+pub struct RwLockReadGuard<'a, M, T, const N: usize>
+where
+    M: RawMutex,
+    T: ?Sized,
+{
+    rwlock: &'a RwLock<M, T, N>,
+}
+
+impl<'a, M, T, const N: usize> RwLockReadGuard<'a, M, T, N>
+where
+    M: RawMutex,
+    T: ?Sized,
+{
+    /// Returns a locked view over a portion of the locked data.
+    pub fn map<U: ?Sized>(this: Self, fun: impl FnOnce(&T) -> &U) -> MappedRwLockReadGuard<'a, M, U, N> {
+        let rwlock = this.rwlock;
+        let value = fun(unsafe { &*this.rwlock.inner.get() });
+        // Don't run the `drop` method for RwLockReadGuard. The ownership of the underlying
+        // locked state is being moved to the returned MappedRwLockReadGuard.
+        mem::forget(this);
+        MappedRwLockReadGuard {
+            state: &rwlock.state,
+            value,
+        }
+    }
+}
+
+impl<M, T, const N: usize> Drop for RwLockReadGuard<'_, M, T, N>
+where
+    M: RawMutex,
+    T: ?Sized,
+{
+    fn drop(&mut self) {
+        self.rwlock.state.lock(|s| {
+            let mut s = unwrap!(s.try_borrow_mut());
+            s.unlock_read();
+        })
+    }
+}
+
+impl<M, T, const N: usize> Deref for RwLockReadGuard<'_, M, T, N>
+where
+    M: RawMutex,
+    T: ?Sized,
+{
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        // Safety: the RwLockReadGuard represents shared access to the contents
+        // of the read-write lock, so it's OK to get it.
+        unsafe { &*(self.rwlock.inner.get() as *const T) }
+    }
+}
+
+impl<M, T, const N: usize> fmt::Debug for RwLockReadGuard<'_, M, T, N>
+where
+    M: RawMutex,
+    T: ?Sized + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<M, T, const N: usize> fmt::Display for RwLockReadGuard<'_, M, T, N>
+where
+    M: RawMutex,
+    T: ?Sized + fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&**self, f)
+    }
+}
+
+/// Async read-write lock write guard.
+///
+/// Owning an instance of this type indicates having
+/// successfully locked the read-write lock for writing, and grants exclusive access to the contents.
+///
+/// Dropping it releases the write lock.
+#[clippy::has_significant_drop]
+#[must_use = "if unused the RwLock will immediately unlock"]
+pub struct RwLockWriteGuard<'a, M, T, const N: usize>
+where
+    M: RawMutex,
+    T: ?Sized,
+{
+    rwlock: &'a RwLock<M, T, N>,
+}
+
+impl<'a, M, T, const N: usize> RwLockWriteGuard<'a, M, T, N>
+where
+    M: RawMutex,
+    T: ?Sized,
+{
+    /// Returns a locked view over a portion of the locked data.
+    pub fn map<U: ?Sized>(this: Self, fun: impl FnOnce(&mut T) -> &mut U) -> MappedRwLockWriteGuard<'a, M, U, N> {
+        let rwlock = this.rwlock;
+        let value = fun(unsafe { &mut *this.rwlock.inner.get() });
+        // Don't run the `drop` method for RwLockWriteGuard. The ownership of the underlying
+        // locked state is being moved to the returned MappedRwLockWriteGuard.
+        mem::forget(this);
+        MappedRwLockWriteGuard {
+            state: &rwlock.state,
+            value,
+        }
+    }
+}
+
+impl<M, T, const N: usize> Drop for RwLockWriteGuard<'_, M, T, N>
+where
+    M: RawMutex,
+    T: ?Sized,
+{
+    fn drop(&mut self) {
+        self.rwlock.state.lock(|s| {
+            let mut s = unwrap!(s.try_borrow_mut());
+            s.unlock_write();
+        })
+    }
+}
+
+impl<M, T, const N: usize> Deref for RwLockWriteGuard<'_, M, T, N>
+where
+    M: RawMutex,
+    T: ?Sized,
+{
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        // Safety: the RwLockWriteGuard represents exclusive access to the contents
+        // of the read-write lock, so it's OK to get it.
+        unsafe { &*(self.rwlock.inner.get() as *const T) }
+    }
+}
+
+impl<M, T, const N: usize> DerefMut for RwLockWriteGuard<'_, M, T, N>
+where
+    M: RawMutex,
+    T: ?Sized,
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // Safety: the RwLockWriteGuard represents exclusive access to the contents
+        // of the read-write lock, so it's OK to get it.
+        unsafe { &mut *(self.rwlock.inner.get()) }
+    }
+}
+
+impl<M, T, const N: usize> fmt::Debug for RwLockWriteGuard<'_, M, T, N>
+where
+    M: RawMutex,
+    T: ?Sized + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<M, T, const N: usize> fmt::Display for RwLockWriteGuard<'_, M, T, N>
+where
+    M: RawMutex,
+    T: ?Sized + fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&**self, f)
+    }
+}
+
+/// A handle to a read-locked `RwLock` that has had a function applied to it via
+/// [`RwLockReadGuard::map`] or [`MappedRwLockReadGuard::map`].
+///
+/// This can be used to hold a subfield of the protected data.
+#[clippy::has_significant_drop]
+pub struct MappedRwLockReadGuard<'a, M, T, const N: usize>
+where
+    M: RawMutex,
+    T: ?Sized,
+{
+    state: &'a BlockingMutex<M, RefCell<State<N>>>,
+    value: *const T,
+}
+
+impl<'a, M, T, const N: usize> MappedRwLockReadGuard<'a, M, T, N>
+where
+    M: RawMutex,
+    T: ?Sized,
+{
+    /// Returns a locked view over a portion of the locked data.
+    pub fn map<U: ?Sized>(this: Self, fun: impl FnOnce(&T) -> &U) -> MappedRwLockReadGuard<'a, M, U, N> {
+        let state = this.state;
+        let value = fun(unsafe { &*this.value });
+        // Don't run the `drop` method for MappedRwLockReadGuard. The ownership of the underlying
+        // locked state is being moved to the returned MappedRwLockReadGuard.
+        mem::forget(this);
+        MappedRwLockReadGuard { state, value }
+    }
+}
+
+impl<M, T, const N: usize> Deref for MappedRwLockReadGuard<'_, M, T, N>
+where
+    M: RawMutex,
+    T: ?Sized,
+{
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        // Safety: the MappedRwLockReadGuard represents shared access to the contents
+        // of the read-write lock, so it's OK to get it.
+        unsafe { &*self.value }
+    }
+}
+
+impl<M, T, const N: usize> Drop for MappedRwLockReadGuard<'_, M, T, N>
+where
+    M: RawMutex,
+    T: ?Sized,
+{
+    fn drop(&mut self) {
+        self.state.lock(|s| {
+            let mut s = unwrap!(s.try_borrow_mut());
+            s.unlock_read();
+        })
+    }
+}
+
+unsafe impl<M, T, const N: usize> Send for MappedRwLockReadGuard<'_, M, T, N>
+where
+    M: RawMutex + Sync,
+    T: Sync + ?Sized,
+{
+}
+
+unsafe impl<M, T, const N: usize> Sync for MappedRwLockReadGuard<'_, M, T, N>
+where
+    M: RawMutex + Sync,
+    T: Sync + ?Sized,
+{
+}
+
+impl<M, T, const N: usize> fmt::Debug for MappedRwLockReadGuard<'_, M, T, N>
+where
+    M: RawMutex,
+    T: ?Sized + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<M, T, const N: usize> fmt::Display for MappedRwLockReadGuard<'_, M, T, N>
+where
+    M: RawMutex,
+    T: ?Sized + fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&**self, f)
+    }
+}
+
+/// A handle to a write-locked `RwLock` that has had a function applied to it via
+/// [`RwLockWriteGuard::map`] or [`MappedRwLockWriteGuard::map`].
+///
+/// This can be used to hold a subfield of the protected data.
+#[clippy::has_significant_drop]
+pub struct MappedRwLockWriteGuard<'a, M, T, const N: usize>
+where
+    M: RawMutex,
+    T: ?Sized,
+{
+    state: &'a BlockingMutex<M, RefCell<State<N>>>,
+    value: *mut T,
+}
+
+impl<'a, M, T, const N: usize> MappedRwLockWriteGuard<'a, M, T, N>
+where
+    M: RawMutex,
+    T: ?Sized,
+{
+    /// Returns a locked view over a portion of the locked data.
+    pub fn map<U: ?Sized>(this: Self, fun: impl FnOnce(&mut T) -> &mut U) -> MappedRwLockWriteGuard<'a, M, U, N> {
+        let state = this.state;
+        let value = fun(unsafe { &mut *this.value });
+        // Don't run the `drop` method for MappedRwLockWriteGuard. The ownership of the underlying
+        // locked state is being moved to the returned MappedRwLockWriteGuard.
+        mem::forget(this);
+        MappedRwLockWriteGuard { state, value }
+    }
+}
+
+impl<M, T, const N: usize> Deref for MappedRwLockWriteGuard<'_, M, T, N>
+where
+    M: RawMutex,
+    T: ?Sized,
+{
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        // Safety: the MappedRwLockWriteGuard represents exclusive access to the contents
+        // of the read-write lock, so it's OK to get it.
+        unsafe { &*self.value }
+    }
+}
+
+impl<M, T, const N: usize> DerefMut for MappedRwLockWriteGuard<'_, M, T, N>
+where
+    M: RawMutex,
+    T: ?Sized,
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // Safety: the MappedRwLockWriteGuard represents exclusive access to the contents
+        // of the read-write lock, so it's OK to get it.
+        unsafe { &mut *self.value }
+    }
+}
+
+impl<M, T, const N: usize> Drop for MappedRwLockWriteGuard<'_, M, T, N>
+where
+    M: RawMutex,
+    T: ?Sized,
+{
+    fn drop(&mut self) {
+        self.state.lock(|s| {
+            let mut s = unwrap!(s.try_borrow_mut());
+            s.unlock_write();
+        })
+    }
+}
+
+unsafe impl<M, T, const N: usize> Send for MappedRwLockWriteGuard<'_, M, T, N>
+where
+    M: RawMutex + Sync,
+    T: Send + ?Sized,
+{
+}
+
+unsafe impl<M, T, const N: usize> Sync for MappedRwLockWriteGuard<'_, M, T, N>
+where
+    M: RawMutex + Sync,
+    T: Sync + ?Sized,
+{
+}
+
+impl<M, T, const N: usize> fmt::Debug for MappedRwLockWriteGuard<'_, M, T, N>
+where
+    M: RawMutex,
+    T: ?Sized + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<M, T, const N: usize> fmt::Display for MappedRwLockWriteGuard<'_, M, T, N>
+where
+    M: RawMutex,
+    T: ?Sized + fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&**self, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::future::Future;
+    use core::pin::pin;
+    use core::task::{Context, Poll};
+
+    use futures_test::task::new_count_waker;
+    use futures_util::poll;
+
+    use crate::blocking_mutex::raw::NoopRawMutex;
+    use crate::rwlock::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+    #[futures_test::test]
+    async fn multiple_readers() {
+        let rwlock: RwLock<NoopRawMutex, u32, 4> = RwLock::new(1);
+
+        let a = rwlock.read().await;
+        let b = rwlock.read().await;
+        assert_eq!(*a, 1);
+        assert_eq!(*b, 1);
+        assert!(rwlock.try_write().is_err());
+
+        drop(a);
+        assert!(rwlock.try_write().is_err());
+        drop(b);
+
+        let mut w = rwlock.try_write().unwrap();
+        *w = 2;
+        assert!(rwlock.try_read().is_err());
+        drop(w);
+
+        assert_eq!(*rwlock.read().await, 2);
+    }
+
+    #[futures_test::test]
+    async fn writer_preference() {
+        let rwlock: RwLock<NoopRawMutex, u32, 4> = RwLock::new(0);
+
+        let r = rwlock.read().await;
+
+        let mut w_fut = pin!(rwlock.write());
+        assert!(poll!(w_fut.as_mut()).is_pending());
+
+        // A writer is waiting, so new readers are held back.
+        assert!(rwlock.try_read().is_err());
+        let mut r2_fut = pin!(rwlock.read());
+        assert!(poll!(r2_fut.as_mut()).is_pending());
+
+        drop(r);
+
+        let mut w = match poll!(w_fut.as_mut()) {
+            Poll::Ready(w) => w,
+            Poll::Pending => panic!("writer should acquire the lock"),
+        };
+        *w = 1;
+        assert!(poll!(r2_fut.as_mut()).is_pending());
+        drop(w);
+
+        match poll!(r2_fut.as_mut()) {
+            Poll::Ready(r2) => assert_eq!(*r2, 1),
+            Poll::Pending => panic!("reader should acquire the lock"),
+        };
+    }
+
+    #[futures_test::test]
+    async fn cancelled_writer_releases_readers() {
+        let rwlock: RwLock<NoopRawMutex, u32, 4> = RwLock::new(0);
+
+        let r = rwlock.read().await;
+        {
+            let mut w_fut = pin!(rwlock.write());
+            assert!(poll!(w_fut.as_mut()).is_pending());
+            assert!(rwlock.try_read().is_err());
+        }
+
+        assert!(rwlock.try_read().is_ok());
+        drop(r);
+        assert!(rwlock.try_write().is_ok());
+    }
+
+    #[futures_test::test]
+    async fn mapped_guards_release_lock_when_dropped() {
+        let rwlock: RwLock<NoopRawMutex, [i32; 2], 4> = RwLock::new([0, 1]);
+
+        {
+            let guard = rwlock.write().await;
+            let mut mapped = RwLockWriteGuard::map(guard, |this| &mut this[1]);
+            assert_eq!(*mapped, 1);
+            *mapped = 2;
+            assert!(rwlock.try_read().is_err());
+        }
+
+        {
+            let guard = rwlock.read().await;
+            let mapped = RwLockReadGuard::map(guard, |this| &this[1]);
+            assert_eq!(*mapped, 2);
+            assert!(rwlock.try_write().is_err());
+            assert!(rwlock.try_read().is_ok());
+        }
+
+        assert_eq!(*rwlock.write().await, [0, 2]);
+    }
+
+    #[futures_test::test]
+    async fn waiting_readers_dont_wake_each_other() {
+        let rwlock: RwLock<NoopRawMutex, u32, 4> = RwLock::new(0);
+
+        let w = rwlock.write().await;
+
+        let (waker_a, count_a) = new_count_waker();
+        let (waker_b, count_b) = new_count_waker();
+        let mut a = pin!(rwlock.read());
+        let mut b = pin!(rwlock.read());
+        for _ in 0..3 {
+            assert!(a.as_mut().poll(&mut Context::from_waker(&waker_a)).is_pending());
+            assert!(b.as_mut().poll(&mut Context::from_waker(&waker_b)).is_pending());
+        }
+        assert_eq!(count_a, 0);
+        assert_eq!(count_b, 0);
+
+        drop(w);
+        assert_eq!(count_a, 1);
+        assert_eq!(count_b, 1);
+        assert!(poll!(a.as_mut()).is_ready());
+        assert!(poll!(b.as_mut()).is_ready());
+    }
+}
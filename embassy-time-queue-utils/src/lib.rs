@@ -1,13 +1,20 @@
-#![no_std]
+#![cfg_attr(not(test), no_std)]
 #![doc = include_str!("../README.md")]
 #![warn(missing_docs)]
 
-#[cfg(feature = "_generic-queue")]
+#[cfg(all(feature = "_generic-queue", feature = "timer-wheel"))]
+compile_error!("At most one of the `generic-queue-*` and `timer-wheel` features can be enabled.");
+
+#[cfg(any(feature = "_generic-queue", test))]
 pub mod queue_generic;
-#[cfg(not(feature = "_generic-queue"))]
+#[cfg(any(not(any(feature = "_generic-queue", feature = "timer-wheel")), test))]
 pub mod queue_integrated;
+#[cfg(any(feature = "timer-wheel", test))]
+pub mod queue_wheel;
 
 #[cfg(feature = "_generic-queue")]
 pub use queue_generic::Queue;
-#[cfg(not(feature = "_generic-queue"))]
+#[cfg(not(any(feature = "_generic-queue", feature = "timer-wheel")))]
 pub use queue_integrated::Queue;
+#[cfg(all(feature = "timer-wheel", not(feature = "_generic-queue")))]
+pub use queue_wheel::Queue;
@@ -0,0 +1,457 @@
+//! Timer wheel queue, with items integrated into tasks.
+//!
+//! This is a hierarchical timer wheel: [`LEVELS`] levels of [`SLOTS`] slots each. Level 0 slots
+//! are one tick wide, and each level's slots are [`SLOTS`] times wider than the previous level's.
+//! Timers too far in the future for the wheel are kept in an unsorted overflow list, and timers
+//! scheduled at or before the wheel's current time are kept in a list of expired timers until the
+//! next call to [`Queue::next_expiration`].
+//!
+//! A timer expiring at `at` is kept at the level of the highest bit in which `at` differs from
+//! the wheel's current time, in the slot given by the bits of `at` at that level. This means
+//! each slot only contains timers that expire after the current time, and that the lowest
+//! occupied slot of the lowest non-empty level contains the next timer to expire.
+//!
+//! Scheduling a timer is O(1). Moving a timer to an earlier expiration time, or advancing the
+//! time past a slot boundary, visits the timers of the affected slots only.
+use core::cell::Cell;
+use core::task::Waker;
+
+use embassy_executor::raw::{wake_task, TaskRef};
+
+const SLOT_BITS: u32 = 6;
+/// Number of slots in each level of the wheel.
+pub const SLOTS: usize = 1 << SLOT_BITS;
+/// Number of levels of the wheel. Timers expiring more than `SLOTS.pow(LEVELS)` ticks in the
+/// future are kept in an overflow list until the time gets closer.
+pub const LEVELS: usize = 4;
+
+const SLOT_MASK: u64 = SLOTS as u64 - 1;
+
+struct Level {
+    slots: [Cell<Option<TaskRef>>; SLOTS],
+    /// Bit `n` is set if slot `n` is not empty.
+    occupied: Cell<u64>,
+}
+
+impl Level {
+    const fn new() -> Self {
+        Self {
+            slots: [const { Cell::new(None) }; SLOTS],
+            occupied: Cell::new(0),
+        }
+    }
+}
+
+/// Where a timer is stored.
+#[derive(Clone, Copy)]
+enum Location {
+    Slot { level: usize, slot: usize },
+    Overflow,
+    Expired,
+}
+
+/// A timer wheel queue, with items integrated into tasks.
+pub struct Queue {
+    /// Time of the last call to `next_expiration`. All timers expiring at or before this
+    /// time have been woken.
+    now: u64,
+    levels: [Level; LEVELS],
+    overflow: Cell<Option<TaskRef>>,
+    expired: Cell<Option<TaskRef>>,
+}
+
+impl Default for Queue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Queue {
+    /// Creates a new timer queue.
+    pub const fn new() -> Self {
+        Self {
+            now: 0,
+            levels: [const { Level::new() }; LEVELS],
+            overflow: Cell::new(None),
+            expired: Cell::new(None),
+        }
+    }
+
+    /// Schedules a task to run at a specific time.
+    ///
+    /// If this function returns `true`, the caller should find the next expiration time and set
+    /// a new alarm for that time.
+    pub fn schedule_wake(&mut self, at: u64, waker: &Waker) -> bool {
+        let task = embassy_executor::raw::task_from_waker(waker);
+        let item = task.timer_queue_item();
+        if item.next.get().is_some() {
+            if at >= item.expires_at.get() {
+                // Task does not need to be updated.
+                return false;
+            }
+            // Expiration is sooner than previously set, move it.
+            self.remove(task);
+        } else if at == u64::MAX {
+            // Never expires.
+            return false;
+        }
+
+        item.expires_at.set(at);
+        self.push(task);
+        true
+    }
+
+    /// Dequeues expired timers and returns the next alarm time.
+    pub fn next_expiration(&mut self, now: u64) -> u64 {
+        if now > self.now {
+            self.advance(now);
+        }
+        self.reinsert(self.expired.take());
+
+        for level in &self.levels {
+            let occupied = level.occupied.get();
+            if occupied != 0 {
+                let slot = occupied.trailing_zeros() as usize;
+                return min_expiration(&level.slots[slot]);
+            }
+        }
+        min_expiration(&self.overflow)
+    }
+
+    fn location(&self, at: u64) -> Location {
+        if at <= self.now {
+            return Location::Expired;
+        }
+        let level = ((63 - (at ^ self.now).leading_zeros()) / SLOT_BITS) as usize;
+        if level < LEVELS {
+            let slot = ((at >> (level as u32 * SLOT_BITS)) & SLOT_MASK) as usize;
+            Location::Slot { level, slot }
+        } else {
+            Location::Overflow
+        }
+    }
+
+    fn list(&self, location: Location) -> &Cell<Option<TaskRef>> {
+        match location {
+            Location::Slot { level, slot } => &self.levels[level].slots[slot],
+            Location::Overflow => &self.overflow,
+            Location::Expired => &self.expired,
+        }
+    }
+
+    /// Add a task to the list matching its expiration time.
+    fn push(&self, task: TaskRef) {
+        let item = task.timer_queue_item();
+        let location = self.location(item.expires_at.get());
+        let list = self.list(location);
+
+        let prev = list.replace(Some(task));
+        item.next.set(if prev.is_none() {
+            Some(unsafe { TaskRef::dangling() })
+        } else {
+            prev
+        });
+
+        if let Location::Slot { level, slot } = location {
+            let level = &self.levels[level];
+            level.occupied.set(level.occupied.get() | 1 << slot);
+        }
+    }
+
+    /// Remove a task from the list matching its expiration time.
+    fn remove(&self, task: TaskRef) {
+        let location = self.location(task.timer_queue_item().expires_at.get());
+        let list = self.list(location);
+
+        let mut prev = list;
+        while let Some(p) = prev.get() {
+            if unsafe { p == TaskRef::dangling() } {
+                break;
+            }
+            let item = p.timer_queue_item();
+            if p == task {
+                prev.set(item.next.get());
+                item.next.set(None);
+                break;
+            }
+            prev = &item.next;
+        }
+
+        if unsafe { list.get() == Some(TaskRef::dangling()) } {
+            list.set(None);
+            if let Location::Slot { level, slot } = location {
+                let level = &self.levels[level];
+                level.occupied.set(level.occupied.get() & !(1 << slot));
+            }
+        }
+    }
+
+    /// Advance the wheel's time to `now`, waking expired tasks and moving the others to the list
+    /// matching their expiration time relative to the new time.
+    fn advance(&mut self, now: u64) {
+        let old = self.now;
+        self.now = now;
+
+        // The highest level at which the old and new times differ. Levels below it only contain
+        // timers expiring before the start of the new time's slot at this level.
+        let top = ((63 - (old ^ now).leading_zeros()) / SLOT_BITS) as usize;
+
+        for level in 0..top.min(LEVELS) {
+            self.take_slots(level, u64::MAX);
+        }
+
+        if top < LEVELS {
+            // Slots between the old and the new time. The slot of the new time contains timers
+            // that now belong to a lower level.
+            let shift = top as u32 * SLOT_BITS;
+            let old_slot = ((old >> shift) & SLOT_MASK) as u32;
+            let new_slot = ((now >> shift) & SLOT_MASK) as u32;
+            let mask = (u64::MAX >> (63 - new_slot)) & !(u64::MAX >> (63 - old_slot));
+            self.take_slots(top, mask);
+        } else {
+            self.reinsert(self.overflow.take());
+        }
+    }
+
+    fn take_slots(&self, level: usize, mask: u64) {
+        let l = &self.levels[level];
+        let mut taken = l.occupied.get() & mask;
+        l.occupied.set(l.occupied.get() & !mask);
+
+        while taken != 0 {
+            let slot = taken.trailing_zeros() as usize;
+            taken &= taken - 1;
+            self.reinsert(l.slots[slot].take());
+        }
+    }
+
+    /// Wake or re-insert all tasks of a list that has been taken out of the wheel.
+    fn reinsert(&self, mut next: Option<TaskRef>) {
+        while let Some(p) = next {
+            if unsafe { p == TaskRef::dangling() } {
+                break;
+            }
+            let item = p.timer_queue_item();
+            next = item.next.get();
+            item.next.set(None);
+
+            if item.expires_at.get() <= self.now {
+                wake_task(p);
+            } else {
+                self.push(p);
+            }
+        }
+    }
+}
+
+fn min_expiration(list: &Cell<Option<TaskRef>>) -> u64 {
+    let mut min = u64::MAX;
+    let mut next = list.get();
+    while let Some(p) = next {
+        if unsafe { p == TaskRef::dangling() } {
+            break;
+        }
+        let item = p.timer_queue_item();
+        min = min.min(item.expires_at.get());
+        next = item.next.get();
+    }
+    min
+}
+
+#[cfg(test)]
+mod tests {
+    use core::future::poll_fn;
+    use core::task::Poll;
+    use std::boxed::Box;
+    use std::sync::{Arc, Mutex};
+    use std::task::Wake;
+    use std::vec::Vec;
+
+    use embassy_executor::raw::{Executor, TaskStorage};
+
+    use super::*;
+    use crate::queue_generic::ConstGenericQueue;
+    use crate::queue_integrated;
+
+    const TASKS: usize = 32;
+
+    #[export_name = "__pender"]
+    fn __pender(_context: *mut ()) {}
+
+    /// A set of executor tasks that record when they are polled after being woken.
+    struct Tasks {
+        executor: &'static Executor,
+        wakers: Vec<Waker>,
+        woken: Arc<Mutex<Vec<usize>>>,
+    }
+
+    impl Tasks {
+        fn new() -> Self {
+            let executor: &'static Executor = Box::leak(Box::new(Executor::new(core::ptr::null_mut())));
+            let wakers = Arc::new(Mutex::new(Vec::new()));
+            let woken = Arc::new(Mutex::new(Vec::new()));
+
+            for id in 0..TASKS {
+                let task = Box::leak(Box::new(TaskStorage::new()));
+                let wakers = wakers.clone();
+                let woken = woken.clone();
+                let mut first = true;
+                let token = task.spawn(move || {
+                    poll_fn(move |cx| {
+                        if first {
+                            wakers.lock().unwrap().push(cx.waker().clone());
+                            first = false;
+                        } else {
+                            woken.lock().unwrap().push(id);
+                        }
+                        Poll::<()>::Pending
+                    })
+                });
+                executor.spawner().spawn(token).unwrap();
+                unsafe { executor.poll() };
+            }
+
+            let wakers = core::mem::take(&mut *wakers.lock().unwrap());
+            Self {
+                executor,
+                wakers,
+                woken,
+            }
+        }
+
+        fn take_woken(&self) -> Vec<usize> {
+            unsafe { self.executor.poll() };
+            let mut woken = core::mem::take(&mut *self.woken.lock().unwrap());
+            woken.sort();
+            woken
+        }
+    }
+
+    /// Plain wakers for the generic queue.
+    struct IdWaker {
+        id: usize,
+        woken: Arc<Mutex<Vec<usize>>>,
+    }
+
+    impl Wake for IdWaker {
+        fn wake(self: Arc<Self>) {
+            self.woken.lock().unwrap().push(self.id);
+        }
+    }
+
+    /// xorshift64
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        /// Random value with a random magnitude, to exercise all levels of the wheel.
+        fn delta(&mut self, max_bits: u64) -> u64 {
+            let bits = self.next() % max_bits;
+            self.next() & ((1 << bits) - 1)
+        }
+    }
+
+    fn compare_with_existing_queues(seed: u64, start: u64) {
+        let mut rng = Rng(seed);
+
+        let wheel_tasks = Tasks::new();
+        let mut wheel = Queue::new();
+        let integrated_tasks = Tasks::new();
+        let mut integrated = queue_integrated::Queue::new();
+        let generic_woken = Arc::new(Mutex::new(Vec::new()));
+        let generic_wakers: Vec<Waker> = (0..TASKS)
+            .map(|id| {
+                Arc::new(IdWaker {
+                    id,
+                    woken: generic_woken.clone(),
+                })
+                .into()
+            })
+            .collect();
+        let mut generic = ConstGenericQueue::<TASKS>::new();
+
+        let mut now = start;
+        for _ in 0..2000 {
+            if rng.next() % 3 != 0 {
+                let id = (rng.next() % TASKS as u64) as usize;
+                let at = now + rng.delta(30);
+                wheel.schedule_wake(at, &wheel_tasks.wakers[id]);
+                integrated.schedule_wake(at, &integrated_tasks.wakers[id]);
+                generic.schedule_wake(at, &generic_wakers[id]);
+            } else {
+                now += rng.delta(26);
+                let next = generic.next_expiration(now);
+                assert_eq!(wheel.next_expiration(now), next, "seed {seed}, now {now}");
+                assert_eq!(integrated.next_expiration(now), next, "seed {seed}, now {now}");
+
+                let mut woken = core::mem::take(&mut *generic_woken.lock().unwrap());
+                woken.sort();
+                assert_eq!(wheel_tasks.take_woken(), woken, "seed {seed}, now {now}");
+                assert_eq!(integrated_tasks.take_woken(), woken, "seed {seed}, now {now}");
+            }
+        }
+    }
+
+    #[test]
+    fn matches_existing_queues() {
+        for seed in 1..20 {
+            compare_with_existing_queues(seed, 0);
+        }
+    }
+
+    #[test]
+    fn matches_existing_queues_far_from_zero() {
+        for seed in 1..20 {
+            compare_with_existing_queues(seed, u64::MAX / 2 + seed);
+        }
+    }
+
+    #[test]
+    fn next_expiration_crosses_levels() {
+        let tasks = Tasks::new();
+        let mut queue = Queue::new();
+        assert_eq!(queue.next_expiration(0), u64::MAX);
+
+        let far = (SLOTS as u64).pow(LEVELS as u32) * 3 + 17;
+        assert!(queue.schedule_wake(far, &tasks.wakers[0]));
+        assert!(queue.schedule_wake(100, &tasks.wakers[1]));
+        assert!(queue.schedule_wake(5000, &tasks.wakers[2]));
+        // Later expirations don't replace earlier ones.
+        assert!(!queue.schedule_wake(6000, &tasks.wakers[2]));
+
+        assert_eq!(queue.next_expiration(10), 100);
+        assert_eq!(queue.next_expiration(100), 5000);
+        assert_eq!(tasks.take_woken(), [1]);
+        assert_eq!(queue.next_expiration(5000), far);
+        assert_eq!(tasks.take_woken(), [2]);
+        assert_eq!(queue.next_expiration(far - 1), far);
+        assert_eq!(queue.next_expiration(far), u64::MAX);
+        assert_eq!(tasks.take_woken(), [0]);
+    }
+
+    #[test]
+    fn reschedule_earlier() {
+        let tasks = Tasks::new();
+        let mut queue = Queue::new();
+
+        assert!(queue.schedule_wake(1_000_000, &tasks.wakers[0]));
+        assert!(queue.schedule_wake(1_000, &tasks.wakers[1]));
+        assert!(queue.schedule_wake(50, &tasks.wakers[0]));
+        assert_eq!(queue.next_expiration(0), 50);
+
+        assert_eq!(queue.next_expiration(50), 1_000);
+        assert_eq!(tasks.take_woken(), [0]);
+
+        // Expirations in the past wake the task on the next call to `next_expiration`.
+        assert!(queue.schedule_wake(20, &tasks.wakers[2]));
+        assert_eq!(tasks.take_woken(), []);
+        assert_eq!(queue.next_expiration(50), 1_000);
+        assert_eq!(tasks.take_woken(), [2]);
+    }
+}
@@ -0,0 +1,305 @@
+use core::fmt;
+
+use crate::UnixTimestamp;
+
+/// A calendar date and time in UTC, with a resolution of one second.
+///
+/// This can be converted to and from a [`UnixTimestamp`], and is meant to be the common
+/// representation of calendar time between RTC drivers and network time code.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DateTime {
+    /// Year, 1970 or later.
+    pub year: u16,
+    /// Month, from 1 (January) to 12 (December).
+    pub month: u8,
+    /// Day of the month, from 1 to 31.
+    pub day: u8,
+    /// Hour, from 0 to 23.
+    pub hour: u8,
+    /// Minute, from 0 to 59.
+    pub minute: u8,
+    /// Second, from 0 to 59.
+    pub second: u8,
+}
+
+/// Day of the week.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[allow(missing_docs)]
+pub enum DayOfWeek {
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+    Sunday,
+}
+
+/// Error returned when a [`DateTime`] field is out of range.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DateTimeError {
+    /// The year is before 1970.
+    InvalidYear,
+    /// The month is not between 1 and 12.
+    InvalidMonth,
+    /// The day does not exist in this month.
+    InvalidDay,
+    /// The hour is not between 0 and 23.
+    InvalidHour,
+    /// The minute is not between 0 and 59.
+    InvalidMinute,
+    /// The second is not between 0 and 59.
+    InvalidSecond,
+}
+
+impl fmt::Display for DateTimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let msg = match self {
+            Self::InvalidYear => "year is before 1970",
+            Self::InvalidMonth => "invalid month",
+            Self::InvalidDay => "invalid day of month",
+            Self::InvalidHour => "invalid hour",
+            Self::InvalidMinute => "invalid minute",
+            Self::InvalidSecond => "invalid second",
+        };
+        f.write_str(msg)
+    }
+}
+
+const SECS_PER_DAY: u64 = 86_400;
+
+/// Days between 0000-03-01 and 1970-01-01 in the proleptic Gregorian calendar.
+const EPOCH_DAYS_FROM_0000_03_01: u64 = 719_468;
+/// Days in a 400 year cycle.
+const DAYS_PER_ERA: u64 = 146_097;
+
+impl DateTime {
+    /// Create a new `DateTime`, checking that all fields are in range.
+    pub const fn new(year: u16, month: u8, day: u8, hour: u8, minute: u8, second: u8) -> Result<Self, DateTimeError> {
+        let this = Self {
+            year,
+            month,
+            day,
+            hour,
+            minute,
+            second,
+        };
+        match this.validate() {
+            Ok(()) => Ok(this),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Check that all fields are in range.
+    pub const fn validate(&self) -> Result<(), DateTimeError> {
+        if self.year < 1970 {
+            Err(DateTimeError::InvalidYear)
+        } else if self.month < 1 || self.month > 12 {
+            Err(DateTimeError::InvalidMonth)
+        } else if self.day < 1 || self.day > days_in_month(self.year, self.month) {
+            Err(DateTimeError::InvalidDay)
+        } else if self.hour > 23 {
+            Err(DateTimeError::InvalidHour)
+        } else if self.minute > 59 {
+            Err(DateTimeError::InvalidMinute)
+        } else if self.second > 59 {
+            Err(DateTimeError::InvalidSecond)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Convert a timestamp to a calendar date and time, dropping the fractional seconds.
+    ///
+    /// Years after 65535 are not representable, and saturate to 65535.
+    pub const fn from_timestamp(timestamp: UnixTimestamp) -> Self {
+        let secs = timestamp.as_secs();
+        let days = secs / SECS_PER_DAY;
+        let time = secs % SECS_PER_DAY;
+
+        // Inverse of `days_from_civil`, from Howard Hinnant's `civil_from_days`.
+        let z = days + EPOCH_DAYS_FROM_0000_03_01;
+        let era = z / DAYS_PER_ERA;
+        let doe = z - era * DAYS_PER_ERA;
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+        Self {
+            year: if year > u16::MAX as u64 { u16::MAX } else { year as u16 },
+            month: month as u8,
+            day: day as u8,
+            hour: (time / 3600) as u8,
+            minute: (time / 60 % 60) as u8,
+            second: (time % 60) as u8,
+        }
+    }
+
+    /// Convert to a timestamp.
+    pub const fn to_timestamp(&self) -> Result<UnixTimestamp, DateTimeError> {
+        if let Err(e) = self.validate() {
+            return Err(e);
+        }
+
+        let days = days_from_civil(self.year, self.month, self.day) as u64;
+        let secs = days * SECS_PER_DAY + self.hour as u64 * 3600 + self.minute as u64 * 60 + self.second as u64;
+        Ok(UnixTimestamp::from_secs(secs))
+    }
+
+    /// Day of the week of this date.
+    ///
+    /// The time of day is ignored, and the year may be before 1970.
+    pub const fn day_of_week(&self) -> DayOfWeek {
+        let days = days_from_civil(self.year, self.month, self.day);
+        // 1970-01-01 was a Thursday.
+        match (days + 3).rem_euclid(7) {
+            0 => DayOfWeek::Monday,
+            1 => DayOfWeek::Tuesday,
+            2 => DayOfWeek::Wednesday,
+            3 => DayOfWeek::Thursday,
+            4 => DayOfWeek::Friday,
+            5 => DayOfWeek::Saturday,
+            _ => DayOfWeek::Sunday,
+        }
+    }
+}
+
+impl From<UnixTimestamp> for DateTime {
+    fn from(timestamp: UnixTimestamp) -> Self {
+        Self::from_timestamp(timestamp)
+    }
+}
+
+impl TryFrom<DateTime> for UnixTimestamp {
+    type Error = DateTimeError;
+
+    fn try_from(datetime: DateTime) -> Result<Self, Self::Error> {
+        datetime.to_timestamp()
+    }
+}
+
+impl fmt::Display for DateTime {
+    /// Formats as ISO 8601, for example `2024-02-29T13:05:00Z`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+}
+
+/// Days since 1970-01-01, negative for earlier dates.
+///
+/// Algorithm from Howard Hinnant's `days_from_civil`, with years starting in March so that leap
+/// days are at the end of the year.
+const fn days_from_civil(year: u16, month: u8, day: u8) -> i64 {
+    let month = month as i64;
+    let year = year as i64 - if month <= 2 { 1 } else { 0 };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * DAYS_PER_ERA as i64 + doe - EPOCH_DAYS_FROM_0000_03_01 as i64
+}
+
+/// Whether `year` is a leap year in the Gregorian calendar.
+pub const fn is_leap_year(year: u16) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+/// Number of days in `month` (1 to 12) of `year`. Returns 0 for invalid months.
+pub const fn days_in_month(year: u16, month: u8) -> u8 {
+    match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dt(year: u16, month: u8, day: u8, hour: u8, minute: u8, second: u8) -> DateTime {
+        DateTime::new(year, month, day, hour, minute, second).unwrap()
+    }
+
+    #[test]
+    fn known_timestamps() {
+        let cases = [
+            (0, dt(1970, 1, 1, 0, 0, 0)),
+            (951_782_400, dt(2000, 2, 29, 0, 0, 0)),
+            (1_709_211_845, dt(2024, 2, 29, 13, 4, 5)),
+            (2_147_483_647, dt(2038, 1, 19, 3, 14, 7)),
+            (4_102_444_800, dt(2100, 1, 1, 0, 0, 0)),
+            (4_107_542_399, dt(2100, 2, 28, 23, 59, 59)),
+            (4_107_542_400, dt(2100, 3, 1, 0, 0, 0)),
+        ];
+        for (secs, datetime) in cases {
+            assert_eq!(DateTime::from_timestamp(UnixTimestamp::from_secs(secs)), datetime);
+            assert_eq!(datetime.to_timestamp(), Ok(UnixTimestamp::from_secs(secs)));
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        // Every day from 1970 to past 2400, at a time of day that changes every day.
+        for day in 0..160_000u64 {
+            let secs = day * SECS_PER_DAY + day * 7919 % SECS_PER_DAY;
+            let datetime = DateTime::from_timestamp(UnixTimestamp::from_secs(secs));
+            assert_eq!(datetime.validate(), Ok(()));
+            assert_eq!(datetime.to_timestamp(), Ok(UnixTimestamp::from_secs(secs)));
+        }
+    }
+
+    #[test]
+    fn drops_fractional_seconds() {
+        let t = UnixTimestamp::from_micros(1_500_000);
+        assert_eq!(DateTime::from(t), dt(1970, 1, 1, 0, 0, 1));
+    }
+
+    #[test]
+    fn validation() {
+        assert_eq!(DateTime::new(1969, 12, 31, 0, 0, 0), Err(DateTimeError::InvalidYear));
+        assert_eq!(DateTime::new(2024, 13, 1, 0, 0, 0), Err(DateTimeError::InvalidMonth));
+        assert_eq!(DateTime::new(2023, 2, 29, 0, 0, 0), Err(DateTimeError::InvalidDay));
+        assert_eq!(DateTime::new(2100, 2, 29, 0, 0, 0), Err(DateTimeError::InvalidDay));
+        assert_eq!(DateTime::new(2024, 4, 31, 0, 0, 0), Err(DateTimeError::InvalidDay));
+        assert_eq!(DateTime::new(2024, 1, 1, 24, 0, 0), Err(DateTimeError::InvalidHour));
+        assert_eq!(DateTime::new(2024, 1, 1, 0, 60, 0), Err(DateTimeError::InvalidMinute));
+        assert_eq!(DateTime::new(2024, 1, 1, 0, 0, 60), Err(DateTimeError::InvalidSecond));
+        assert!(DateTime::new(2000, 2, 29, 23, 59, 59).is_ok());
+    }
+
+    #[test]
+    fn day_of_week() {
+        assert_eq!(dt(1970, 1, 1, 0, 0, 0).day_of_week(), DayOfWeek::Thursday);
+        assert_eq!(dt(2000, 1, 1, 12, 0, 0).day_of_week(), DayOfWeek::Saturday);
+        assert_eq!(dt(2024, 2, 29, 23, 59, 59).day_of_week(), DayOfWeek::Thursday);
+        assert_eq!(dt(2025, 1, 5, 0, 0, 0).day_of_week(), DayOfWeek::Sunday);
+        let before_epoch = DateTime {
+            year: 1969,
+            month: 7,
+            day: 20,
+            hour: 20,
+            minute: 17,
+            second: 0,
+        };
+        assert_eq!(before_epoch.day_of_week(), DayOfWeek::Sunday);
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(std::format!("{}", dt(2024, 2, 9, 3, 4, 5)), "2024-02-09T03:04:05Z");
+    }
+}
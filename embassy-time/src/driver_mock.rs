@@ -62,6 +62,27 @@ impl MockDriver {
             inner.queue.next_expiration(inner.now.as_ticks());
         })
     }
+
+    /// Advances the time to the next scheduled wakeup, waking the tasks waiting for it.
+    ///
+    /// Returns `false`, without advancing the time, if no wakeup is scheduled.
+    ///
+    /// This is meant to be used with a simulation executor: when all tasks are waiting, time can
+    /// jump straight to the moment the next one is woken.
+    pub fn advance_to_next_expiration(&self) -> bool {
+        critical_section::with(|cs| {
+            let inner = &mut *self.0.borrow_ref_mut(cs);
+
+            let next = inner.queue.next_expiration(inner.now.as_ticks());
+            if next == u64::MAX {
+                return false;
+            }
+
+            inner.now = Instant::from_ticks(next);
+            inner.queue.next_expiration(next);
+            true
+        })
+    }
 }
 
 impl Driver for MockDriver {
@@ -142,4 +163,32 @@ mod tests {
         driver.advance(Duration::from_secs(1));
         assert_eq!(true, CALLBACK_CALLED.load(Ordering::Relaxed));
     }
+
+    #[test]
+    #[serial]
+    fn test_advance_to_next_expiration() {
+        setup();
+
+        static CALLBACK_CALLED: AtomicBool = AtomicBool::new(false);
+
+        struct MockWaker;
+
+        impl Wake for MockWaker {
+            fn wake(self: Arc<Self>) {
+                CALLBACK_CALLED.store(true, Ordering::Relaxed);
+            }
+        }
+        let waker = Arc::new(MockWaker).into();
+
+        let driver = MockDriver::get();
+        assert_eq!(false, driver.advance_to_next_expiration());
+        assert_eq!(0, driver.now());
+
+        driver.schedule_wake(1234, &waker);
+        assert_eq!(true, driver.advance_to_next_expiration());
+        assert_eq!(1234, driver.now());
+        assert_eq!(true, CALLBACK_CALLED.load(Ordering::Relaxed));
+
+        assert_eq!(false, driver.advance_to_next_expiration());
+    }
 }
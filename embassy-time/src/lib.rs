@@ -10,9 +10,11 @@
 // This mod MUST go first, so that the others see its macros.
 pub(crate) mod fmt;
 
+mod datetime;
 mod delay;
 mod duration;
 mod instant;
+mod system_time;
 mod timer;
 
 #[cfg(feature = "mock-driver")]
@@ -26,10 +28,12 @@ mod driver_std;
 #[cfg(feature = "wasm")]
 mod driver_wasm;
 
+pub use datetime::{days_in_month, is_leap_year, DateTime, DateTimeError, DayOfWeek};
 pub use delay::{block_for, Delay};
 pub use duration::Duration;
 pub use embassy_time_driver::TICK_HZ;
 pub use instant::Instant;
+pub use system_time::{SystemTime, UnixTimestamp};
 pub use timer::{with_deadline, with_timeout, Ticker, TimeoutError, Timer, WithTimeout};
 
 const fn gcd(a: u64, b: u64) -> u64 {
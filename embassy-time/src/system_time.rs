@@ -0,0 +1,492 @@
+use core::cell::Cell;
+use core::fmt;
+use core::ops::{Add, AddAssign, Sub, SubAssign};
+
+use critical_section::Mutex;
+
+use crate::{DateTime, Duration, Instant};
+
+/// A point in time in UTC, as microseconds since the Unix epoch (1970-01-01T00:00:00Z).
+///
+/// Leap seconds are not counted, as in POSIX time. Use [`DateTime`] to convert to and from
+/// calendar dates.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct UnixTimestamp {
+    micros: u64,
+}
+
+impl UnixTimestamp {
+    /// The Unix epoch, 1970-01-01T00:00:00Z.
+    pub const UNIX_EPOCH: UnixTimestamp = UnixTimestamp { micros: 0 };
+
+    /// Create a timestamp from a second count since the Unix epoch.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the timestamp is more than about 584 thousand years after the epoch, which
+    /// overflows 64 bits of microseconds. Use [`try_from_secs`](Self::try_from_secs) to handle this case.
+    pub const fn from_secs(secs: u64) -> Self {
+        match Self::try_from_secs(secs) {
+            Some(t) => t,
+            None => panic!("UnixTimestamp overflow"),
+        }
+    }
+
+    /// Create a timestamp from a millisecond count since the Unix epoch.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the timestamp is more than about 584 thousand years after the epoch, which
+    /// overflows 64 bits of microseconds. Use [`try_from_millis`](Self::try_from_millis) to handle this case.
+    pub const fn from_millis(millis: u64) -> Self {
+        match Self::try_from_millis(millis) {
+            Some(t) => t,
+            None => panic!("UnixTimestamp overflow"),
+        }
+    }
+
+    /// Try to create a timestamp from a second count since the Unix epoch.
+    /// Fails if the number of seconds is too large.
+    pub const fn try_from_secs(secs: u64) -> Option<Self> {
+        let Some(micros) = secs.checked_mul(1_000_000) else {
+            return None;
+        };
+        Some(Self { micros })
+    }
+
+    /// Try to create a timestamp from a millisecond count since the Unix epoch.
+    /// Fails if the number of milliseconds is too large.
+    pub const fn try_from_millis(millis: u64) -> Option<Self> {
+        let Some(micros) = millis.checked_mul(1000) else {
+            return None;
+        };
+        Some(Self { micros })
+    }
+
+    /// Create a timestamp from a microsecond count since the Unix epoch.
+    pub const fn from_micros(micros: u64) -> Self {
+        Self { micros }
+    }
+
+    /// Seconds since the Unix epoch, rounding down.
+    pub const fn as_secs(&self) -> u64 {
+        self.micros / 1_000_000
+    }
+
+    /// Milliseconds since the Unix epoch, rounding down.
+    pub const fn as_millis(&self) -> u64 {
+        self.micros / 1000
+    }
+
+    /// Microseconds since the Unix epoch.
+    pub const fn as_micros(&self) -> u64 {
+        self.micros
+    }
+
+    /// Fractional part of the second, in microseconds.
+    pub const fn subsec_micros(&self) -> u32 {
+        (self.micros % 1_000_000) as u32
+    }
+
+    /// Calendar date and time of this timestamp.
+    pub const fn to_datetime(&self) -> DateTime {
+        DateTime::from_timestamp(*self)
+    }
+
+    /// Duration between this timestamp and an earlier one, or `None` if `earlier` is later.
+    pub fn checked_duration_since(&self, earlier: UnixTimestamp) -> Option<Duration> {
+        self.micros.checked_sub(earlier.micros).map(Duration::from_micros)
+    }
+
+    /// Adds a Duration to self, returning a new `UnixTimestamp` or None in the event of an overflow.
+    pub fn checked_add(&self, duration: Duration) -> Option<UnixTimestamp> {
+        self.micros
+            .checked_add(duration.as_micros())
+            .map(|micros| UnixTimestamp { micros })
+    }
+
+    /// Subtracts a Duration from self, returning a new `UnixTimestamp` or None in the event of an overflow.
+    pub fn checked_sub(&self, duration: Duration) -> Option<UnixTimestamp> {
+        self.micros
+            .checked_sub(duration.as_micros())
+            .map(|micros| UnixTimestamp { micros })
+    }
+
+    /// Add a signed offset in microseconds, saturating at the bounds.
+    const fn saturating_offset(&self, offset: i64) -> UnixTimestamp {
+        let micros = if offset < 0 {
+            self.micros.saturating_sub(offset.unsigned_abs())
+        } else {
+            self.micros.saturating_add(offset as u64)
+        };
+        UnixTimestamp { micros }
+    }
+}
+
+impl Add<Duration> for UnixTimestamp {
+    type Output = UnixTimestamp;
+
+    fn add(self, other: Duration) -> UnixTimestamp {
+        self.checked_add(other)
+            .expect("overflow when adding duration to timestamp")
+    }
+}
+
+impl AddAssign<Duration> for UnixTimestamp {
+    fn add_assign(&mut self, other: Duration) {
+        *self = *self + other;
+    }
+}
+
+impl Sub<Duration> for UnixTimestamp {
+    type Output = UnixTimestamp;
+
+    fn sub(self, other: Duration) -> UnixTimestamp {
+        self.checked_sub(other)
+            .expect("overflow when subtracting duration from timestamp")
+    }
+}
+
+impl SubAssign<Duration> for UnixTimestamp {
+    fn sub_assign(&mut self, other: Duration) {
+        *self = *self - other;
+    }
+}
+
+impl fmt::Display for UnixTimestamp {
+    /// Formats as ISO 8601 with microseconds, for example `2024-02-29T13:05:00.250000Z`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let d = self.to_datetime();
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:06}Z",
+            d.year,
+            d.month,
+            d.day,
+            d.hour,
+            d.minute,
+            d.second,
+            self.subsec_micros()
+        )
+    }
+}
+
+/// Mapping from [`Instant`]s to UTC.
+#[derive(Debug, Copy, Clone)]
+struct Anchor {
+    instant: Instant,
+    timestamp: UnixTimestamp,
+    /// Correction in microseconds still to be applied after `instant`, at `SLEW_RATE_PPM`.
+    slew: i64,
+}
+
+impl Anchor {
+    fn timestamp_at(&self, instant: Instant) -> UnixTimestamp {
+        match instant.checked_duration_since(self.instant) {
+            Some(elapsed) => {
+                let elapsed = elapsed.as_micros();
+                let slewed = (elapsed.saturating_mul(SystemTime::SLEW_RATE_PPM as u64) / 1_000_000)
+                    .min(self.slew.unsigned_abs()) as i64;
+                let base = UnixTimestamp {
+                    micros: self.timestamp.micros.saturating_add(elapsed),
+                };
+                base.saturating_offset(if self.slew < 0 { -slewed } else { slewed })
+            }
+            None => self
+                .timestamp
+                .saturating_offset(-((self.instant - instant).as_micros() as i64)),
+        }
+    }
+
+    fn instant_at(&self, timestamp: UnixTimestamp) -> Instant {
+        let Some(target) = timestamp.micros.checked_sub(self.timestamp.micros) else {
+            let before = Duration::from_micros(self.timestamp.micros - timestamp.micros);
+            return self.instant.saturating_sub(before);
+        };
+
+        // While slewing, the clock runs faster or slower by `SLEW_RATE_PPM`.
+        let rate = SystemTime::SLEW_RATE_PPM as u64;
+        let slew = self.slew.unsigned_abs();
+        let slew_end = slew.saturating_mul(1_000_000) / rate;
+        let scale = |rate: u64| (target as u128 * 1_000_000 / rate as u128) as u64;
+        let elapsed = if self.slew >= 0 {
+            if target <= slew_end.saturating_add(slew) {
+                scale(1_000_000 + rate)
+            } else {
+                target - slew
+            }
+        } else if target <= slew_end - slew {
+            scale(1_000_000 - rate)
+        } else {
+            target + slew
+        };
+        self.instant.saturating_add(Duration::from_micros(elapsed))
+    }
+
+    /// Re-anchor at `instant`, keeping the same mapping.
+    fn rebase(&self, instant: Instant) -> Anchor {
+        if instant <= self.instant {
+            return *self;
+        }
+        let applied = self.timestamp_at(instant).micros as i64
+            - self.timestamp.micros as i64
+            - (instant - self.instant).as_micros() as i64;
+        Anchor {
+            instant,
+            timestamp: self.timestamp_at(instant),
+            slew: self.slew - applied,
+        }
+    }
+}
+
+static CLOCK: Mutex<Cell<Option<Anchor>>> = Mutex::new(Cell::new(None));
+
+/// The system's wall-clock time.
+///
+/// The wall clock is an offset from the monotonic [`Instant`] clock to UTC. It is unknown at
+/// boot, and must be set from an external time source, such as an RTC, GPS or NTP, with
+/// [`set`](SystemTime::set) or [`set_at`](SystemTime::set_at).
+///
+/// Once set, small corrections can be made with [`slew`](SystemTime::slew), which gradually
+/// speeds up or slows down the wall clock instead of jumping, so that timestamps don't go back
+/// in time. Larger corrections can be made with [`step`](SystemTime::step).
+///
+/// The wall clock is global, shared by all users of `embassy-time`.
+pub struct SystemTime {
+    _private: (),
+}
+
+impl SystemTime {
+    /// Rate at which [`slew`](SystemTime::slew) corrects the clock, in parts per million.
+    pub const SLEW_RATE_PPM: u32 = 500;
+
+    /// Current wall-clock time, or `None` if it has not been set.
+    pub fn now() -> Option<UnixTimestamp> {
+        Self::from_instant(Instant::now())
+    }
+
+    /// Whether the wall-clock time has been set.
+    pub fn is_set() -> bool {
+        critical_section::with(|cs| CLOCK.borrow(cs).get().is_some())
+    }
+
+    /// Wall-clock time at `instant`, or `None` if it has not been set.
+    pub fn from_instant(instant: Instant) -> Option<UnixTimestamp> {
+        critical_section::with(|cs| CLOCK.borrow(cs).get()).map(|a| a.timestamp_at(instant))
+    }
+
+    /// Instant at which the wall clock will read `timestamp`, or `None` if it has not been set.
+    ///
+    /// This is useful to wait until a wall-clock time with [`Timer::at`](crate::Timer::at). The
+    /// result is only accurate until the wall clock is next set, stepped or slewed.
+    pub fn to_instant(timestamp: UnixTimestamp) -> Option<Instant> {
+        critical_section::with(|cs| CLOCK.borrow(cs).get()).map(|a| a.instant_at(timestamp))
+    }
+
+    /// Set the current wall-clock time.
+    ///
+    /// This cancels any slew in progress.
+    pub fn set(timestamp: UnixTimestamp) {
+        Self::set_at(Instant::now(), timestamp)
+    }
+
+    /// Set the wall-clock time, knowing that it was `timestamp` at `instant`.
+    ///
+    /// This allows sources with latency to set the time precisely, by recording the instant at
+    /// which the time was sampled, such as the arrival of a GPS pulse-per-second signal.
+    ///
+    /// This cancels any slew in progress.
+    pub fn set_at(instant: Instant, timestamp: UnixTimestamp) {
+        critical_section::with(|cs| {
+            CLOCK.borrow(cs).set(Some(Anchor {
+                instant,
+                timestamp,
+                slew: 0,
+            }))
+        })
+    }
+
+    /// Immediately add `offset` microseconds to the wall-clock time.
+    ///
+    /// A negative offset makes the wall clock go back in time. This does nothing if the wall
+    /// clock has not been set.
+    pub fn step(offset: i64) {
+        let now = Instant::now();
+        critical_section::with(|cs| {
+            let clock = CLOCK.borrow(cs);
+            if let Some(anchor) = clock.get() {
+                let mut anchor = anchor.rebase(now);
+                anchor.timestamp = anchor.timestamp.saturating_offset(offset);
+                clock.set(Some(anchor));
+            }
+        })
+    }
+
+    /// Gradually add `offset` microseconds to the wall-clock time.
+    ///
+    /// The wall clock runs faster (or slower, for negative offsets) by
+    /// [`SLEW_RATE_PPM`](SystemTime::SLEW_RATE_PPM) until the offset has been applied, so it
+    /// never goes back in time. Correcting one millisecond takes two seconds.
+    ///
+    /// This replaces any slew in progress. It does nothing if the wall clock has not been set.
+    pub fn slew(offset: i64) {
+        let now = Instant::now();
+        critical_section::with(|cs| {
+            let clock = CLOCK.borrow(cs);
+            if let Some(anchor) = clock.get() {
+                let mut anchor = anchor.rebase(now);
+                anchor.slew = offset;
+                clock.set(Some(anchor));
+            }
+        })
+    }
+
+    /// Part of the last [`slew`](SystemTime::slew) offset that has not been applied yet, in microseconds.
+    pub fn remaining_slew() -> i64 {
+        let now = Instant::now();
+        critical_section::with(|cs| CLOCK.borrow(cs).get()).map_or(0, |a| a.rebase(now).slew)
+    }
+}
+
+#[cfg(all(test, feature = "mock-driver"))]
+mod tests {
+    use serial_test::serial;
+
+    use super::*;
+    use crate::MockDriver;
+
+    fn setup() {
+        MockDriver::get().reset();
+        critical_section::with(|cs| CLOCK.borrow(cs).set(None));
+    }
+
+    #[test]
+    fn timestamp_overflow() {
+        assert_eq!(
+            UnixTimestamp::try_from_secs(u64::MAX / 1_000_000),
+            Some(UnixTimestamp::from_secs(u64::MAX / 1_000_000))
+        );
+        assert_eq!(UnixTimestamp::try_from_secs(u64::MAX / 1_000_000 + 1), None);
+        assert_eq!(
+            UnixTimestamp::try_from_millis(u64::MAX / 1000),
+            Some(UnixTimestamp::from_millis(u64::MAX / 1000))
+        );
+        assert_eq!(UnixTimestamp::try_from_millis(u64::MAX), None);
+    }
+
+    #[test]
+    #[serial]
+    fn not_set() {
+        setup();
+        assert!(!SystemTime::is_set());
+        assert_eq!(SystemTime::now(), None);
+        SystemTime::slew(1000);
+        SystemTime::step(1000);
+        assert_eq!(SystemTime::now(), None);
+    }
+
+    #[test]
+    #[serial]
+    fn follows_instant() {
+        setup();
+        let t0 = UnixTimestamp::from_secs(1_700_000_000);
+        SystemTime::set(t0);
+        assert_eq!(SystemTime::now(), Some(t0));
+
+        MockDriver::get().advance(Duration::from_millis(1500));
+        assert_eq!(SystemTime::now(), Some(t0 + Duration::from_millis(1500)));
+        assert_eq!(
+            SystemTime::to_instant(t0 + Duration::from_secs(10)),
+            Some(Instant::from_secs(10))
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn set_at_past_instant() {
+        setup();
+        MockDriver::get().advance(Duration::from_secs(5));
+        let t0 = UnixTimestamp::from_secs(1_700_000_000);
+        SystemTime::set_at(Instant::from_secs(2), t0);
+        assert_eq!(SystemTime::now(), Some(t0 + Duration::from_secs(3)));
+        assert_eq!(
+            SystemTime::from_instant(Instant::from_secs(1)),
+            Some(t0 - Duration::from_secs(1))
+        );
+        assert_eq!(
+            SystemTime::to_instant(t0 - Duration::from_secs(1)),
+            Some(Instant::from_secs(1))
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn step() {
+        setup();
+        let t0 = UnixTimestamp::from_secs(1_700_000_000);
+        SystemTime::set(t0);
+        MockDriver::get().advance(Duration::from_secs(1));
+        SystemTime::step(-250_000);
+        assert_eq!(SystemTime::now(), Some(t0 + Duration::from_millis(750)));
+    }
+
+    #[test]
+    #[serial]
+    fn slew_forward() {
+        setup();
+        let t0 = UnixTimestamp::from_secs(1_700_000_000);
+        SystemTime::set(t0);
+        SystemTime::slew(1000);
+
+        // 1ms at 500ppm takes 2s.
+        MockDriver::get().advance(Duration::from_secs(1));
+        assert_eq!(SystemTime::now(), Some(t0 + Duration::from_micros(1_000_500)));
+        assert_eq!(SystemTime::remaining_slew(), 500);
+
+        MockDriver::get().advance(Duration::from_secs(9));
+        assert_eq!(SystemTime::now(), Some(t0 + Duration::from_micros(10_001_000)));
+        assert_eq!(SystemTime::remaining_slew(), 0);
+    }
+
+    #[test]
+    #[serial]
+    fn slew_backward_is_monotonic() {
+        setup();
+        let t0 = UnixTimestamp::from_secs(1_700_000_000);
+        SystemTime::set(t0);
+        SystemTime::slew(-1000);
+
+        let mut last = SystemTime::now().unwrap();
+        for _ in 0..30 {
+            MockDriver::get().advance(Duration::from_millis(100));
+            let now = SystemTime::now().unwrap();
+            assert!(now > last);
+            last = now;
+        }
+        assert_eq!(last, t0 + Duration::from_micros(3_000_000 - 1000));
+    }
+
+    #[test]
+    #[serial]
+    fn to_instant_while_slewing() {
+        setup();
+        let t0 = UnixTimestamp::from_secs(1_700_000_000);
+        SystemTime::set(t0);
+        for slew in [1000, -1000] {
+            SystemTime::slew(slew);
+            for ms in [0, 500, 1999, 2000, 2001, 5000] {
+                let instant = Instant::now() + Duration::from_millis(ms);
+                let timestamp = SystemTime::from_instant(instant).unwrap();
+                let back = SystemTime::to_instant(timestamp).unwrap();
+                assert!(back.as_micros().abs_diff(instant.as_micros()) <= 1, "{slew} {ms}");
+            }
+        }
+    }
+
+    #[test]
+    fn display() {
+        let t = UnixTimestamp::from_micros(1_709_211_845_250_000);
+        assert_eq!(std::format!("{}", t), "2024-02-29T13:04:05.250000Z");
+    }
+}
@@ -0,0 +1,99 @@
+use clap::Parser;
+use embassy_executor::{Executor, Spawner};
+use embassy_net::dhcp_server::{self, DhcpServer};
+use embassy_net::udp::PacketMetadata;
+use embassy_net::{Config, Ipv4Address, Ipv4Cidr, StackResources};
+use embassy_net_tuntap::TunTapDevice;
+use embassy_time::{Duration, Timer};
+use heapless::Vec;
+use log::*;
+use rand_core::{OsRng, RngCore};
+use static_cell::StaticCell;
+
+#[derive(Parser)]
+#[clap(version = "1.0")]
+struct Opts {
+    /// TAP device name
+    #[clap(long, default_value = "tap0")]
+    tap: String,
+}
+
+#[embassy_executor::task]
+async fn net_task(mut runner: embassy_net::Runner<'static, TunTapDevice>) -> ! {
+    runner.run().await
+}
+
+#[embassy_executor::task]
+async fn dhcp_task(mut server: DhcpServer<'static, 8>) -> ! {
+    server.run().await
+}
+
+#[embassy_executor::task]
+async fn main_task(spawner: Spawner) {
+    let opts: Opts = Opts::parse();
+
+    // Init network device
+    let device = TunTapDevice::new(&opts.tap).unwrap();
+
+    // The DHCP server needs a static IP
+    let address = Ipv4Address::new(192, 168, 69, 2);
+    let config = Config::ipv4_static(embassy_net::StaticConfigV4 {
+        address: Ipv4Cidr::new(address, 24),
+        dns_servers: Vec::new(),
+        gateway: None,
+    });
+
+    // Generate random seed
+    let mut seed = [0; 8];
+    OsRng.fill_bytes(&mut seed);
+    let seed = u64::from_le_bytes(seed);
+
+    // Init network stack
+    static RESOURCES: StaticCell<StackResources<3>> = StaticCell::new();
+    let (stack, runner) = embassy_net::new(device, config, RESOURCES.init(StackResources::new()), seed);
+
+    // Launch network task
+    spawner.spawn(net_task(runner)).unwrap();
+
+    // Then we can use it!
+    let config = dhcp_server::Config {
+        pool_start: Ipv4Address::new(192, 168, 69, 50),
+        lease_time: Duration::from_secs(600),
+        router: Some(address),
+        dns_servers: Vec::from_slice(&[address]).unwrap(),
+    };
+    static RX_META: StaticCell<[PacketMetadata; 4]> = StaticCell::new();
+    static RX_BUFFER: StaticCell<[u8; 2048]> = StaticCell::new();
+    static TX_META: StaticCell<[PacketMetadata; 4]> = StaticCell::new();
+    static TX_BUFFER: StaticCell<[u8; 2048]> = StaticCell::new();
+    let server = DhcpServer::new(
+        stack,
+        config,
+        RX_META.init([PacketMetadata::EMPTY; 4]),
+        RX_BUFFER.init([0; 2048]),
+        TX_META.init([PacketMetadata::EMPTY; 4]),
+        TX_BUFFER.init([0; 2048]),
+    )
+    .unwrap();
+    spawner.spawn(dhcp_task(server)).unwrap();
+
+    info!("DHCP server running on {}", address);
+    loop {
+        Timer::after_secs(60).await;
+    }
+}
+
+static EXECUTOR: StaticCell<Executor> = StaticCell::new();
+
+fn main() {
+    env_logger::builder()
+        .filter_level(log::LevelFilter::Debug)
+        .filter_module("async_io", log::LevelFilter::Info)
+        .format_timestamp_nanos()
+        .init();
+
+    let executor = EXECUTOR.init(Executor::new());
+    executor.run(|spawner| {
+        spawner.spawn(main_task(spawner)).unwrap();
+    });
+}
@@ -0,0 +1,80 @@
+use clap::Parser;
+use embassy_executor::{Executor, Spawner};
+use embassy_net::{Config, DhcpConfig, StackResources};
+use embassy_net_tuntap::TunTapDevice;
+use embassy_time::Timer;
+use log::*;
+use rand_core::{OsRng, RngCore};
+use static_cell::StaticCell;
+
+#[derive(Parser)]
+#[clap(version = "1.0")]
+struct Opts {
+    /// TAP device name
+    #[clap(long, default_value = "tap0")]
+    tap: String,
+    /// use DHCP, and a link-local address until a lease arrives
+    #[clap(long)]
+    dhcp: bool,
+}
+
+#[embassy_executor::task]
+async fn net_task(mut runner: embassy_net::Runner<'static, TunTapDevice>) -> ! {
+    runner.run().await
+}
+
+#[embassy_executor::task]
+async fn main_task(spawner: Spawner) {
+    let opts: Opts = Opts::parse();
+
+    // Init network device
+    let device = TunTapDevice::new(&opts.tap).unwrap();
+
+    // Claim a 169.254.x.y address, optionally alongside DHCP
+    let config = if opts.dhcp {
+        let mut dhcp = DhcpConfig::default();
+        dhcp.link_local_fallback = true;
+        Config::dhcpv4(dhcp)
+    } else {
+        Config::ipv4_link_local()
+    };
+
+    // Generate random seed
+    let mut seed = [0; 8];
+    OsRng.fill_bytes(&mut seed);
+    let seed = u64::from_le_bytes(seed);
+
+    // Init network stack
+    static RESOURCES: StaticCell<StackResources<3>> = StaticCell::new();
+    let (stack, runner) = embassy_net::new(device, config, RESOURCES.init(StackResources::new()), seed);
+
+    // Launch network task
+    spawner.spawn(net_task(runner)).unwrap();
+
+    info!("Claiming an address...");
+    stack.wait_config_up().await;
+
+    loop {
+        info!(
+            "IPv4 config: {:?} from {:?}",
+            stack.config_v4(),
+            stack.config_v4_source()
+        );
+        Timer::after_secs(5).await;
+    }
+}
+
+static EXECUTOR: StaticCell<Executor> = StaticCell::new();
+
+fn main() {
+    env_logger::builder()
+        .filter_level(log::LevelFilter::Debug)
+        .filter_module("async_io", log::LevelFilter::Info)
+        .format_timestamp_nanos()
+        .init();
+
+    let executor = EXECUTOR.init(Executor::new());
+    executor.run(|spawner| {
+        spawner.spawn(main_task(spawner)).unwrap();
+    });
+}
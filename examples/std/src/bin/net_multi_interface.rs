@@ -0,0 +1,118 @@
+use clap::Parser;
+use embassy_executor::{Executor, Spawner};
+use embassy_net::tcp::TcpSocket;
+use embassy_net::{Config, InterfaceResources, Ipv4Address, Ipv4Cidr, Stack, StackResources};
+use embassy_net_tuntap::TunTapDevice;
+use embassy_time::Duration;
+use embedded_io_async::Write;
+use heapless::Vec;
+use log::*;
+use rand_core::{OsRng, RngCore};
+use static_cell::StaticCell;
+
+#[derive(Parser)]
+#[clap(version = "1.0")]
+struct Opts {
+    /// TAP device name of the first interface (192.168.69.0/24)
+    #[clap(long, default_value = "tap0")]
+    tap: String,
+    /// TAP device name of the second interface (192.168.70.0/24)
+    #[clap(long, default_value = "tap1")]
+    tap2: String,
+}
+
+#[embassy_executor::task(pool_size = 2)]
+async fn net_task(mut runner: embassy_net::Runner<'static, TunTapDevice>) -> ! {
+    runner.run().await
+}
+
+async fn send_hello(stack: Stack<'static>, remote: Ipv4Address) {
+    let mut rx_buffer = [0; 1024];
+    let mut tx_buffer = [0; 1024];
+    let mut socket = TcpSocket::new(stack, &mut rx_buffer, &mut tx_buffer);
+    socket.set_timeout(Some(Duration::from_secs(10)));
+
+    info!(
+        "connecting to {}:8000 through {:?}...",
+        remote,
+        stack.route(remote.into())
+    );
+    if let Err(e) = socket.connect((remote, 8000)).await {
+        warn!("connect error: {:?}", e);
+        return;
+    }
+    info!(
+        "connected from {:?} on {:?}",
+        socket.local_endpoint(),
+        socket.interface()
+    );
+
+    if let Err(e) = socket.write_all(b"Hello!\r\n").await {
+        warn!("write error: {:?}", e);
+    }
+    socket.close();
+    let _ = socket.flush().await;
+}
+
+#[embassy_executor::task]
+async fn main_task(spawner: Spawner) {
+    let opts: Opts = Opts::parse();
+
+    let config = |a, b, c, d| {
+        Config::ipv4_static(embassy_net::StaticConfigV4 {
+            address: Ipv4Cidr::new(Ipv4Address::new(a, b, c, d), 24),
+            dns_servers: Vec::new(),
+            gateway: None,
+        })
+    };
+
+    // Generate random seed
+    let mut seed = [0; 8];
+    OsRng.fill_bytes(&mut seed);
+    let seed = u64::from_le_bytes(seed);
+
+    // Init network stack, with the first interface
+    let device = TunTapDevice::new(&opts.tap).unwrap();
+    static RESOURCES: StaticCell<StackResources<3>> = StaticCell::new();
+    let (stack, runner) = embassy_net::new(
+        device,
+        config(192, 168, 69, 2),
+        RESOURCES.init(StackResources::new()),
+        seed,
+    );
+    spawner.spawn(net_task(runner)).unwrap();
+
+    // Add the second interface
+    let device = TunTapDevice::new(&opts.tap2).unwrap();
+    static IFACE_RESOURCES: StaticCell<InterfaceResources<2>> = StaticCell::new();
+    let (iface, runner) = stack.add_interface(
+        device,
+        config(192, 168, 70, 2),
+        IFACE_RESOURCES.init(InterfaceResources::new()),
+        seed.wrapping_add(1),
+    );
+    spawner.spawn(net_task(runner)).unwrap();
+
+    stack.wait_config_up().await;
+    iface.wait_config_up().await;
+    info!("second interface up: {:?} {:?}", iface.id(), iface.config_v4());
+
+    // Each connection goes out through the interface on the subnet of the remote address.
+    send_hello(stack, Ipv4Address::new(192, 168, 69, 100)).await;
+    send_hello(stack, Ipv4Address::new(192, 168, 70, 100)).await;
+}
+
+static EXECUTOR: StaticCell<Executor> = StaticCell::new();
+
+fn main() {
+    env_logger::builder()
+        .filter_level(log::LevelFilter::Debug)
+        .filter_module("async_io", log::LevelFilter::Info)
+        .format_timestamp_nanos()
+        .init();
+
+    let executor = EXECUTOR.init(Executor::new());
+    executor.run(|spawner| {
+        spawner.spawn(main_task(spawner)).unwrap();
+    });
+}
@@ -0,0 +1,115 @@
+use core::cell::RefCell;
+use std::fs::File;
+use std::io::Write as _;
+
+use clap::Parser;
+use embassy_executor::{Executor, Spawner};
+use embassy_net::pcap::PcapSink;
+use embassy_net::tcp::TcpSocket;
+use embassy_net::{Config, Ipv4Address, StackResources};
+use embassy_net_tuntap::TunTapDevice;
+use embassy_time::{Duration, Timer};
+use embedded_io_async::Write;
+use log::*;
+use rand_core::{OsRng, RngCore};
+use static_cell::StaticCell;
+
+#[derive(Parser)]
+#[clap(version = "1.0")]
+struct Opts {
+    /// TAP device name
+    #[clap(long, default_value = "tap0")]
+    tap: String,
+    /// File to write the capture to
+    #[clap(long, default_value = "capture.pcapng")]
+    output: String,
+}
+
+/// Writes the capture to a file.
+struct FileSink(RefCell<File>);
+
+impl PcapSink for FileSink {
+    fn write(&self, parts: &[&[u8]]) -> bool {
+        let mut file = self.0.borrow_mut();
+        for part in parts {
+            file.write_all(part).unwrap();
+        }
+        true
+    }
+}
+
+#[embassy_executor::task]
+async fn net_task(mut runner: embassy_net::Runner<'static, TunTapDevice>) -> ! {
+    runner.run().await
+}
+
+#[embassy_executor::task]
+async fn main_task(spawner: Spawner) {
+    let opts: Opts = Opts::parse();
+
+    // Init network device
+    let device = TunTapDevice::new(&opts.tap).unwrap();
+
+    // Generate random seed
+    let mut seed = [0; 8];
+    OsRng.fill_bytes(&mut seed);
+    let seed = u64::from_le_bytes(seed);
+
+    // Init network stack
+    static RESOURCES: StaticCell<StackResources<3>> = StaticCell::new();
+    let (stack, mut runner) = embassy_net::new(
+        device,
+        Config::dhcpv4(Default::default()),
+        RESOURCES.init(StackResources::new()),
+        seed,
+    );
+
+    // Capture everything from the start, DHCP included.
+    static SINK: StaticCell<FileSink> = StaticCell::new();
+    let sink = SINK.init(FileSink(RefCell::new(File::create(&opts.output).unwrap())));
+    runner.set_pcap_sink(Some(sink));
+
+    // Launch network task
+    spawner.spawn(net_task(runner)).unwrap();
+
+    info!("Waiting for DHCP...");
+    stack.wait_config_up().await;
+    info!("IP address: {:?}", stack.config_v4().unwrap().address);
+
+    // Then we can use it!
+    let mut rx_buffer = [0; 4096];
+    let mut tx_buffer = [0; 4096];
+    let mut socket = TcpSocket::new(stack, &mut rx_buffer, &mut tx_buffer);
+    socket.set_timeout(Some(Duration::from_secs(10)));
+
+    let remote_endpoint = (Ipv4Address::new(192, 168, 69, 100), 8000);
+    info!("connecting to {:?}...", remote_endpoint);
+    match socket.connect(remote_endpoint).await {
+        Ok(()) => {
+            socket.write_all(b"Hello!\r\n").await.unwrap();
+            socket.close();
+            socket.flush().await.unwrap();
+        }
+        Err(e) => warn!("connect error: {:?}", e),
+    }
+
+    // Let the connection close, then stop.
+    Timer::after_secs(1).await;
+    info!("capture written to {}", opts.output);
+    std::process::exit(0);
+}
+
+static EXECUTOR: StaticCell<Executor> = StaticCell::new();
+
+fn main() {
+    env_logger::builder()
+        .filter_level(log::LevelFilter::Debug)
+        .filter_module("async_io", log::LevelFilter::Info)
+        .format_timestamp_nanos()
+        .init();
+
+    let executor = EXECUTOR.init(Executor::new());
+    executor.run(|spawner| {
+        spawner.spawn(main_task(spawner)).unwrap();
+    });
+}
@@ -2,10 +2,17 @@
 //!
 //!     echo myuser $(hostname) mypass 192.168.7.10 >> /etc/ppp/pap-secrets
 //!     socat -v -x PTY,link=pty1,rawer PTY,link=pty2,rawer
-//!     sudo pppd $PWD/pty1 115200 192.168.7.1: ms-dns 8.8.4.4 ms-dns 8.8.8.8 nodetach debug local persist silent noproxyarp
+//!     sudo pppd $PWD/pty1 115200 192.168.7.1: ms-dns 8.8.4.4 ms-dns 8.8.8.8 nodetach debug local persist silent noproxyarp +ipv6
 //!     RUST_LOG=trace cargo run --bin net_ppp -- --device pty2
 //!     ping 192.168.7.10
+//!     ping fe80::<remote LL address logged by pppd>%ppp0
 //!     nc 192.168.7.10 1234
+//!
+//! With a cellular modem, pass `--apn <apn>` to dial it with AT commands first. It's dialed
+//! again whenever the PPP connection ends.
+//!
+//! IPv6 is configured with SLAAC, using the interface identifier negotiated with IPV6CP. A global
+//! address needs room for 3 addresses: set `SMOLTCP_IFACE_MAX_ADDR_COUNT=3` in your environment.
 
 #![allow(async_fn_in_trait)]
 
@@ -16,8 +23,8 @@ use async_io::Async;
 use clap::Parser;
 use embassy_executor::{Executor, Spawner};
 use embassy_net::tcp::TcpSocket;
-use embassy_net::{Config, ConfigV4, Ipv4Cidr, Stack, StackResources};
-use embassy_net_ppp::Runner;
+use embassy_net::{Config, ConfigV4, ConfigV6, Ipv4Cidr, SlaacConfig, Stack, StackResources};
+use embassy_net_ppp::{Dialer, Runner};
 use embedded_io_async::Write;
 use futures::io::BufReader;
 use heapless::Vec;
@@ -34,6 +41,9 @@ struct Opts {
     /// Serial port device name
     #[clap(short, long)]
     device: String,
+    /// Access point name, to dial a cellular modem before starting PPP
+    #[clap(long)]
+    apn: Option<String>,
 }
 
 #[embassy_executor::task]
@@ -42,7 +52,12 @@ async fn net_task(mut runner: embassy_net::Runner<'static, embassy_net_ppp::Devi
 }
 
 #[embassy_executor::task]
-async fn ppp_task(stack: Stack<'static>, mut runner: Runner<'static>, port: SerialPort) -> ! {
+async fn ppp_task(
+    stack: Stack<'static>,
+    mut runner: Runner<'static>,
+    port: SerialPort,
+    apn: Option<&'static str>,
+) -> ! {
     let port = Async::new(port).unwrap();
     let port = BufReader::new(port);
     let port = embedded_io_adapters::futures_03::FromFutures::new(port);
@@ -52,25 +67,49 @@ async fn ppp_task(stack: Stack<'static>, mut runner: Runner<'static>, port: Seri
         password: b"mypass",
     };
 
-    runner
-        .run(port, config, |ipv4| {
-            let Some(addr) = ipv4.address else {
-                warn!("PPP did not provide an IP address.");
-                return;
-            };
-            let mut dns_servers = Vec::new();
-            for s in ipv4.dns_servers.iter().flatten() {
-                let _ = dns_servers.push(*s);
-            }
-            let config = ConfigV4::Static(embassy_net::StaticConfigV4 {
-                address: Ipv4Cidr::new(addr, 0),
-                gateway: None,
-                dns_servers,
-            });
-            stack.set_config_v4(config);
-        })
-        .await
-        .unwrap();
+    let on_ipv4_up = |ipv4: embassy_net_ppp::Ipv4Status| {
+        let Some(addr) = ipv4.address else {
+            warn!("PPP did not provide an IP address.");
+            return;
+        };
+        let mut dns_servers = Vec::new();
+        for s in ipv4.dns_servers.iter().flatten() {
+            let _ = dns_servers.push(*s);
+        }
+        let config = ConfigV4::Static(embassy_net::StaticConfigV4 {
+            address: Ipv4Cidr::new(addr, 0),
+            gateway: None,
+            dns_servers,
+        });
+        stack.set_config_v4(config);
+    };
+    let on_ipv6_up = |ipv6: embassy_net_ppp::Ipv6Status| {
+        let Some(iid) = ipv6.interface_identifier else {
+            warn!("PPP did not provide an IPv6 interface identifier.");
+            return;
+        };
+        // The link-local address uses the negotiated identifier, and so do global addresses if
+        // the peer advertises a prefix.
+        let mut config = SlaacConfig::default();
+        config.interface_identifier = Some(iid);
+        stack.set_config_v6(ConfigV6::Slaac(config));
+    };
+
+    match apn {
+        Some(apn) => {
+            let dialer = Dialer::cellular(apn);
+            runner
+                .run_with_dialer_ipv6(port, &dialer, config, on_ipv4_up, on_ipv6_up)
+                .await
+                .unwrap();
+        }
+        None => {
+            runner
+                .run_with_ipv6(port, config, on_ipv4_up, on_ipv6_up)
+                .await
+                .unwrap();
+        }
+    }
     unreachable!()
 }
 
@@ -103,7 +142,8 @@ async fn main_task(spawner: Spawner) {
 
     // Launch network task
     spawner.spawn(net_task(net_runner)).unwrap();
-    spawner.spawn(ppp_task(stack, runner, port)).unwrap();
+    let apn = opts.apn.map(|apn| &*apn.leak());
+    spawner.spawn(ppp_task(stack, runner, port, apn)).unwrap();
 
     // Then we can use it!
     let mut rx_buffer = [0; 4096];
@@ -0,0 +1,119 @@
+use clap::Parser;
+use embassy_executor::{Executor, Spawner};
+use embassy_net::sntp::SntpClient;
+use embassy_net::udp::PacketMetadata;
+use embassy_net::{Config, Ipv4Address, Ipv4Cidr, StackResources};
+use embassy_net_tuntap::TunTapDevice;
+use embassy_time::{Duration, SystemTime, Timer};
+use heapless::Vec;
+use log::*;
+use rand_core::{OsRng, RngCore};
+use static_cell::StaticCell;
+
+#[derive(Parser)]
+#[clap(version = "1.0")]
+struct Opts {
+    /// TAP device name
+    #[clap(long, default_value = "tap0")]
+    tap: String,
+    /// use a static IP instead of DHCP
+    #[clap(long)]
+    static_ip: bool,
+    /// NTP server name or address. Run the `sntp_server` example on the host for a local stand-in.
+    #[clap(long, default_value = "192.168.69.100")]
+    server: String,
+}
+
+#[embassy_executor::task]
+async fn net_task(mut runner: embassy_net::Runner<'static, TunTapDevice>) -> ! {
+    runner.run().await
+}
+
+#[embassy_executor::task]
+async fn sntp_task(mut client: SntpClient<'static>, server: &'static str) -> ! {
+    client.run(server, Duration::from_secs(16)).await
+}
+
+#[embassy_executor::task]
+async fn main_task(spawner: Spawner) {
+    let opts: &'static Opts = Box::leak(Box::new(Opts::parse()));
+
+    // Init network device
+    let device = TunTapDevice::new(&opts.tap).unwrap();
+
+    // Choose between dhcp or static ip
+    let config = if opts.static_ip {
+        Config::ipv4_static(embassy_net::StaticConfigV4 {
+            address: Ipv4Cidr::new(Ipv4Address::new(192, 168, 69, 2), 24),
+            dns_servers: Vec::from_slice(&[Ipv4Address::new(8, 8, 8, 8)]).unwrap(),
+            gateway: Some(Ipv4Address::new(192, 168, 69, 100)),
+        })
+    } else {
+        Config::dhcpv4(Default::default())
+    };
+
+    // Generate random seed
+    let mut seed = [0; 8];
+    OsRng.fill_bytes(&mut seed);
+    let seed = u64::from_le_bytes(seed);
+
+    // Init network stack
+    static RESOURCES: StaticCell<StackResources<3>> = StaticCell::new();
+    let (stack, runner) = embassy_net::new(device, config, RESOURCES.init(StackResources::new()), seed);
+
+    // Launch network task
+    spawner.spawn(net_task(runner)).unwrap();
+
+    stack.wait_config_up().await;
+
+    // Then we can use it!
+    static RX_META: StaticCell<[PacketMetadata; 2]> = StaticCell::new();
+    static RX_BUFFER: StaticCell<[u8; 256]> = StaticCell::new();
+    static TX_META: StaticCell<[PacketMetadata; 2]> = StaticCell::new();
+    static TX_BUFFER: StaticCell<[u8; 256]> = StaticCell::new();
+    let mut client = SntpClient::new(
+        stack,
+        RX_META.init([PacketMetadata::EMPTY; 2]),
+        RX_BUFFER.init([0; 256]),
+        TX_META.init([PacketMetadata::EMPTY; 2]),
+        TX_BUFFER.init([0; 256]),
+        OsRng.next_u64(),
+    )
+    .unwrap();
+
+    match client.query(&opts.server).await {
+        Ok(m) => info!(
+            "server {} (stratum {}): {}, delay {} us",
+            m.server,
+            m.stratum,
+            m.timestamp,
+            m.delay.as_micros()
+        ),
+        Err(e) => warn!("query failed: {:?}", e),
+    }
+
+    spawner.spawn(sntp_task(client, &opts.server)).unwrap();
+
+    loop {
+        match SystemTime::now() {
+            Some(now) => info!("UTC time: {}", now),
+            None => info!("UTC time not set yet"),
+        }
+        Timer::after_secs(5).await;
+    }
+}
+
+static EXECUTOR: StaticCell<Executor> = StaticCell::new();
+
+fn main() {
+    env_logger::builder()
+        .filter_level(log::LevelFilter::Debug)
+        .filter_module("async_io", log::LevelFilter::Info)
+        .format_timestamp_nanos()
+        .init();
+
+    let executor = EXECUTOR.init(Executor::new());
+    executor.run(|spawner| {
+        spawner.spawn(main_task(spawner)).unwrap();
+    });
+}
@@ -0,0 +1,122 @@
+use clap::Parser;
+use embassy_executor::{Executor, Spawner};
+use embassy_net::tcp::TcpSocket;
+use embassy_net::{Config, Ipv4Address, Ipv4Cidr, Stack, StackResources, StaticConfigV4};
+use embassy_net_virtual::{Device, Switch};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_time::Duration;
+use embedded_io_async::Write;
+use heapless::Vec;
+use log::*;
+use static_cell::StaticCell;
+
+#[derive(Parser)]
+#[clap(version = "1.0")]
+struct Opts {
+    /// Probability a packet is dropped
+    #[clap(long, default_value = "0.05")]
+    loss: f32,
+}
+
+type VirtualSwitch = Switch<CriticalSectionRawMutex, 2, 1514, 16>;
+type VirtualDevice = Device<'static, CriticalSectionRawMutex, 2, 1514, 16>;
+
+const SERVER_ADDRESS: Ipv4Address = Ipv4Address::new(10, 0, 0, 1);
+const CLIENT_ADDRESS: Ipv4Address = Ipv4Address::new(10, 0, 0, 2);
+const LEN: usize = 64 * 1024;
+
+#[embassy_executor::task(pool_size = 2)]
+async fn net_task(mut runner: embassy_net::Runner<'static, VirtualDevice>) -> ! {
+    runner.run().await
+}
+
+/// Receives data, and counts it.
+#[embassy_executor::task]
+async fn sink_task(stack: Stack<'static>) {
+    let mut rx_buffer = [0; 4096];
+    let mut tx_buffer = [0; 4096];
+    let mut buf = [0; 4096];
+    let mut socket = TcpSocket::new(stack, &mut rx_buffer, &mut tx_buffer);
+    socket.accept(1234).await.unwrap();
+    let mut received = 0;
+    loop {
+        match socket.read(&mut buf).await {
+            Ok(0) | Err(_) => break,
+            Ok(n) => received += n,
+        }
+    }
+    info!("server: received {} bytes, {:?}", received, socket.stats());
+}
+
+fn static_config(address: Ipv4Address) -> Config {
+    Config::ipv4_static(StaticConfigV4 {
+        address: Ipv4Cidr::new(address, 24),
+        dns_servers: Vec::new(),
+        gateway: None,
+    })
+}
+
+#[embassy_executor::task]
+async fn main_task(spawner: Spawner) {
+    let opts: Opts = Opts::parse();
+
+    // Init the virtual network, with some packet loss.
+    static SWITCH: StaticCell<VirtualSwitch> = StaticCell::new();
+    let switch = SWITCH.init(Switch::new(embassy_net_virtual::Config {
+        latency: Duration::from_millis(5),
+        loss: opts.loss,
+        ..Default::default()
+    }));
+
+    static SERVER_RESOURCES: StaticCell<StackResources<3>> = StaticCell::new();
+    let (server, runner) = embassy_net::new(
+        switch.device([0x02, 0, 0, 0, 0, 1]),
+        static_config(SERVER_ADDRESS),
+        SERVER_RESOURCES.init(StackResources::new()),
+        1,
+    );
+    spawner.spawn(net_task(runner)).unwrap();
+    spawner.spawn(sink_task(server)).unwrap();
+
+    static CLIENT_RESOURCES: StaticCell<StackResources<3>> = StaticCell::new();
+    let (client, runner) = embassy_net::new(
+        switch.device([0x02, 0, 0, 0, 0, 2]),
+        static_config(CLIENT_ADDRESS),
+        CLIENT_RESOURCES.init(StackResources::new()),
+        2,
+    );
+    spawner.spawn(net_task(runner)).unwrap();
+
+    // Send data to the server, through the lossy network.
+    let mut rx_buffer = [0; 4096];
+    let mut tx_buffer = [0; 4096];
+    let mut socket = TcpSocket::new(client, &mut rx_buffer, &mut tx_buffer);
+    socket.set_timeout(Some(Duration::from_secs(10)));
+    socket.connect((SERVER_ADDRESS, 1234)).await.unwrap();
+    socket.write_all(&[0x55; LEN]).await.unwrap();
+    socket.close();
+    socket.flush().await.unwrap();
+
+    info!("client: sent {} bytes, {:?}", LEN, socket.stats());
+    info!("client stack: {:?}", client.stats());
+    embassy_time::Timer::after_millis(100).await;
+    info!("server stack: {:?}", server.stats());
+
+    std::process::exit(0);
+}
+
+static EXECUTOR: StaticCell<Executor> = StaticCell::new();
+
+fn main() {
+    env_logger::builder()
+        .filter_level(log::LevelFilter::Debug)
+        .filter_module("async_io", log::LevelFilter::Info)
+        .filter_module("smoltcp", log::LevelFilter::Info)
+        .format_timestamp_nanos()
+        .init();
+
+    let executor = EXECUTOR.init(Executor::new());
+    executor.run(|spawner| {
+        spawner.spawn(main_task(spawner)).unwrap();
+    });
+}
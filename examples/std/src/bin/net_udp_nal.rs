@@ -0,0 +1,101 @@
+use core::net::{Ipv4Addr, SocketAddr};
+
+use clap::Parser;
+use embassy_executor::{Executor, Spawner};
+use embassy_net::udp::client::{UdpClient, UdpClientState};
+use embassy_net::{Config, Ipv4Address, Ipv4Cidr, StackResources};
+use embassy_net_tuntap::TunTapDevice;
+use embedded_nal_async::{ConnectedUdp, UdpStack, UnconnectedUdp};
+use heapless::Vec;
+use log::*;
+use rand_core::{OsRng, RngCore};
+use static_cell::StaticCell;
+
+#[derive(Parser)]
+#[clap(version = "1.0")]
+struct Opts {
+    /// TAP device name
+    #[clap(long, default_value = "tap0")]
+    tap: String,
+    /// use a static IP instead of DHCP
+    #[clap(long)]
+    static_ip: bool,
+}
+
+#[embassy_executor::task]
+async fn net_task(mut runner: embassy_net::Runner<'static, TunTapDevice>) -> ! {
+    runner.run().await
+}
+
+#[embassy_executor::task]
+async fn main_task(spawner: Spawner) {
+    let opts: Opts = Opts::parse();
+
+    // Init network device
+    let device = TunTapDevice::new(&opts.tap).unwrap();
+
+    // Choose between dhcp or static ip
+    let config = if opts.static_ip {
+        Config::ipv4_static(embassy_net::StaticConfigV4 {
+            address: Ipv4Cidr::new(Ipv4Address::new(192, 168, 69, 2), 24),
+            dns_servers: Vec::new(),
+            gateway: Some(Ipv4Address::new(192, 168, 69, 1)),
+        })
+    } else {
+        Config::dhcpv4(Default::default())
+    };
+
+    // Generate random seed
+    let mut seed = [0; 8];
+    OsRng.fill_bytes(&mut seed);
+    let seed = u64::from_le_bytes(seed);
+
+    // Init network stack
+    static RESOURCES: StaticCell<StackResources<4>> = StaticCell::new();
+    let (stack, runner) = embassy_net::new(device, config, RESOURCES.init(StackResources::new()), seed);
+
+    // Launch network task
+    spawner.spawn(net_task(runner)).unwrap();
+
+    stack.wait_config_up().await;
+
+    // Then we can use it, through the embedded-nal-async traits!
+    static STATE: StaticCell<UdpClientState<2, 1024, 1024, 4>> = StaticCell::new();
+    let client = UdpClient::new(stack, STATE.init(UdpClientState::new()));
+    let mut buf = [0; 1024];
+
+    // Talk to a single peer.
+    let remote = SocketAddr::new(Ipv4Addr::new(192, 168, 69, 100).into(), 9400);
+    let (local, mut connection) = client.connect(remote).await.unwrap();
+    info!("connected from {} to {}", local, remote);
+    connection.send(b"Hello!").await.unwrap();
+    let n = connection.receive_into(&mut buf).await.unwrap();
+    info!("rx from {}: {:02x?}", remote, &buf[..n]);
+    drop(connection);
+
+    // Echo everything received on port 9400, from any address of the stack.
+    let mut socket = client
+        .bind_multiple(SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 9400))
+        .await
+        .unwrap();
+    loop {
+        let (n, local, remote) = socket.receive_into(&mut buf).await.unwrap();
+        info!("rx on {} from {}: {:02x?}", local, remote, &buf[..n]);
+        socket.send(local, remote, &buf[..n]).await.unwrap();
+    }
+}
+
+static EXECUTOR: StaticCell<Executor> = StaticCell::new();
+
+fn main() {
+    env_logger::builder()
+        .filter_level(log::LevelFilter::Debug)
+        .filter_module("async_io", log::LevelFilter::Info)
+        .format_timestamp_nanos()
+        .init();
+
+    let executor = EXECUTOR.init(Executor::new());
+    executor.run(|spawner| {
+        spawner.spawn(main_task(spawner)).unwrap();
+    });
+}
@@ -0,0 +1,176 @@
+use clap::Parser;
+use embassy_executor::{Executor, Spawner};
+use embassy_net::dhcp_server::{self, DhcpServer};
+use embassy_net::tcp::TcpSocket;
+use embassy_net::udp::PacketMetadata;
+use embassy_net::{Config, Ipv4Address, Ipv4Cidr, Stack, StackResources};
+use embassy_net_virtual::{Device, Switch};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_time::{Duration, Instant};
+use embedded_io_async::Write;
+use heapless::Vec;
+use log::*;
+use static_cell::StaticCell;
+
+#[derive(Parser)]
+#[clap(version = "1.0")]
+struct Opts {
+    /// Latency of the network, in milliseconds
+    #[clap(long, default_value = "5")]
+    latency: u64,
+    /// Maximum random delay added to the latency, in milliseconds
+    #[clap(long, default_value = "10")]
+    jitter: u64,
+    /// Probability a packet is dropped
+    #[clap(long, default_value = "0.05")]
+    loss: f32,
+    /// Seed of the random delays and drops
+    #[clap(long, default_value = "1")]
+    seed: u64,
+}
+
+type VirtualSwitch = Switch<CriticalSectionRawMutex, 2, 1514, 16>;
+type VirtualDevice = Device<'static, CriticalSectionRawMutex, 2, 1514, 16>;
+
+const SERVER_ADDRESS: Ipv4Address = Ipv4Address::new(10, 0, 0, 1);
+const LEN: usize = 64 * 1024;
+
+#[embassy_executor::task(pool_size = 2)]
+async fn net_task(mut runner: embassy_net::Runner<'static, VirtualDevice>) -> ! {
+    runner.run().await
+}
+
+#[embassy_executor::task]
+async fn dhcp_task(stack: Stack<'static>) -> ! {
+    let config = dhcp_server::Config {
+        pool_start: Ipv4Address::new(10, 0, 0, 100),
+        lease_time: Duration::from_secs(3600),
+        router: None,
+        dns_servers: Vec::new(),
+    };
+    let mut rx_meta = [PacketMetadata::EMPTY; 4];
+    let mut rx_buffer = [0; 2048];
+    let mut tx_meta = [PacketMetadata::EMPTY; 4];
+    let mut tx_buffer = [0; 2048];
+    let mut server = DhcpServer::<8>::new(
+        stack,
+        config,
+        &mut rx_meta,
+        &mut rx_buffer,
+        &mut tx_meta,
+        &mut tx_buffer,
+    )
+    .unwrap();
+    server.run().await
+}
+
+#[embassy_executor::task]
+async fn echo_task(stack: Stack<'static>) -> ! {
+    let mut rx_buffer = [0; 4096];
+    let mut tx_buffer = [0; 4096];
+    let mut buf = [0; 4096];
+    loop {
+        let mut socket = TcpSocket::new(stack, &mut rx_buffer, &mut tx_buffer);
+        if let Err(e) = socket.accept(1234).await {
+            warn!("accept error: {:?}", e);
+            continue;
+        }
+        loop {
+            let n = match socket.read(&mut buf).await {
+                Ok(0) | Err(_) => break,
+                Ok(n) => n,
+            };
+            if socket.write_all(&buf[..n]).await.is_err() {
+                break;
+            }
+        }
+    }
+}
+
+#[embassy_executor::task]
+async fn main_task(spawner: Spawner) {
+    let opts: Opts = Opts::parse();
+
+    // Init the virtual network
+    static SWITCH: StaticCell<VirtualSwitch> = StaticCell::new();
+    let switch = SWITCH.init(Switch::new(embassy_net_virtual::Config {
+        latency: Duration::from_millis(opts.latency),
+        jitter: Duration::from_millis(opts.jitter),
+        loss: opts.loss,
+        seed: opts.seed,
+    }));
+
+    // Init the server stack, with a static address
+    let config = Config::ipv4_static(embassy_net::StaticConfigV4 {
+        address: Ipv4Cidr::new(SERVER_ADDRESS, 24),
+        dns_servers: Vec::new(),
+        gateway: None,
+    });
+    static SERVER_RESOURCES: StaticCell<StackResources<3>> = StaticCell::new();
+    let (server, runner) = embassy_net::new(
+        switch.device([0x02, 0, 0, 0, 0, 1]),
+        config,
+        SERVER_RESOURCES.init(StackResources::new()),
+        1,
+    );
+    spawner.spawn(net_task(runner)).unwrap();
+    spawner.spawn(dhcp_task(server)).unwrap();
+    spawner.spawn(echo_task(server)).unwrap();
+
+    // Init the client stack, configured by the server
+    static CLIENT_RESOURCES: StaticCell<StackResources<3>> = StaticCell::new();
+    let (client, runner) = embassy_net::new(
+        switch.device([0x02, 0, 0, 0, 0, 2]),
+        Config::dhcpv4(Default::default()),
+        CLIENT_RESOURCES.init(StackResources::new()),
+        2,
+    );
+    spawner.spawn(net_task(runner)).unwrap();
+
+    client.wait_config_up().await;
+    info!("client address: {:?}", client.config_v4().unwrap().address);
+
+    // Send data to the echo server, and check it comes back intact.
+    let mut rx_buffer = [0; 4096];
+    let mut tx_buffer = [0; 4096];
+    let mut socket = TcpSocket::new(client, &mut rx_buffer, &mut tx_buffer);
+    socket.set_timeout(Some(Duration::from_secs(10)));
+    socket.connect((SERVER_ADDRESS, 1234)).await.unwrap();
+
+    let start = Instant::now();
+    let data: std::vec::Vec<u8> = (0..LEN).map(|i| (i % 251) as u8).collect();
+    let mut received = std::vec::Vec::with_capacity(LEN);
+    let (mut reader, mut writer) = socket.split();
+    let write = async {
+        writer.write_all(&data).await.unwrap();
+        writer.flush().await.unwrap();
+    };
+    let read = async {
+        let mut buf = [0; 4096];
+        while received.len() < LEN {
+            let n = reader.read(&mut buf).await.unwrap();
+            received.extend_from_slice(&buf[..n]);
+        }
+    };
+    futures::future::join(write, read).await;
+    assert_eq!(data, received);
+    info!("echoed {} bytes in {} ms", LEN, start.elapsed().as_millis());
+
+    std::process::exit(0);
+}
+
+static EXECUTOR: StaticCell<Executor> = StaticCell::new();
+
+fn main() {
+    env_logger::builder()
+        .filter_level(log::LevelFilter::Debug)
+        .filter_module("async_io", log::LevelFilter::Info)
+        .filter_module("smoltcp", log::LevelFilter::Info)
+        .format_timestamp_nanos()
+        .init();
+
+    let executor = EXECUTOR.init(Executor::new());
+    executor.run(|spawner| {
+        spawner.spawn(main_task(spawner)).unwrap();
+    });
+}
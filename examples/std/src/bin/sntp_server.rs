@@ -0,0 +1,69 @@
+//! Minimal SNTP server running on the host, as a local stand-in for testing `embassy_net::sntp`
+//! through a TAP interface. Run it with `sudo`, since port 123 is privileged.
+
+use std::net::UdpSocket;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use clap::Parser;
+use log::*;
+
+#[derive(Parser)]
+#[clap(version = "1.0")]
+struct Opts {
+    /// Address to listen on
+    #[clap(long, default_value = "192.168.69.100:123")]
+    bind: String,
+    /// Offset added to the host's time, in milliseconds, to test clock corrections
+    #[clap(long, default_value = "0", allow_hyphen_values = true)]
+    offset_ms: i64,
+}
+
+/// Seconds between the NTP epoch (1900-01-01) and the Unix epoch (1970-01-01).
+const NTP_UNIX_OFFSET: u64 = 2_208_988_800;
+
+fn ntp_now(offset_ms: i64) -> [u8; 8] {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+    let now = if offset_ms < 0 {
+        now - Duration::from_millis(offset_ms.unsigned_abs())
+    } else {
+        now + Duration::from_millis(offset_ms as u64)
+    };
+    let secs = (now.as_secs() + NTP_UNIX_OFFSET) as u32;
+    let fraction = ((now.subsec_nanos() as u64) << 32) / 1_000_000_000;
+    let mut bytes = [0; 8];
+    bytes[..4].copy_from_slice(&secs.to_be_bytes());
+    bytes[4..].copy_from_slice(&(fraction as u32).to_be_bytes());
+    bytes
+}
+
+fn main() {
+    env_logger::builder().filter_level(log::LevelFilter::Debug).init();
+    let opts = Opts::parse();
+
+    let socket = UdpSocket::bind(&opts.bind).unwrap();
+    info!("listening on {}", opts.bind);
+
+    let mut buf = [0; 68];
+    loop {
+        let (n, peer) = socket.recv_from(&mut buf).unwrap();
+        let received = ntp_now(opts.offset_ms);
+        if n < 48 || buf[0] & 0b111 != 3 {
+            warn!("ignoring invalid request from {}", peer);
+            continue;
+        }
+
+        let mut response = [0; 48];
+        // Leap indicator 0, version 4, mode 4 (server).
+        response[0] = 0b00_100_100;
+        // Stratum 1, with a made up reference clock.
+        response[1] = 1;
+        response[12..16].copy_from_slice(b"LOCL");
+        response[16..24].copy_from_slice(&received);
+        // Originate timestamp: the client's transmit timestamp.
+        response[24..32].copy_from_slice(&buf[40..48]);
+        response[32..40].copy_from_slice(&received);
+        response[40..48].copy_from_slice(&ntp_now(opts.offset_ms));
+        socket.send_to(&response, peer).unwrap();
+        info!("answered {}", peer);
+    }
+}
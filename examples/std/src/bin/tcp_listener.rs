@@ -0,0 +1,111 @@
+use clap::Parser;
+use embassy_executor::{Executor, Spawner};
+use embassy_net::tcp::client::{TcpClientState, TcpConnection};
+use embassy_net::tcp::TcpListener;
+use embassy_net::{Config, Ipv4Address, Ipv4Cidr, StackResources};
+use embassy_net_tuntap::TunTapDevice;
+use embassy_time::Duration;
+use embedded_io_async::{Read, Write};
+use heapless::Vec;
+use log::*;
+use rand_core::{OsRng, RngCore};
+use static_cell::StaticCell;
+
+#[derive(Parser)]
+#[clap(version = "1.0")]
+struct Opts {
+    /// TAP device name
+    #[clap(long, default_value = "tap0")]
+    tap: String,
+    /// use a static IP instead of DHCP
+    #[clap(long)]
+    static_ip: bool,
+}
+
+/// Maximum number of concurrent connections.
+const CONNECTIONS: usize = 4;
+
+type Connection = TcpConnection<'static, CONNECTIONS, 1024, 1024>;
+
+#[embassy_executor::task]
+async fn net_task(mut runner: embassy_net::Runner<'static, TunTapDevice>) -> ! {
+    runner.run().await
+}
+
+#[embassy_executor::task(pool_size = CONNECTIONS)]
+async fn echo_task(mut connection: Connection) {
+    let remote = connection.remote_endpoint();
+    let mut buf = [0; 1024];
+    loop {
+        let n = match connection.read(&mut buf).await {
+            Ok(0) | Err(_) => break,
+            Ok(n) => n,
+        };
+        if connection.write_all(&buf[..n]).await.is_err() {
+            break;
+        }
+    }
+    info!("{:?} disconnected", remote);
+}
+
+#[embassy_executor::task]
+async fn main_task(spawner: Spawner) {
+    let opts: Opts = Opts::parse();
+
+    // Init network device
+    let device = TunTapDevice::new(&opts.tap).unwrap();
+
+    // Choose between dhcp or static ip
+    let config = if opts.static_ip {
+        Config::ipv4_static(embassy_net::StaticConfigV4 {
+            address: Ipv4Cidr::new(Ipv4Address::new(192, 168, 69, 2), 24),
+            dns_servers: Vec::new(),
+            gateway: Some(Ipv4Address::new(192, 168, 69, 1)),
+        })
+    } else {
+        Config::dhcpv4(Default::default())
+    };
+
+    // Generate random seed
+    let mut seed = [0; 8];
+    OsRng.fill_bytes(&mut seed);
+    let seed = u64::from_le_bytes(seed);
+
+    // Init network stack, with room for the sockets of the listener
+    static RESOURCES: StaticCell<StackResources<{ CONNECTIONS + 2 }>> = StaticCell::new();
+    let (stack, runner) = embassy_net::new(device, config, RESOURCES.init(StackResources::new()), seed);
+
+    // Launch network task
+    spawner.spawn(net_task(runner)).unwrap();
+
+    // Keep all the sockets listening, and serve each connection in its own task.
+    static STATE: StaticCell<TcpClientState<CONNECTIONS, 1024, 1024>> = StaticCell::new();
+    let mut listener = TcpListener::new(stack, STATE.init(TcpClientState::new()), 9999).unwrap();
+    listener.set_timeout(Some(Duration::from_secs(30)));
+    info!("Listening on TCP:9999...");
+
+    loop {
+        match listener.accept().await {
+            Ok(connection) => {
+                info!("Accepted a connection from {:?}", connection.remote_endpoint());
+                spawner.spawn(echo_task(connection)).unwrap();
+            }
+            Err(e) => warn!("accept error: {:?}", e),
+        }
+    }
+}
+
+static EXECUTOR: StaticCell<Executor> = StaticCell::new();
+
+fn main() {
+    env_logger::builder()
+        .filter_level(log::LevelFilter::Debug)
+        .filter_module("async_io", log::LevelFilter::Info)
+        .format_timestamp_nanos()
+        .init();
+
+    let executor = EXECUTOR.init(Executor::new());
+    executor.run(|spawner| {
+        spawner.spawn(main_task(spawner)).unwrap();
+    });
+}